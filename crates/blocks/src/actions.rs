@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use config::timestamp::TimestampFormatter;
+
+use crate::block::Block;
+
+/// What the UI should do after a block action runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionOutcome {
+    CopyToClipboard(String),
+    /// Run a command line in the session
+    RunCommand(String),
+    /// Save the text to a file chosen by the user
+    Export(String),
+    /// Hand the text to the share flow
+    Share(String),
+    TogglePin(usize),
+    ToggleWordWrap(usize),
+    Delete(usize),
+    /// Open a directory in the system file manager
+    OpenPath(String),
+    /// Summarize the block's output with the AI provider
+    Summarize(usize),
+    /// Show the text in a popover next to the block
+    ShowDetails(String),
+    /// Ask how to watch the block, then re-run it (see `watch::WatchSession`)
+    Watch(usize),
+    Nothing,
+}
+
+/// Who contributed an action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionSource {
+    Core,
+    Plugin(String),
+}
+
+type Handler = Arc<dyn Fn(&Block) -> ActionOutcome + Send + Sync>;
+type Predicate = Arc<dyn Fn(&Block) -> bool + Send + Sync>;
+
+/// An entry of the block context menu
+#[derive(Clone)]
+pub struct BlockAction {
+    pub id: String,
+    pub label: String,
+    pub source: ActionSource,
+    handler: Handler,
+    enabled: Option<Predicate>,
+}
+
+impl BlockAction {
+    pub fn new<F>(id: &str, label: &str, handler: F) -> Self
+    where
+        F: Fn(&Block) -> ActionOutcome + Send + Sync + 'static,
+    {
+        Self {
+            id: id.to_string(),
+            label: label.to_string(),
+            source: ActionSource::Core,
+            handler: Arc::new(handler),
+            enabled: None,
+        }
+    }
+
+    /// Mark the action as contributed by a plugin
+    pub fn contributed_by(mut self, plugin: &str) -> Self {
+        self.source = ActionSource::Plugin(plugin.to_string());
+        self
+    }
+
+    /// Only enable the action for blocks matching the predicate
+    pub fn enabled_when<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Block) -> bool + Send + Sync + 'static,
+    {
+        self.enabled = Some(Arc::new(predicate));
+        self
+    }
+
+    pub fn is_enabled(&self, block: &Block) -> bool {
+        match &self.enabled {
+            Some(predicate) => predicate(block),
+            None => true,
+        }
+    }
+}
+
+/// A menu entry generated for a specific block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem {
+    pub id: String,
+    pub label: String,
+    pub enabled: bool,
+    /// First item contributed by a plugin, drawn after a separator
+    pub separator_before: bool,
+}
+
+/// Registry of block actions; the context menu is generated from it
+pub struct BlockActionRegistry {
+    actions: Vec<BlockAction>,
+}
+
+impl BlockActionRegistry {
+    /// Registry with the built-in actions
+    pub fn new() -> Self {
+        Self::with_timestamps(TimestampFormatter::default())
+    }
+
+    /// Built-in actions, with exports dated using `timestamps`
+    pub fn with_timestamps(timestamps: TimestampFormatter) -> Self {
+        let mut registry = Self::empty();
+        for action in core_actions(Arc::new(timestamps)) {
+            registry.register(action);
+        }
+        registry
+    }
+
+    pub fn empty() -> Self {
+        Self { actions: Vec::new() }
+    }
+
+    /// Add an action, replacing any action with the same id
+    pub fn register(&mut self, action: BlockAction) {
+        match self.actions.iter_mut().find(|a| a.id == action.id) {
+            Some(existing) => *existing = action,
+            None => self.actions.push(action),
+        }
+    }
+
+    pub fn unregister(&mut self, id: &str) -> bool {
+        let len = self.actions.len();
+        self.actions.retain(|a| a.id != id);
+        self.actions.len() != len
+    }
+
+    /// Remove every action contributed by a plugin, e.g. when it is unloaded
+    pub fn unregister_plugin(&mut self, plugin: &str) {
+        self.actions
+            .retain(|a| a.source != ActionSource::Plugin(plugin.to_string()));
+    }
+
+    /// Menu for a block: core actions first, then plugin actions
+    pub fn menu_for(&self, block: &Block) -> Vec<MenuItem> {
+        let core = self.actions.iter().filter(|a| a.source == ActionSource::Core);
+        let plugins = self.actions.iter().filter(|a| a.source != ActionSource::Core);
+
+        let mut items: Vec<MenuItem> = core
+            .map(|action| Self::menu_item(action, block, false))
+            .collect();
+        let has_core = !items.is_empty();
+        items.extend(
+            plugins
+                .enumerate()
+                .map(|(i, action)| Self::menu_item(action, block, i == 0 && has_core)),
+        );
+        items
+    }
+
+    fn menu_item(action: &BlockAction, block: &Block, separator_before: bool) -> MenuItem {
+        MenuItem {
+            id: action.id.clone(),
+            label: action.label.clone(),
+            enabled: action.is_enabled(block),
+            separator_before,
+        }
+    }
+
+    /// Run an action on a block
+    pub fn invoke(&self, id: &str, block: &Block) -> Result<ActionOutcome> {
+        let action = self
+            .actions
+            .iter()
+            .find(|a| a.id == id)
+            .ok_or_else(|| anyhow!("Unknown block action: {}", id))?;
+
+        if !action.is_enabled(block) {
+            return Err(anyhow!("Action {} is not available for this block", id));
+        }
+
+        Ok((action.handler)(block))
+    }
+}
+
+impl Default for BlockActionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn core_actions(timestamps: Arc<TimestampFormatter>) -> Vec<BlockAction> {
+    let share_timestamps = timestamps.clone();
+    vec![
+        BlockAction::new("copy-output", "Copy Output", |block| {
+            ActionOutcome::CopyToClipboard(block.output_text())
+        })
+        .enabled_when(|block| !block.output_text().is_empty()),
+        BlockAction::new("copy-command", "Copy Command", |block| {
+            ActionOutcome::CopyToClipboard(block.command.raw.clone())
+        }),
+        BlockAction::new("rerun", "Re-run", |block| {
+            ActionOutcome::RunCommand(block.command.raw.clone())
+        }),
+        BlockAction::new("export", "Export…", move |block| {
+            ActionOutcome::Export(block.to_markdown_dated(&timestamps))
+        }),
+        BlockAction::new("share", "Share…", move |block| {
+            ActionOutcome::Share(block.to_markdown_dated(&share_timestamps))
+        }),
+        BlockAction::new("pin", "Pin / Unpin", |block| ActionOutcome::TogglePin(block.id)),
+        BlockAction::new("toggle-wrap", "Toggle Word Wrap", |block| {
+            ActionOutcome::ToggleWordWrap(block.id)
+        }),
+        BlockAction::new("delete", "Delete", |block| ActionOutcome::Delete(block.id))
+            .enabled_when(|block| block.exit_code.is_some()),
+        BlockAction::new("summarize", "Summarize Output", |block| {
+            ActionOutcome::Summarize(block.id)
+        })
+        .enabled_when(|block| block.exit_code.is_some() && block.is_long_output()),
+        BlockAction::new("open-cwd", "Open Working Directory", |block| {
+            ActionOutcome::OpenPath(block.command.working_dir.clone())
+        })
+        .enabled_when(Block::is_local),
+        BlockAction::new("resource-usage", "Resource Usage", |block| {
+            let Some(usage) = &block.resources else {
+                return ActionOutcome::Nothing;
+            };
+            let mut details = usage.summary();
+            if let Some(load) = block.duration_ms.and_then(|ms| usage.cpu_load(ms)) {
+                details.push_str(&format!(", {:.1} cores on average", load));
+            }
+            ActionOutcome::ShowDetails(details)
+        })
+        .enabled_when(|block| block.resources.is_some()),
+        BlockAction::new("watch", "Watch…", |block| ActionOutcome::Watch(block.id))
+            .enabled_when(|block| block.exit_code.is_some()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn test_menu_generation() {
+        let mut registry = BlockActionRegistry::new();
+        registry.register(
+            BlockAction::new("explain", "Explain", |_| ActionOutcome::Nothing).contributed_by("ai"),
+        );
+
+        let block = Block::new(3, Command::new("ls -la"));
+        let menu = registry.menu_for(&block);
+
+        assert_eq!(menu[0].id, "copy-output");
+        assert!(!menu[0].enabled);
+        let explain = menu.iter().find(|item| item.id == "explain").unwrap();
+        assert!(explain.separator_before);
+
+        assert_eq!(
+            registry.invoke("rerun", &block).unwrap(),
+            ActionOutcome::RunCommand("ls -la".to_string())
+        );
+        assert!(registry.invoke("delete", &block).is_err());
+
+        let mut remote = block.clone();
+        remote.context = Some("ssh prod-1".to_string());
+        assert!(registry.invoke("open-cwd", &block).is_ok());
+        assert!(registry.invoke("open-cwd", &remote).is_err());
+
+        registry.unregister_plugin("ai");
+        assert!(registry.menu_for(&block).iter().all(|item| item.id != "explain"));
+    }
+}
@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Languages whose code fences can be run in the shell
+const SHELL_LANGUAGES: &[&str] = &["sh", "bash", "zsh", "fish", "shell", "console"];
+
+/// A piece of a Markdown response
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseSegment {
+    Text(String),
+    /// A fenced code block; offered as a copyable snippet
+    Code {
+        language: Option<String>,
+        code: String,
+        /// Shell snippet that can be sent to the prompt
+        runnable: bool,
+        /// The closing fence has not streamed in yet
+        complete: bool,
+    },
+}
+
+/// An AI answer streamed into the block list
+///
+/// AI blocks sit next to command blocks but have no command or exit code and
+/// are never recorded in shell history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiBlock {
+    pub id: usize,
+    /// Header line, e.g. `Explain: tar -xzf`
+    pub title: String,
+    pub prompt: String,
+    /// Markdown received so far
+    pub response: String,
+    pub created_at: DateTime<Utc>,
+    /// All tokens have arrived
+    pub finished: bool,
+    /// The provider failed; shown instead of or after the partial response
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl AiBlock {
+    pub fn new(id: usize, title: &str, prompt: &str) -> Self {
+        Self {
+            id,
+            title: title.to_string(),
+            prompt: prompt.to_string(),
+            response: String::new(),
+            created_at: Utc::now(),
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Append streamed response text
+    pub fn push_token(&mut self, token: &str) {
+        if !self.finished {
+            self.response.push_str(token);
+        }
+    }
+
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    pub fn fail(&mut self, error: &str) {
+        self.error = Some(error.to_string());
+        self.finished = true;
+    }
+
+    /// The response split into text and code fences; an unterminated fence
+    /// at the end is still streaming
+    pub fn segments(&self) -> Vec<ResponseSegment> {
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut code: Option<(Option<String>, String)> = None;
+
+        for line in self.response.split_inclusive('\n') {
+            let fence = line.trim_start().strip_prefix("```");
+            match (&mut code, fence) {
+                (None, Some(info)) => {
+                    if !text.is_empty() {
+                        segments.push(ResponseSegment::Text(std::mem::take(&mut text)));
+                    }
+                    let language = info.trim();
+                    let language = (!language.is_empty()).then(|| language.to_string());
+                    code = Some((language, String::new()));
+                }
+                (Some(_), Some(_)) => {
+                    let (language, body) = code.take().unwrap();
+                    segments.push(code_segment(language, body, true));
+                }
+                (Some((_, body)), None) => body.push_str(line),
+                (None, None) => text.push_str(line),
+            }
+        }
+
+        if let Some((language, body)) = code {
+            segments.push(code_segment(language, body, false));
+        } else if !text.is_empty() {
+            segments.push(ResponseSegment::Text(text));
+        }
+        segments
+    }
+
+    /// Code fences that can be copied or run, in order
+    pub fn snippets(&self) -> Vec<ResponseSegment> {
+        self.segments()
+            .into_iter()
+            .filter(|segment| matches!(segment, ResponseSegment::Code { .. }))
+            .collect()
+    }
+}
+
+fn code_segment(language: Option<String>, mut code: String, complete: bool) -> ResponseSegment {
+    if code.ends_with('\n') {
+        code.pop();
+    }
+    let runnable = match language.as_deref() {
+        Some(language) => SHELL_LANGUAGES.contains(&language),
+        None => true,
+    };
+    ResponseSegment::Code {
+        language,
+        code,
+        runnable,
+        complete,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streamed_segments() {
+        let mut block = AiBlock::new(0, "Explain: tar", "what does tar -xzf do?");
+        for token in ["It extracts.\n``", "`bash\ntar -xzf a.tgz\n", "```\nDone"] {
+            block.push_token(token);
+        }
+        block.finish();
+
+        let segments = block.segments();
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], ResponseSegment::Text("It extracts.\n".to_string()));
+        assert_eq!(
+            segments[1],
+            ResponseSegment::Code {
+                language: Some("bash".to_string()),
+                code: "tar -xzf a.tgz".to_string(),
+                runnable: true,
+                complete: true,
+            }
+        );
+
+        let mut partial = AiBlock::new(1, "Chat", "");
+        partial.push_token("```python\nprint(1)\n");
+        assert!(matches!(
+            partial.segments()[0],
+            ResponseSegment::Code { runnable: false, complete: false, .. }
+        ));
+    }
+}
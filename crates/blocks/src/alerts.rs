@@ -0,0 +1,108 @@
+use config::{NotificationConfig, NotificationRule};
+
+use crate::block::Block;
+
+/// An alert raised when a finished block matches a notification rule
+#[derive(Debug, Clone)]
+pub struct Alert {
+    pub block_id: usize,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// Send a desktop notification
+    pub desktop: bool,
+    /// Ring the bell / show a toast in the UI
+    pub bell: bool,
+}
+
+impl Alert {
+    /// Short title for notifications and toasts
+    pub fn title(&self) -> String {
+        match self.exit_code {
+            Some(0) => "Command finished".to_string(),
+            Some(code) => format!("Command failed ({})", code),
+            None => "Command finished".to_string(),
+        }
+    }
+
+    /// Notification body with the command and its duration
+    pub fn body(&self) -> String {
+        format!("{} ({:.1}s)", self.command, self.duration_ms as f64 / 1000.0)
+    }
+}
+
+/// Evaluates notification rules against finished blocks
+pub struct AlertEvaluator {
+    rules: Vec<NotificationRule>,
+}
+
+impl AlertEvaluator {
+    pub fn new(config: &NotificationConfig) -> Self {
+        Self {
+            rules: config.rules.clone(),
+        }
+    }
+
+    /// Check a finished block against the rules; the first matching rule wins
+    pub fn evaluate(&self, block: &Block) -> Option<Alert> {
+        let duration_ms = block.duration_ms?;
+        let program = block.command.program().map(program_name).unwrap_or("");
+
+        let rule = self
+            .rules
+            .iter()
+            .find(|rule| rule_matches(rule, program, duration_ms, block.exit_code))?;
+
+        Some(Alert {
+            block_id: block.id,
+            command: block.command.raw.clone(),
+            exit_code: block.exit_code,
+            duration_ms,
+            desktop: rule.desktop,
+            bell: rule.bell,
+        })
+    }
+}
+
+fn rule_matches(rule: &NotificationRule, program: &str, duration_ms: u64, exit_code: Option<i32>) -> bool {
+    if duration_ms < rule.min_duration_secs * 1000 {
+        return false;
+    }
+
+    if rule.only_on_failure && matches!(exit_code, Some(0) | None) {
+        return false;
+    }
+
+    if rule.exclude.iter().any(|p| p == program) {
+        return false;
+    }
+
+    rule.commands.is_empty() || rule.commands.iter().any(|p| p == program)
+}
+
+/// Strip any leading path so `/usr/bin/ssh` matches a rule for `ssh`
+fn program_name(program: &str) -> &str {
+    program.rsplit('/').next().unwrap_or(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn finished(raw: &str, duration_ms: u64, exit_code: i32) -> Block {
+        let mut block = Block::new(0, Command::new(raw));
+        block.duration_ms = Some(duration_ms);
+        block.exit_code = Some(exit_code);
+        block
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let evaluator = AlertEvaluator::new(&NotificationConfig::default());
+
+        assert!(evaluator.evaluate(&finished("cargo build", 45_000, 0)).is_some());
+        assert!(evaluator.evaluate(&finished("cargo build", 5_000, 0)).is_none());
+        assert!(evaluator.evaluate(&finished("/usr/bin/ssh host", 600_000, 0)).is_none());
+    }
+}
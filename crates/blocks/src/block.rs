@@ -1,8 +1,18 @@
 use crate::command::Command;
+use crate::diagnostics::{Diagnostic, DiagnosticParser};
+use crate::image::BlockImage;
 use crate::output::Output;
+use crate::progress::{ProgressTracker, Stream};
+use crate::resources::ResourceUsage;
+use crate::status::StatusBadge;
+use crate::view::WrapMode;
 use chrono::{DateTime, Utc};
+use config::timestamp::TimestampFormatter;
 use serde::{Deserialize, Serialize};
 
+/// Output lines from which "Summarize Output" is offered
+const LONG_OUTPUT_LINES: usize = 200;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
     pub id: usize,
@@ -13,6 +23,28 @@ pub struct Block {
     pub duration_ms: Option<u64>,
     pub is_pinned: bool,
     pub is_folded: bool,
+    /// Images the command drew, in output order
+    #[serde(default)]
+    pub images: Vec<BlockImage>,
+    /// Soft-wrap the output; when off, long lines scroll horizontally
+    #[serde(default = "default_word_wrap")]
+    pub word_wrap: bool,
+    /// Compiler and linter locations found in the output
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    /// AI summary of the output, pinned above it
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// Where the command ran when not on this machine, e.g. `ssh prod-1`
+    /// (see `term::remote::SessionContext::badge`)
+    #[serde(default)]
+    pub context: Option<String>,
+    /// CPU time and peak memory, when they were captured
+    #[serde(default)]
+    pub resources: Option<ResourceUsage>,
+    /// Progress reported by the running command
+    #[serde(skip)]
+    progress: ProgressTracker,
 }
 
 impl Block {
@@ -26,6 +58,190 @@ impl Block {
             duration_ms: None,
             is_pinned: false,
             is_folded: false,
+            images: Vec::new(),
+            word_wrap: true,
+            diagnostics: Vec::new(),
+            summary: None,
+            context: None,
+            resources: None,
+            progress: ProgressTracker::new(),
+        }
+    }
+
+    pub fn toggle_word_wrap(&mut self) {
+        self.word_wrap = !self.word_wrap;
+    }
+
+    /// Layout for the output at the given width
+    pub fn wrap_mode(&self, width: usize, scroll_x: usize) -> WrapMode {
+        if self.word_wrap {
+            WrapMode::Wrap { width }
+        } else {
+            WrapMode::NoWrap { width, scroll_x }
+        }
+    }
+
+    /// Success/failure badge for the block header
+    pub fn status_badge(&self) -> StatusBadge {
+        StatusBadge::from_exit_code(self.exit_code)
+    }
+
+    /// Extract diagnostics from the output, replacing earlier results
+    pub fn analyze(&mut self, parser: &DiagnosticParser) {
+        self.diagnostics = parser.parse(&self.output_text());
+    }
+
+    /// Append command output, dropping intermediate progress redraws
+    pub fn append_output(&mut self, stream: Stream, data: &[u8]) {
+        let stored = self.progress.feed(stream, data);
+        match stream {
+            Stream::Stdout => self.output.append_stdout(&stored),
+            Stream::Stderr => self.output.append_stderr(&stored),
+        }
+    }
+
+    /// Record the exit status and flush any unterminated output
+    pub fn finish(&mut self, exit_code: i32, duration_ms: u64) {
+        let stdout = self.progress.finish(Stream::Stdout);
+        self.output.append_stdout(&stdout);
+        let stderr = self.progress.finish(Stream::Stderr);
+        self.output.append_stderr(&stderr);
+
+        self.output.set_status(exit_code);
+        self.exit_code = Some(exit_code);
+        self.duration_ms = Some(duration_ms);
+    }
+
+    /// Attach the resources the finished command used
+    pub fn set_resources(&mut self, usage: ResourceUsage) {
+        self.resources = Some(usage);
+    }
+
+    /// Progress bar for the block header while the command runs
+    pub fn progress_bar(&self, width: usize) -> Option<String> {
+        if self.exit_code.is_some() {
+            return None;
+        }
+        self.progress.render_bar(width)
+    }
+
+    /// Overall progress of the running command (0.0-1.0), if it reports any
+    pub fn progress(&self) -> Option<f32> {
+        self.progress.fraction().filter(|_| self.exit_code.is_none())
+    }
+
+    /// Attach an image drawn after the given output line
+    pub fn attach_image(&mut self, image: BlockImage) {
+        let at = self.images.partition_point(|existing| existing.line <= image.line);
+        self.images.insert(at, image);
+    }
+
+    /// Whether the output is long enough to offer a summary
+    pub fn is_long_output(&self) -> bool {
+        self.output_text().lines().count() >= LONG_OUTPUT_LINES
+    }
+
+    /// Whether the command ran on this machine, so its paths can be opened
+    pub fn is_local(&self) -> bool {
+        self.context.is_none()
+    }
+
+    /// Pin a summary annotation; `None` removes it
+    pub fn set_summary(&mut self, summary: Option<String>) {
+        self.summary = summary;
+    }
+
+    /// Stdout followed by stderr, as one text
+    pub fn output_text(&self) -> String {
+        let mut text = self.output.stdout_string();
+        let stderr = self.output.stderr_string();
+        if !stderr.is_empty() {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(&stderr);
+        }
+        text
+    }
+
+    /// Markdown rendering of the command and its output, for export and sharing
+    ///
+    /// Images are embedded as data URIs between code fences.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("```console\n$ {}\n", self.command.raw);
+        let output = self.output_text();
+        let mut images = self.images.iter().peekable();
+
+        for (index, line) in output.lines().enumerate() {
+            markdown.push_str(line);
+            markdown.push('\n');
+            while let Some(image) = images.next_if(|image| image.line <= index) {
+                markdown.push_str(&format!("```\n\n![image]({})\n\n```console\n", image.data_uri()));
+            }
+        }
+        markdown.push_str("```\n");
+        for image in images {
+            markdown.push_str(&format!("\n![image]({})\n", image.data_uri()));
+        }
+
+        if let Some(code) = self.exit_code.filter(|&code| code != 0) {
+            markdown.push_str(&format!("\nExit code: {}\n", code));
+        }
+        markdown
+    }
+
+    /// `to_markdown` preceded by when the command ran, in the user's
+    /// timestamp format
+    pub fn to_markdown_dated(&self, timestamps: &TimestampFormatter) -> String {
+        format!("_{}_\n\n{}", timestamps.absolute(self.created_at), self.to_markdown())
+    }
+
+    /// Time shown in the block header; see `TimestampFormatter::next_refresh`
+    /// for when to redraw it
+    pub fn header_time(&self, timestamps: &TimestampFormatter, now: DateTime<Utc>) -> String {
+        timestamps.format(self.created_at, now)
+    }
+
+    /// Standalone HTML fragment of the command, its output and images
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<div class=\"block\">\n<pre class=\"command\">$ {}</pre>\n<pre class=\"output\">",
+            escape_html(&self.command.raw)
+        );
+        let output = self.output_text();
+        let mut images = self.images.iter().peekable();
+
+        for (index, line) in output.lines().enumerate() {
+            html.push_str(&escape_html(line));
+            html.push('\n');
+            while let Some(image) = images.next_if(|image| image.line <= index) {
+                html.push_str(&image_tag(image));
+            }
         }
+        for image in images {
+            html.push_str(&image_tag(image));
+        }
+        html.push_str("</pre>\n</div>\n");
+        html
     }
 }
+
+fn default_word_wrap() -> bool {
+    true
+}
+
+fn image_tag(image: &BlockImage) -> String {
+    format!(
+        "<img src=\"{}\" width=\"{}\" height=\"{}\">\n",
+        image.data_uri(),
+        image.width,
+        image.height
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,430 @@
+use config::CopyModeKeys;
+
+use crate::block::Block;
+use crate::output::strip_escapes;
+use crate::pager::PagerKey;
+use crate::selection::RectSelection;
+
+/// What the caller should do after a key press in copy mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopyAction {
+    /// The cursor or selection moved and should be redrawn
+    Redraw,
+    /// The key was not bound
+    Ignored,
+    /// Leave copy mode without copying
+    Quit,
+    /// Put this text on the clipboard and leave copy mode
+    Copy(String),
+}
+
+/// Line and column (in characters) of a position in the text
+pub type Position = (usize, usize);
+
+/// What a visual selection covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Chars,
+    /// Whole lines (vi `V`)
+    Lines,
+    /// The columns between anchor and cursor on each line (vi `ctrl+v`)
+    Block,
+}
+
+/// Keyboard-driven cursor and selection over a block's output or the
+/// scrollback, like tmux copy mode with vi or emacs keys
+pub struct CopyMode {
+    lines: Vec<Vec<char>>,
+    keys: CopyModeKeys,
+    cursor: Position,
+    /// Where the selection started, while selecting
+    anchor: Option<Position>,
+    shape: Shape,
+    /// First visible line
+    top: usize,
+    height: usize,
+    /// Pending `g` of a `gg`
+    pending_g: bool,
+}
+
+impl CopyMode {
+    /// Copy mode over the output of a block, starting on its last line
+    pub fn new(block: &Block, height: usize, keys: CopyModeKeys) -> Self {
+        Self::from_text(&block.output_text(), height, keys)
+    }
+
+    /// Copy mode over any text, e.g. the visible screen and scrollback
+    pub fn from_text(text: &str, height: usize, keys: CopyModeKeys) -> Self {
+        let mut lines: Vec<Vec<char>> = text
+            .lines()
+            .map(|line| strip_escapes(line).chars().collect())
+            .collect();
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+        let mut mode = Self {
+            cursor: (lines.len() - 1, 0),
+            lines,
+            keys,
+            anchor: None,
+            shape: Shape::Chars,
+            top: 0,
+            height: height.max(1),
+            pending_g: false,
+        };
+        mode.scroll_to_cursor();
+        mode
+    }
+
+    pub fn cursor(&self) -> Position {
+        self.cursor
+    }
+
+    /// First visible line (0-based)
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Update the number of visible rows, e.g. after a resize
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height.max(1);
+        self.scroll_to_cursor();
+    }
+
+    /// Selected range, start before end and both inclusive; whole lines
+    /// when `is_linewise`, a rectangle when `is_blockwise`
+    pub fn selection(&self) -> Option<(Position, Position)> {
+        let anchor = self.anchor?;
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    pub fn is_linewise(&self) -> bool {
+        self.shape == Shape::Lines
+    }
+
+    pub fn is_blockwise(&self) -> bool {
+        self.shape == Shape::Block
+    }
+
+    /// Mode indicator for the status line
+    pub fn status_line(&self) -> String {
+        let mode = match (self.anchor.is_some(), self.shape) {
+            (false, _) => "COPY",
+            (true, Shape::Chars) => "VISUAL",
+            (true, Shape::Lines) => "VISUAL LINE",
+            (true, Shape::Block) => "VISUAL BLOCK",
+        };
+        format!(
+            "-- {} -- {}:{}",
+            mode,
+            self.cursor.0 + 1,
+            self.cursor.1 + 1
+        )
+    }
+
+    pub fn handle_key(&mut self, key: PagerKey) -> CopyAction {
+        let action = match self.keys {
+            CopyModeKeys::Vi => self.handle_vi_key(key),
+            CopyModeKeys::Emacs => self.handle_emacs_key(key),
+        };
+        if action == CopyAction::Redraw {
+            self.clamp_cursor();
+            self.scroll_to_cursor();
+        }
+        action
+    }
+
+    fn handle_vi_key(&mut self, key: PagerKey) -> CopyAction {
+        let pending_g = std::mem::replace(&mut self.pending_g, false);
+        let half_page = (self.height / 2).max(1);
+
+        match key {
+            PagerKey::Char('q') => return CopyAction::Quit,
+            PagerKey::Escape if self.anchor.is_none() => return CopyAction::Quit,
+            PagerKey::Escape => self.anchor = None,
+            PagerKey::Char('h') | PagerKey::Left | PagerKey::Backspace => self.left(),
+            PagerKey::Char('l') | PagerKey::Right | PagerKey::Char(' ') => self.right(),
+            PagerKey::Char('j') | PagerKey::Down => self.down(1),
+            PagerKey::Char('k') | PagerKey::Up => self.up(1),
+            PagerKey::Char('w') => self.next_word(),
+            PagerKey::Char('b') => self.previous_word(),
+            PagerKey::Char('e') => self.word_end(),
+            PagerKey::Char('0') | PagerKey::Home => self.cursor.1 = 0,
+            PagerKey::Char('^') => self.cursor.1 = self.first_non_blank(),
+            PagerKey::Char('$') | PagerKey::End => self.cursor.1 = self.line_end(),
+            PagerKey::Char('g') if pending_g => self.cursor = (0, 0),
+            PagerKey::Char('g') => self.pending_g = true,
+            PagerKey::Char('G') => self.cursor = (self.lines.len() - 1, 0),
+            PagerKey::Ctrl('d') => self.down(half_page),
+            PagerKey::Ctrl('u') => self.up(half_page),
+            PagerKey::Ctrl('f') | PagerKey::PageDown => self.down(self.height),
+            PagerKey::Ctrl('b') | PagerKey::PageUp => self.up(self.height),
+            PagerKey::Char('v') => self.toggle_selection(Shape::Chars),
+            PagerKey::Char('V') => self.toggle_selection(Shape::Lines),
+            PagerKey::Ctrl('v') => self.toggle_selection(Shape::Block),
+            PagerKey::Char('o') => self.swap_anchor(),
+            PagerKey::Char('y') | PagerKey::Enter => return self.copy(),
+            _ => return CopyAction::Ignored,
+        }
+        CopyAction::Redraw
+    }
+
+    fn handle_emacs_key(&mut self, key: PagerKey) -> CopyAction {
+        match key {
+            PagerKey::Char('q') | PagerKey::Escape => return CopyAction::Quit,
+            PagerKey::Ctrl('g') if self.anchor.is_none() => return CopyAction::Quit,
+            PagerKey::Ctrl('g') => self.anchor = None,
+            PagerKey::Ctrl('b') | PagerKey::Left => self.left(),
+            PagerKey::Ctrl('f') | PagerKey::Right => self.right(),
+            PagerKey::Ctrl('n') | PagerKey::Down => self.down(1),
+            PagerKey::Ctrl('p') | PagerKey::Up => self.up(1),
+            PagerKey::Alt('f') => self.next_word(),
+            PagerKey::Alt('b') => self.previous_word(),
+            PagerKey::Ctrl('a') | PagerKey::Home => self.cursor.1 = 0,
+            PagerKey::Alt('m') => self.cursor.1 = self.first_non_blank(),
+            PagerKey::Ctrl('e') | PagerKey::End => self.cursor.1 = self.line_end(),
+            PagerKey::Alt('<') => self.cursor = (0, 0),
+            PagerKey::Alt('>') => self.cursor = (self.lines.len() - 1, 0),
+            PagerKey::Ctrl('v') | PagerKey::PageDown => self.down(self.height),
+            PagerKey::Alt('v') | PagerKey::PageUp => self.up(self.height),
+            PagerKey::Ctrl(' ') => self.toggle_selection(Shape::Chars),
+            PagerKey::Ctrl('x') => self.swap_anchor(),
+            PagerKey::Alt('w') | PagerKey::Ctrl('w') | PagerKey::Enter => return self.copy(),
+            _ => return CopyAction::Ignored,
+        }
+        CopyAction::Redraw
+    }
+
+    fn toggle_selection(&mut self, shape: Shape) {
+        if self.anchor.is_some() && self.shape == shape {
+            self.anchor = None;
+        } else {
+            self.anchor.get_or_insert(self.cursor);
+            self.shape = shape;
+        }
+    }
+
+    fn swap_anchor(&mut self) {
+        if let Some(anchor) = self.anchor.as_mut() {
+            std::mem::swap(anchor, &mut self.cursor);
+        }
+    }
+
+    /// Copy the selection, or the cursor line when nothing is selected
+    fn copy(&mut self) -> CopyAction {
+        let ((start_line, start_col), (end_line, end_col)) = match self.selection() {
+            Some(_) if self.shape == Shape::Block => {
+                let anchor = self.anchor.take().unwrap_or(self.cursor);
+                let text = RectSelection::new(anchor, self.cursor).text(&self.lines);
+                return CopyAction::Copy(text);
+            }
+            Some(range) if self.shape == Shape::Chars => range,
+            Some(((start, _), (end, _))) => ((start, 0), (end, usize::MAX)),
+            None => ((self.cursor.0, 0), (self.cursor.0, usize::MAX)),
+        };
+
+        let text: Vec<String> = (start_line..=end_line)
+            .map(|line| {
+                let chars = &self.lines[line];
+                let from = if line == start_line { start_col } else { 0 };
+                let to = if line == end_line {
+                    end_col.saturating_add(1).min(chars.len())
+                } else {
+                    chars.len()
+                };
+                chars[from.min(to)..to].iter().collect()
+            })
+            .collect();
+        self.anchor = None;
+        CopyAction::Copy(text.join("\n"))
+    }
+
+    fn line(&self) -> &[char] {
+        &self.lines[self.cursor.0]
+    }
+
+    fn line_end(&self) -> usize {
+        self.line().len().saturating_sub(1)
+    }
+
+    fn first_non_blank(&self) -> usize {
+        self.line()
+            .iter()
+            .position(|c| !c.is_whitespace())
+            .unwrap_or(0)
+    }
+
+    fn left(&mut self) {
+        self.cursor.1 = self.cursor.1.saturating_sub(1);
+    }
+
+    fn right(&mut self) {
+        self.cursor.1 = (self.cursor.1 + 1).min(self.line_end());
+    }
+
+    fn up(&mut self, n: usize) {
+        self.cursor.0 = self.cursor.0.saturating_sub(n);
+    }
+
+    fn down(&mut self, n: usize) {
+        self.cursor.0 = (self.cursor.0 + n).min(self.lines.len() - 1);
+    }
+
+    /// Character at a position, with line ends as whitespace
+    fn char_at(&self, (line, col): Position) -> char {
+        self.lines[line].get(col).copied().unwrap_or(' ')
+    }
+
+    /// The position after `position`, crossing into the next line
+    fn step_forward(&self, (line, col): Position) -> Option<Position> {
+        if col < self.lines[line].len() {
+            Some((line, col + 1))
+        } else if line + 1 < self.lines.len() {
+            Some((line + 1, 0))
+        } else {
+            None
+        }
+    }
+
+    fn step_back(&self, (line, col): Position) -> Option<Position> {
+        if col > 0 {
+            Some((line, col.min(self.lines[line].len()) - 1))
+        } else if line > 0 {
+            Some((line - 1, self.lines[line - 1].len()))
+        } else {
+            None
+        }
+    }
+
+    fn next_word(&mut self) {
+        let start = word_class(self.char_at(self.cursor));
+        let mut position = self.cursor;
+        let mut left_word = false;
+        while let Some(next) = self.step_forward(position) {
+            position = next;
+            let class = word_class(self.char_at(position));
+            left_word |= class != start || next.1 == 0;
+            if left_word && class != WordClass::Blank {
+                self.cursor = position;
+                return;
+            }
+        }
+    }
+
+    fn previous_word(&mut self) {
+        let mut position = self.cursor;
+        // Skip blanks before the cursor, then go to the start of that word
+        while let Some(previous) = self.step_back(position) {
+            position = previous;
+            if word_class(self.char_at(position)) != WordClass::Blank {
+                break;
+            }
+        }
+        let class = word_class(self.char_at(position));
+        while let Some(previous) = self.step_back(position) {
+            if previous.0 != position.0 || word_class(self.char_at(previous)) != class {
+                break;
+            }
+            position = previous;
+        }
+        self.cursor = position;
+    }
+
+    fn word_end(&mut self) {
+        let mut position = self.cursor;
+        // Skip blanks after the cursor, then go to the end of that word
+        while let Some(next) = self.step_forward(position) {
+            position = next;
+            if word_class(self.char_at(position)) != WordClass::Blank {
+                break;
+            }
+        }
+        let class = word_class(self.char_at(position));
+        while let Some(next) = self.step_forward(position) {
+            if next.0 != position.0 || word_class(self.char_at(next)) != class {
+                break;
+            }
+            position = next;
+        }
+        self.cursor = position;
+    }
+
+    fn clamp_cursor(&mut self) {
+        self.cursor.1 = self.cursor.1.min(self.line_end());
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        if self.cursor.0 < self.top {
+            self.top = self.cursor.0;
+        } else if self.cursor.0 >= self.top + self.height {
+            self.top = self.cursor.0 + 1 - self.height;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WordClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn word_class(c: char) -> WordClass {
+    if c.is_whitespace() {
+        WordClass::Blank
+    } else if c.is_alphanumeric() || c == '_' {
+        WordClass::Word
+    } else {
+        WordClass::Punctuation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "cargo build\nerror: expected `;`\n  --> src/main.rs:3:5";
+
+    #[test]
+    fn test_vi_selection() {
+        let mut mode = CopyMode::from_text(TEXT, 10, CopyModeKeys::Vi);
+        assert_eq!(mode.cursor(), (2, 0));
+
+        mode.handle_key(PagerKey::Char('g'));
+        mode.handle_key(PagerKey::Char('g'));
+        mode.handle_key(PagerKey::Char('w'));
+        assert_eq!(mode.cursor(), (0, 6));
+        mode.handle_key(PagerKey::Char('v'));
+        mode.handle_key(PagerKey::Char('e'));
+        assert_eq!(mode.handle_key(PagerKey::Char('y')), CopyAction::Copy("build".into()));
+
+        mode.handle_key(PagerKey::Char('j'));
+        mode.handle_key(PagerKey::Char('V'));
+        mode.handle_key(PagerKey::Char('j'));
+        assert_eq!(
+            mode.handle_key(PagerKey::Enter),
+            CopyAction::Copy("error: expected `;`\n  --> src/main.rs:3:5".into())
+        );
+
+        mode.handle_key(PagerKey::Char('k'));
+        mode.handle_key(PagerKey::Char('0'));
+        mode.handle_key(PagerKey::Ctrl('v'));
+        mode.handle_key(PagerKey::Char('j'));
+        mode.handle_key(PagerKey::Char('l'));
+        mode.handle_key(PagerKey::Char('l'));
+        mode.handle_key(PagerKey::Char('l'));
+        assert_eq!(mode.status_line(), "-- VISUAL BLOCK -- 3:4");
+        assert_eq!(mode.handle_key(PagerKey::Char('y')), CopyAction::Copy("erro\n  --".into()));
+    }
+
+    #[test]
+    fn test_emacs_selection() {
+        let mut mode = CopyMode::from_text(TEXT, 10, CopyModeKeys::Emacs);
+        mode.handle_key(PagerKey::Ctrl('p'));
+        mode.handle_key(PagerKey::Ctrl(' '));
+        mode.handle_key(PagerKey::Alt('f'));
+        mode.handle_key(PagerKey::Ctrl('b'));
+        assert_eq!(mode.handle_key(PagerKey::Alt('w')), CopyAction::Copy("error".into()));
+        assert_eq!(mode.handle_key(PagerKey::Ctrl('g')), CopyAction::Quit);
+    }
+}
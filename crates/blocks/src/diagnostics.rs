@@ -0,0 +1,328 @@
+use std::path::Path;
+
+use config::{DiagnosticsConfig, ErrorFormatConfig};
+use serde::{Deserialize, Serialize};
+
+use crate::output::strip_escapes;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn parse(word: &str) -> Option<Self> {
+        match word.to_ascii_lowercase().as_str() {
+            "error" | "fatal" | "failed" => Some(Severity::Error),
+            "warning" | "warn" => Some(Severity::Warning),
+            "note" | "help" | "info" => Some(Severity::Note),
+            _ => None,
+        }
+    }
+}
+
+/// A compiler or linter message pointing at a source location
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub path: String,
+    pub line: u32,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// Line of the block output the location was found on
+    pub output_line: usize,
+}
+
+impl Diagnostic {
+    /// Command line opening the location in an editor
+    pub fn editor_command(&self, editor: &str, working_dir: &str) -> Vec<String> {
+        let mut args: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+        let path = Path::new(working_dir).join(&self.path).to_string_lossy().into_owned();
+        let column = self.column.unwrap_or(1);
+
+        let program = args
+            .first()
+            .and_then(|program| Path::new(program).file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        match program.as_str() {
+            "code" | "codium" | "cursor" => {
+                args.push("-g".to_string());
+                args.push(format!("{}:{}:{}", path, self.line, column));
+            }
+            "subl" | "zed" | "hx" | "helix" => {
+                args.push(format!("{}:{}:{}", path, self.line, column));
+            }
+            _ => {
+                // vi, emacs, nano and most others understand `+line`
+                args.push(format!("+{}", self.line));
+                args.push(path);
+            }
+        }
+
+        args
+    }
+}
+
+/// Editor from the config, then `$VISUAL`, then `$EDITOR`
+pub fn resolve_editor(config: &DiagnosticsConfig) -> Option<String> {
+    config
+        .editor
+        .clone()
+        .or_else(|| std::env::var("VISUAL").ok())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .filter(|editor| !editor.trim().is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(char),
+    File,
+    Line,
+    Column,
+    Severity,
+    Message,
+    Any,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Captures {
+    file: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+    severity: Option<Severity>,
+    message: Option<String>,
+}
+
+/// A compiled errorformat-style pattern
+#[derive(Debug, Clone)]
+pub struct ErrorFormat {
+    pub name: String,
+    tokens: Vec<Token>,
+    severity: Option<Severity>,
+}
+
+impl ErrorFormat {
+    pub fn new(name: &str, pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                tokens.push(Token::Literal(c));
+                continue;
+            }
+            match chars.next() {
+                Some('f') => tokens.push(Token::File),
+                Some('l') => tokens.push(Token::Line),
+                Some('c') => tokens.push(Token::Column),
+                Some('s') => tokens.push(Token::Severity),
+                Some('m') => tokens.push(Token::Message),
+                Some('*') => tokens.push(Token::Any),
+                Some(other) => tokens.push(Token::Literal(other)),
+                None => tokens.push(Token::Literal('%')),
+            }
+        }
+
+        Self {
+            name: name.to_string(),
+            tokens,
+            severity: None,
+        }
+    }
+
+    /// Severity used when the pattern has no `%s`
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = Some(severity);
+        self
+    }
+
+    fn from_config(config: &ErrorFormatConfig) -> Self {
+        let format = Self::new(&config.name, &config.pattern);
+        match config.severity.as_deref().and_then(Severity::parse) {
+            Some(severity) => format.with_severity(severity),
+            None => format,
+        }
+    }
+
+    fn matches(&self, line: &str) -> Option<Captures> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut captures = match_tokens(&self.tokens, &chars, Captures::default())?;
+        if captures.severity.is_none() {
+            captures.severity = self.severity;
+        }
+        Some(captures)
+    }
+}
+
+fn match_tokens(tokens: &[Token], chars: &[char], captures: Captures) -> Option<Captures> {
+    let Some((token, rest)) = tokens.split_first() else {
+        return chars.is_empty().then_some(captures);
+    };
+
+    match token {
+        Token::Literal(c) => {
+            if chars.first() == Some(c) {
+                match_tokens(rest, &chars[1..], captures)
+            } else {
+                None
+            }
+        }
+        Token::File => (1..=chars.len())
+            .take_while(|&len| !chars[len - 1].is_whitespace())
+            .find_map(|len| {
+                let mut captures = captures.clone();
+                captures.file = Some(chars[..len].iter().collect());
+                match_tokens(rest, &chars[len..], captures)
+            }),
+        Token::Line | Token::Column => {
+            let len = chars.iter().take_while(|c| c.is_ascii_digit()).count();
+            let number: u32 = chars[..len].iter().collect::<String>().parse().ok()?;
+            let mut captures = captures;
+            if *token == Token::Line {
+                captures.line = Some(number);
+            } else {
+                captures.column = Some(number);
+            }
+            match_tokens(rest, &chars[len..], captures)
+        }
+        Token::Severity => {
+            let len = chars.iter().take_while(|c| c.is_alphabetic()).count();
+            let word: String = chars[..len].iter().collect();
+            let mut captures = captures;
+            captures.severity = Some(Severity::parse(&word)?);
+            match_tokens(rest, &chars[len..], captures)
+        }
+        Token::Message => {
+            let mut captures = captures;
+            captures.message = Some(chars.iter().collect::<String>().trim().to_string());
+            match_tokens(rest, &[], captures)
+        }
+        Token::Any => {
+            (0..=chars.len()).find_map(|skip| match_tokens(rest, &chars[skip..], captures.clone()))
+        }
+    }
+}
+
+/// Extracts diagnostics from command output
+///
+/// Patterns without `%f` are headers (rustc's `error[E0308]: ...`); their
+/// severity and message carry over to the next location-only match.
+pub struct DiagnosticParser {
+    formats: Vec<ErrorFormat>,
+}
+
+impl DiagnosticParser {
+    pub fn new(config: &DiagnosticsConfig) -> Self {
+        let mut formats: Vec<ErrorFormat> =
+            config.formats.iter().map(ErrorFormat::from_config).collect();
+        formats.extend(builtin_formats());
+        Self { formats }
+    }
+
+    pub fn parse(&self, output: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut header: Option<(Severity, String)> = None;
+
+        for (index, line) in output.lines().enumerate() {
+            let line = strip_escapes(line);
+            let Some(captures) = self.formats.iter().find_map(|format| format.matches(&line)) else {
+                continue;
+            };
+
+            let (Some(path), Some(line_number)) = (captures.file, captures.line) else {
+                if let Some(severity) = captures.severity {
+                    header = Some((severity, captures.message.unwrap_or_default()));
+                }
+                continue;
+            };
+
+            let (severity, message) = match (captures.severity, captures.message) {
+                (Some(severity), Some(message)) => (severity, message),
+                (severity, message) => {
+                    let (header_severity, header_message) =
+                        header.take().unwrap_or((Severity::Error, String::new()));
+                    (
+                        severity.unwrap_or(header_severity),
+                        message.unwrap_or(header_message),
+                    )
+                }
+            };
+
+            diagnostics.push(Diagnostic {
+                path,
+                line: line_number,
+                column: captures.column,
+                severity,
+                message,
+                output_line: index,
+            });
+        }
+
+        diagnostics
+    }
+}
+
+fn builtin_formats() -> Vec<ErrorFormat> {
+    vec![
+        // rustc: `  --> src/main.rs:4:5` under an `error[E0308]: ...` header
+        ErrorFormat::new("rustc", "%*--> %f:%l:%c"),
+        ErrorFormat::new("gcc", "%f:%l:%c: %s: %m"),
+        ErrorFormat::new("gcc", "%f:%l: %s: %m"),
+        ErrorFormat::new("tsc", "%f(%l,%c): %s TS%*: %m"),
+        ErrorFormat::new("pytest", "%f:%l: %m").with_severity(Severity::Error),
+        ErrorFormat::new("rustc", "%s[%*]: %m"),
+        ErrorFormat::new("rustc", "%s: %m"),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_formats() {
+        let parser = DiagnosticParser::new(&DiagnosticsConfig::default());
+        let output = "\
+error[E0308]: mismatched types
+ --> src/main.rs:4:18
+  |
+main.c:3:5: warning: unused variable 'x'
+app.ts(12,7): error TS2322: Type 'string' is not assignable
+tests/test_app.py:9: AssertionError
+";
+        let diagnostics = parser.parse(output);
+        assert_eq!(diagnostics.len(), 4);
+
+        assert_eq!(diagnostics[0].path, "src/main.rs");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (4, Some(18)));
+        assert_eq!(diagnostics[0].message, "mismatched types");
+        assert_eq!(diagnostics[0].output_line, 1);
+
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+        assert_eq!(diagnostics[2].path, "app.ts");
+        assert_eq!(diagnostics[3].message, "AssertionError");
+    }
+
+    #[test]
+    fn test_editor_command() {
+        let diagnostic = Diagnostic {
+            path: "src/lib.rs".to_string(),
+            line: 10,
+            column: Some(2),
+            severity: Severity::Error,
+            message: String::new(),
+            output_line: 0,
+        };
+        assert_eq!(
+            diagnostic.editor_command("nvim", "/work"),
+            vec!["nvim", "+10", "/work/src/lib.rs"]
+        );
+        assert_eq!(
+            diagnostic.editor_command("code --wait", "/work"),
+            vec!["code", "--wait", "-g", "/work/src/lib.rs:10:2"]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+
+use anyhow::{Context, Result};
+use config::{HighlightRule, HighlightRuleSet};
+use regex::Regex;
+
+use crate::output::strip_escapes;
+
+/// Styling for part of a highlighted line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightSpan {
+    /// Byte range in `HighlightedLine::text`
+    pub start: usize,
+    pub end: usize,
+    pub color: Option<String>,
+    pub bold: bool,
+}
+
+/// An output line after the highlighting rules ran, with masks applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightedLine {
+    pub text: String,
+    pub spans: Vec<HighlightSpan>,
+}
+
+struct CompiledRule {
+    regex: Regex,
+    rule: HighlightRule,
+}
+
+struct CompiledSet {
+    name: String,
+    rules: Vec<CompiledRule>,
+}
+
+/// The `blocks.highlighters` rule sets of a session, each of which can be
+/// switched on and off
+pub struct Highlighters {
+    sets: Vec<CompiledSet>,
+    enabled: HashSet<String>,
+}
+
+impl Highlighters {
+    pub fn new(sets: &[HighlightRuleSet]) -> Result<Self> {
+        let mut compiled = Vec::new();
+        let mut enabled = HashSet::new();
+        for set in sets {
+            let rules = set
+                .rules
+                .iter()
+                .map(|rule| {
+                    let regex = Regex::new(&rule.pattern).with_context(|| {
+                        format!("Invalid pattern in highlighter {}: {}", set.name, rule.pattern)
+                    })?;
+                    Ok(CompiledRule {
+                        regex,
+                        rule: rule.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if set.enabled {
+                enabled.insert(set.name.clone());
+            }
+            compiled.push(CompiledSet {
+                name: set.name.clone(),
+                rules,
+            });
+        }
+        Ok(Self {
+            sets: compiled,
+            enabled,
+        })
+    }
+
+    /// Names of the rule sets and whether each is on, for a toggle menu
+    pub fn rule_sets(&self) -> Vec<(&str, bool)> {
+        self.sets
+            .iter()
+            .map(|set| (set.name.as_str(), self.enabled.contains(&set.name)))
+            .collect()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.contains(name)
+    }
+
+    /// Switch a rule set on or off; false when there is no such set
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        if !self.sets.iter().any(|set| set.name == name) {
+            return false;
+        }
+        if enabled {
+            self.enabled.insert(name.to_string());
+        } else {
+            self.enabled.remove(name);
+        }
+        true
+    }
+
+    pub fn toggle(&mut self, name: &str) -> bool {
+        let enabled = !self.is_enabled(name);
+        self.set_enabled(name, enabled)
+    }
+
+    /// Run the enabled rules over one line of output
+    pub fn highlight(&self, line: &str) -> HighlightedLine {
+        let line = strip_escapes(line);
+        // Matches in rule order, skipping those overlapping an earlier one
+        let mut matches: Vec<(usize, usize, &HighlightRule)> = Vec::new();
+        let rules = self
+            .sets
+            .iter()
+            .filter(|set| self.enabled.contains(&set.name))
+            .flat_map(|set| &set.rules);
+        for compiled in rules {
+            for found in compiled.regex.find_iter(&line) {
+                let overlaps = matches
+                    .iter()
+                    .any(|(start, end, _)| found.start() < *end && *start < found.end());
+                if !overlaps && !found.is_empty() {
+                    matches.push((found.start(), found.end(), &compiled.rule));
+                }
+            }
+        }
+        matches.sort_by_key(|(start, _, _)| *start);
+
+        let mut text = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut copied = 0;
+        for (start, end, rule) in matches {
+            text.push_str(&line[copied..start]);
+            let span_start = text.len();
+            text.push_str(rule.mask.as_deref().unwrap_or(&line[start..end]));
+            if rule.color.is_some() || rule.bold {
+                spans.push(HighlightSpan {
+                    start: span_start,
+                    end: text.len(),
+                    color: rule.color.clone(),
+                    bold: rule.bold,
+                });
+            }
+            copied = end;
+        }
+        text.push_str(&line[copied..]);
+        HighlightedLine { text, spans }
+    }
+}
+
+/// Highlights a block's output as it arrives, each complete line once
+#[derive(Debug, Default)]
+pub struct HighlightStream {
+    /// Bytes after the last newline
+    pending: Vec<u8>,
+}
+
+impl HighlightStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lines completed by `data`
+    pub fn feed(&mut self, highlighters: &Highlighters, data: &[u8]) -> Vec<HighlightedLine> {
+        self.pending.extend_from_slice(data);
+        let Some(last_newline) = self.pending.iter().rposition(|&b| b == b'\n') else {
+            return Vec::new();
+        };
+        let complete: Vec<u8> = self.pending.drain(..=last_newline).collect();
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .map(|line| highlighters.highlight(line))
+            .collect()
+    }
+
+    /// The unfinished last line, e.g. a prompt waiting for input; it is
+    /// highlighted again once complete
+    pub fn partial(&self, highlighters: &Highlighters) -> Option<HighlightedLine> {
+        (!self.pending.is_empty())
+            .then(|| highlighters.highlight(&String::from_utf8_lossy(&self.pending)))
+    }
+
+    /// The unfinished last line once the command has exited
+    pub fn finish(&mut self, highlighters: &Highlighters) -> Option<HighlightedLine> {
+        let line = self.partial(highlighters);
+        self.pending.clear();
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::BlocksConfig;
+
+    #[test]
+    fn test_highlight() {
+        let mut highlighters = Highlighters::new(&BlocksConfig::default().highlighters).unwrap();
+        let line = highlighters.highlight("\x1b[2m12:00\x1b[0m ERROR from 10.0.0.7:443");
+        assert_eq!(line.text, "12:00 ERROR from 10.0.0.7:443");
+        assert_eq!(line.spans.len(), 2);
+        assert_eq!(&line.text[line.spans[0].start..line.spans[0].end], "ERROR");
+        assert!(line.spans[0].bold);
+        assert_eq!(&line.text[line.spans[1].start..line.spans[1].end], "10.0.0.7:443");
+
+        assert!(!highlighters.is_enabled("card-numbers"));
+        assert!(highlighters.toggle("card-numbers"));
+        assert!(!highlighters.toggle("no-such-set"));
+        let line = highlighters.highlight("paid with 4111 1111 1111 1111.");
+        assert_eq!(line.text, "paid with •••• •••• •••• ••••.");
+        assert!(line.spans.is_empty());
+    }
+
+    #[test]
+    fn test_stream() {
+        let highlighters = Highlighters::new(&BlocksConfig::default().highlighters).unwrap();
+        let mut stream = HighlightStream::new();
+        assert!(stream.feed(&highlighters, b"WARN disk ").is_empty());
+        let lines = stream.feed(&highlighters, b"full\r\nINFO ret");
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "WARN disk full");
+        assert_eq!(lines[0].spans[0].end, 4);
+        assert_eq!(stream.partial(&highlighters).unwrap().text, "INFO ret");
+        assert_eq!(stream.finish(&highlighters).unwrap().spans.len(), 1);
+        assert!(stream.partial(&highlighters).is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// Graphics protocol an image arrived through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImageProtocol {
+    Sixel,
+    Kitty,
+    Iterm,
+}
+
+/// An image emitted by a command, kept with its block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockImage {
+    pub protocol: ImageProtocol,
+    /// MIME type of `data`, e.g. `image/png`
+    pub mime: String,
+    /// Encoded image bytes
+    pub data: Vec<u8>,
+    /// Size in pixels
+    pub width: u32,
+    pub height: u32,
+    /// Output line the image was placed after
+    pub line: usize,
+}
+
+impl BlockImage {
+    /// `data:` URI for embedding in exported HTML or Markdown
+    pub fn data_uri(&self) -> String {
+        format!("data:{};base64,{}", self.mime, base64_encode(&self.data))
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - i * 6) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::command::Command;
+
+    #[test]
+    fn test_export_with_images() {
+        let mut block = Block::new(0, Command::new("plot"));
+        block.output.append_stdout(b"before\nafter\n");
+        block.attach_image(BlockImage {
+            protocol: ImageProtocol::Kitty,
+            mime: "image/png".to_string(),
+            data: b"fo".to_vec(),
+            width: 2,
+            height: 2,
+            line: 0,
+        });
+
+        assert_eq!(
+            block.to_markdown(),
+            "```console\n$ plot\nbefore\n```\n\n![image](data:image/png;base64,Zm8=)\n\n```console\nafter\n```\n"
+        );
+        assert!(block
+            .to_html()
+            .contains("before\n<img src=\"data:image/png;base64,Zm8=\" width=\"2\" height=\"2\">\nafter"));
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"f"), "Zg==");
+        assert_eq!(base64_encode(b"fo"), "Zm8=");
+        assert_eq!(base64_encode(b"foobar"), "Zm9vYmFy");
+    }
+}
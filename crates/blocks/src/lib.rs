@@ -2,21 +2,53 @@
 //
 // This module provides reusable UI components for terminal interfaces
 
+pub mod actions;
+pub mod ai;
+pub mod alerts;
 /// Represents a UI block in the terminal
 mod block;
 mod command;
+pub mod copy_mode;
+pub mod diagnostics;
+pub mod highlighters;
+pub mod image;
 mod navigation;
 mod output;
+pub mod pager;
+pub mod progress;
+pub mod report;
+pub mod resources;
+pub mod selection;
+pub mod status;
+pub mod view;
+pub mod watch;
 
 use std::sync::Arc;
+use config::CopyModeKeys;
 use tokio::sync::Mutex;
 
+pub use actions::{ActionOutcome, BlockAction, BlockActionRegistry};
+pub use ai::{AiBlock, ResponseSegment};
 pub use block::Block;
+pub use command::Command;
+pub use copy_mode::{CopyAction, CopyMode};
+pub use diagnostics::{Diagnostic, DiagnosticParser};
+pub use highlighters::{HighlightStream, HighlightedLine, Highlighters};
+pub use image::{BlockImage, ImageProtocol};
+pub use pager::Pager;
+pub use progress::Stream;
+pub use report::BlockReport;
+pub use resources::ResourceUsage;
+pub use selection::{RectSelection, SemanticUnit};
+pub use status::{FailureSummary, StatusBadge};
+pub use view::{DisplayLine, OutputView, VisualRow, WrapMode};
 
 /// Block manager that stores and manages terminal UI blocks
 pub struct BlockManager<A> {
     state: Arc<Mutex<A>>,
     blocks: Vec<Block>,
+    /// AI answers, kept apart from command blocks and history
+    ai_blocks: Vec<AiBlock>,
 }
 
 impl<A> BlockManager<A> {
@@ -24,6 +56,7 @@ impl<A> BlockManager<A> {
         Self {
             state,
             blocks: Vec::new(),
+            ai_blocks: Vec::new(),
         }
     }
 
@@ -38,4 +71,67 @@ impl<A> BlockManager<A> {
     pub fn get_current_block(&self) -> Option<&Block> {
         self.blocks.last()
     }
+
+    /// Attach an image emitted by the running command to the active block
+    pub fn attach_image(&mut self, image: BlockImage) -> bool {
+        match self.blocks.last_mut() {
+            Some(block) => {
+                block.attach_image(image);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Text of a block-level semantic unit (its command line or its output)
+    pub fn select(&self, id: usize, unit: SemanticUnit) -> Option<String> {
+        let block = self.get_block(id)?;
+        match unit {
+            SemanticUnit::Command => Some(block.command.raw.clone()),
+            SemanticUnit::Output => Some(block.output_text()),
+            SemanticUnit::Word | SemanticUnit::Path => None,
+        }
+    }
+
+    /// Failed commands of the session with their durations
+    pub fn failure_summary(&self) -> FailureSummary {
+        FailureSummary::from_blocks(&self.blocks)
+    }
+
+    /// Start an AI block that the provider's response streams into
+    pub fn start_ai_block(&mut self, title: &str, prompt: &str) -> usize {
+        let id = self.ai_blocks.len();
+        self.ai_blocks.push(AiBlock::new(id, title, prompt));
+        id
+    }
+
+    pub fn get_ai_block(&self, id: usize) -> Option<&AiBlock> {
+        self.ai_blocks.get(id)
+    }
+
+    pub fn get_ai_block_mut(&mut self, id: usize) -> Option<&mut AiBlock> {
+        self.ai_blocks.get_mut(id)
+    }
+
+    /// Open the built-in pager over a block's output
+    pub fn open_pager(&self, id: usize, height: usize) -> Option<Pager> {
+        self.get_block(id).map(|block| Pager::new(block, height))
+    }
+
+    /// Enter keyboard copy mode over a block's output
+    pub fn open_copy_mode(&self, id: usize, height: usize, keys: CopyModeKeys) -> Option<CopyMode> {
+        self.get_block(id).map(|block| CopyMode::new(block, height, keys))
+    }
+
+    /// Block before or after `current` for keyboard focus; from no focus,
+    /// backwards starts at the latest block
+    pub fn adjacent_block(&self, current: Option<usize>, forward: bool) -> Option<usize> {
+        let last = self.blocks.len().checked_sub(1)?;
+        match (current, forward) {
+            (None, false) => Some(last),
+            (None, true) => None,
+            (Some(id), false) => id.checked_sub(1),
+            (Some(id), true) => (id < last).then_some(id + 1),
+        }
+    }
 }
@@ -60,6 +60,32 @@ impl From<process::Output> for Output {
     }
 }
 
+/// Drop ANSI escape sequences so lines can be searched and measured
+pub(crate) fn strip_escapes(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            out.push(c);
+            continue;
+        }
+        if chars.peek() == Some(&'[') {
+            chars.next();
+            // Skip parameters up to the final byte
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        } else {
+            chars.next();
+        }
+    }
+
+    out
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,4 +101,9 @@ mod tests {
         assert_eq!(output.stderr_string(), "Error message");
         assert!(output.success());
     }
+
+    #[test]
+    fn test_strip_escapes() {
+        assert_eq!(strip_escapes("\x1b[1;31merror\x1b[0m: x"), "error: x");
+    }
 }
@@ -0,0 +1,325 @@
+use crate::block::Block;
+use crate::output::strip_escapes;
+
+/// Keys understood by the pager and copy mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagerKey {
+    Char(char),
+    /// A character pressed with Ctrl held
+    Ctrl(char),
+    /// A character pressed with Alt held
+    Alt(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+}
+
+/// What the caller should do after a key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagerAction {
+    /// The view changed and should be redrawn
+    Redraw,
+    /// The key was not bound
+    Ignored,
+    /// Close the pager
+    Quit,
+}
+
+/// A line ready to be drawn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PagerLine<'a> {
+    /// 1-based line number
+    pub number: usize,
+    pub text: &'a str,
+    /// Byte ranges of search matches within `text`
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// Built-in pager over a block's output, with vi-style navigation and search
+pub struct Pager {
+    block_id: usize,
+    lines: Vec<String>,
+    /// First visible line
+    top: usize,
+    height: usize,
+    line_numbers: bool,
+    query: String,
+    /// Query being typed after `/` or `?`
+    search_input: Option<String>,
+    /// Search direction of the last query
+    search_forward: bool,
+    /// Line index of the current match
+    current_match: Option<usize>,
+    /// Pending `g` of a `gg`
+    pending_g: bool,
+}
+
+impl Pager {
+    /// Open a pager over the stdout and stderr of a block
+    pub fn new(block: &Block, height: usize) -> Self {
+        Self::from_text(block.id, &block.output_text(), height)
+    }
+
+    fn from_text(block_id: usize, text: &str, height: usize) -> Self {
+        Self {
+            block_id,
+            lines: text.lines().map(strip_escapes).collect(),
+            top: 0,
+            height: height.max(1),
+            line_numbers: true,
+            query: String::new(),
+            search_input: None,
+            search_forward: true,
+            current_match: None,
+            pending_g: false,
+        }
+    }
+
+    pub fn block_id(&self) -> usize {
+        self.block_id
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// First visible line (0-based)
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    pub fn show_line_numbers(&self) -> bool {
+        self.line_numbers
+    }
+
+    /// Update the number of visible rows, e.g. after a resize
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height.max(1);
+        self.top = self.top.min(self.max_top());
+    }
+
+    /// Whether a search query is being typed
+    pub fn is_searching(&self) -> bool {
+        self.search_input.is_some()
+    }
+
+    pub fn handle_key(&mut self, key: PagerKey) -> PagerAction {
+        if let Some(input) = self.search_input.as_mut() {
+            match key {
+                PagerKey::Char(c) => input.push(c),
+                PagerKey::Backspace => {
+                    if input.pop().is_none() {
+                        self.search_input = None;
+                    }
+                }
+                PagerKey::Enter => {
+                    let query = self.search_input.take().unwrap_or_default();
+                    if !query.is_empty() {
+                        self.query = query;
+                    }
+                    self.current_match = None;
+                    self.find_next(self.search_forward);
+                }
+                PagerKey::Escape => self.search_input = None,
+                _ => return PagerAction::Ignored,
+            }
+            return PagerAction::Redraw;
+        }
+
+        let pending_g = std::mem::replace(&mut self.pending_g, false);
+        let half_page = (self.height / 2).max(1);
+
+        match key {
+            PagerKey::Char('q') | PagerKey::Escape => return PagerAction::Quit,
+            PagerKey::Char('j') | PagerKey::Down | PagerKey::Enter => self.scroll_down(1),
+            PagerKey::Char('k') | PagerKey::Up => self.scroll_up(1),
+            PagerKey::Ctrl('d') => self.scroll_down(half_page),
+            PagerKey::Ctrl('u') => self.scroll_up(half_page),
+            PagerKey::Char(' ') | PagerKey::Char('f') | PagerKey::Ctrl('f') | PagerKey::PageDown => {
+                self.scroll_down(self.height)
+            }
+            PagerKey::Char('b') | PagerKey::Ctrl('b') | PagerKey::PageUp => {
+                self.scroll_up(self.height)
+            }
+            PagerKey::Char('g') if pending_g => self.top = 0,
+            PagerKey::Char('g') => self.pending_g = true,
+            PagerKey::Home => self.top = 0,
+            PagerKey::Char('G') | PagerKey::End => self.top = self.max_top(),
+            PagerKey::Char('/') => {
+                self.search_forward = true;
+                self.search_input = Some(String::new());
+            }
+            PagerKey::Char('?') => {
+                self.search_forward = false;
+                self.search_input = Some(String::new());
+            }
+            PagerKey::Char('n') => self.find_next(self.search_forward),
+            PagerKey::Char('N') => self.find_next(!self.search_forward),
+            PagerKey::Char('#') => self.line_numbers = !self.line_numbers,
+            _ => return PagerAction::Ignored,
+        }
+
+        PagerAction::Redraw
+    }
+
+    /// Lines currently in view, with search matches marked
+    pub fn visible_lines(&self) -> Vec<PagerLine<'_>> {
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(self.top)
+            .take(self.height)
+            .map(|(index, text)| PagerLine {
+                number: index + 1,
+                text,
+                matches: self.matches_in(text),
+            })
+            .collect()
+    }
+
+    /// Width needed for the line number gutter
+    pub fn gutter_width(&self) -> usize {
+        if self.line_numbers {
+            self.lines.len().max(1).to_string().len() + 1
+        } else {
+            0
+        }
+    }
+
+    /// Bottom status line, like `less`
+    pub fn status_line(&self) -> String {
+        if let Some(input) = &self.search_input {
+            let prompt = if self.search_forward { '/' } else { '?' };
+            return format!("{}{}", prompt, input);
+        }
+
+        let last = (self.top + self.height).min(self.lines.len());
+        let position = if last >= self.lines.len() {
+            "(END)".to_string()
+        } else {
+            format!("{}%", last * 100 / self.lines.len().max(1))
+        };
+
+        if !self.query.is_empty() && self.current_match.is_none() {
+            format!("Pattern not found: {}  {}", self.query, position)
+        } else {
+            format!("lines {}-{}/{} {}", self.top + 1, last, self.lines.len(), position)
+        }
+    }
+
+    fn max_top(&self) -> usize {
+        self.lines.len().saturating_sub(self.height)
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        self.top = (self.top + n).min(self.max_top());
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        self.top = self.top.saturating_sub(n);
+    }
+
+    /// Move to the next line matching the query, wrapping around
+    fn find_next(&mut self, forward: bool) {
+        if self.query.is_empty() || self.lines.is_empty() {
+            return;
+        }
+
+        let count = self.lines.len();
+        let start = self.current_match.unwrap_or(if forward {
+            self.top.wrapping_sub(1)
+        } else {
+            self.top
+        });
+
+        for step in 1..=count {
+            let index = if forward {
+                start.wrapping_add(step) % count
+            } else {
+                (start + count * 2 - step) % count
+            };
+            if !self.matches_in(&self.lines[index]).is_empty() {
+                self.current_match = Some(index);
+                if index < self.top || index >= self.top + self.height {
+                    self.top = index.min(self.max_top());
+                }
+                return;
+            }
+        }
+
+        self.current_match = None;
+    }
+
+    /// Case-insensitive unless the query has an uppercase letter
+    fn matches_in(&self, text: &str) -> Vec<(usize, usize)> {
+        if self.query.is_empty() {
+            return Vec::new();
+        }
+
+        let smart_case = self.query.chars().any(|c| c.is_uppercase());
+        let (haystack, needle) = if smart_case {
+            (text.to_string(), self.query.clone())
+        } else {
+            (text.to_ascii_lowercase(), self.query.to_ascii_lowercase())
+        };
+
+        haystack
+            .match_indices(&needle)
+            .map(|(start, found)| (start, start + found.len()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager(lines: usize, height: usize) -> Pager {
+        let text: Vec<String> = (1..=lines).map(|i| format!("line {}", i)).collect();
+        Pager::from_text(0, &text.join("\n"), height)
+    }
+
+    #[test]
+    fn test_navigation() {
+        let mut pager = pager(20, 5);
+        pager.handle_key(PagerKey::Char('j'));
+        assert_eq!(pager.top(), 1);
+
+        pager.handle_key(PagerKey::Char('G'));
+        assert_eq!(pager.top(), 15);
+        assert!(pager.status_line().ends_with("(END)"));
+
+        pager.handle_key(PagerKey::Char('g'));
+        pager.handle_key(PagerKey::Char('g'));
+        assert_eq!(pager.top(), 0);
+
+        pager.handle_key(PagerKey::Ctrl('d'));
+        assert_eq!(pager.top(), 2);
+        assert_eq!(pager.handle_key(PagerKey::Char('q')), PagerAction::Quit);
+    }
+
+    #[test]
+    fn test_search() {
+        let mut pager = pager(20, 5);
+        for key in "/line 1".chars().map(PagerKey::Char) {
+            pager.handle_key(key);
+        }
+        assert_eq!(pager.status_line(), "/line 1");
+        pager.handle_key(PagerKey::Enter);
+
+        // "line 1" is visible already; the next match is "line 10"
+        assert_eq!(pager.visible_lines()[0].matches, vec![(0, 6)]);
+        pager.handle_key(PagerKey::Char('n'));
+        assert_eq!(pager.top(), 9);
+        pager.handle_key(PagerKey::Char('N'));
+        assert_eq!(pager.current_match, Some(0));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::output::strip_escapes;
+
+/// Output stream of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Line buffer of one stream; lines rewritten with `\r` are never stored
+#[derive(Debug, Clone, Default)]
+struct LineFilter {
+    line: Vec<u8>,
+    /// The previous byte was `\r`; the next decides between CRLF and a rewrite
+    cr_pending: bool,
+}
+
+/// Detects `\r`-rewritten progress output, tracks the reported progress and
+/// drops the intermediate frames from what gets stored
+#[derive(Debug, Clone, Default)]
+pub struct ProgressTracker {
+    stdout: LineFilter,
+    stderr: LineFilter,
+    /// Latest fraction (0.0-1.0) per task; docker reports one task per layer
+    tasks: Vec<(String, f32)>,
+    /// Number of intermediate frames that were not stored
+    suppressed: usize,
+}
+
+impl ProgressTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed raw output, returning the bytes that should be stored
+    pub fn feed(&mut self, stream: Stream, data: &[u8]) -> Vec<u8> {
+        let mut filter = std::mem::take(self.filter_mut(stream));
+        let mut stored = Vec::with_capacity(data.len());
+
+        for &byte in data {
+            if filter.cr_pending {
+                filter.cr_pending = false;
+                if byte == b'\n' {
+                    self.observe(&filter.line);
+                    stored.append(&mut filter.line);
+                    stored.extend_from_slice(b"\r\n");
+                    continue;
+                }
+                // A bare CR: the line is being redrawn
+                self.observe(&filter.line);
+                filter.line.clear();
+                self.suppressed += 1;
+            }
+
+            match byte {
+                b'\r' => filter.cr_pending = true,
+                b'\n' => {
+                    self.observe(&filter.line);
+                    stored.append(&mut filter.line);
+                    stored.push(b'\n');
+                }
+                _ => filter.line.push(byte),
+            }
+        }
+
+        *self.filter_mut(stream) = filter;
+        stored
+    }
+
+    /// Flush the unterminated last line of a stream when the command ends
+    pub fn finish(&mut self, stream: Stream) -> Vec<u8> {
+        let filter = self.filter_mut(stream);
+        filter.cr_pending = false;
+        std::mem::take(&mut filter.line)
+    }
+
+    /// Aggregate progress across tasks, if any progress was reported
+    pub fn fraction(&self) -> Option<f32> {
+        if self.tasks.is_empty() {
+            return None;
+        }
+        let total: f32 = self.tasks.iter().map(|(_, fraction)| fraction).sum();
+        Some(total / self.tasks.len() as f32)
+    }
+
+    /// Number of progress frames dropped from the stored output
+    pub fn suppressed_frames(&self) -> usize {
+        self.suppressed
+    }
+
+    /// Text progress bar for the block header, e.g. `[#####-----]  50%`
+    pub fn render_bar(&self, width: usize) -> Option<String> {
+        let fraction = self.fraction()?;
+        let filled = ((fraction * width as f32).round() as usize).min(width);
+        Some(format!(
+            "[{}{}] {:>3}%",
+            "#".repeat(filled),
+            "-".repeat(width - filled),
+            (fraction * 100.0).round() as u32
+        ))
+    }
+
+    fn filter_mut(&mut self, stream: Stream) -> &mut LineFilter {
+        match stream {
+            Stream::Stdout => &mut self.stdout,
+            Stream::Stderr => &mut self.stderr,
+        }
+    }
+
+    fn observe(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        if let Some(fraction) = parse_progress(&line) {
+            let task = task_key(&line);
+            match self.tasks.iter_mut().find(|(key, _)| *key == task) {
+                Some(entry) => entry.1 = fraction,
+                None => self.tasks.push((task, fraction)),
+            }
+        }
+    }
+}
+
+/// Docker prints `<layer id>: Downloading ...`; everything else is one task
+fn task_key(line: &str) -> String {
+    match line.split_once(':') {
+        Some((id, _)) if id.len() >= 12 && id.chars().all(|c| c.is_ascii_hexdigit()) => {
+            id.to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+/// Parse a progress fraction from a line: `42%`, `12/240` or `1.2MB/4.8MB`
+pub fn parse_progress(line: &str) -> Option<f32> {
+    let line = strip_escapes(line);
+
+    if let Some(percent) = last_percentage(&line) {
+        return Some((percent / 100.0).clamp(0.0, 1.0));
+    }
+
+    line.split_whitespace().rev().find_map(|token| {
+        let token = token.trim_matches(|c: char| matches!(c, '[' | ']' | '(' | ')' | ',' | ':'));
+        let (done, total) = token.split_once('/')?;
+        let done = parse_quantity(done)?;
+        let total = parse_quantity(total)?;
+        (total > 0.0 && done <= total).then(|| done / total)
+    })
+}
+
+fn last_percentage(line: &str) -> Option<f32> {
+    let bytes = line.as_bytes();
+    let end = line.rfind('%')?;
+    let mut start = end;
+    while start > 0 && (bytes[start - 1].is_ascii_digit() || bytes[start - 1] == b'.') {
+        start -= 1;
+    }
+    line[start..end].parse().ok()
+}
+
+/// A number with an optional byte-size unit, e.g. `12`, `3.5MB`, `20KiB`
+fn parse_quantity(text: &str) -> Option<f32> {
+    let split = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let value: f32 = number.parse().ok()?;
+    let scale = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "kb" | "kib" | "k" => 1024.0,
+        "mb" | "mib" | "m" => 1024.0 * 1024.0,
+        "gb" | "gib" | "g" => 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    Some(value * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(parse_progress("Downloading  42% |####  |"), Some(0.42));
+        assert_eq!(parse_progress("   Building [=====>   ] 12/48: serde"), Some(0.25));
+        assert_eq!(
+            parse_progress("a3ed95caeb02: Downloading [==>  ]  1MB/4MB"),
+            Some(0.25)
+        );
+        assert_eq!(parse_progress("see docs/README"), None);
+    }
+
+    #[test]
+    fn test_suppresses_rewritten_lines() {
+        let mut tracker = ProgressTracker::new();
+        let mut stored = tracker.feed(Stream::Stderr, b"start\n 10%\r 50%");
+        assert_eq!(tracker.fraction(), Some(0.1));
+        stored.extend(tracker.feed(Stream::Stderr, b"\r100%\r\ndone\n"));
+
+        assert_eq!(stored, b"start\n100%\r\ndone\n");
+        assert_eq!(tracker.suppressed_frames(), 2);
+        assert_eq!(tracker.render_bar(4).as_deref(), Some("[####] 100%"));
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::block::Block;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::output::strip_escapes;
+
+/// A finished block as printed by `voidcli run`, for CI and scripts
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockReport {
+    pub command: String,
+    /// Output as displayed: escape sequences and progress redraws removed
+    pub output: String,
+    pub exit_code: Option<i32>,
+    pub duration_ms: Option<u64>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl BlockReport {
+    pub fn new(block: &Block) -> Self {
+        let mut output: String = block
+            .output_text()
+            .lines()
+            .map(|line| strip_escapes(line) + "\n")
+            .collect();
+        output.truncate(output.trim_end().len());
+        Self {
+            command: block.command.raw.clone(),
+            output,
+            exit_code: block.exit_code,
+            duration_ms: block.duration_ms,
+            diagnostics: block.diagnostics.clone(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// The output followed by the diagnostics in `file:line:col` form
+    pub fn to_text(&self) -> String {
+        let mut text = self.output.clone();
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        if !self.diagnostics.is_empty() {
+            text.push_str(&format!("\n{} diagnostic(s):\n", self.diagnostics.len()));
+        }
+        for diagnostic in &self.diagnostics {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note => "note",
+            };
+            text.push_str(&format!(
+                "{}:{}:{}: {}: {}\n",
+                diagnostic.path,
+                diagnostic.line,
+                diagnostic.column.unwrap_or(1),
+                severity,
+                diagnostic.message
+            ));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+    use crate::diagnostics::DiagnosticParser;
+    use crate::progress::Stream;
+    use config::DiagnosticsConfig;
+
+    #[test]
+    fn test_report() {
+        let mut block = Block::new(0, Command::new("cargo build"));
+        block.append_output(
+            Stream::Stdout,
+            b"\x1b[32mCompiling\x1b[0m app\r\n 50%\r100%\r\nsrc/main.rs:3:5: error: oops\r\n",
+        );
+        block.finish(101, 1200);
+        block.analyze(&DiagnosticParser::new(&DiagnosticsConfig::default()));
+
+        let report = BlockReport::new(&block);
+        assert_eq!(report.output, "Compiling app\n100%\nsrc/main.rs:3:5: error: oops");
+        assert_eq!(report.exit_code, Some(101));
+        assert_eq!(report.diagnostics.len(), 1);
+        assert!(report.to_text().ends_with("\n1 diagnostic(s):\nsrc/main.rs:3:5: error: oops\n"));
+    }
+}
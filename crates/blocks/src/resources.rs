@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// CPU time and peak memory of a finished command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceUsage {
+    pub user_ms: u64,
+    pub system_ms: u64,
+    /// Peak resident set size of the largest process, in KiB
+    pub max_rss_kb: u64,
+}
+
+impl ResourceUsage {
+    pub fn cpu_ms(&self) -> u64 {
+        self.user_ms + self.system_ms
+    }
+
+    /// Share of a core kept busy over the command's wall time, e.g. 3.5 for
+    /// a build using three and a half cores
+    pub fn cpu_load(&self, duration_ms: u64) -> Option<f64> {
+        (duration_ms > 0).then(|| self.cpu_ms() as f64 / duration_ms as f64)
+    }
+
+    /// e.g. "cpu 2.10s (user 1.80s, sys 0.30s), max rss 512.0 MiB"
+    pub fn summary(&self) -> String {
+        format!(
+            "cpu {} (user {}, sys {}), max rss {}",
+            seconds(self.cpu_ms()),
+            seconds(self.user_ms),
+            seconds(self.system_ms),
+            size(self.max_rss_kb)
+        )
+    }
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{:.2}s", ms as f64 / 1000.0)
+}
+
+fn size(kb: u64) -> String {
+    match kb {
+        0..=1023 => format!("{} KiB", kb),
+        1024..=1048575 => format!("{:.1} MiB", kb as f64 / 1024.0),
+        _ => format!("{:.1} GiB", kb as f64 / 1048576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let usage = ResourceUsage {
+            user_ms: 1800,
+            system_ms: 300,
+            max_rss_kb: 512 * 1024,
+        };
+        assert_eq!(usage.summary(), "cpu 2.10s (user 1.80s, sys 0.30s), max rss 512.0 MiB");
+        assert_eq!(usage.cpu_load(600), Some(3.5));
+        assert_eq!(usage.cpu_load(0), None);
+    }
+}
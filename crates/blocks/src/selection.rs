@@ -0,0 +1,173 @@
+use std::ops::{Range, RangeInclusive};
+
+/// Semantic units a selection can be expanded to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticUnit {
+    /// The word under the cursor
+    Word,
+    /// The path or URL under the cursor
+    Path,
+    /// The command line of the block under the cursor
+    Command,
+    /// The whole output of the block under the cursor
+    Output,
+}
+
+impl SemanticUnit {
+    pub const ALL: [SemanticUnit; 4] = [
+        SemanticUnit::Word,
+        SemanticUnit::Path,
+        SemanticUnit::Command,
+        SemanticUnit::Output,
+    ];
+
+    /// Action name, as used in keybindings and the command palette
+    pub fn action_name(&self) -> &'static str {
+        match self {
+            SemanticUnit::Word => "select-word",
+            SemanticUnit::Path => "select-path",
+            SemanticUnit::Command => "select-command",
+            SemanticUnit::Output => "select-output",
+        }
+    }
+
+    pub fn from_action_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|unit| unit.action_name() == name)
+    }
+}
+
+/// A selection within one line, in character columns (end is exclusive)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineSelection {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// Expand the cursor column to the word or path containing it
+///
+/// Only `Word` and `Path` are meaningful within a line; block-level units
+/// are resolved by `BlockManager::select`.
+pub fn select_in_line(line: &str, col: usize, unit: SemanticUnit) -> Option<LineSelection> {
+    let chars: Vec<char> = line.chars().collect();
+    let is_part: fn(char) -> bool = match unit {
+        SemanticUnit::Word => is_word_char,
+        SemanticUnit::Path => is_path_char,
+        _ => return None,
+    };
+
+    if col >= chars.len() || !is_part(chars[col]) {
+        return None;
+    }
+
+    let mut start = col;
+    while start > 0 && is_part(chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = col + 1;
+    while end < chars.len() && is_part(chars[end]) {
+        end += 1;
+    }
+
+    // Sentence punctuation after a path is almost never part of it
+    if unit == SemanticUnit::Path {
+        while end > col + 1 && matches!(chars[end - 1], '.' | ',' | ':' | ';' | '!' | '?') {
+            end -= 1;
+        }
+    }
+
+    Some(LineSelection {
+        start,
+        end,
+        text: chars[start..end].iter().collect(),
+    })
+}
+
+/// A rectangle of cells, as selected by alt-dragging over the grid or
+/// blockwise visual mode; positions are (row, column) in characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RectSelection {
+    pub anchor: (usize, usize),
+    pub head: (usize, usize),
+}
+
+impl RectSelection {
+    pub fn new(anchor: (usize, usize), head: (usize, usize)) -> Self {
+        Self { anchor, head }
+    }
+
+    /// Move the corner under the pointer while dragging
+    pub fn extend_to(&mut self, head: (usize, usize)) {
+        self.head = head;
+    }
+
+    pub fn rows(&self) -> RangeInclusive<usize> {
+        self.anchor.0.min(self.head.0)..=self.anchor.0.max(self.head.0)
+    }
+
+    /// Selected columns, end exclusive
+    pub fn columns(&self) -> Range<usize> {
+        self.anchor.1.min(self.head.1)..self.anchor.1.max(self.head.1) + 1
+    }
+
+    /// Column-wise copy: the selected columns of each row, one line per row
+    /// with trailing blanks removed
+    pub fn text<L: AsRef<[char]>>(&self, lines: &[L]) -> String {
+        let columns = self.columns();
+        self.rows()
+            .map(|row| {
+                let chars = lines.get(row).map_or(&[][..], |line| line.as_ref());
+                let end = columns.end.min(chars.len());
+                let text: String = chars[columns.start.min(end)..end].iter().collect();
+                text.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_path_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, '"' | '\'' | '`' | '<' | '>' | '(' | ')' | '[' | ']' | '{' | '}' | '|')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_word_and_path() {
+        let line = "error: see src/main.rs:10:5, or https://example.com/a?b=1.";
+
+        let word = select_in_line(line, 2, SemanticUnit::Word).unwrap();
+        assert_eq!(word.text, "error");
+        assert_eq!((word.start, word.end), (0, 5));
+
+        let path = select_in_line(line, 15, SemanticUnit::Path).unwrap();
+        assert_eq!(path.text, "src/main.rs:10:5");
+
+        let url = select_in_line(line, 40, SemanticUnit::Path).unwrap();
+        assert_eq!(url.text, "https://example.com/a?b=1");
+
+        assert_eq!(select_in_line(line, 6, SemanticUnit::Word), None);
+        assert_eq!(
+            SemanticUnit::from_action_name("select-output"),
+            Some(SemanticUnit::Output)
+        );
+    }
+
+    #[test]
+    fn test_rect_selection() {
+        let lines: Vec<Vec<char>> = ["PID  USER  CMD", "1    root  init", "42   me"]
+            .iter()
+            .map(|line| line.chars().collect())
+            .collect();
+        let mut selection = RectSelection::new((0, 5), (0, 5));
+        selection.extend_to((2, 9));
+        assert_eq!(selection.columns(), 5..10);
+        assert_eq!(selection.text(&lines), "USER\nroot\nme");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::block::Block;
+
+/// Outcome of a block, for the badge drawn in its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusBadge {
+    Running,
+    Succeeded,
+    Failed { code: i32 },
+    /// Killed by a signal (exit codes above 128)
+    Interrupted { signal: i32 },
+}
+
+impl StatusBadge {
+    pub fn from_exit_code(exit_code: Option<i32>) -> Self {
+        match exit_code {
+            None => StatusBadge::Running,
+            Some(0) => StatusBadge::Succeeded,
+            Some(code) if code > 128 => StatusBadge::Interrupted { signal: code - 128 },
+            Some(code) => StatusBadge::Failed { code },
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            StatusBadge::Running => "…",
+            StatusBadge::Succeeded => "✓",
+            StatusBadge::Failed { .. } => "✗",
+            StatusBadge::Interrupted { .. } => "⚡",
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            StatusBadge::Running => "running".to_string(),
+            StatusBadge::Succeeded => "ok".to_string(),
+            StatusBadge::Failed { code } => format!("exit {}", code),
+            StatusBadge::Interrupted { signal } => match signal {
+                2 => "interrupted".to_string(),
+                9 => "killed".to_string(),
+                15 => "terminated".to_string(),
+                _ => format!("signal {}", signal),
+            },
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, StatusBadge::Failed { .. } | StatusBadge::Interrupted { .. })
+    }
+}
+
+/// A failed command of the session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedCommand {
+    pub block_id: usize,
+    pub command: String,
+    pub badge: StatusBadge,
+    pub duration_ms: Option<u64>,
+}
+
+/// Failed commands of a session, for the status bar and exports
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FailureSummary {
+    pub failures: Vec<FailedCommand>,
+    /// Number of finished blocks
+    pub finished: usize,
+}
+
+impl FailureSummary {
+    pub fn from_blocks<'a>(blocks: impl IntoIterator<Item = &'a Block>) -> Self {
+        let mut summary = Self::default();
+        for block in blocks {
+            let badge = block.status_badge();
+            if badge == StatusBadge::Running {
+                continue;
+            }
+            summary.finished += 1;
+            if badge.is_failure() {
+                summary.failures.push(FailedCommand {
+                    block_id: block.id,
+                    command: block.command.raw.clone(),
+                    badge,
+                    duration_ms: block.duration_ms,
+                });
+            }
+        }
+        summary
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Short text for a status bar widget, e.g. `2/14 failed`
+    pub fn status_text(&self) -> String {
+        format!("{}/{} failed", self.failures.len(), self.finished)
+    }
+
+    /// Markdown list of the failures, for exports
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("### Failed commands ({})\n\n", self.status_text());
+        for failure in &self.failures {
+            let duration = failure
+                .duration_ms
+                .map(|ms| format!(", {:.1}s", ms as f64 / 1000.0))
+                .unwrap_or_default();
+            markdown.push_str(&format!(
+                "- `{}` ({}{})\n",
+                failure.command,
+                failure.badge.label(),
+                duration
+            ));
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::Command;
+
+    fn finished(id: usize, raw: &str, code: i32) -> Block {
+        let mut block = Block::new(id, Command::new(raw));
+        block.finish(code, 1500);
+        block
+    }
+
+    #[test]
+    fn test_failure_summary() {
+        let blocks = vec![
+            finished(0, "cargo build", 0),
+            finished(1, "cargo test", 101),
+            finished(2, "sleep 100", 130),
+            Block::new(3, Command::new("make")),
+        ];
+
+        assert_eq!(blocks[2].status_badge().label(), "interrupted");
+        assert_eq!(blocks[3].status_badge(), StatusBadge::Running);
+
+        let summary = FailureSummary::from_blocks(&blocks);
+        assert_eq!(summary.status_text(), "2/3 failed");
+        assert_eq!(
+            summary.to_markdown(),
+            "### Failed commands (2/3 failed)\n\n- `cargo test` (exit 101, 1.5s)\n- `sleep 100` (interrupted, 1.5s)\n"
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use config::BlocksConfig;
+
+use crate::block::Block;
+
+/// A line as displayed; runs of identical lines share one entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayLine {
+    pub text: String,
+    /// Number of identical consecutive lines this entry stands for
+    pub repeat: usize,
+    /// Index of the first original line in the block output
+    pub first_line: usize,
+}
+
+impl DisplayLine {
+    /// Text with the repeat counter appended
+    pub fn render(&self) -> String {
+        if self.repeat > 1 {
+            format!("{}  × {}", self.text, self.repeat)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// How display lines are laid out into rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapMode {
+    /// Soft-wrap at the given width
+    Wrap { width: usize },
+    /// One row per line, scrolled horizontally by `scroll_x` columns
+    NoWrap { width: usize, scroll_x: usize },
+}
+
+/// A row on screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisualRow {
+    /// Index into `OutputView::lines`
+    pub line: usize,
+    pub text: String,
+    /// The row continues the previous row's line
+    pub continuation: bool,
+}
+
+/// Original lines of an oversized block that are not loaded yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HiddenLines {
+    /// Display index the expand marker is drawn before
+    pub position: usize,
+    /// First hidden original line
+    pub start: usize,
+    /// One past the last hidden original line
+    pub end: usize,
+}
+
+impl HiddenLines {
+    pub fn count(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Text of the expand marker
+    pub fn marker(&self) -> String {
+        format!("{} lines hidden — expand", self.count())
+    }
+}
+
+/// Display model of a block's output; the stored output keeps the originals
+#[derive(Debug, Clone)]
+pub struct OutputView {
+    lines: Vec<DisplayLine>,
+    /// Number of original lines represented by `lines`
+    loaded_lines: usize,
+    hidden: Option<HiddenLines>,
+    collapse_repeated: bool,
+    /// Wrap points per display line, computed on demand for `wrap_width`
+    wrap_cache: Vec<Option<Vec<usize>>>,
+    wrap_width: usize,
+}
+
+impl OutputView {
+    pub fn new(block: &Block, config: &BlocksConfig) -> Self {
+        Self::from_text(&block.output_text(), config)
+    }
+
+    pub fn from_text(text: &str, config: &BlocksConfig) -> Self {
+        let total = text.lines().count();
+        let preview = config.truncated_preview_lines;
+        let mut view = Self {
+            lines: Vec::new(),
+            loaded_lines: 0,
+            hidden: None,
+            collapse_repeated: config.collapse_repeated_lines,
+            wrap_cache: Vec::new(),
+            wrap_width: 0,
+        };
+
+        if total > config.truncate_after_lines && preview * 2 < total {
+            view.push_lines(text.lines().enumerate().take(preview));
+            view.hidden = Some(HiddenLines {
+                position: view.lines.len(),
+                start: preview,
+                end: total - preview,
+            });
+            view.push_lines(text.lines().enumerate().skip(total - preview));
+        } else {
+            view.push_lines(text.lines().enumerate());
+        }
+
+        view
+    }
+
+    fn push_lines<'a>(&mut self, lines: impl Iterator<Item = (usize, &'a str)>) {
+        let mut collapsed = collapse(lines, self.collapse_repeated);
+        self.loaded_lines += collapsed.iter().map(|line| line.repeat).sum::<usize>();
+        self.lines.append(&mut collapsed);
+    }
+
+    pub fn lines(&self) -> &[DisplayLine] {
+        &self.lines
+    }
+
+    /// The not yet loaded middle of a truncated block
+    pub fn hidden(&self) -> Option<HiddenLines> {
+        self.hidden
+    }
+
+    /// Load up to `limit` hidden lines (all when `None`) from the block output
+    pub fn expand(&mut self, text: &str, limit: Option<usize>) {
+        let Some(hidden) = self.hidden else {
+            return;
+        };
+
+        let count = limit.unwrap_or(usize::MAX).min(hidden.count());
+        let loaded = collapse(
+            text.lines().enumerate().skip(hidden.start).take(count),
+            self.collapse_repeated,
+        );
+        let inserted = loaded.len();
+        self.loaded_lines += count;
+        self.lines.splice(hidden.position..hidden.position, loaded);
+        self.wrap_cache.clear();
+
+        self.hidden = if hidden.start + count < hidden.end {
+            Some(HiddenLines {
+                position: hidden.position + inserted,
+                start: hidden.start + count,
+                end: hidden.end,
+            })
+        } else {
+            None
+        };
+    }
+
+    /// Rows for up to `max_rows` rows starting at display line `first_line`
+    ///
+    /// Wrapping is only computed for the lines that are laid out, and cached
+    /// until the width changes.
+    pub fn visual_rows(
+        &mut self,
+        first_line: usize,
+        max_rows: usize,
+        mode: WrapMode,
+    ) -> Vec<VisualRow> {
+        let mut rows = Vec::new();
+
+        for index in first_line..self.lines.len() {
+            if rows.len() >= max_rows {
+                break;
+            }
+            let text = self.lines[index].render();
+
+            match mode {
+                WrapMode::NoWrap { width, scroll_x } => {
+                    rows.push(VisualRow {
+                        line: index,
+                        text: text.chars().skip(scroll_x).take(width).collect(),
+                        continuation: false,
+                    });
+                }
+                WrapMode::Wrap { width } => {
+                    let breaks = self.wrap_points(index, &text, width);
+                    let chars: Vec<char> = text.chars().collect();
+                    let mut start = 0;
+                    let ends = breaks.iter().copied().chain(std::iter::once(chars.len()));
+                    for (n, end) in ends.enumerate() {
+                        if rows.len() >= max_rows {
+                            break;
+                        }
+                        rows.push(VisualRow {
+                            line: index,
+                            text: chars[start..end].iter().collect(),
+                            continuation: n > 0,
+                        });
+                        start = end;
+                    }
+                }
+            }
+        }
+
+        rows
+    }
+
+    /// Number of rows a display line takes when wrapped at `width`
+    pub fn row_count(&mut self, index: usize, width: usize) -> usize {
+        let text = self.lines[index].render();
+        self.wrap_points(index, &text, width).len() + 1
+    }
+
+    /// Widest line among `range`, for the horizontal scroll extent
+    pub fn max_width(&self, range: std::ops::Range<usize>) -> usize {
+        self.lines[range.start.min(self.lines.len())..range.end.min(self.lines.len())]
+            .iter()
+            .map(|line| line.render().chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn wrap_points(&mut self, index: usize, text: &str, width: usize) -> Vec<usize> {
+        if width != self.wrap_width || self.wrap_cache.len() != self.lines.len() {
+            self.wrap_width = width;
+            self.wrap_cache = vec![None; self.lines.len()];
+        }
+        self.wrap_cache[index]
+            .get_or_insert_with(|| wrap_points(text, width))
+            .clone()
+    }
+
+    /// Number of loaded original lines hidden by collapsing
+    pub fn collapsed_count(&self) -> usize {
+        self.loaded_lines - self.lines.len()
+    }
+}
+
+/// Char offsets where wrapped rows start, preferring to break after spaces
+fn wrap_points(text: &str, width: usize) -> Vec<usize> {
+    let chars: Vec<char> = text.chars().collect();
+    let width = width.max(1);
+    let mut points = Vec::new();
+    let mut start = 0;
+
+    while chars.len() - start > width {
+        let limit = start + width;
+        let end = chars[start + 1..=limit]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map(|pos| start + pos + 2)
+            .unwrap_or(limit);
+        points.push(end);
+        start = end;
+    }
+
+    points
+}
+
+fn collapse<'a>(
+    lines: impl Iterator<Item = (usize, &'a str)>,
+    collapse_repeated: bool,
+) -> Vec<DisplayLine> {
+    let mut collapsed: Vec<DisplayLine> = Vec::new();
+
+    for (index, line) in lines {
+        if collapse_repeated {
+            if let Some(last) = collapsed.last_mut().filter(|last| last.text == line) {
+                last.repeat += 1;
+                continue;
+            }
+        }
+        collapsed.push(DisplayLine {
+            text: line.to_string(),
+            repeat: 1,
+            first_line: index,
+        });
+    }
+
+    collapsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collapse_repeated_lines() {
+        let config = BlocksConfig::default();
+        let view = OutputView::from_text("start\nping\nping\nping\ndone\nping\n", &config);
+
+        let rendered: Vec<String> = view.lines().iter().map(DisplayLine::render).collect();
+        assert_eq!(rendered, vec!["start", "ping  × 3", "done", "ping"]);
+        assert_eq!(view.lines()[2].first_line, 4);
+        assert_eq!(view.collapsed_count(), 2);
+
+        let config = BlocksConfig {
+            collapse_repeated_lines: false,
+            ..BlocksConfig::default()
+        };
+        assert_eq!(OutputView::from_text("a\na\n", &config).lines().len(), 2);
+    }
+
+    #[test]
+    fn test_lazy_expansion() {
+        let config = BlocksConfig {
+            truncate_after_lines: 10,
+            truncated_preview_lines: 2,
+            ..BlocksConfig::default()
+        };
+        let text: String = (0..20).map(|i| format!("{}\n", i)).collect();
+        let mut view = OutputView::from_text(&text, &config);
+
+        let hidden = view.hidden().unwrap();
+        assert_eq!(hidden.marker(), "16 lines hidden — expand");
+        assert_eq!(hidden.position, 2);
+        assert_eq!(view.lines().len(), 4);
+        assert_eq!(view.lines()[2].text, "18");
+
+        view.expand(&text, Some(10));
+        assert_eq!(view.lines()[2].text, "2");
+        assert_eq!(view.hidden().unwrap().count(), 6);
+        assert_eq!(view.hidden().unwrap().position, 12);
+
+        view.expand(&text, None);
+        assert!(view.hidden().is_none());
+        let all: Vec<&str> = view.lines().iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(all, text.lines().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_wrap_modes() {
+        let mut view = OutputView::from_text("the quick brown fox\nab\n", &BlocksConfig::default());
+
+        let rows = view.visual_rows(0, 10, WrapMode::Wrap { width: 10 });
+        let texts: Vec<&str> = rows.iter().map(|row| row.text.as_str()).collect();
+        assert_eq!(texts, vec!["the quick ", "brown fox", "ab"]);
+        assert!(rows[1].continuation);
+        assert_eq!(view.row_count(0, 4), 6);
+
+        let rows = view.visual_rows(0, 1, WrapMode::NoWrap { width: 5, scroll_x: 4 });
+        assert_eq!(rows, vec![VisualRow { line: 0, text: "quick".to_string(), continuation: false }]);
+        assert_eq!(view.max_width(0..2), 19);
+    }
+}
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use config::{WatchConfig, WatchOutput};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Quiet time after a file change before re-running, so an editor saving
+/// several files causes one run
+const CHANGE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// When a watched block runs again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchTrigger {
+    Interval(Duration),
+    /// When something under these paths changes, like `entr`
+    Paths(Vec<PathBuf>),
+}
+
+/// A line of a run's output, with the characters that differ from the
+/// previous run at the same position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffLine {
+    pub text: String,
+    /// Changed character ranges, end exclusive
+    pub changed: Vec<(usize, usize)>,
+}
+
+/// Lines of `new` with what changed since `old`, compared position by
+/// position as `watch -d` does
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    new.lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let before: Vec<char> = old.get(index).copied().unwrap_or("").chars().collect();
+            let mut changed: Vec<(usize, usize)> = Vec::new();
+            for (col, c) in line.chars().enumerate() {
+                if before.get(col) == Some(&c) {
+                    continue;
+                }
+                match changed.last_mut() {
+                    Some((_, end)) if *end == col => *end += 1,
+                    _ => changed.push((col, col + 1)),
+                }
+            }
+            DiffLine {
+                text: line.to_string(),
+                changed,
+            }
+        })
+        .collect()
+}
+
+/// A block re-running its command, the built-in `watch`/`entr`
+pub struct WatchSession {
+    pub block_id: usize,
+    pub command: String,
+    trigger: WatchTrigger,
+    output: WatchOutput,
+    highlight_changes: bool,
+    running: bool,
+    last_started: Option<Instant>,
+    /// A file changed at this time and the command hasn't run since
+    changed_at: Option<Instant>,
+    previous_output: String,
+    runs: usize,
+}
+
+impl WatchSession {
+    pub fn new(
+        block_id: usize,
+        command: &str,
+        trigger: WatchTrigger,
+        config: &WatchConfig,
+    ) -> Self {
+        Self {
+            block_id,
+            command: command.to_string(),
+            trigger,
+            output: config.output,
+            highlight_changes: config.highlight_changes,
+            running: false,
+            last_started: None,
+            changed_at: None,
+            previous_output: String::new(),
+            runs: 0,
+        }
+    }
+
+    /// Watch on the configured interval
+    pub fn every_interval(block_id: usize, command: &str, config: &WatchConfig) -> Self {
+        let interval = Duration::from_secs(config.interval_secs.max(1));
+        Self::new(block_id, command, WatchTrigger::Interval(interval), config)
+    }
+
+    pub fn trigger(&self) -> &WatchTrigger {
+        &self.trigger
+    }
+
+    /// Whether each run replaces the block's output or is appended to it
+    pub fn output(&self) -> WatchOutput {
+        self.output
+    }
+
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Record a change under the watched paths
+    pub fn paths_changed(&mut self, now: Instant) {
+        self.changed_at = Some(now);
+    }
+
+    /// Whether to start the command now
+    pub fn due(&self, now: Instant) -> bool {
+        if self.running {
+            return false;
+        }
+        match (&self.trigger, self.last_started) {
+            (_, None) => true,
+            (WatchTrigger::Interval(interval), Some(started)) => {
+                now.duration_since(started) >= *interval
+            }
+            (WatchTrigger::Paths(_), Some(_)) => self
+                .changed_at
+                .is_some_and(|changed| now.duration_since(changed) >= CHANGE_DEBOUNCE),
+        }
+    }
+
+    /// Time until `due` may turn true, for scheduling a wakeup
+    pub fn next_wakeup(&self, now: Instant) -> Option<Duration> {
+        if self.running {
+            return None;
+        }
+        let at = match (&self.trigger, self.last_started) {
+            (_, None) => now,
+            (WatchTrigger::Interval(interval), Some(started)) => started + *interval,
+            (WatchTrigger::Paths(_), Some(_)) => self.changed_at? + CHANGE_DEBOUNCE,
+        };
+        Some(at.saturating_duration_since(now))
+    }
+
+    pub fn started(&mut self, now: Instant) {
+        self.running = true;
+        self.last_started = Some(now);
+        self.changed_at = None;
+    }
+
+    /// Lines of the finished run, with changes marked when enabled
+    pub fn finished(&mut self, output: &str) -> Vec<DiffLine> {
+        self.running = false;
+        self.runs += 1;
+        let lines = if self.highlight_changes && self.runs > 1 {
+            diff_lines(&self.previous_output, output)
+        } else {
+            diff_lines(output, output)
+        };
+        self.previous_output = output.to_string();
+        lines
+    }
+}
+
+/// Reports changes under watched paths, for `WatchTrigger::Paths`
+pub struct PathWatcher {
+    _watcher: RecommendedWatcher,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl PathWatcher {
+    pub fn new(paths: &[PathBuf]) -> Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .context("Failed to start the file watcher")?;
+        for path in paths {
+            watcher
+                .watch(path, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch {}", path.display()))?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    /// Whether anything was modified, created or removed since the last call
+    pub fn changed(&self) -> bool {
+        // Drain every queued event, not just up to the first change
+        let mut changed = false;
+        for event in self.events.try_iter() {
+            changed |= matches!(event, Ok(event) if !matches!(event.kind, EventKind::Access(_)));
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let lines = diff_lines("load 0.52\nusers 3", "load 0.61\nusers 3\nnew");
+        assert_eq!(lines[0].changed, vec![(7, 9)]);
+        assert!(lines[1].changed.is_empty());
+        assert_eq!(lines[2].changed, vec![(0, 3)]);
+    }
+
+    #[test]
+    fn test_schedule() {
+        let config = WatchConfig::default();
+        let start = Instant::now();
+        let mut session = WatchSession::every_interval(0, "date", &config);
+        assert!(session.due(start));
+        session.started(start);
+        assert!(!session.due(start + Duration::from_secs(5)));
+        let lines = session.finished("12:00:00");
+        assert!(lines[0].changed.is_empty());
+        assert_eq!(session.next_wakeup(start), Some(Duration::from_secs(2)));
+        assert!(session.due(start + Duration::from_secs(2)));
+
+        let trigger = WatchTrigger::Paths(vec![PathBuf::from("src")]);
+        let mut session = WatchSession::new(0, "cargo test", trigger, &config);
+        session.started(start);
+        session.finished("ok");
+        assert!(!session.due(start + Duration::from_secs(60)));
+        session.paths_changed(start + Duration::from_secs(60));
+        assert!(!session.due(start + Duration::from_millis(60_050)));
+        assert!(session.due(start + Duration::from_millis(60_100)));
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use config::AiLimitsConfig;
+
+use super::{AiProvider, AiRequest};
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Rough token count used for budgets: about four characters per token
+fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// Error for a request skipped because a budget in `ai.limits` is spent
+///
+/// Suggestions check for it with `error.is::<BudgetExceeded>()` and show
+/// nothing; actions the user asked for report it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BudgetExceeded;
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AI request budget for this minute is spent")
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
+
+/// Counters for the status bar and `voidcli` diagnostics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AiUsage {
+    /// Calls that reached the provider
+    pub requests: u64,
+    pub cache_hits: u64,
+    /// Requests dropped because a budget was spent
+    pub skipped: u64,
+    pub prompt_tokens: u64,
+    pub response_tokens: u64,
+}
+
+#[derive(Default)]
+struct State {
+    /// Calls in the last minute with their estimated tokens
+    window: VecDeque<(Instant, u64)>,
+    cache: HashMap<AiRequest, String>,
+    cache_order: VecDeque<AiRequest>,
+    usage: AiUsage,
+}
+
+impl State {
+    fn prune(&mut self, now: Instant) {
+        while let Some((at, _)) = self.window.front() {
+            if now.duration_since(*at) < WINDOW {
+                break;
+            }
+            self.window.pop_front();
+        }
+    }
+
+    fn remember(&mut self, request: AiRequest, response: String, capacity: usize) {
+        if capacity == 0 || self.cache.contains_key(&request) {
+            return;
+        }
+        while self.cache_order.len() >= capacity {
+            if let Some(oldest) = self.cache_order.pop_front() {
+                self.cache.remove(&oldest);
+            }
+        }
+        self.cache_order.push_back(request.clone());
+        self.cache.insert(request, response);
+    }
+}
+
+/// Shared layer every AI feature calls through
+///
+/// Identical requests are answered from a cache and calls are held to the
+/// per-minute budgets in `ai.limits`. Over budget the request is skipped
+/// with a [`BudgetExceeded`] error instead of reaching the provider.
+pub struct LimitedProvider {
+    inner: Box<dyn AiProvider>,
+    limits: AiLimitsConfig,
+    state: Mutex<State>,
+}
+
+impl LimitedProvider {
+    pub fn new(inner: Box<dyn AiProvider>, limits: &AiLimitsConfig) -> Self {
+        Self {
+            inner,
+            limits: limits.clone(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn usage(&self) -> AiUsage {
+        self.state.lock().unwrap().usage
+    }
+
+    /// Whether a request of `prompt_tokens` would be skipped right now
+    pub fn over_budget(&self, prompt_tokens: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        state.prune(Instant::now());
+        self.exceeds(&state, prompt_tokens)
+    }
+
+    fn exceeds(&self, state: &State, prompt_tokens: u64) -> bool {
+        let spent: u64 = state.window.iter().map(|(_, tokens)| tokens).sum();
+        state.window.len() >= self.limits.requests_per_minute as usize
+            || spent + prompt_tokens > self.limits.tokens_per_minute
+    }
+}
+
+impl AiProvider for LimitedProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn endpoint(&self) -> Option<&str> {
+        self.inner.endpoint()
+    }
+
+    fn in_process(&self) -> bool {
+        self.inner.in_process()
+    }
+
+    fn stream(&self, request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()> {
+        let prompt_tokens = estimate_tokens(&request.prompt);
+        let slot = {
+            let mut state = self.state.lock().unwrap();
+            if let Some(cached) = state.cache.get(request).cloned() {
+                state.usage.cache_hits += 1;
+                drop(state);
+                on_token(&cached);
+                return Ok(());
+            }
+
+            let now = Instant::now();
+            state.prune(now);
+            if self.exceeds(&state, prompt_tokens) {
+                state.usage.skipped += 1;
+                return Err(BudgetExceeded.into());
+            }
+            state.window.push_back((now, prompt_tokens));
+            state.usage.requests += 1;
+            state.usage.prompt_tokens += prompt_tokens;
+            now
+        };
+
+        // Not holding the lock while the provider streams
+        let mut response = String::new();
+        let result = self.inner.stream(request, &mut |token| {
+            response.push_str(token);
+            on_token(token);
+        });
+
+        let response_tokens = estimate_tokens(&response);
+        let mut state = self.state.lock().unwrap();
+        state.usage.response_tokens += response_tokens;
+        if let Some(entry) = state.window.iter_mut().find(|(at, _)| *at == slot) {
+            entry.1 += response_tokens;
+        }
+        if result.is_ok() {
+            state.remember(request.clone(), response, self.limits.cache_entries);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::AiRequestKind;
+
+    struct Fixed;
+
+    impl AiProvider for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn stream(&self, _request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()> {
+            on_token("ls -la");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cache_and_budget() {
+        let limits = AiLimitsConfig {
+            requests_per_minute: 2,
+            ..AiLimitsConfig::default()
+        };
+        let provider = LimitedProvider::new(Box::new(Fixed), &limits);
+        let ask = |prompt: &str| {
+            let mut response = String::new();
+            let request = AiRequest::new(AiRequestKind::Translate, prompt);
+            provider
+                .stream(&request, &mut |token| response.push_str(token))
+                .map(|_| response)
+        };
+
+        assert_eq!(ask("list files").unwrap(), "ls -la");
+        assert_eq!(ask("list files").unwrap(), "ls -la");
+        assert_eq!(ask("list all files").unwrap(), "ls -la");
+        assert!(ask("show files").unwrap_err().is::<BudgetExceeded>());
+
+        let usage = provider.usage();
+        assert_eq!((usage.requests, usage.cache_hits, usage.skipped), (2, 1, 1));
+        assert!(provider.over_budget(1));
+    }
+}
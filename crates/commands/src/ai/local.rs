@@ -0,0 +1,181 @@
+use std::net::{IpAddr, ToSocketAddrs};
+
+use anyhow::{anyhow, Result};
+
+use super::semantic::Embedder;
+use super::{AiProvider, AiRequest};
+
+/// Host and port of an `http(s)://host[:port]/…` endpoint
+fn host_and_port(endpoint: &str) -> Option<(String, u16)> {
+    let (scheme, rest) = endpoint.split_once("://")?;
+    let default_port = match scheme {
+        "http" => 80,
+        "https" => 443,
+        _ => return None,
+    };
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit('@').next()?;
+
+    if let Some(bracketed) = authority.strip_prefix('[') {
+        // [::1]:11434
+        let (host, after) = bracketed.split_once(']')?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None => default_port,
+        };
+        return Some((host.to_string(), port));
+    }
+    match authority.split_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok()?)),
+        None => Some((authority.to_string(), default_port)),
+    }
+}
+
+/// Whether every address `endpoint` resolves to is on this machine
+///
+/// Resolves the host each time so a name that later points elsewhere is
+/// caught.
+pub fn is_local_endpoint(endpoint: &str) -> bool {
+    let Some((host, port)) = host_and_port(endpoint) else {
+        return false;
+    };
+    if host.is_empty() {
+        return false;
+    }
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return ip.is_loopback();
+    }
+    match (host.as_str(), port).to_socket_addrs() {
+        Ok(addresses) => {
+            let addresses: Vec<_> = addresses.collect();
+            !addresses.is_empty() && addresses.iter().all(|address| address.ip().is_loopback())
+        }
+        Err(_) => false,
+    }
+}
+
+/// Fails unless a backend runs in-process or at a local endpoint
+///
+/// A backend declaring neither is refused, so one that forgot to report its
+/// endpoint cannot slip past local-only mode.
+fn check_local(name: &str, endpoint: Option<&str>, in_process: bool) -> Result<()> {
+    match endpoint {
+        Some(endpoint) if !is_local_endpoint(endpoint) => Err(anyhow!(
+            "Local-only AI mode: refusing to send a request to {}",
+            endpoint
+        )),
+        Some(_) => Ok(()),
+        None if in_process => Ok(()),
+        None => Err(anyhow!(
+            "Local-only AI mode: {} does not say where it sends requests",
+            name
+        )),
+    }
+}
+
+/// Wraps the provider when `ai.local_only` is set, refusing any request
+/// whose backend is not on localhost (Ollama, llamafile)
+pub struct LocalOnlyProvider {
+    inner: Box<dyn AiProvider>,
+}
+
+impl LocalOnlyProvider {
+    pub fn new(inner: Box<dyn AiProvider>) -> Self {
+        Self { inner }
+    }
+}
+
+impl AiProvider for LocalOnlyProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn endpoint(&self) -> Option<&str> {
+        self.inner.endpoint()
+    }
+
+    fn in_process(&self) -> bool {
+        self.inner.in_process()
+    }
+
+    fn stream(&self, request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()> {
+        check_local(self.inner.name(), self.inner.endpoint(), self.inner.in_process())?;
+        self.inner.stream(request, on_token)
+    }
+}
+
+/// [`LocalOnlyProvider`] for the embedders behind semantic search
+pub struct LocalOnlyEmbedder {
+    inner: Box<dyn Embedder>,
+}
+
+impl LocalOnlyEmbedder {
+    pub fn new(inner: Box<dyn Embedder>) -> Self {
+        Self { inner }
+    }
+}
+
+impl Embedder for LocalOnlyEmbedder {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn endpoint(&self) -> Option<&str> {
+        self.inner.endpoint()
+    }
+
+    fn in_process(&self) -> bool {
+        self.inner.in_process()
+    }
+
+    fn lexical(&self) -> bool {
+        self.inner.lexical()
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        check_local(self.inner.name(), self.inner.endpoint(), self.inner.in_process())?;
+        self.inner.embed(texts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::semantic::HashingEmbedder;
+    use crate::ai::AiRequestKind;
+
+    #[test]
+    fn test_is_local_endpoint() {
+        assert!(is_local_endpoint("http://127.0.0.1:11434/api/generate"));
+        assert!(is_local_endpoint("http://[::1]:8080/v1"));
+        assert!(!is_local_endpoint("https://93.184.216.34/v1/chat"));
+        assert!(!is_local_endpoint("https://user@10.0.0.5:8443"));
+        assert!(!is_local_endpoint("ftp://127.0.0.1"));
+        assert!(!is_local_endpoint("127.0.0.1:11434"));
+    }
+
+    /// Declares neither an endpoint nor running in-process
+    struct Undeclared;
+
+    impl AiProvider for Undeclared {
+        fn name(&self) -> &str {
+            "undeclared"
+        }
+
+        fn stream(&self, _request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()> {
+            on_token("ok");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_fails_closed() {
+        let request = AiRequest::new(AiRequestKind::Chat, "hi");
+        let provider = LocalOnlyProvider::new(Box::new(Undeclared));
+        assert!(provider.stream(&request, &mut |_| {}).is_err());
+
+        let embedder = LocalOnlyEmbedder::new(Box::new(HashingEmbedder));
+        assert!(embedder.embed(&["ls"]).is_ok());
+        assert!(check_local("remote", Some("https://93.184.216.34/v1"), false).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use config::AiConfig;
+use serde::{Deserialize, Serialize};
+
+mod context;
+mod limits;
+mod local;
+mod proposal;
+pub mod redact;
+pub mod semantic;
+pub mod summarize;
+
+pub use context::{ContextBlock, ContextBuilder};
+pub use limits::{AiUsage, BudgetExceeded, LimitedProvider};
+pub use local::{is_local_endpoint, LocalOnlyEmbedder, LocalOnlyProvider};
+pub use proposal::{ApprovedCommand, CommandProposal, ProposalAction, ProposalAuditLog};
+pub use semantic::{Embedder, RedactingEmbedder};
+
+/// What the user asked the AI provider for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AiRequestKind {
+    /// Explain a command or its output
+    Explain,
+    /// Turn a natural-language description into a command
+    Translate,
+    Chat,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AiRequest {
+    pub kind: AiRequestKind,
+    pub prompt: String,
+}
+
+impl AiRequest {
+    pub fn new(kind: AiRequestKind, prompt: &str) -> Self {
+        Self {
+            kind,
+            prompt: prompt.to_string(),
+        }
+    }
+
+    /// Header of the AI block the response streams into
+    pub fn title(&self) -> String {
+        let label = match self.kind {
+            AiRequestKind::Explain => "Explain",
+            AiRequestKind::Translate => "Translate",
+            AiRequestKind::Chat => "Chat",
+        };
+        let first_line = self.prompt.lines().next().unwrap_or_default();
+        if first_line.chars().count() > 60 {
+            let short: String = first_line.chars().take(59).collect();
+            format!("{}: {}…", label, short)
+        } else {
+            format!("{}: {}", label, first_line)
+        }
+    }
+}
+
+/// A backend answering AI requests
+pub trait AiProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// URL requests are sent to, if any
+    fn endpoint(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether the backend runs inside this process, sending nothing; local-only
+    /// mode refuses backends with neither this nor a local [`endpoint`]
+    ///
+    /// [`endpoint`]: AiProvider::endpoint
+    fn in_process(&self) -> bool {
+        false
+    }
+
+    /// Answer `request`, passing Markdown to `on_token` as it arrives
+    fn stream(&self, request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()>;
+}
+
+/// Wrap a backend the way every AI feature uses it: the local-only check
+/// when `ai.local_only` is set, then caching and budgets
+pub fn guarded_provider(inner: Box<dyn AiProvider>, config: &AiConfig) -> LimitedProvider {
+    let inner: Box<dyn AiProvider> = if config.local_only {
+        Box::new(LocalOnlyProvider::new(inner))
+    } else {
+        inner
+    };
+    LimitedProvider::new(inner, &config.limits)
+}
+
+/// Wrap an embedder for semantic search like [`guarded_provider`]: the
+/// local-only check when `ai.local_only` is set, and secret redaction of
+/// everything it is given
+pub fn guarded_embedder(inner: Box<dyn Embedder>, config: &AiConfig) -> Box<dyn Embedder> {
+    let inner: Box<dyn Embedder> = if config.local_only {
+        Box::new(LocalOnlyEmbedder::new(inner))
+    } else {
+        inner
+    };
+    Box::new(RedactingEmbedder::new(inner, &config.redact_prefixes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl AiProvider for Echo {
+        fn name(&self) -> &str {
+            "echo"
+        }
+
+        fn stream(&self, request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()> {
+            for word in request.prompt.split_inclusive(' ') {
+                on_token(word);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_stream_and_title() {
+        let request = AiRequest::new(AiRequestKind::Explain, "tar -xzf a.tgz");
+        assert_eq!(request.title(), "Explain: tar -xzf a.tgz");
+
+        let mut tokens = Vec::new();
+        Echo.stream(&request, &mut |token| tokens.push(token.to_string())).unwrap();
+        assert_eq!(tokens, vec!["tar ", "-xzf ", "a.tgz"]);
+    }
+}
@@ -0,0 +1,148 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::danger::{assess, Risk};
+
+/// What the user lets an approved proposal do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProposalAction {
+    /// Put the command on the prompt for editing
+    Insert,
+    Run,
+}
+
+/// A command suggested by an AI feature, waiting for the user
+///
+/// Proposals can only reach the shell through [`CommandProposal::approve`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandProposal {
+    pub command: String,
+    pub rationale: String,
+    pub risk: Risk,
+    /// Why the command was classified above `Safe`, shown in the dialog
+    pub risk_reasons: Vec<String>,
+}
+
+/// A proposal the user accepted; the only way to obtain one is `approve`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovedCommand {
+    pub command: String,
+    pub rationale: String,
+    pub risk: Risk,
+    pub action: ProposalAction,
+    /// Seconds since the Unix epoch
+    pub approved_at: u64,
+}
+
+impl CommandProposal {
+    pub fn new(command: &str, rationale: &str) -> Self {
+        let assessment = assess(command);
+        Self {
+            command: command.trim().to_string(),
+            rationale: rationale.to_string(),
+            risk: assessment.risk,
+            risk_reasons: assessment.reasons,
+        }
+    }
+
+    /// Dangerous proposals need the user to acknowledge the risk, and can
+    /// only be inserted, never run directly
+    pub fn approve(
+        self,
+        action: ProposalAction,
+        risk_acknowledged: bool,
+    ) -> Result<ApprovedCommand> {
+        if self.risk == Risk::Dangerous {
+            if !risk_acknowledged {
+                let reasons = self.risk_reasons.join(", ");
+                return Err(anyhow!("Proposal is dangerous: {}", reasons));
+            }
+            if action == ProposalAction::Run {
+                return Err(anyhow!("Dangerous proposals can only be inserted"));
+            }
+        }
+
+        let approved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        Ok(ApprovedCommand {
+            command: self.command,
+            rationale: self.rationale,
+            risk: self.risk,
+            action,
+            approved_at,
+        })
+    }
+}
+
+/// Append-only JSON lines log of accepted proposals
+pub struct ProposalAuditLog {
+    path: PathBuf,
+}
+
+impl ProposalAuditLog {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// ~/.local/share/voidcli/ai-audit.jsonl
+    pub fn default_path() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_default()
+            .join("voidcli")
+            .join("ai-audit.jsonl")
+    }
+
+    pub fn record(&self, approved: &ApprovedCommand) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create audit log directory")?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open AI audit log")?;
+        let line = serde_json::to_string(approved).context("Failed to serialize proposal")?;
+        writeln!(file, "{}", line).context("Failed to write AI audit log")?;
+        Ok(())
+    }
+
+    pub fn entries(&self) -> Result<Vec<ApprovedCommand>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = fs::read_to_string(&self.path).context("Failed to read AI audit log")?;
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approval() {
+        let proposal = CommandProposal::new("cargo build", "Builds the project");
+        assert_eq!(proposal.risk, Risk::Safe);
+        let approved = proposal.approve(ProposalAction::Run, false).unwrap();
+        assert_eq!(approved.command, "cargo build");
+
+        let proposal = CommandProposal::new("rm -rf ~", "Frees space");
+        assert_eq!(proposal.risk, Risk::Dangerous);
+        assert!(proposal.clone().approve(ProposalAction::Insert, false).is_err());
+        assert!(proposal.clone().approve(ProposalAction::Run, true).is_err());
+        assert!(proposal.approve(ProposalAction::Insert, true).is_ok());
+    }
+}
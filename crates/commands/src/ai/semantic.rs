@@ -0,0 +1,260 @@
+use anyhow::Result;
+
+use super::redact::redact;
+
+/// Dimensions of the built-in hashing embedder
+const HASH_DIMENSIONS: usize = 256;
+
+/// Output kept per indexed block; the start usually says what happened
+const OUTPUT_EXCERPT_CHARS: usize = 2000;
+
+/// Turns text into vectors for semantic search
+///
+/// Implemented by local models (candle, ort) or a provider's embedding API.
+pub trait Embedder: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// URL texts are sent to, if any
+    fn endpoint(&self) -> Option<&str> {
+        None
+    }
+
+    /// Whether the model runs inside this process; see
+    /// [`AiProvider::in_process`](super::AiProvider::in_process)
+    fn in_process(&self) -> bool {
+        false
+    }
+
+    /// Whether vectors only reflect shared words rather than meaning, so
+    /// search through them is keyword search
+    fn lexical(&self) -> bool {
+        false
+    }
+
+    /// One vector per text, all of the same length
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// Model-free embedder hashing words and character trigrams
+///
+/// Finds commands sharing vocabulary with the query ("docker network" →
+/// `docker network prune`) and is the fallback when no model is configured.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HashingEmbedder;
+
+impl Embedder for HashingEmbedder {
+    fn name(&self) -> &str {
+        "hashing"
+    }
+
+    fn in_process(&self) -> bool {
+        true
+    }
+
+    fn lexical(&self) -> bool {
+        true
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| hash_embed(text)).collect())
+    }
+}
+
+/// Redacts secrets from every text before the inner embedder sees it
+pub struct RedactingEmbedder {
+    inner: Box<dyn Embedder>,
+    redact_prefixes: Vec<String>,
+}
+
+impl RedactingEmbedder {
+    pub fn new(inner: Box<dyn Embedder>, redact_prefixes: &[String]) -> Self {
+        Self {
+            inner,
+            redact_prefixes: redact_prefixes.to_vec(),
+        }
+    }
+}
+
+impl Embedder for RedactingEmbedder {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn endpoint(&self) -> Option<&str> {
+        self.inner.endpoint()
+    }
+
+    fn in_process(&self) -> bool {
+        self.inner.in_process()
+    }
+
+    fn lexical(&self) -> bool {
+        self.inner.lexical()
+    }
+
+    fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let texts: Vec<String> =
+            texts.iter().map(|text| redact(text, &self.redact_prefixes)).collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.inner.embed(&texts)
+    }
+}
+
+fn hash_embed(text: &str) -> Vec<f32> {
+    let mut vector = vec![0.0; HASH_DIMENSIONS];
+    let text = text.to_lowercase();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        vector[bucket(word)] += 1.0;
+        let chars: Vec<char> = format!(" {} ", word).chars().collect();
+        for trigram in chars.windows(3) {
+            vector[bucket(&trigram.iter().collect::<String>())] += 0.5;
+        }
+    }
+    vector
+}
+
+/// FNV-1a, stable across runs unlike `DefaultHasher`
+fn bucket(token: &str) -> usize {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in token.bytes() {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash as usize % HASH_DIMENSIONS
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+/// A history entry or block output in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedCommand {
+    pub command: String,
+    pub working_dir: String,
+    /// Start of the block's output, when it was indexed from a block
+    pub output: Option<String>,
+}
+
+/// A search hit, best first
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticMatch {
+    pub command: IndexedCommand,
+    pub score: f32,
+}
+
+/// Embedding index over history entries and block outputs
+pub struct SemanticIndex {
+    embedder: Box<dyn Embedder>,
+    entries: Vec<(IndexedCommand, Vec<f32>)>,
+}
+
+impl SemanticIndex {
+    pub fn new(embedder: Box<dyn Embedder>) -> Self {
+        Self {
+            embedder,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn embedder_name(&self) -> &str {
+        self.embedder.name()
+    }
+
+    /// Whether searches match shared words rather than meaning
+    pub fn is_lexical(&self) -> bool {
+        self.embedder.lexical()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Index a command, replacing an earlier entry for the same command line
+    /// run in the same directory; the same command elsewhere is kept, since
+    /// the directory and output are what tell those runs apart
+    pub fn add(&mut self, command: &str, working_dir: &str, output: Option<&str>) -> Result<()> {
+        let output = output.map(|output| output.chars().take(OUTPUT_EXCERPT_CHARS).collect());
+        let entry = IndexedCommand {
+            command: command.to_string(),
+            working_dir: working_dir.to_string(),
+            output,
+        };
+        let text = match &entry.output {
+            Some(output) => format!("{}\n{}", entry.command, output),
+            None => entry.command.clone(),
+        };
+        let vector = self.embedder.embed(&[&text])?.pop().unwrap_or_default();
+
+        self.entries.retain(|(existing, _)| {
+            existing.command != entry.command || existing.working_dir != entry.working_dir
+        });
+        self.entries.push((entry, vector));
+        Ok(())
+    }
+
+    /// The `limit` entries closest in meaning to `query`
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SemanticMatch>> {
+        let query = self.embedder.embed(&[query])?.pop().unwrap_or_default();
+        let mut matches: Vec<SemanticMatch> = self
+            .entries
+            .iter()
+            .map(|(command, vector)| SemanticMatch {
+                command: command.clone(),
+                score: cosine_similarity(&query, vector),
+            })
+            .filter(|hit| hit.score > 0.0)
+            .collect();
+        matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_semantic_search() {
+        let mut index = SemanticIndex::new(Box::new(HashingEmbedder));
+        index.add("ls -la", "/", None).unwrap();
+        index
+            .add(
+                "sudo systemctl restart docker",
+                "/",
+                Some("fixed bridge networking for containers"),
+            )
+            .unwrap();
+        index.add("cargo test --workspace", "/src", None).unwrap();
+        index.add("ls -la", "/tmp", None).unwrap();
+        index.add("ls -la", "/", Some("total 0")).unwrap();
+        assert_eq!(index.len(), 4);
+        let hits = index.search("ls", 4).unwrap();
+        let runs: Vec<_> = hits.iter().filter(|hit| hit.command.command == "ls -la").collect();
+        assert_eq!(runs.len(), 2);
+        assert!(runs.iter().any(|hit| hit.command.output.as_deref() == Some("total 0")));
+        assert!(index.is_lexical());
+
+        let hits = index.search("that command that fixed docker networking", 2).unwrap();
+        assert_eq!(hits[0].command.command, "sudo systemctl restart docker");
+        assert!(index.search("", 5).unwrap().is_empty());
+
+        let redacting = RedactingEmbedder::new(Box::new(HashingEmbedder), &[]);
+        assert_eq!(
+            redacting.embed(&["export API_TOKEN=abc123"]).unwrap(),
+            HashingEmbedder.embed(&["export API_TOKEN=[REDACTED]"]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Result};
+
+use super::{AiProvider, AiRequest, AiRequestKind, ContextBuilder};
+
+/// Characters of output sent per map request
+const CHUNK_CHARS: usize = 12_000;
+
+/// Chunk summaries merged per reduce request
+const REDUCE_FAN_IN: usize = 8;
+
+/// Split output into chunks of at most `max_chars`, on line boundaries
+/// where possible
+pub fn chunk_output(output: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    for line in output.split_inclusive('\n') {
+        if !chunk.is_empty() && chunk.len() + line.len() > max_chars {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        let mut line = line;
+        while line.len() > max_chars {
+            let mut end = max_chars;
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            chunks.push(line[..end].to_string());
+            line = &line[end..];
+        }
+        chunk.push_str(line);
+    }
+    if !chunk.trim().is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Summarize a block's output with map-reduce prompting
+///
+/// Each chunk is summarized on its own, then the partial summaries are
+/// merged in groups until one remains. What is sent goes through `context`
+/// like any other AI request: its allowlist decides whether the command and
+/// output may leave the machine, both are redacted, and its context leads
+/// every prompt.
+pub fn summarize_output(
+    provider: &dyn AiProvider,
+    context: &ContextBuilder,
+    command: &str,
+    output: &str,
+) -> Result<String> {
+    let output = context
+        .output_text(output)
+        .ok_or_else(|| anyhow!("Sending command output is disabled by ai.context.output"))?;
+    let subject = match context.command_text(command) {
+        Some(command) => format!("the output of `{}`", command.trim()),
+        None => "a command's output".to_string(),
+    };
+    let preamble = match context.build() {
+        built if built.is_empty() => built,
+        built => format!("{}\n", built),
+    };
+    let chunks = chunk_output(&output, CHUNK_CHARS);
+    let total = chunks.len();
+
+    let mut summaries = Vec::with_capacity(total);
+    for (index, chunk) in chunks.iter().enumerate() {
+        let prompt = format!(
+            "{}Part {} of {} of {}. Summarize it in a few bullet points, \
+             keeping errors, failing tests and final results:\n\n{}",
+            preamble,
+            index + 1,
+            total,
+            subject,
+            chunk
+        );
+        summaries.push(ask(provider, &prompt)?);
+    }
+
+    while summaries.len() > 1 {
+        let mut merged = Vec::new();
+        for group in summaries.chunks(REDUCE_FAN_IN) {
+            let prompt = format!(
+                "{}Combine these partial summaries of {} into one short summary, \
+                 leading with the outcome:\n\n{}",
+                preamble,
+                subject,
+                group.join("\n\n---\n\n")
+            );
+            merged.push(ask(provider, &prompt)?);
+        }
+        summaries = merged;
+    }
+
+    summaries
+        .pop()
+        .ok_or_else(|| anyhow!("No output to summarize"))
+}
+
+fn ask(provider: &dyn AiProvider, prompt: &str) -> Result<String> {
+    let mut response = String::new();
+    let request = AiRequest::new(AiRequestKind::Chat, prompt);
+    provider.stream(&request, &mut |token| response.push_str(token))?;
+    Ok(response.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::AiConfig;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_chunk_output() {
+        let chunks = chunk_output("aaaa\nbbbb\ncc\n", 9);
+        assert_eq!(chunks, vec!["aaaa\n", "bbbb\ncc\n"]);
+        assert_eq!(chunk_output("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert!(chunk_output("\n\n", 10).is_empty());
+    }
+
+    /// Answers "summary N" for the Nth call and keeps the prompts
+    #[derive(Default)]
+    struct Recording(Mutex<Vec<String>>);
+
+    impl AiProvider for Recording {
+        fn name(&self) -> &str {
+            "recording"
+        }
+
+        fn stream(&self, request: &AiRequest, on_token: &mut dyn FnMut(&str)) -> Result<()> {
+            let mut prompts = self.0.lock().unwrap();
+            on_token(&format!("summary {}", prompts.len()));
+            prompts.push(request.prompt.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_map_reduce() {
+        let mut config = AiConfig::default();
+        let provider = Recording::default();
+        let context = ContextBuilder::new(&config).cwd("/work");
+        let output = "test ok\n".repeat(4000);
+        let command = "mysql --password hunter2 -e 'select 1'";
+        let summary = summarize_output(&provider, &context, command, &output).unwrap();
+
+        // 32000 characters make three map calls and one reduce call
+        assert_eq!(summary, "summary 3");
+        let prompts = provider.0.lock().unwrap().clone();
+        assert_eq!(prompts.len(), 4);
+        for prompt in &prompts {
+            assert!(prompt.contains("Working directory: /work"));
+            assert!(prompt.contains("--password [REDACTED]") && !prompt.contains("hunter2"));
+        }
+        assert!(summarize_output(&provider, &context, "true", "").is_err());
+
+        config.context.output = false;
+        let context = ContextBuilder::new(&config);
+        assert!(summarize_output(&provider, &context, "make", "ok").is_err());
+    }
+}
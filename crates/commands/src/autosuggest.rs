@@ -0,0 +1,137 @@
+use crate::completion::Completion;
+use crate::history::History;
+use crate::SuggestionSource;
+
+/// A fish-style inline suggestion shown as dimmed ghost text after the cursor
+#[derive(Debug, Clone)]
+pub struct Autosuggestion {
+    /// The input the suggestion was computed for
+    pub input: String,
+    /// The text that would be appended to the input (the ghost text)
+    pub suffix: String,
+    /// Where the suggestion came from
+    pub source: SuggestionSource,
+}
+
+impl Autosuggestion {
+    /// The full line if the whole suggestion is accepted
+    pub fn accept_line(&self) -> String {
+        format!("{}{}", self.input, self.suffix)
+    }
+
+    /// The line if only the next word of the suggestion is accepted
+    pub fn accept_word(&self) -> String {
+        format!("{}{}", self.input, self.next_word())
+    }
+
+    /// The next word of the ghost text, including any leading whitespace
+    pub fn next_word(&self) -> &str {
+        let start = self.suffix.len() - self.suffix.trim_start().len();
+        let end = self.suffix[start..]
+            .find(char::is_whitespace)
+            .map(|i| start + i)
+            .unwrap_or(self.suffix.len());
+        &self.suffix[..end]
+    }
+}
+
+/// Computes autosuggestions from history first, then from completions
+///
+/// Runs on every keystroke, so completions come only from what Tab
+/// completion has already cached (`Completion::complete_cached`).
+#[derive(Debug, Clone)]
+pub struct Autosuggester {
+    /// Minimum input length before suggestions are offered
+    min_input_len: usize,
+}
+
+impl Autosuggester {
+    pub fn new() -> Self {
+        Self { min_input_len: 1 }
+    }
+
+    pub fn with_min_input_len(mut self, len: usize) -> Self {
+        self.min_input_len = len;
+        self
+    }
+
+    /// Find the best suggestion for the given input line
+    pub fn suggest(
+        &self,
+        input: &str,
+        history: &History,
+        completion: &mut Completion,
+    ) -> Option<Autosuggestion> {
+        if input.trim().len() < self.min_input_len {
+            return None;
+        }
+
+        // The most recent history entry wins, as in fish
+        let from_history = history
+            .entries()
+            .iter()
+            .rev()
+            .map(|entry| entry.command.as_str())
+            .find(|command| command.len() > input.len() && command.starts_with(input));
+
+        if let Some(command) = from_history {
+            return suggestion_from(input, command, SuggestionSource::History);
+        }
+
+        // Only offer a completion when it is unambiguous
+        if input.ends_with(char::is_whitespace) {
+            return None;
+        }
+
+        let completions = completion.complete_cached(input, input.len());
+        if completions.len() != 1 {
+            return None;
+        }
+
+        let partial = input
+            .rsplit(char::is_whitespace)
+            .next()
+            .unwrap_or(input);
+        let candidate = &completions[0];
+        if candidate.len() <= partial.len() || !candidate.starts_with(partial) {
+            return None;
+        }
+
+        Some(Autosuggestion {
+            input: input.to_string(),
+            suffix: candidate[partial.len()..].to_string(),
+            source: SuggestionSource::Builtin,
+        })
+    }
+}
+
+impl Default for Autosuggester {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn suggestion_from(input: &str, candidate: &str, source: SuggestionSource) -> Option<Autosuggestion> {
+    candidate.strip_prefix(input).map(|suffix| Autosuggestion {
+        input: input.to_string(),
+        suffix: suffix.to_string(),
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_word_and_line() {
+        let suggestion = suggestion_from("git", "git commit -m fix", SuggestionSource::History).unwrap();
+        assert_eq!(suggestion.suffix, " commit -m fix");
+        assert_eq!(suggestion.next_word(), " commit");
+        assert_eq!(suggestion.accept_word(), "git commit");
+        assert_eq!(suggestion.accept_line(), "git commit -m fix");
+
+        let suggestion = suggestion_from("car", "cargo", SuggestionSource::History).unwrap();
+        assert_eq!(suggestion.accept_word(), "cargo");
+    }
+}
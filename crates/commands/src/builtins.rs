@@ -0,0 +1,147 @@
+use anyhow::{anyhow, Result};
+
+/// Builtin namespace: each group and the subcommands it accepts
+const NAMESPACE: &[(&str, &[&str])] = &[
+    ("theme", &["set", "list"]),
+    ("session", &["save", "restore"]),
+    ("block", &["export", "pin", "fold"]),
+    ("help", &[]),
+];
+
+/// A command handled natively by VoidCLI instead of the shell
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuiltinCommand {
+    ThemeSet(String),
+    ThemeList,
+    SessionSave(Option<String>),
+    SessionRestore(Option<String>),
+    BlockExport(usize),
+    BlockPin(usize),
+    BlockFold(usize),
+    Help,
+}
+
+/// Result of running an input line through the builtin interceptor
+#[derive(Debug, Clone, PartialEq)]
+pub enum Intercepted {
+    /// Not a builtin, send the line to the shell unchanged
+    Shell(String),
+    /// A builtin command to route to the dispatcher
+    Builtin(BuiltinCommand),
+}
+
+/// Strip the `void ` or `:` prefix, returning the builtin arguments
+///
+/// A group name must follow `:` directly, so that the shell's own `:`
+/// (`: ${VAR:=x}`, `: > file`) still reaches the shell.
+fn builtin_args(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    if let Some(rest) = line.strip_prefix(':') {
+        return rest.starts_with(|c: char| c.is_ascii_alphabetic()).then_some(rest);
+    }
+    line.strip_prefix("void ")
+}
+
+/// Whether the line is addressed to the builtin namespace
+pub fn is_builtin(line: &str) -> bool {
+    builtin_args(line).is_some()
+}
+
+/// Intercept a line before it reaches the shell
+pub fn intercept(line: &str) -> Result<Intercepted> {
+    match builtin_args(line) {
+        Some(args) => parse(args).map(Intercepted::Builtin),
+        None => Ok(Intercepted::Shell(line.to_string())),
+    }
+}
+
+fn parse(args: &str) -> Result<BuiltinCommand> {
+    let tokens: Vec<&str> = args.split_whitespace().collect();
+
+    match tokens.as_slice() {
+        ["theme", "set", name] => Ok(BuiltinCommand::ThemeSet(name.to_string())),
+        ["theme", "list"] => Ok(BuiltinCommand::ThemeList),
+        ["session", "save"] => Ok(BuiltinCommand::SessionSave(None)),
+        ["session", "save", name] => Ok(BuiltinCommand::SessionSave(Some(name.to_string()))),
+        ["session", "restore"] => Ok(BuiltinCommand::SessionRestore(None)),
+        ["session", "restore", name] => {
+            Ok(BuiltinCommand::SessionRestore(Some(name.to_string())))
+        }
+        ["block", "export", id] => Ok(BuiltinCommand::BlockExport(parse_block_id(id)?)),
+        ["block", "pin", id] => Ok(BuiltinCommand::BlockPin(parse_block_id(id)?)),
+        ["block", "fold", id] => Ok(BuiltinCommand::BlockFold(parse_block_id(id)?)),
+        [] | ["help"] => Ok(BuiltinCommand::Help),
+        _ => Err(anyhow!("Unknown void command: {}", args.trim())),
+    }
+}
+
+fn parse_block_id(id: &str) -> Result<usize> {
+    id.parse::<usize>()
+        .map_err(|_| anyhow!("Invalid block id: {}", id))
+}
+
+/// Complete the builtin namespace for the text before the cursor
+pub fn complete(before_cursor: &str) -> Vec<String> {
+    let args = match builtin_args(before_cursor) {
+        Some(args) => args,
+        None => return Vec::new(),
+    };
+
+    let mut tokens: Vec<&str> = args.split_whitespace().collect();
+    let partial = if args.is_empty() || args.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        tokens.pop().unwrap_or("")
+    };
+
+    let candidates: Vec<&str> = match tokens.as_slice() {
+        [] => NAMESPACE.iter().map(|(group, _)| *group).collect(),
+        [group] => NAMESPACE
+            .iter()
+            .find(|(name, _)| name == group)
+            .map(|(_, subcommands)| subcommands.to_vec())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    };
+
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(partial))
+        .map(|candidate| candidate.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intercept() {
+        assert_eq!(
+            intercept("void theme set nord").unwrap(),
+            Intercepted::Builtin(BuiltinCommand::ThemeSet("nord".to_string()))
+        );
+        assert_eq!(
+            intercept(":block export 12").unwrap(),
+            Intercepted::Builtin(BuiltinCommand::BlockExport(12))
+        );
+        assert_eq!(
+            intercept("voidctl status").unwrap(),
+            Intercepted::Shell("voidctl status".to_string())
+        );
+        assert!(intercept("void block export twelve").is_err());
+
+        // The `:` shell builtin is left alone
+        for line in [": ${EDITOR:=vi}", ": > out.log", ":"] {
+            assert_eq!(intercept(line).unwrap(), Intercepted::Shell(line.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(complete("void th"), vec!["theme"]);
+        assert_eq!(complete(":session "), vec!["save", "restore"]);
+        assert_eq!(complete("void block e"), vec!["export"]);
+        assert!(complete("ls -l").is_empty());
+    }
+}
@@ -1,11 +1,13 @@
 use std::{
     fs,
     os::unix::fs::PermissionsExt,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use anyhow::Result;
 
+use crate::{builtins, dynamic::{DynamicCompletion, DynamicProvider}, env, git::GitCompletion};
+
 pub struct CommandCompletion {
     cache: Vec<String>,
     system_paths: Vec<PathBuf>,
@@ -82,6 +84,12 @@ pub struct Completion {
     system_paths: Vec<PathBuf>,
     command_cache: Vec<String>,
     cache_initialized: bool,
+    /// Environment of the session's child process, for `$VAR` completion
+    environment: Vec<(String, String)>,
+    /// Working directory of the block being edited
+    working_dir: PathBuf,
+    git: GitCompletion,
+    dynamic: DynamicCompletion,
 }
 
 impl Completion {
@@ -96,9 +104,32 @@ impl Completion {
             system_paths,
             command_cache: Vec::new(),
             cache_initialized: false,
+            environment: std::env::vars().collect(),
+            working_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+            git: GitCompletion::new(),
+            dynamic: DynamicCompletion::new(),
         }
     }
 
+    /// Set the working directory used for path and git completions
+    pub fn set_working_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        self.working_dir = dir.as_ref().to_path_buf();
+    }
+
+    /// Use the session's live environment for `$VAR` completion
+    pub fn set_environment(&mut self, environment: Vec<(String, String)>) {
+        self.environment = environment;
+    }
+
+    /// Register a subprocess-backed completion provider
+    pub fn register_dynamic_provider(&mut self, provider: DynamicProvider) {
+        self.dynamic.register(provider);
+    }
+
+    pub fn complete_env_var(&self, token: &str) -> Vec<String> {
+        env::complete_var(token, self.environment.iter().map(|(key, _)| key.as_str()))
+    }
+
     pub fn initialize_cache(&mut self) -> Result<()> {
         if self.cache_initialized {
             return Ok(());
@@ -154,6 +185,14 @@ impl Completion {
             .collect()
     }
 
+    /// Every executable found on `PATH`
+    pub fn known_commands(&mut self) -> &[String] {
+        if !self.cache_initialized {
+            let _ = self.initialize_cache();
+        }
+        &self.command_cache
+    }
+
     pub fn complete_path(&self, partial: &str) -> Vec<String> {
         let mut results = Vec::new();
 
@@ -198,22 +237,71 @@ impl Completion {
     }
 
     pub fn complete(&mut self, line: &str, cursor_pos: usize) -> Vec<String> {
+        self.complete_with(line, cursor_pos, false)
+    }
+
+    /// Like `complete`, but only from what is already cached: never scans
+    /// `PATH` or directories and never runs git or a dynamic provider, so it
+    /// is safe to call on every keystroke
+    pub fn complete_cached(&mut self, line: &str, cursor_pos: usize) -> Vec<String> {
+        self.complete_with(line, cursor_pos, true)
+    }
+
+    fn complete_with(&mut self, line: &str, cursor_pos: usize, cached_only: bool) -> Vec<String> {
         if line.is_empty() {
             return Vec::new();
         }
 
         let (before_cursor, _) = line.split_at(cursor_pos);
+
+        if builtins::is_builtin(before_cursor) {
+            return builtins::complete(before_cursor);
+        }
+
         let tokens: Vec<&str> = before_cursor.split_whitespace().collect();
 
         if tokens.is_empty() {
             return Vec::new();
         }
 
+        let partial = tokens.last().unwrap();
+        if partial.starts_with('$') && !before_cursor.ends_with(char::is_whitespace) {
+            return self.complete_env_var(partial);
+        }
+
         if tokens.len() == 1 {
+            if cached_only && !self.cache_initialized {
+                return Vec::new();
+            }
             return self.complete_command(tokens[0]);
         }
 
-        let partial = tokens.last().unwrap();
+        let (preceding, partial) = if before_cursor.ends_with(char::is_whitespace) {
+            (&tokens[..], "")
+        } else {
+            (&tokens[..tokens.len() - 1], *partial)
+        };
+
+        if tokens[0] == "git" {
+            let completions = if cached_only {
+                self.git.complete_cached(&self.working_dir, &preceding[1..], partial)
+            } else {
+                self.git.complete(&self.working_dir, &preceding[1..], partial)
+            };
+            if !completions.is_empty() {
+                return completions;
+            }
+        }
+
+        let completions = if cached_only {
+            self.dynamic.complete_cached(&self.working_dir, preceding, partial)
+        } else {
+            self.dynamic.complete(&self.working_dir, preceding, partial)
+        };
+        if !completions.is_empty() || cached_only {
+            return completions;
+        }
+
         self.complete_path(partial)
     }
 }
@@ -231,4 +319,17 @@ mod test {
         assert!(result.is_ok());
         assert!(completion.cache_initialized);
     }
+
+    #[test]
+    fn test_complete_cached() {
+        let mut completion = Completion::new();
+        assert!(completion.complete_cached("l", 1).is_empty());
+        assert!(completion.complete_cached("git checkout ma", 15).is_empty());
+        assert!(!completion.cache_initialized);
+        assert_eq!(completion.complete_cached("$PA", 3), completion.complete_env_var("$PA"));
+
+        completion.command_cache = vec!["ls".to_string(), "cargo".to_string()];
+        completion.cache_initialized = true;
+        assert_eq!(completion.complete_cached("l", 1), vec!["ls"]);
+    }
 }
@@ -0,0 +1,166 @@
+use crate::ai::{AiRequest, AiRequestKind};
+
+/// A corrected command line offered in the "did you mean" banner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correction {
+    pub command: String,
+    /// Why it is suggested, shown next to the command
+    pub reason: String,
+}
+
+/// Most corrections shown in the banner
+const MAX_CORRECTIONS: usize = 3;
+
+/// Optimal string alignment distance: edits, with adjacent swaps counting once
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// Suggest corrections for a failed command
+///
+/// Handles unknown programs (exit 127) by edit distance over `known_commands`
+/// and a few output patterns such as git's "most similar command" hint and
+/// permission errors.
+pub fn suggest_corrections<'a>(
+    command_line: &str,
+    exit_code: i32,
+    output: &str,
+    known_commands: impl IntoIterator<Item = &'a str>,
+) -> Vec<Correction> {
+    let command_line = command_line.trim();
+    let Some((program, rest)) = split_program(command_line) else {
+        return Vec::new();
+    };
+    let mut corrections = Vec::new();
+
+    if exit_code == 127 || output.contains("command not found") {
+        let max_distance = if program.chars().count() <= 3 { 1 } else { 2 };
+        let mut candidates: Vec<(usize, &str)> = known_commands
+            .into_iter()
+            .filter(|candidate| *candidate != program)
+            .map(|candidate| (edit_distance(program, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .collect();
+        candidates.sort_by(|a, b| {
+            a.0.cmp(&b.0)
+                .then(a.1.len().cmp(&b.1.len()))
+                .then(a.1.cmp(b.1))
+        });
+        candidates.dedup_by(|a, b| a.1 == b.1);
+
+        for (_, candidate) in candidates {
+            corrections.push(Correction {
+                command: format!("{}{}", candidate, rest),
+                reason: format!("'{}' not found", program),
+            });
+        }
+    }
+
+    if program == "git" {
+        if let Some(correction) = git_correction(command_line, output) {
+            corrections.push(correction);
+        }
+    }
+
+    let lower = output.to_lowercase();
+    if program != "sudo"
+        && (lower.contains("permission denied") || lower.contains("are you root"))
+    {
+        corrections.push(Correction {
+            command: format!("sudo {}", command_line),
+            reason: "permission denied".to_string(),
+        });
+    }
+
+    corrections.truncate(MAX_CORRECTIONS);
+    corrections
+}
+
+/// Prompt for the AI fallback when no local correction applies
+pub fn correction_request(command_line: &str, output: &str) -> AiRequest {
+    let tail: Vec<&str> = output.lines().rev().take(20).collect();
+    let tail: Vec<&str> = tail.into_iter().rev().collect();
+    AiRequest::new(
+        AiRequestKind::Translate,
+        &format!(
+            "This command failed:\n{}\n\nOutput:\n{}\n\nReply with only the corrected command.",
+            command_line,
+            tail.join("\n")
+        ),
+    )
+}
+
+/// Program and the remainder of the line, including its leading space
+fn split_program(command_line: &str) -> Option<(&str, &str)> {
+    let end = command_line.find(char::is_whitespace).unwrap_or(command_line.len());
+    let program = &command_line[..end];
+    (!program.is_empty()).then(|| (program, &command_line[end..]))
+}
+
+/// `git: 'stauts' is not a git command ... The most similar command is\n\tstatus`
+fn git_correction(command_line: &str, output: &str) -> Option<Correction> {
+    let typo = output
+        .split_once("git: '")
+        .and_then(|(_, rest)| rest.split_once('\''))
+        .map(|(typo, _)| typo)?;
+    let (_, hint) = output
+        .split_once("The most similar command is")
+        .or_else(|| output.split_once("The most similar commands are"))?;
+    let suggestion = hint.split_whitespace().next()?;
+
+    Some(Correction {
+        command: command_line.replacen(typo, suggestion, 1),
+        reason: format!("'{}' is not a git command", typo),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("gti", "git"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "ls"), 2);
+    }
+
+    #[test]
+    fn test_suggest_corrections() {
+        let known = ["git", "grep", "gist", "ls", "cargo"];
+        let corrections = suggest_corrections("gti status", 127, "", known);
+        assert_eq!(corrections[0].command, "git status");
+
+        let output = "git: 'stauts' is not a git command. See 'git --help'.\n\n\
+                      The most similar command is\n\tstatus\n";
+        let corrections = suggest_corrections("git stauts -s", 1, output, known);
+        assert_eq!(corrections[0].command, "git status -s");
+
+        let corrections = suggest_corrections("apt install jq", 100, "Permission denied", known);
+        assert_eq!(corrections[0].command, "sudo apt install jq");
+
+        assert!(suggest_corrections("ls", 0, "", known).is_empty());
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Provider results kept at once; expired and then the oldest go first
+const MAX_CACHE_ENTRIES: usize = 64;
+
+/// How a provider's output is turned into candidates
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// One candidate per line
+    Lines,
+    /// Cobra `__complete` output: `value\tdescription` lines and a `:directive` trailer
+    Cobra,
+    /// `cargo metadata` JSON, keeping targets of the given kind (`bin`, `example`, `test`)
+    CargoTargets(String),
+}
+
+/// A completion source that shells out to a registered command
+#[derive(Debug, Clone)]
+pub struct DynamicProvider {
+    /// Program whose arguments this provider completes, e.g. `kubectl`
+    pub program: String,
+    /// Subcommands the provider applies to (empty means any)
+    pub subcommands: Vec<String>,
+    /// Only complete the argument following this flag, e.g. `--bin`
+    pub after_flag: Option<String>,
+    /// Command to run; `{args}` expands to the typed arguments and
+    /// `{partial}` to an empty token, so that one run covers every
+    /// completion at that position and typing only filters it
+    pub command: Vec<String>,
+    pub format: OutputFormat,
+    /// How long results stay cached
+    pub ttl: Duration,
+    /// Hard limit on how long the command may run
+    pub timeout: Duration,
+}
+
+impl DynamicProvider {
+    pub fn new(program: &str, command: &[&str], format: OutputFormat) -> Self {
+        Self {
+            program: program.to_string(),
+            subcommands: Vec::new(),
+            after_flag: None,
+            command: command.iter().map(|s| s.to_string()).collect(),
+            format,
+            ttl: Duration::from_secs(10),
+            timeout: Duration::from_millis(500),
+        }
+    }
+
+    pub fn with_subcommands(mut self, subcommands: &[&str]) -> Self {
+        self.subcommands = subcommands.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn with_after_flag(mut self, flag: &str) -> Self {
+        self.after_flag = Some(flag.to_string());
+        self
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether this provider handles the given command line position;
+    /// `tokens` starts with the program and excludes the partial token
+    fn applies_to(&self, tokens: &[&str]) -> bool {
+        if tokens.first() != Some(&self.program.as_str()) {
+            return false;
+        }
+
+        if !self.subcommands.is_empty()
+            && !tokens
+                .get(1)
+                .map_or(false, |sub| self.subcommands.iter().any(|s| s == sub))
+        {
+            return false;
+        }
+
+        match &self.after_flag {
+            Some(flag) => tokens.last() == Some(&flag.as_str()),
+            None => true,
+        }
+    }
+
+    fn expand(&self, tokens: &[&str]) -> Vec<String> {
+        let mut command = Vec::with_capacity(self.command.len() + tokens.len());
+        for part in &self.command {
+            match part.as_str() {
+                "{args}" => command.extend(tokens[1..].iter().map(|s| s.to_string())),
+                "{partial}" => command.push(String::new()),
+                _ => command.push(part.clone()),
+            }
+        }
+        command
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    fetched_at: Instant,
+    candidates: Vec<String>,
+}
+
+/// Registry of dynamic providers with a shared result cache
+#[derive(Debug, Clone)]
+pub struct DynamicCompletion {
+    providers: Vec<DynamicProvider>,
+    cache: HashMap<(PathBuf, Vec<String>), CacheEntry>,
+}
+
+impl DynamicCompletion {
+    /// Create a registry with the default docker/kubectl/cargo providers
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        for provider in default_providers() {
+            registry.register(provider);
+        }
+        registry
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            providers: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, provider: DynamicProvider) {
+        self.providers.push(provider);
+    }
+
+    /// Complete `partial` using the first provider that applies
+    pub fn complete(&mut self, working_dir: &Path, tokens: &[&str], partial: &str) -> Vec<String> {
+        let provider = match self.providers.iter().find(|p| p.applies_to(tokens)) {
+            Some(provider) => provider,
+            None => return Vec::new(),
+        };
+
+        // Cache on the command without the partial, so typing more
+        // characters filters cached results instead of running it again
+        let command = provider.expand(tokens);
+        let key = (working_dir.to_path_buf(), command.clone());
+
+        let cached = self
+            .cache
+            .get(&key)
+            .filter(|entry| entry.fetched_at.elapsed() < provider.ttl)
+            .map(|entry| entry.candidates.clone());
+
+        let candidates = match cached {
+            Some(candidates) => candidates,
+            None => {
+                let candidates = run_with_timeout(working_dir, &command, provider.timeout)
+                    .map(|output| parse_output(&output, &provider.format))
+                    .unwrap_or_default();
+                self.evict();
+                self.cache.insert(
+                    key,
+                    CacheEntry {
+                        fetched_at: Instant::now(),
+                        candidates: candidates.clone(),
+                    },
+                );
+                candidates
+            }
+        };
+
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .collect()
+    }
+}
+
+impl DynamicCompletion {
+    /// Like `complete`, but only from results still cached; never runs a
+    /// provider
+    pub fn complete_cached(
+        &self,
+        working_dir: &Path,
+        tokens: &[&str],
+        partial: &str,
+    ) -> Vec<String> {
+        let provider = match self.providers.iter().find(|p| p.applies_to(tokens)) {
+            Some(provider) => provider,
+            None => return Vec::new(),
+        };
+
+        let key = (working_dir.to_path_buf(), provider.expand(tokens));
+        self.cache
+            .get(&key)
+            .filter(|entry| entry.fetched_at.elapsed() < provider.ttl)
+            .map(|entry| {
+                entry
+                    .candidates
+                    .iter()
+                    .filter(|candidate| candidate.starts_with(partial))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Make room for one more entry
+    fn evict(&mut self) {
+        let ttl = self.providers.iter().map(|p| p.ttl).max().unwrap_or_default();
+        self.cache.retain(|_, entry| entry.fetched_at.elapsed() < ttl);
+        while self.cache.len() >= MAX_CACHE_ENTRIES {
+            let oldest = self
+                .cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.cache.remove(&key),
+                None => break,
+            };
+        }
+    }
+}
+
+impl Default for DynamicCompletion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn default_providers() -> Vec<DynamicProvider> {
+    vec![
+        DynamicProvider::new("kubectl", &["kubectl", "__complete", "{args}", "{partial}"], OutputFormat::Cobra),
+        DynamicProvider::new("docker", &["docker", "ps", "--format", "{{.Names}}"], OutputFormat::Lines)
+            .with_subcommands(&["exec", "logs", "stop", "restart", "kill", "attach", "top", "stats"]),
+        DynamicProvider::new("docker", &["docker", "ps", "-a", "--format", "{{.Names}}"], OutputFormat::Lines)
+            .with_subcommands(&["start", "rm", "inspect"]),
+        DynamicProvider::new("docker", &["docker", "images", "--format", "{{.Repository}}:{{.Tag}}"], OutputFormat::Lines)
+            .with_subcommands(&["run", "rmi", "tag", "push"]),
+        DynamicProvider::new(
+            "cargo",
+            &["cargo", "metadata", "--no-deps", "--format-version", "1"],
+            OutputFormat::CargoTargets("bin".to_string()),
+        )
+        .with_after_flag("--bin")
+        .with_ttl(Duration::from_secs(60))
+        .with_timeout(Duration::from_secs(2)),
+        DynamicProvider::new(
+            "cargo",
+            &["cargo", "metadata", "--no-deps", "--format-version", "1"],
+            OutputFormat::CargoTargets("example".to_string()),
+        )
+        .with_after_flag("--example")
+        .with_ttl(Duration::from_secs(60))
+        .with_timeout(Duration::from_secs(2)),
+        DynamicProvider::new(
+            "cargo",
+            &["cargo", "metadata", "--no-deps", "--format-version", "1"],
+            OutputFormat::CargoTargets("test".to_string()),
+        )
+        .with_after_flag("--test")
+        .with_ttl(Duration::from_secs(60))
+        .with_timeout(Duration::from_secs(2)),
+    ]
+}
+
+/// Run a command, killing it if it exceeds `timeout`; its output must also
+/// arrive in time, since a process it left behind may hold the pipe open
+fn run_with_timeout(working_dir: &Path, command: &[String], timeout: Duration) -> Option<String> {
+    let (program, args) = command.split_first()?;
+    let mut child = Command::new(program)
+        .args(args)
+        .current_dir(working_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read on a separate thread so a full pipe can't block the child
+    let mut stdout = child.stdout.take()?;
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut output = String::new();
+        let _ = stdout.read_to_string(&mut output);
+        let _ = sender.send(output);
+    });
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => break,
+            Ok(Some(_)) => return None,
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+
+    receiver
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .ok()
+}
+
+fn parse_output(output: &str, format: &OutputFormat) -> Vec<String> {
+    match format {
+        OutputFormat::Lines => output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect(),
+        OutputFormat::Cobra => output
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with(':'))
+            .map(|line| line.split('\t').next().unwrap_or(line).to_string())
+            .collect(),
+        OutputFormat::CargoTargets(kind) => {
+            let metadata: serde_json::Value = match serde_json::from_str(output) {
+                Ok(metadata) => metadata,
+                Err(_) => return Vec::new(),
+            };
+
+            let mut targets = Vec::new();
+            for package in metadata
+                .get("packages")
+                .and_then(|p| p.as_array())
+                .into_iter()
+                .flatten()
+            {
+                for target in package
+                    .get("targets")
+                    .and_then(|t| t.as_array())
+                    .into_iter()
+                    .flatten()
+                {
+                    let is_kind = target
+                        .get("kind")
+                        .and_then(|k| k.as_array())
+                        .map_or(false, |kinds| kinds.iter().any(|k| k.as_str() == Some(kind)));
+                    if let (true, Some(name)) = (is_kind, target.get("name").and_then(|n| n.as_str())) {
+                        targets.push(name.to_string());
+                    }
+                }
+            }
+            targets
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_matching() {
+        let providers = default_providers();
+        let matching = |tokens: &[&str]| providers.iter().position(|p| p.applies_to(tokens));
+
+        assert_eq!(matching(&["kubectl", "get", "pods"]), Some(0));
+        assert_eq!(matching(&["docker", "exec"]), Some(1));
+        assert_eq!(matching(&["docker", "rm"]), Some(2));
+        assert_eq!(matching(&["cargo", "run", "--bin"]), Some(4));
+        assert_eq!(matching(&["cargo", "run"]), None);
+    }
+
+    #[test]
+    fn test_parse_cobra_output() {
+        let output = "pods\tPod resources\nservices\n:4\n";
+        assert_eq!(parse_output(output, &OutputFormat::Cobra), vec!["pods", "services"]);
+    }
+
+    #[test]
+    fn test_cache_and_timeout() {
+        let mut registry = DynamicCompletion::empty();
+        let echo = ["echo", "alpha", "{partial}"];
+        registry.register(DynamicProvider::new("x", &echo, OutputFormat::Lines));
+        let dir = std::env::temp_dir();
+        assert!(registry.complete_cached(&dir, &["x"], "a").is_empty());
+        assert_eq!(registry.complete(&dir, &["x"], "a"), vec!["alpha"]);
+        assert_eq!(registry.complete_cached(&dir, &["x"], "al"), vec!["alpha"]);
+        assert!(registry.complete(&dir, &["x"], "b").is_empty());
+        assert_eq!(registry.cache.len(), 1);
+
+        // A background process keeping stdout open does not hold up the result
+        let started = Instant::now();
+        let command = ["sh", "-c", "sleep 5 & echo hi"].map(String::from);
+        assert_eq!(run_with_timeout(&dir, &command, Duration::from_millis(200)), None);
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
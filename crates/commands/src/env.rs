@@ -0,0 +1,93 @@
+/// Searchable view over a session's environment variables
+#[derive(Debug, Clone, Default)]
+pub struct EnvInspector {
+    vars: Vec<(String, String)>,
+}
+
+impl EnvInspector {
+    pub fn new(vars: Vec<(String, String)>) -> Self {
+        let mut inspector = Self::default();
+        inspector.set_vars(vars);
+        inspector
+    }
+
+    /// Replace the inspected environment, keeping it sorted by name
+    pub fn set_vars(&mut self, mut vars: Vec<(String, String)>) {
+        vars.sort();
+        self.vars = vars;
+    }
+
+    pub fn vars(&self) -> &[(String, String)] {
+        &self.vars
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Case-insensitive search over variable names and values
+    pub fn search(&self, query: &str) -> Vec<&(String, String)> {
+        let query = query.to_lowercase();
+        self.vars
+            .iter()
+            .filter(|(key, value)| {
+                key.to_lowercase().contains(&query) || value.to_lowercase().contains(&query)
+            })
+            .collect()
+    }
+}
+
+/// Complete a `$VAR` or `${VAR` token against the given variable names
+pub fn complete_var<'a, I>(token: &str, names: I) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let (prefix, braced) = if let Some(rest) = token.strip_prefix("${") {
+        (rest, true)
+    } else if let Some(rest) = token.strip_prefix('$') {
+        (rest, false)
+    } else {
+        return Vec::new();
+    };
+
+    let mut results: Vec<String> = names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| {
+            if braced {
+                format!("${{{}}}", name)
+            } else {
+                format!("${}", name)
+            }
+        })
+        .collect();
+    results.sort();
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complete_var() {
+        let names = ["HOME", "HOSTNAME", "PATH"];
+        assert_eq!(complete_var("$HO", names), vec!["$HOME", "$HOSTNAME"]);
+        assert_eq!(complete_var("${PA", names), vec!["${PATH}"]);
+        assert!(complete_var("HO", names).is_empty());
+    }
+
+    #[test]
+    fn test_inspector_search() {
+        let inspector = EnvInspector::new(vec![
+            ("PATH".to_string(), "/usr/bin".to_string()),
+            ("EDITOR".to_string(), "vim".to_string()),
+        ]);
+        assert_eq!(inspector.vars()[0].0, "EDITOR");
+        assert_eq!(inspector.search("usr").len(), 1);
+        assert_eq!(inspector.get("EDITOR"), Some("vim"));
+    }
+}
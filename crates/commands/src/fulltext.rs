@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+
+use anyhow::{anyhow, Context, Result};
+use config::SearchIndexConfig;
+use serde::{Deserialize, Serialize};
+
+/// Output stored per block; the rest of very long outputs isn't searchable
+const MAX_OUTPUT_BYTES: usize = 256 * 1024;
+
+/// Words longer than this are usually hashes or base64 and aren't indexed
+const MAX_TOKEN_CHARS: usize = 64;
+
+/// Evict down to this share of the limit so eviction doesn't rewrite the
+/// index file on every block
+const EVICT_TO_PERCENT: usize = 90;
+
+/// Matches of command lines count more than matches in their output
+const COMMAND_WEIGHT: f32 = 3.0;
+
+/// A finished block as stored in the index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedBlock {
+    /// Session the block belongs to
+    pub session: String,
+    pub block_id: usize,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub command: String,
+    pub working_dir: String,
+    pub output: String,
+}
+
+impl IndexedBlock {
+    fn size(&self) -> usize {
+        self.command.len() + self.working_dir.len() + self.output.len() + self.session.len()
+    }
+}
+
+/// A search result, best first
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub session: String,
+    pub block_id: usize,
+    pub timestamp: u64,
+    pub command: String,
+    /// First line of the command or output containing a query word
+    pub snippet: String,
+    pub score: f32,
+}
+
+/// Inverted index over block commands and outputs of all sessions
+///
+/// Documents are appended to a JSON lines file as they are added; the word
+/// postings are rebuilt from it on open. The oldest blocks are evicted once
+/// the index exceeds `search_index.max_size_mb` or `max_age_days`.
+pub struct FullTextIndex {
+    path: Option<PathBuf>,
+    max_bytes: usize,
+    max_age_secs: u64,
+    docs: BTreeMap<u64, IndexedBlock>,
+    next_doc: u64,
+    /// Word to the documents containing it
+    postings: BTreeMap<String, BTreeSet<u64>>,
+    bytes: usize,
+}
+
+impl FullTextIndex {
+    /// An index kept only in memory
+    pub fn new(config: &SearchIndexConfig) -> Self {
+        Self {
+            path: None,
+            max_bytes: config.max_size_mb as usize * 1024 * 1024,
+            max_age_secs: config.max_age_days * 24 * 60 * 60,
+            docs: BTreeMap::new(),
+            next_doc: 0,
+            postings: BTreeMap::new(),
+            bytes: 0,
+        }
+    }
+
+    /// Load the index persisted at `path`, or start one there
+    pub fn open<P: AsRef<Path>>(path: P, config: &SearchIndexConfig) -> Result<Self> {
+        let mut index = Self::new(config);
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            let file = File::open(&path).context("Failed to open search index")?;
+            for line in BufReader::new(file).lines() {
+                let line = line.context("Failed to read search index")?;
+                // Skip lines torn by a crash mid-write
+                if let Ok(block) = serde_json::from_str(&line) {
+                    index.insert(block);
+                }
+            }
+        }
+        index.path = Some(path);
+        Ok(index)
+    }
+
+    /// Default location, under the user's data directory
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("voidcli").join("search-index.jsonl"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Size of the indexed text in bytes
+    pub fn size(&self) -> usize {
+        self.bytes
+    }
+
+    fn insert(&mut self, block: IndexedBlock) {
+        let doc = self.next_doc;
+        self.next_doc += 1;
+        for word in words(&block.command).chain(words(&block.output)) {
+            self.postings.entry(word).or_default().insert(doc);
+        }
+        self.bytes += block.size();
+        self.docs.insert(doc, block);
+    }
+
+    fn remove(&mut self, doc: u64) {
+        let Some(block) = self.docs.remove(&doc) else {
+            return;
+        };
+        for word in words(&block.command).chain(words(&block.output)) {
+            if let Some(docs) = self.postings.get_mut(&word) {
+                docs.remove(&doc);
+                if docs.is_empty() {
+                    self.postings.remove(&word);
+                }
+            }
+        }
+        self.bytes -= block.size();
+    }
+
+    /// Index a finished block, evicting old ones to stay within the limits
+    pub fn add(&mut self, mut block: IndexedBlock, now: u64) -> Result<()> {
+        if block.output.len() > MAX_OUTPUT_BYTES {
+            let mut end = MAX_OUTPUT_BYTES;
+            while !block.output.is_char_boundary(end) {
+                end -= 1;
+            }
+            block.output.truncate(end);
+        }
+        // A failed write still leaves the block searchable until restart
+        let written = match &self.path {
+            Some(path) => append(path, &block),
+            None => Ok(()),
+        };
+        self.insert(block);
+
+        if self.evict(now) {
+            self.compact()?;
+        }
+        written
+    }
+
+    /// Drop blocks past the age limit, and the oldest while over the size
+    /// limit; returns whether anything was dropped
+    fn evict(&mut self, now: u64) -> bool {
+        let oldest_allowed = now.saturating_sub(self.max_age_secs);
+        let expired: Vec<u64> = self
+            .docs
+            .iter()
+            .filter(|(_, block)| block.timestamp < oldest_allowed)
+            .map(|(doc, _)| *doc)
+            .collect();
+        let mut evicted = !expired.is_empty();
+        for doc in expired {
+            self.remove(doc);
+        }
+
+        if self.bytes > self.max_bytes {
+            let target = self.max_bytes / 100 * EVICT_TO_PERCENT;
+            while self.bytes > target {
+                let Some(&oldest) = self.docs.keys().next() else {
+                    break;
+                };
+                self.remove(oldest);
+                evicted = true;
+            }
+        }
+        evicted
+    }
+
+    /// Rewrite the index file with only the blocks still indexed
+    fn compact(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let staged = path.with_extension("jsonl.tmp");
+        let mut file = File::create(&staged).context("Failed to write search index")?;
+        for block in self.docs.values() {
+            let line = serde_json::to_string(block).context("Failed to serialize block")?;
+            writeln!(file, "{}", line).context("Failed to write search index")?;
+        }
+        std::fs::rename(&staged, path).context("Failed to replace search index")
+    }
+
+    /// Blocks containing every word of `query`, the last word also as a
+    /// prefix so results update while typing
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let terms: Vec<String> = words(query).collect();
+        let Some((last, rest)) = terms.split_last() else {
+            return Vec::new();
+        };
+
+        let mut candidates: Option<BTreeSet<u64>> = None;
+        for term in rest {
+            let docs = self.postings.get(term).cloned().unwrap_or_default();
+            candidates = Some(match candidates {
+                Some(current) => current.intersection(&docs).copied().collect(),
+                None => docs,
+            });
+        }
+        let prefixed: BTreeSet<u64> = self
+            .postings
+            .range(last.clone()..)
+            .take_while(|(word, _)| word.starts_with(last.as_str()))
+            .flat_map(|(_, docs)| docs.iter().copied())
+            .collect();
+        let candidates = match candidates {
+            Some(current) => current.intersection(&prefixed).copied().collect(),
+            None => prefixed,
+        };
+
+        let mut hits: Vec<(u64, SearchHit)> = candidates
+            .into_iter()
+            .filter_map(|doc| Some((doc, self.hit(self.docs.get(&doc)?, &terms))))
+            .collect();
+        // Best score first, newest first among equals
+        hits.sort_by(|(a_doc, a), (b_doc, b)| {
+            b.score.total_cmp(&a.score).then_with(|| b_doc.cmp(a_doc))
+        });
+        hits.into_iter().take(limit).map(|(_, hit)| hit).collect()
+    }
+
+    fn hit(&self, block: &IndexedBlock, terms: &[String]) -> SearchHit {
+        let command = block.command.to_lowercase();
+        let output = block.output.to_lowercase();
+        let score = terms
+            .iter()
+            .map(|term| {
+                let in_command = command.contains(term.as_str()) as u8 as f32;
+                let in_output = output.matches(term.as_str()).take(10).count() as f32;
+                in_command * COMMAND_WEIGHT + (1.0 + in_output).ln()
+            })
+            .sum();
+        let snippet = std::iter::once(block.command.as_str())
+            .chain(block.output.lines())
+            .find(|line| {
+                let line = line.to_lowercase();
+                terms.iter().any(|term| line.contains(term.as_str()))
+            })
+            .unwrap_or(&block.command)
+            .trim()
+            .to_string();
+        SearchHit {
+            session: block.session.clone(),
+            block_id: block.block_id,
+            timestamp: block.timestamp,
+            command: block.command.clone(),
+            snippet,
+            score,
+        }
+    }
+}
+
+/// Lowercased words of letters, digits and `_`
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty() && word.chars().count() <= MAX_TOKEN_CHARS)
+        .map(str::to_lowercase)
+}
+
+fn append(path: &Path, block: &IndexedBlock) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).context("Failed to create search index directory")?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context("Failed to open search index")?;
+    let line = serde_json::to_string(block).context("Failed to serialize block")?;
+    writeln!(file, "{}", line).context("Failed to write search index")
+}
+
+/// Indexes blocks on a background thread so finishing a command never waits
+/// for tokenizing its output
+pub struct BackgroundIndexer {
+    index: Arc<Mutex<FullTextIndex>>,
+    sender: mpsc::Sender<(IndexedBlock, u64)>,
+}
+
+impl BackgroundIndexer {
+    pub fn spawn(index: FullTextIndex) -> Self {
+        let index = Arc::new(Mutex::new(index));
+        let (sender, receiver) = mpsc::channel::<(IndexedBlock, u64)>();
+        let worker = index.clone();
+        std::thread::spawn(move || {
+            for (block, now) in receiver {
+                let Ok(mut index) = worker.lock() else {
+                    break;
+                };
+                let _ = index.add(block, now);
+            }
+        });
+        Self { index, sender }
+    }
+
+    /// Queue a finished block for indexing
+    pub fn submit(&self, block: IndexedBlock, now: u64) -> Result<()> {
+        self.sender
+            .send((block, now))
+            .map_err(|_| anyhow!("Search indexer has stopped"))
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        match self.index.lock() {
+            Ok(index) => index.search(query, limit),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(id: usize, timestamp: u64, command: &str, output: &str) -> IndexedBlock {
+        IndexedBlock {
+            session: "main".to_string(),
+            block_id: id,
+            timestamp,
+            command: command.to_string(),
+            working_dir: "/work".to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let mut index = FullTextIndex::new(&SearchIndexConfig::default());
+        index.add(block(0, 100, "cargo build", "error[E0425]: cannot find value"), 100).unwrap();
+        index.add(block(1, 200, "cargo test", "test result: ok"), 200).unwrap();
+        index.add(block(2, 300, "make deploy", "deploying to staging"), 300).unwrap();
+
+        let hits = index.search("cargo", 10);
+        assert_eq!(hits.iter().map(|hit| hit.block_id).collect::<Vec<_>>(), vec![1, 0]);
+        let hits = index.search("cannot fi", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].snippet, "error[E0425]: cannot find value");
+        assert!(index.search("cargo deploy", 10).is_empty());
+        assert_eq!(index.search("DEPLOY", 10)[0].command, "make deploy");
+    }
+
+    #[test]
+    fn test_limits_and_persistence() {
+        let dir = std::env::temp_dir().join(format!("voidcli-index-{}", std::process::id()));
+        let path = dir.join("index.jsonl");
+        let config = SearchIndexConfig {
+            max_age_days: 1,
+            ..SearchIndexConfig::default()
+        };
+        let day = 24 * 60 * 60;
+
+        let mut index = FullTextIndex::open(&path, &config).unwrap();
+        index.add(block(0, 0, "old command", ""), 0).unwrap();
+        index.add(block(1, day, "recent command", ""), day).unwrap();
+        index.add(block(2, 2 * day, "new command", ""), 2 * day).unwrap();
+        assert_eq!(index.len(), 2);
+
+        let reopened = FullTextIndex::open(&path, &config).unwrap();
+        let hits = reopened.search("command", 10);
+        assert_eq!(hits.iter().map(|hit| hit.block_id).collect::<Vec<_>>(), vec![2, 1]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
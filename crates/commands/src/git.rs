@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Files under `.git` whose mtimes change with refs, HEAD and the index
+const WATCHED: &[&str] = &["", "HEAD", "packed-refs", "refs/heads", "refs/tags", "index"];
+
+/// Longest cached data is used; working tree edits touch nothing under `.git`
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Cached repository data, valid for a while and as long as the watched
+/// mtimes are unchanged
+#[derive(Debug, Clone)]
+struct RepoCache {
+    mtimes: Vec<Option<SystemTime>>,
+    fetched_at: Instant,
+    branches: Vec<String>,
+    tags: Vec<String>,
+    remotes: Vec<String>,
+    modified: Vec<String>,
+}
+
+impl RepoCache {
+    fn candidates(&self, kind: GitArg, partial: &str) -> Vec<String> {
+        let candidates: Vec<&String> = match kind {
+            GitArg::Ref => self.branches.iter().chain(self.tags.iter()).collect(),
+            GitArg::Branch => self.branches.iter().collect(),
+            GitArg::Tag => self.tags.iter().collect(),
+            GitArg::Remote => self.remotes.iter().collect(),
+            GitArg::Path => self.modified.iter().collect(),
+        };
+
+        candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(partial))
+            .cloned()
+            .collect()
+    }
+}
+
+/// What kind of git object an argument position expects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GitArg {
+    Ref,
+    Branch,
+    Tag,
+    Remote,
+    Path,
+}
+
+/// Completes git branches, tags, remotes and modified paths by querying
+/// the repository in the working directory
+#[derive(Debug, Clone, Default)]
+pub struct GitCompletion {
+    cache: HashMap<PathBuf, RepoCache>,
+}
+
+impl GitCompletion {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Complete `partial` for a `git` command line; `args` excludes `git`
+    /// itself and the partial token being completed
+    pub fn complete(&mut self, working_dir: &Path, args: &[&str], partial: &str) -> Vec<String> {
+        let kind = match arg_kind(args) {
+            Some(kind) => kind,
+            None => return Vec::new(),
+        };
+
+        match self.repo(working_dir) {
+            Some(cache) => cache.candidates(kind, partial),
+            None => Vec::new(),
+        }
+    }
+
+    /// Like `complete`, but only from data already cached for the
+    /// repository, however old; never touches the repository
+    pub fn complete_cached(&self, working_dir: &Path, args: &[&str], partial: &str) -> Vec<String> {
+        let kind = match arg_kind(args) {
+            Some(kind) => kind,
+            None => return Vec::new(),
+        };
+
+        // The innermost cached repository containing `working_dir`
+        self.cache
+            .iter()
+            .filter(|(git_dir, _)| {
+                git_dir.parent().is_some_and(|root| working_dir.starts_with(root))
+            })
+            .max_by_key(|(git_dir, _)| git_dir.as_os_str().len())
+            .map(|(_, cache)| cache.candidates(kind, partial))
+            .unwrap_or_default()
+    }
+
+    /// Get the cached data for the repository containing `working_dir`,
+    /// refreshing it when the repository has changed
+    fn repo(&mut self, working_dir: &Path) -> Option<&RepoCache> {
+        let git_dir = find_git_dir(working_dir)?;
+        let mtimes = watched_mtimes(&git_dir);
+
+        let stale = self.cache.get(&git_dir).map_or(true, |cache| {
+            cache.mtimes != mtimes || cache.fetched_at.elapsed() >= CACHE_TTL
+        });
+
+        if stale {
+            let cache = RepoCache {
+                mtimes,
+                fetched_at: Instant::now(),
+                branches: git_lines(working_dir, &["for-each-ref", "--format=%(refname:short)", "refs/heads"]),
+                tags: git_lines(working_dir, &["for-each-ref", "--format=%(refname:short)", "refs/tags"]),
+                remotes: git_lines(working_dir, &["remote"]),
+                modified: git_lines(working_dir, &["status", "--porcelain"])
+                    .iter()
+                    .filter_map(|line| line.get(3..))
+                    .map(|path| path.rsplit(" -> ").next().unwrap_or(path).to_string())
+                    .collect(),
+            };
+            self.cache.insert(git_dir.clone(), cache);
+        }
+
+        self.cache.get(&git_dir)
+    }
+}
+
+/// Decide what to complete from the git subcommand and argument position
+fn arg_kind(args: &[&str]) -> Option<GitArg> {
+    let subcommand = *args.first()?;
+    let positional = args[1..].iter().filter(|arg| !arg.starts_with('-')).count();
+
+    match subcommand {
+        "checkout" | "switch" | "merge" | "rebase" | "log" | "cherry-pick" | "reset" => {
+            Some(GitArg::Ref)
+        }
+        "branch" => Some(GitArg::Branch),
+        "tag" => Some(GitArg::Tag),
+        "push" | "pull" | "fetch" => {
+            if positional == 0 {
+                Some(GitArg::Remote)
+            } else {
+                Some(GitArg::Branch)
+            }
+        }
+        "remote" => Some(GitArg::Remote),
+        "add" | "restore" | "diff" | "rm" | "stash" => Some(GitArg::Path),
+        _ => None,
+    }
+}
+
+fn watched_mtimes(git_dir: &Path) -> Vec<Option<SystemTime>> {
+    WATCHED
+        .iter()
+        .map(|name| fs::metadata(git_dir.join(name)).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Walk up from `dir` to find the repository's `.git` entry
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(".git"))
+        .find(|candidate| candidate.exists())
+}
+
+fn git_lines(working_dir: &Path, args: &[&str]) -> Vec<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(working_dir)
+        .args(args)
+        .output();
+
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arg_kind() {
+        assert_eq!(arg_kind(&["checkout"]), Some(GitArg::Ref));
+        assert_eq!(arg_kind(&["push"]), Some(GitArg::Remote));
+        assert_eq!(arg_kind(&["push", "-f", "origin"]), Some(GitArg::Branch));
+        assert_eq!(arg_kind(&["add"]), Some(GitArg::Path));
+        assert_eq!(arg_kind(&["status"]), None);
+    }
+
+    #[test]
+    fn test_new_branch_changes_mtimes() {
+        let dir = std::env::temp_dir().join(format!("voidcli-git-test-{}", std::process::id()));
+        let git_dir = dir.join(".git");
+        fs::create_dir_all(git_dir.join("refs/heads")).unwrap();
+        let before = watched_mtimes(&git_dir);
+
+        // A new branch leaves the mtime of `.git` itself alone
+        std::thread::sleep(Duration::from_millis(10));
+        fs::write(git_dir.join("refs/heads/feature"), "0000\n").unwrap();
+        let after = watched_mtimes(&git_dir);
+        assert_eq!(before[0], after[0]);
+        assert_ne!(before, after);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{Context, Result};
+use config::timestamp::TimestampFormatter;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Write};
@@ -181,4 +182,9 @@ impl HistoryEntry {
         self.duration_ms = Some(duration_ms);
         self
     }
+
+    /// When the command ran, for the history list; `now` in Unix seconds
+    pub fn when(&self, timestamps: &TimestampFormatter, now: u64) -> String {
+        timestamps.format_unix(self.timestamp, now)
+    }
 }
@@ -8,7 +8,18 @@ use serde::{Deserialize, Serialize};
 
 mod history;
 mod completion;
+pub mod dynamic;
+pub mod env;
+pub mod fulltext;
+mod git;
 mod suggestions;
+pub mod ai;
+pub mod autosuggest;
+pub mod builtins;
+pub mod correction;
+pub mod danger;
+pub mod multiline;
+pub mod workflows;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommandSuggestion {
@@ -25,10 +36,25 @@ pub enum SuggestionSource {
     Custom,
 }
 
+/// How the palette matches history
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistorySearchMode {
+    /// Case-insensitive substring of the command line
+    #[default]
+    Substring,
+    /// Closest in meaning, over commands and their output; only shared
+    /// words count while the index uses the model-free embedder
+    Semantic,
+}
+
 pub struct CommandPalette {
     history: history::History,
     completion: completion::Completion,
     suggestions: suggestions::SuggestionEngine,
+    autosuggester: autosuggest::Autosuggester,
+    env_inspector: env::EnvInspector,
+    semantic_index: Option<ai::semantic::SemanticIndex>,
+    full_text: Option<fulltext::BackgroundIndexer>,
 }
 
 impl CommandPalette {
@@ -37,10 +63,157 @@ impl CommandPalette {
             history: history::History::new(),
             completion: completion::Completion::new(),
             suggestions: suggestions::SuggestionEngine::new(),
+            autosuggester: autosuggest::Autosuggester::new(),
+            env_inspector: env::EnvInspector::new(std::env::vars().collect()),
+            semantic_index: None,
+            full_text: None,
         }
     }
 
     pub async fn get_suggestions(&self, input: &str) -> Result<Vec<CommandSuggestion>> {
         Ok(Vec::new())
     }
+
+    /// Update completions and the inspector with the session's environment
+    pub fn set_environment(&mut self, environment: Vec<(String, String)>) {
+        self.completion.set_environment(environment.clone());
+        self.env_inspector.set_vars(environment);
+    }
+
+    /// Environment inspector view for the palette
+    pub fn env_inspector(&self) -> &env::EnvInspector {
+        &self.env_inspector
+    }
+
+    /// "Did you mean" corrections for a failed command
+    pub fn did_you_mean(
+        &mut self,
+        command_line: &str,
+        exit_code: i32,
+        output: &str,
+    ) -> Vec<correction::Correction> {
+        let known = self.completion.known_commands();
+        correction::suggest_corrections(
+            command_line,
+            exit_code,
+            output,
+            known.iter().map(String::as_str),
+        )
+    }
+
+    /// Enable semantic search, indexing existing history with `embedder`
+    ///
+    /// The embedder is guarded like AI providers: it must be local when
+    /// `ai.local_only` is set, and it only ever sees redacted text.
+    pub fn enable_semantic_search(
+        &mut self,
+        embedder: Box<dyn ai::semantic::Embedder>,
+        config: &config::AiConfig,
+    ) -> Result<()> {
+        let embedder = ai::guarded_embedder(embedder, config);
+        let mut index = ai::semantic::SemanticIndex::new(embedder);
+        for entry in self.history.entries() {
+            index.add(&entry.command, &entry.working_dir, None)?;
+        }
+        self.semantic_index = Some(index);
+        Ok(())
+    }
+
+    /// Open the persisted full-text index over past block output
+    pub fn enable_full_text_search(&mut self, config: &config::SearchIndexConfig) -> Result<()> {
+        if !config.enabled {
+            return Ok(());
+        }
+        let index = match fulltext::FullTextIndex::default_path() {
+            Some(path) => fulltext::FullTextIndex::open(path, config)?,
+            None => fulltext::FullTextIndex::new(config),
+        };
+        self.full_text = Some(fulltext::BackgroundIndexer::spawn(index));
+        Ok(())
+    }
+
+    /// Add a finished block to the semantic and full-text indexes that are
+    /// enabled
+    pub fn index_block(&mut self, block: &fulltext::IndexedBlock) -> Result<()> {
+        if let Some(index) = &mut self.semantic_index {
+            index.add(&block.command, &block.working_dir, Some(&block.output))?;
+        }
+        if let Some(indexer) = &self.full_text {
+            indexer.submit(block.clone(), block.timestamp)?;
+        }
+        Ok(())
+    }
+
+    /// Blocks of any session whose command or output contains the query
+    pub fn search_output(&self, query: &str, limit: usize) -> Vec<fulltext::SearchHit> {
+        match &self.full_text {
+            Some(indexer) => indexer.search(query, limit),
+            None => Vec::new(),
+        }
+    }
+
+    /// Name the palette shows for `mode`, saying what the search really
+    /// does: semantic mode is keyword search with the model-free embedder,
+    /// and substring search while no index is enabled
+    pub fn history_search_label(&self, mode: HistorySearchMode) -> &'static str {
+        match (mode, &self.semantic_index) {
+            (HistorySearchMode::Semantic, Some(index)) if index.is_lexical() => "Keyword",
+            (HistorySearchMode::Semantic, Some(_)) => "Semantic",
+            _ => "Substring",
+        }
+    }
+
+    /// Search history for the palette; semantic mode falls back to substring
+    /// matching while no index is enabled
+    pub fn search_history(
+        &self,
+        query: &str,
+        mode: HistorySearchMode,
+        limit: usize,
+    ) -> Result<Vec<CommandSuggestion>> {
+        if let (HistorySearchMode::Semantic, Some(index)) = (mode, &self.semantic_index) {
+            return Ok(index
+                .search(query, limit)?
+                .into_iter()
+                .map(|hit| CommandSuggestion {
+                    command: hit.command.command,
+                    description: hit.command.working_dir,
+                    source: SuggestionSource::History,
+                })
+                .collect());
+        }
+
+        Ok(self
+            .history
+            .search(query)
+            .into_iter()
+            .rev()
+            .take(limit)
+            .map(|entry| CommandSuggestion {
+                command: entry.command.clone(),
+                description: entry.working_dir.clone(),
+                source: SuggestionSource::History,
+            })
+            .collect())
+    }
+
+    /// Repeated command sequences in history worth saving as workflows
+    pub fn workflow_candidates(
+        &self,
+        min_occurrences: usize,
+    ) -> Vec<workflows::WorkflowCandidate> {
+        let commands: Vec<&str> = self
+            .history
+            .entries()
+            .iter()
+            .map(|entry| entry.command.as_str())
+            .collect();
+        workflows::detect_workflows(&commands, min_occurrences)
+    }
+
+    /// Get the ghost-text autosuggestion for the current input line
+    pub fn autosuggest(&mut self, input: &str) -> Option<autosuggest::Autosuggestion> {
+        self.autosuggester
+            .suggest(input, &self.history, &mut self.completion)
+    }
 }
@@ -0,0 +1,570 @@
+/// Kinds of tokens produced by the multi-line editor highlighter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HighlightKind {
+    Command,
+    Argument,
+    String,
+    Variable,
+    Operator,
+    Comment,
+}
+
+/// A highlighted byte range within a single line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HighlightSpan {
+    pub start: usize,
+    pub end: usize,
+    pub kind: HighlightKind,
+}
+
+/// Whether the input is incomplete and should open the multi-line editor:
+/// unterminated quotes, a trailing backslash, or a dangling pipe/operator
+pub fn needs_continuation(input: &str) -> bool {
+    let mut quote: Option<char> = None;
+    let mut escape_next = false;
+
+    for c in input.chars() {
+        if escape_next {
+            escape_next = false;
+            continue;
+        }
+
+        match (quote, c) {
+            (Some('\''), '\'') => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escape_next = true,
+            (Some(q), c) if c == q => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            _ => {}
+        }
+    }
+
+    if quote.is_some() || escape_next {
+        return true;
+    }
+
+    let trimmed = input.trim_end();
+    trimmed.ends_with('|') || trimmed.ends_with("&&")
+}
+
+/// One of the editor's cursors, with the selection it may carry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub row: usize,
+    /// Byte offset within the line
+    pub col: usize,
+    /// Other end of a selection on the same line, as a byte offset
+    pub anchor: Option<usize>,
+}
+
+impl Cursor {
+    fn start(&self) -> usize {
+        self.anchor.map_or(self.col, |anchor| anchor.min(self.col))
+    }
+}
+
+/// Editor buffer used while composing a multi-line command
+#[derive(Debug, Clone)]
+pub struct MultilineEditor {
+    lines: Vec<String>,
+    /// Cursor row within `lines`
+    cursor_row: usize,
+    /// Cursor position as a byte offset within the current line
+    cursor_col: usize,
+    /// Selection of the main cursor, see `Cursor::anchor`
+    anchor: Option<usize>,
+    /// Cursors added with `add_cursor_above`/`below` or
+    /// `edit_all_occurrences`; typing and deleting happen at all of them
+    extra_cursors: Vec<Cursor>,
+}
+
+impl MultilineEditor {
+    pub fn new() -> Self {
+        Self {
+            lines: vec![String::new()],
+            cursor_row: 0,
+            cursor_col: 0,
+            anchor: None,
+            extra_cursors: Vec::new(),
+        }
+    }
+
+    /// Open the editor with existing input, cursor at the end
+    pub fn from_input(input: &str) -> Self {
+        let mut lines: Vec<String> = input.split('\n').map(|l| l.to_string()).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+
+        let cursor_row = lines.len() - 1;
+        let cursor_col = lines[cursor_row].len();
+
+        Self {
+            lines,
+            cursor_row,
+            cursor_col,
+            anchor: None,
+            extra_cursors: Vec::new(),
+        }
+    }
+
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor_row, self.cursor_col)
+    }
+
+    /// The main cursor followed by the extra ones, for drawing
+    pub fn cursors(&self) -> Vec<Cursor> {
+        let primary = Cursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            anchor: self.anchor,
+        };
+        std::iter::once(primary).chain(self.extra_cursors.iter().copied()).collect()
+    }
+
+    fn has_extra_cursors(&self) -> bool {
+        !self.extra_cursors.is_empty() || self.anchor.is_some()
+    }
+
+    /// Back to the main cursor alone, without a selection
+    pub fn clear_cursors(&mut self) {
+        self.anchor = None;
+        self.extra_cursors.clear();
+    }
+
+    /// Add a cursor at the same column on the line above the topmost one;
+    /// false on the first line
+    pub fn add_cursor_above(&mut self) -> bool {
+        self.add_cursor_vertically(false)
+    }
+
+    /// Add a cursor on the line below the bottommost one
+    pub fn add_cursor_below(&mut self) -> bool {
+        self.add_cursor_vertically(true)
+    }
+
+    fn add_cursor_vertically(&mut self, below: bool) -> bool {
+        let cursors = self.cursors();
+        let edge = if below {
+            cursors.iter().max_by_key(|cursor| cursor.row)
+        } else {
+            cursors.iter().min_by_key(|cursor| cursor.row)
+        };
+        let Some(&edge) = edge else {
+            return false;
+        };
+        let row = match (below, edge.row.checked_sub(1)) {
+            (true, _) => edge.row + 1,
+            (false, Some(row)) => row,
+            (false, None) => return false,
+        };
+        if row >= self.lines.len() {
+            return false;
+        }
+
+        // Same character column, as far as the line reaches
+        let column = self.lines[edge.row][..edge.col].chars().count();
+        let line = &self.lines[row];
+        let col = line.char_indices().nth(column).map_or(line.len(), |(i, _)| i);
+        self.extra_cursors.push(Cursor {
+            row,
+            col,
+            anchor: None,
+        });
+        true
+    }
+
+    /// Select every whole-word occurrence of the word under the cursor, each
+    /// with its own cursor, so typing replaces them all; returns how many
+    /// were found
+    pub fn edit_all_occurrences(&mut self) -> usize {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+        let line = &self.lines[self.cursor_row];
+        let start = line[..self.cursor_col]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| is_word(*c))
+            .last()
+            .map_or(self.cursor_col, |(i, _)| i);
+        let end = line[self.cursor_col..]
+            .char_indices()
+            .find(|(_, c)| !is_word(*c))
+            .map_or(line.len(), |(i, _)| self.cursor_col + i);
+        if start == end {
+            return 0;
+        }
+
+        let word = &line[start..end];
+        let mut cursors = vec![Cursor {
+            row: self.cursor_row,
+            col: end,
+            anchor: Some(start),
+        }];
+        for (row, line) in self.lines.iter().enumerate() {
+            for (index, _) in line.match_indices(word) {
+                let whole = !line[..index].ends_with(is_word)
+                    && !line[index + word.len()..].starts_with(is_word);
+                if whole && (row, index) != (self.cursor_row, start) {
+                    cursors.push(Cursor {
+                        row,
+                        col: index + word.len(),
+                        anchor: Some(index),
+                    });
+                }
+            }
+        }
+        let count = cursors.len();
+        self.set_cursors(cursors);
+        count
+    }
+
+    /// Make the first cursor the main one, dropping duplicates
+    fn set_cursors(&mut self, mut cursors: Vec<Cursor>) {
+        let primary = cursors.remove(0);
+        self.cursor_row = primary.row;
+        self.cursor_col = primary.col;
+        self.anchor = primary.anchor;
+        cursors.retain(|cursor| (cursor.row, cursor.col) != (primary.row, primary.col));
+        cursors.sort_by_key(|cursor| (cursor.row, cursor.col));
+        cursors.dedup_by_key(|cursor| (cursor.row, cursor.col));
+        self.extra_cursors = cursors;
+    }
+
+    /// Apply a single-line edit at every cursor; `edit` gets the line, the
+    /// cursor and its anchor, and returns the new cursor column
+    fn edit_each(&mut self, edit: impl Fn(&mut String, usize, Option<usize>) -> usize) {
+        let mut cursors = self.cursors();
+        // Last first, so an edit never moves the cursors still to be edited
+        let mut order: Vec<usize> = (0..cursors.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse((cursors[i].row, cursors[i].start())));
+
+        for (done, &i) in order.iter().enumerate() {
+            let Cursor { row, col, anchor } = cursors[i];
+            let line = &mut self.lines[row];
+            let before = line.len();
+            let col = edit(line, col, anchor);
+            let grown = line.len() as isize - before as isize;
+            cursors[i] = Cursor {
+                row,
+                col,
+                anchor: None,
+            };
+            // Cursors already edited further along the line move with the text
+            for &j in &order[..done] {
+                if cursors[j].row == row {
+                    cursors[j].col = cursors[j].col.saturating_add_signed(grown);
+                }
+            }
+        }
+        self.set_cursors(cursors);
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        if c == '\n' {
+            self.clear_cursors();
+            self.newline();
+            return;
+        }
+        if self.has_extra_cursors() {
+            self.edit_each(|line, col, anchor| {
+                let start = delete_selection(line, col, anchor);
+                line.insert(start, c);
+                start + c.len_utf8()
+            });
+            return;
+        }
+
+        self.lines[self.cursor_row].insert(self.cursor_col, c);
+        self.cursor_col += c.len_utf8();
+    }
+
+    pub fn insert_str(&mut self, text: &str) {
+        for c in text.chars() {
+            self.insert_char(c);
+        }
+    }
+
+    /// Split the current line at the cursor
+    pub fn newline(&mut self) {
+        let rest = self.lines[self.cursor_row].split_off(self.cursor_col);
+        self.cursor_row += 1;
+        self.cursor_col = 0;
+        self.lines.insert(self.cursor_row, rest);
+    }
+
+    /// Delete the character before the cursor, joining lines at line start;
+    /// with several cursors, the selections or the characters before each
+    pub fn backspace(&mut self) {
+        if self.has_extra_cursors() {
+            self.edit_each(|line, col, anchor| match anchor {
+                Some(_) => delete_selection(line, col, anchor),
+                None => {
+                    let prev = previous_boundary(line, col);
+                    line.replace_range(prev..col, "");
+                    prev
+                }
+            });
+            return;
+        }
+
+        if self.cursor_col > 0 {
+            let line = &mut self.lines[self.cursor_row];
+            let prev = line[..self.cursor_col]
+                .char_indices()
+                .last()
+                .map(|(i, _)| i)
+                .unwrap_or(0);
+            line.remove(prev);
+            self.cursor_col = prev;
+        } else if self.cursor_row > 0 {
+            let line = self.lines.remove(self.cursor_row);
+            self.cursor_row -= 1;
+            self.cursor_col = self.lines[self.cursor_row].len();
+            self.lines[self.cursor_row].push_str(&line);
+        }
+    }
+
+    /// Move left; with several cursors, each moves within its line
+    pub fn move_left(&mut self) {
+        if self.has_extra_cursors() {
+            self.edit_each(|line, col, _| previous_boundary(line, col));
+            return;
+        }
+
+        if self.cursor_col > 0 {
+            self.cursor_col = previous_boundary(&self.lines[self.cursor_row], self.cursor_col);
+        } else if self.cursor_row > 0 {
+            self.cursor_row -= 1;
+            self.cursor_col = self.lines[self.cursor_row].len();
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if self.has_extra_cursors() {
+            self.edit_each(|line, col, _| {
+                line[col..].chars().next().map_or(col, |c| col + c.len_utf8())
+            });
+            return;
+        }
+
+        let line = &self.lines[self.cursor_row];
+        if let Some(c) = line[self.cursor_col..].chars().next() {
+            self.cursor_col += c.len_utf8();
+        } else if self.cursor_row + 1 < self.lines.len() {
+            self.cursor_row += 1;
+            self.cursor_col = 0;
+        }
+    }
+
+    /// Move up, back to a single cursor
+    pub fn move_up(&mut self) {
+        self.clear_cursors();
+        if self.cursor_row > 0 {
+            self.cursor_row -= 1;
+            self.clamp_cursor_col();
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        self.clear_cursors();
+        if self.cursor_row + 1 < self.lines.len() {
+            self.cursor_row += 1;
+            self.clamp_cursor_col();
+        }
+    }
+
+    fn clamp_cursor_col(&mut self) {
+        let line = &self.lines[self.cursor_row];
+        let mut col = self.cursor_col.min(line.len());
+        while !line.is_char_boundary(col) {
+            col -= 1;
+        }
+        self.cursor_col = col;
+    }
+
+    /// The whole buffer as it will be sent to the shell and stored in history
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Whether the buffer is a complete command that can be submitted
+    pub fn is_complete(&self) -> bool {
+        !needs_continuation(&self.text())
+    }
+
+    /// Highlight spans for every line in the buffer
+    pub fn highlight(&self) -> Vec<Vec<HighlightSpan>> {
+        let mut expect_command = true;
+        let mut continued = false;
+        self.lines
+            .iter()
+            .map(|line| {
+                // A new line starts a new command unless the previous one was continued
+                if !continued {
+                    expect_command = true;
+                }
+                continued = line.trim_end().ends_with('\\');
+                highlight_line(line, &mut expect_command)
+            })
+            .collect()
+    }
+}
+
+/// Byte offset of the character before `col`
+fn previous_boundary(line: &str, col: usize) -> usize {
+    line[..col].char_indices().last().map_or(0, |(i, _)| i)
+}
+
+/// Remove the text between `col` and `anchor`, returning where it started
+fn delete_selection(line: &mut String, col: usize, anchor: Option<usize>) -> usize {
+    match anchor {
+        Some(anchor) => {
+            let (start, end) = (anchor.min(col), anchor.max(col));
+            line.replace_range(start..end, "");
+            start
+        }
+        None => col,
+    }
+}
+
+impl Default for MultilineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Words after which the next word is again in command position
+const COMMAND_PREFIXES: &[&str] = &[
+    "do", "then", "else", "elif", "if", "while", "until", "time", "sudo", "exec", "!",
+];
+
+/// Highlight a single line, tracking whether the next word is a command
+fn highlight_line(line: &str, expect_command: &mut bool) -> Vec<HighlightSpan> {
+    let mut spans = Vec::new();
+    let bytes = line.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let start = i;
+        let kind = match bytes[i] {
+            b' ' | b'\t' => {
+                i += 1;
+                continue;
+            }
+            b'#' => {
+                i = bytes.len();
+                HighlightKind::Comment
+            }
+            b'"' | b'\'' => {
+                let quote = bytes[i];
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    if bytes[i] == b'\\' && quote == b'"' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                i = (i + 1).min(bytes.len());
+                *expect_command = false;
+                HighlightKind::String
+            }
+            b'$' => {
+                i += 1;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || b"_{}".contains(&bytes[i])) {
+                    i += 1;
+                }
+                *expect_command = false;
+                HighlightKind::Variable
+            }
+            b'|' | b'&' | b';' | b'>' | b'<' => {
+                while i < bytes.len() && b"|&;><".contains(&bytes[i]) {
+                    i += 1;
+                }
+                *expect_command = !matches!(&line[start..i], ">" | ">>" | "<");
+                HighlightKind::Operator
+            }
+            b'\\' => {
+                // Skip the backslash and the whole character it escapes
+                i += 1 + line[i + 1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            _ => {
+                while i < bytes.len() && !b" \t|&;><\"'$".contains(&bytes[i]) {
+                    i += 1;
+                }
+                if std::mem::replace(expect_command, false) {
+                    *expect_command = COMMAND_PREFIXES.contains(&&line[start..i]);
+                    HighlightKind::Command
+                } else {
+                    HighlightKind::Argument
+                }
+            }
+        };
+
+        spans.push(HighlightSpan { start, end: i, kind });
+    }
+
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_continuation() {
+        assert!(needs_continuation("echo \"hello"));
+        assert!(needs_continuation("ls \\"));
+        assert!(needs_continuation("cat file |"));
+        assert!(!needs_continuation("echo 'it''s'"));
+        assert!(!needs_continuation("echo \"a \\\" b\""));
+    }
+
+    #[test]
+    fn test_editor_compose() {
+        let mut editor = MultilineEditor::from_input("for f in *; do");
+        editor.newline();
+        editor.insert_str("  echo $f");
+        editor.newline();
+        editor.insert_str("done");
+        assert_eq!(editor.text(), "for f in *; do\n  echo $f\ndone");
+
+        editor.backspace();
+        editor.backspace();
+        editor.backspace();
+        editor.backspace();
+        editor.backspace();
+        assert_eq!(editor.cursor(), (1, 9));
+
+        let spans = editor.highlight();
+        assert_eq!(spans[0][0].kind, HighlightKind::Command);
+        assert_eq!(spans[1][0].kind, HighlightKind::Command);
+        assert_eq!(spans[1][1].kind, HighlightKind::Variable);
+
+        // An escaped multi-byte character is skipped whole
+        let spans = MultilineEditor::from_input("echo \\é x").highlight();
+        assert_eq!(spans[0].last().unwrap().start, 9);
+    }
+
+    #[test]
+    fn test_multiple_cursors() {
+        let mut editor = MultilineEditor::from_input("echo a\necho b");
+        assert!(editor.add_cursor_above());
+        assert!(!editor.add_cursor_above());
+        editor.insert_str("!!");
+        editor.backspace();
+        assert_eq!(editor.text(), "echo a!\necho b!");
+        editor.move_down();
+        assert_eq!(editor.cursors().len(), 1);
+
+        let mut editor = MultilineEditor::from_input("mv src/a src/b && ls src");
+        assert_eq!(editor.edit_all_occurrences(), 3);
+        editor.insert_str("lib");
+        assert_eq!(editor.text(), "mv lib/a lib/b && ls lib");
+        assert_eq!(editor.cursor(), (0, 24));
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::ai::{AiRequest, AiRequestKind};
+
+/// Shortest and longest command sequences considered
+const MIN_STEPS: usize = 2;
+const MAX_STEPS: usize = 5;
+
+/// A named, parameterized sequence of commands
+///
+/// Steps refer to parameters as `{{name}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Workflow {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub steps: Vec<String>,
+    #[serde(default)]
+    pub parameters: Vec<String>,
+}
+
+impl Workflow {
+    /// Steps with parameters filled in; fails if one is missing
+    pub fn render(&self, values: &BTreeMap<String, String>) -> Result<Vec<String>> {
+        self.steps
+            .iter()
+            .map(|step| {
+                let mut line = step.clone();
+                for parameter in &self.parameters {
+                    let value = values
+                        .get(parameter)
+                        .ok_or_else(|| anyhow!("Missing workflow parameter: {}", parameter))?;
+                    line = line.replace(&format!("{{{{{}}}}}", parameter), value);
+                }
+                Ok(line)
+            })
+            .collect()
+    }
+}
+
+/// A repeated command sequence found in history
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowCandidate {
+    pub workflow: Workflow,
+    /// How often the sequence was run
+    pub occurrences: usize,
+}
+
+/// Find command sequences run at least `min_occurrences` times
+///
+/// Sequences match on each command's program; arguments that differ between
+/// runs become parameters. Longer sequences are preferred over the shorter
+/// sequences they contain.
+pub fn detect_workflows(commands: &[&str], min_occurrences: usize) -> Vec<WorkflowCandidate> {
+    let programs: Vec<&str> = commands
+        .iter()
+        .map(|command| command.split_whitespace().next().unwrap_or_default())
+        .collect();
+
+    let mut found: Vec<(Vec<&str>, Vec<usize>)> = Vec::new();
+    for len in MIN_STEPS..=MAX_STEPS.min(programs.len()) {
+        let mut starts: HashMap<&[&str], Vec<usize>> = HashMap::new();
+        for start in 0..=programs.len() - len {
+            let shape = &programs[start..start + len];
+            if shape.iter().any(|program| program.is_empty()) {
+                continue;
+            }
+            let runs = starts.entry(shape).or_default();
+            // Count non-overlapping runs only
+            let overlaps = match runs.last() {
+                Some(last) => start < last + len,
+                None => false,
+            };
+            if !overlaps {
+                runs.push(start);
+            }
+        }
+        for (shape, runs) in starts {
+            if runs.len() >= min_occurrences {
+                found.push((shape.to_vec(), runs));
+            }
+        }
+    }
+
+    found.sort_by(|a, b| {
+        (b.0.len() * b.1.len())
+            .cmp(&(a.0.len() * a.1.len()))
+            .then(b.0.len().cmp(&a.0.len()))
+            .then(a.1[0].cmp(&b.1[0]))
+    });
+    let mut accepted: Vec<Vec<&str>> = Vec::new();
+    let mut candidates = Vec::new();
+    for (shape, runs) in found {
+        if accepted.iter().any(|longer| longer.windows(shape.len()).any(|w| w == shape)) {
+            continue;
+        }
+        candidates.push(WorkflowCandidate {
+            workflow: parameterize(commands, &runs, shape.len()),
+            occurrences: runs.len(),
+        });
+        accepted.push(shape);
+    }
+    candidates
+}
+
+/// Build a workflow from the runs of one sequence
+fn parameterize(commands: &[&str], runs: &[usize], len: usize) -> Workflow {
+    let latest = runs[runs.len() - 1];
+    let mut steps = Vec::with_capacity(len);
+    // Values of each varying argument across runs, mapped to a parameter name
+    let mut parameters: Vec<(Vec<&str>, String)> = Vec::new();
+
+    for step in 0..len {
+        let runs_words: Vec<Vec<&str>> = runs
+            .iter()
+            .map(|start| commands[start + step].split_whitespace().collect())
+            .collect();
+        let words = &runs_words[runs_words.len() - 1];
+        if runs_words.iter().any(|other| other.len() != words.len()) {
+            steps.push(commands[latest + step].to_string());
+            continue;
+        }
+
+        let mut line = Vec::with_capacity(words.len());
+        for (position, word) in words.iter().enumerate() {
+            let values: Vec<&str> = runs_words.iter().map(|other| other[position]).collect();
+            if values.iter().all(|value| value == word) {
+                line.push(word.to_string());
+                continue;
+            }
+            let name = match parameters.iter().find(|(seen, _)| *seen == values) {
+                Some((_, name)) => name.clone(),
+                None => {
+                    let name = format!("arg{}", parameters.len() + 1);
+                    parameters.push((values, name.clone()));
+                    name
+                }
+            };
+            line.push(format!("{{{{{}}}}}", name));
+        }
+        steps.push(line.join(" "));
+    }
+
+    let programs: Vec<&str> = steps
+        .iter()
+        .filter_map(|step| step.split_whitespace().next())
+        .collect();
+    Workflow {
+        name: programs.join("-"),
+        description: String::new(),
+        steps,
+        parameters: parameters.into_iter().map(|(_, name)| name).collect(),
+    }
+}
+
+/// Prompt asking the AI provider to name and describe a workflow
+///
+/// The reply is expected as a name on the first line and a one-sentence
+/// description on the second; see [`apply_naming`].
+pub fn naming_request(workflow: &Workflow) -> AiRequest {
+    AiRequest::new(
+        AiRequestKind::Chat,
+        &format!(
+            "These shell commands are often run together:\n{}\n\nReply with a short \
+             kebab-case name on the first line and a one-sentence description on the second.",
+            workflow.steps.join("\n")
+        ),
+    )
+}
+
+/// Apply the provider's reply to [`naming_request`], keeping the generated
+/// name if the reply has none
+pub fn apply_naming(workflow: &mut Workflow, reply: &str) {
+    let mut lines = reply.lines().map(str::trim).filter(|line| !line.is_empty());
+    if let Some(name) = lines.next() {
+        let name = name.trim_matches(|c: char| c == '`' || c == '"' || c == '*');
+        if !name.is_empty() && !name.contains(char::is_whitespace) {
+            workflow.name = name.to_string();
+        }
+    }
+    if let Some(description) = lines.next() {
+        workflow.description = description.to_string();
+    }
+}
+
+/// Saved workflows, kept as JSON in the config directory
+pub struct WorkflowStore {
+    path: PathBuf,
+    workflows: Vec<Workflow>,
+}
+
+impl WorkflowStore {
+    /// ~/.config/voidcli/workflows.json
+    pub fn default_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_default()
+            .join("voidcli")
+            .join("workflows.json")
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let workflows = if path.exists() {
+            let contents = fs::read_to_string(&path).context("Failed to read workflows")?;
+            serde_json::from_str(&contents).context("Failed to parse workflows")?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path, workflows })
+    }
+
+    pub fn workflows(&self) -> &[Workflow] {
+        &self.workflows
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Workflow> {
+        self.workflows.iter().find(|workflow| workflow.name == name)
+    }
+
+    /// Add or replace a workflow by name and save
+    pub fn save_workflow(&mut self, workflow: Workflow) -> Result<()> {
+        match self.workflows.iter_mut().find(|w| w.name == workflow.name) {
+            Some(existing) => *existing = workflow,
+            None => self.workflows.push(workflow),
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).context("Failed to create workflows directory")?;
+        }
+        let json = serde_json::to_string_pretty(&self.workflows)
+            .context("Failed to serialize workflows")?;
+        fs::write(&self.path, json).context("Failed to write workflows")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_workflows() {
+        let history = [
+            "git checkout -b feat-a",
+            "cargo test",
+            "git push -u origin feat-a",
+            "ls",
+            "git checkout -b fix-b",
+            "cargo test",
+            "git push -u origin fix-b",
+            "vim notes.md",
+            "git checkout -b docs-c",
+            "cargo test",
+            "git push -u origin docs-c",
+        ];
+        let candidates = detect_workflows(&history, 3);
+        assert_eq!(candidates.len(), 1);
+
+        let mut workflow = candidates[0].workflow.clone();
+        assert_eq!(candidates[0].occurrences, 3);
+        assert_eq!(
+            workflow.steps,
+            vec!["git checkout -b {{arg1}}", "cargo test", "git push -u origin {{arg1}}"]
+        );
+
+        apply_naming(&mut workflow, "`new-branch`\nCreates, tests and pushes a branch.");
+        assert_eq!(workflow.name, "new-branch");
+        let values = BTreeMap::from([("arg1".to_string(), "x".to_string())]);
+        assert_eq!(workflow.render(&values).unwrap()[2], "git push -u origin x");
+    }
+}
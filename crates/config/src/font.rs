@@ -0,0 +1,129 @@
+// OpenType feature settings passed to the text shaper
+//
+// Features are written as a tag and an optional value, e.g. `calt off`,
+// `ss01 on`, `zero`, `cv05 2`, or in the CSS/HarfBuzz form `-calt`, `+ss01`
+// and `cv05=2`.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use crate::FontConfig;
+
+/// One OpenType feature and the value to shape with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FontFeature {
+    pub tag: [u8; 4],
+    /// 0 turns the feature off, 1 on; larger values pick an alternate
+    pub value: u32,
+}
+
+impl FontFeature {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let (tag, value) = if let Some(tag) = text.strip_prefix('-') {
+            (tag, Some("off"))
+        } else if let Some(tag) = text.strip_prefix('+') {
+            (tag, None)
+        } else {
+            match text.split_once(|c: char| c == '=' || c.is_whitespace()) {
+                Some((tag, value)) => (tag, Some(value.trim())),
+                None => (text, None),
+            }
+        };
+
+        let tag: [u8; 4] = tag
+            .as_bytes()
+            .try_into()
+            .ok()
+            .filter(|tag: &[u8; 4]| tag.iter().all(|byte| (0x20..=0x7e).contains(byte)))
+            .ok_or_else(|| anyhow!("Feature tag must be 4 ASCII characters in '{}'", text))?;
+        let value = match value.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("on") | Some("true") => 1,
+            Some("off") | Some("false") => 0,
+            Some(number) => number
+                .parse()
+                .map_err(|_| anyhow!("Invalid feature value '{}' in '{}'", number, text))?,
+        };
+        Ok(FontFeature { tag, value })
+    }
+
+    pub fn tag_str(&self) -> &str {
+        std::str::from_utf8(&self.tag).unwrap_or("????")
+    }
+}
+
+impl fmt::Display for FontFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value {
+            0 => write!(f, "-{}", self.tag_str()),
+            1 => write!(f, "+{}", self.tag_str()),
+            value => write!(f, "{}={}", self.tag_str(), value),
+        }
+    }
+}
+
+impl FontConfig {
+    /// Features to shape `family` with: the global list, then that family's
+    /// own settings, a later setting of a tag replacing an earlier one
+    pub fn features_for(&self, family: &str) -> Result<Vec<FontFeature>> {
+        let own = self
+            .family_features
+            .iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(family))
+            .flat_map(|(_, features)| features);
+
+        let mut resolved: Vec<FontFeature> = Vec::new();
+        for text in self.features.iter().chain(own) {
+            let feature = FontFeature::parse(text)?;
+            resolved.retain(|existing| existing.tag != feature.tag);
+            resolved.push(feature);
+        }
+        Ok(resolved)
+    }
+
+    /// Check every configured feature parses
+    pub fn validate_features(&self) -> Result<()> {
+        for text in self.features.iter().chain(self.family_features.values().flatten()) {
+            FontFeature::parse(text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_parse_features() {
+        let feature = |text| FontFeature::parse(text).unwrap().to_string();
+        assert_eq!(feature("calt off"), "-calt");
+        assert_eq!(feature("ss01 on"), "+ss01");
+        assert_eq!(feature("zero"), "+zero");
+        assert_eq!(feature("cv05=2"), "cv05=2");
+        assert_eq!(feature("-liga"), "-liga");
+        assert!(FontFeature::parse("ligatures off").is_err());
+        assert!(FontFeature::parse("ss01 maybe").is_err());
+    }
+
+    #[test]
+    fn test_per_family_features() {
+        let mut font = Config::default().font;
+        font.features = vec!["calt off".to_string(), "zero".to_string()];
+        font.family_features = BTreeMap::from([(
+            "Fira Code".to_string(),
+            vec!["calt on".to_string(), "ss01".to_string()],
+        )]);
+
+        let fira: Vec<String> = font
+            .features_for("fira code")
+            .unwrap()
+            .iter()
+            .map(|feature| feature.to_string())
+            .collect();
+        assert_eq!(fira, vec!["+zero", "+calt", "+ss01"]);
+        assert_eq!(font.features_for("JetBrains Mono").unwrap().len(), 2);
+    }
+}
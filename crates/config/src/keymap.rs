@@ -0,0 +1,469 @@
+// Keybinding DSL: chords, multi-key sequences, a leader key and per-mode maps
+//
+// Sequences are written as chords separated by `then`, e.g. `ctrl+a then c`
+// or `leader then w`.
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use crate::KeybindingsConfig;
+
+/// Input modes with their own key maps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mode {
+    Normal,
+    Search,
+    BlockNavigation,
+    Pager,
+    /// The escape-sequence inspector has focus
+    Inspector,
+    /// Keyboard selection over a block or the scrollback
+    Copy,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 6] = [
+        Mode::Normal,
+        Mode::Search,
+        Mode::BlockNavigation,
+        Mode::Pager,
+        Mode::Inspector,
+        Mode::Copy,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Search => "search",
+            Mode::BlockNavigation => "block-navigation",
+            Mode::Pager => "pager",
+            Mode::Inspector => "inspector",
+            Mode::Copy => "copy",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// A key with modifiers, e.g. `ctrl+shift+enter`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub super_key: bool,
+    /// A character, or a lowercase named key like `enter`
+    pub key: String,
+}
+
+impl KeyChord {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut chord = KeyChord {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            super_key: false,
+            key: String::new(),
+        };
+
+        let text = text.trim();
+        // `ctrl++` binds the plus key
+        let (modifiers, key) = match text.strip_suffix("++") {
+            Some(modifiers) => (modifiers, "+"),
+            None => match text.rsplit_once('+') {
+                Some((modifiers, key)) => (modifiers, key),
+                None => ("", text),
+            },
+        };
+
+        for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "alt" | "option" | "meta" => chord.alt = true,
+                "shift" => chord.shift = true,
+                "super" | "cmd" | "win" => chord.super_key = true,
+                other => return Err(anyhow!("Unknown modifier '{}' in '{}'", other, text)),
+            }
+        }
+
+        if key.is_empty() {
+            return Err(anyhow!("Missing key in '{}'", text));
+        }
+        chord.key = match key.to_ascii_lowercase().as_str() {
+            "return" => "enter".to_string(),
+            "esc" => "escape".to_string(),
+            "del" => "delete".to_string(),
+            "pgup" => "pageup".to_string(),
+            "pgdown" => "pagedown".to_string(),
+            _ if key.chars().count() == 1 => key.to_string(),
+            other => other.to_string(),
+        };
+
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        if self.super_key {
+            write!(f, "super+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// One or more chords pressed in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeySequence(pub Vec<KeyChord>);
+
+impl KeySequence {
+    /// Parse `ctrl+a then c`; `leader` expands to the leader sequence
+    pub fn parse(text: &str, leader: Option<&KeySequence>) -> Result<Self> {
+        let mut chords = Vec::new();
+        for part in text.split(" then ") {
+            if part.trim().eq_ignore_ascii_case("leader") {
+                let leader = leader
+                    .ok_or_else(|| anyhow!("'{}' uses leader but no leader key is set", text))?;
+                chords.extend(leader.0.iter().cloned());
+            } else {
+                chords.push(KeyChord::parse(part)?);
+            }
+        }
+        Ok(KeySequence(chords))
+    }
+
+    fn starts_with(&self, prefix: &[KeyChord]) -> bool {
+        self.0.len() >= prefix.len() && self.0[..prefix.len()] == *prefix
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, chord) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " then ")?;
+            }
+            write!(f, "{}", chord)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingSource {
+    Default,
+    User,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub mode: Mode,
+    pub sequence: KeySequence,
+    pub action: String,
+    pub source: BindingSource,
+}
+
+/// Two bindings that cannot both work
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub mode: Mode,
+    /// The shadowing binding
+    pub first: KeySequence,
+    /// The binding that is shadowed or replaced
+    pub second: KeySequence,
+    pub actions: (String, String),
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.first == self.second {
+            write!(
+                f,
+                "[{}] '{}' is bound to both '{}' and '{}'",
+                self.mode.name(),
+                self.first,
+                self.actions.0,
+                self.actions.1
+            )
+        } else {
+            write!(
+                f,
+                "[{}] '{}' ({}) makes '{}' ({}) unreachable",
+                self.mode.name(),
+                self.first,
+                self.actions.0,
+                self.second,
+                self.actions.1
+            )
+        }
+    }
+}
+
+/// Result of feeding a chord to the keymap
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyResult {
+    /// A binding matched
+    Action(String),
+    /// The chords so far are a prefix of a binding; wait for more
+    Pending,
+    /// Nothing matched; the chords should be passed on to the terminal
+    Unbound(Vec<KeyChord>),
+}
+
+/// Effective key bindings for all modes
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<Binding>,
+    conflicts: Vec<Conflict>,
+    pending: Vec<KeyChord>,
+}
+
+impl Keymap {
+    pub fn from_config(config: &KeybindingsConfig) -> Result<Self> {
+        let leader = config
+            .leader
+            .as_deref()
+            .map(|leader| KeySequence::parse(leader, None))
+            .transpose()?;
+
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+            conflicts: Vec::new(),
+            pending: Vec::new(),
+        };
+
+        let defaults = [
+            (&config.accept_suggestion, "accept-suggestion"),
+            (&config.accept_suggestion_word, "accept-suggestion-word"),
+            (&config.compose_multiline, "compose-multiline"),
+            (&config.open_pager, "open-pager"),
+            (&config.block_menu, "block-menu"),
+            (&config.select_output, "select-output"),
+            (&config.select_command, "select-command"),
+            (&config.select_path, "select-path"),
+            (&config.select_word, "select-word"),
+            (&config.zoom_pane, "zoom-pane"),
+            (&config.accept_correction, "accept-correction"),
+            (&config.link_scroll, "link-scroll"),
+            (&config.monitor_activity, "monitor-activity"),
+            (&config.monitor_silence, "monitor-silence"),
+            (&config.toggle_debug_hud, "toggle-debug-hud"),
+            (&config.toggle_inspector, "toggle-inspector"),
+            (&config.ui_zoom_in, "ui-zoom-in"),
+            (&config.ui_zoom_out, "ui-zoom-out"),
+            (&config.ui_zoom_reset, "ui-zoom-reset"),
+            (&config.copy_mode, "copy-mode"),
+            (&config.focus_previous_block, "focus-previous-block"),
+            (&config.focus_next_block, "focus-next-block"),
+            (&config.previous_prompt, "previous-prompt"),
+            (&config.next_prompt, "next-prompt"),
+            (&config.select_last_output, "select-last-output"),
+            (&config.focus_previous_pane, "focus-previous-pane"),
+            (&config.focus_next_pane, "focus-next-pane"),
+            (&config.resize_pane_left, "resize-pane-left"),
+            (&config.resize_pane_right, "resize-pane-right"),
+            (&config.resize_pane_up, "resize-pane-up"),
+            (&config.resize_pane_down, "resize-pane-down"),
+            (&config.add_cursor_above, "add-cursor-above"),
+            (&config.add_cursor_below, "add-cursor-below"),
+            (&config.edit_all_occurrences, "edit-all-occurrences"),
+        ];
+        for (keys, action) in defaults {
+            let sequence = KeySequence::parse(keys, leader.as_ref())?;
+            keymap.insert(Mode::Normal, sequence, action, BindingSource::Default);
+        }
+
+        let inspector_defaults = [
+            (&config.toggle_inspector, "toggle-inspector"),
+            (&config.inspector_pause, "inspector-pause"),
+            (&config.inspector_step, "inspector-step"),
+        ];
+        for (keys, action) in inspector_defaults {
+            let sequence = KeySequence::parse(keys, leader.as_ref())?;
+            keymap.insert(Mode::Inspector, sequence, action, BindingSource::Default);
+        }
+
+        for (mode_name, bindings) in &config.modes {
+            let mode = Mode::from_name(mode_name)
+                .ok_or_else(|| anyhow!("Unknown keybinding mode '{}'", mode_name))?;
+            for (keys, action) in bindings {
+                let sequence = KeySequence::parse(keys, leader.as_ref())?;
+                keymap.insert(mode, sequence, action, BindingSource::User);
+            }
+        }
+
+        keymap.detect_prefix_conflicts();
+        Ok(keymap)
+    }
+
+    /// User bindings replace defaults silently; duplicate user bindings conflict
+    fn insert(&mut self, mode: Mode, sequence: KeySequence, action: &str, source: BindingSource) {
+        if let Some(existing) = self
+            .bindings
+            .iter_mut()
+            .find(|b| b.mode == mode && b.sequence == sequence)
+        {
+            if existing.source == source && existing.action != action {
+                self.conflicts.push(Conflict {
+                    mode,
+                    first: sequence.clone(),
+                    second: sequence.clone(),
+                    actions: (existing.action.clone(), action.to_string()),
+                });
+            }
+            existing.action = action.to_string();
+            existing.source = source;
+            return;
+        }
+
+        self.bindings.push(Binding {
+            mode,
+            sequence,
+            action: action.to_string(),
+            source,
+        });
+    }
+
+    fn detect_prefix_conflicts(&mut self) {
+        for short in &self.bindings {
+            for long in &self.bindings {
+                if short.mode == long.mode
+                    && short.sequence.0.len() < long.sequence.0.len()
+                    && long.sequence.starts_with(&short.sequence.0)
+                {
+                    self.conflicts.push(Conflict {
+                        mode: short.mode,
+                        first: short.sequence.clone(),
+                        second: long.sequence.clone(),
+                        actions: (short.action.clone(), long.action.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Bindings of a mode (or all modes), sorted for display
+    pub fn list(&self, mode: Option<Mode>) -> Vec<&Binding> {
+        let mut bindings: Vec<&Binding> = self
+            .bindings
+            .iter()
+            .filter(|b| mode.is_none() || mode == Some(b.mode))
+            .collect();
+        bindings.sort_by(|a, b| {
+            (a.mode, a.sequence.to_string()).cmp(&(b.mode, b.sequence.to_string()))
+        });
+        bindings
+    }
+
+    /// Feed a chord pressed in `mode`
+    pub fn feed(&mut self, mode: Mode, chord: KeyChord) -> KeyResult {
+        self.pending.push(chord);
+
+        let mut is_prefix = false;
+        for binding in self.bindings.iter().filter(|b| b.mode == mode) {
+            if binding.sequence.0 == self.pending {
+                self.pending.clear();
+                return KeyResult::Action(binding.action.clone());
+            }
+            is_prefix |= binding.sequence.starts_with(&self.pending);
+        }
+
+        if is_prefix {
+            KeyResult::Pending
+        } else {
+            KeyResult::Unbound(std::mem::take(&mut self.pending))
+        }
+    }
+
+    /// Abandon a partially typed sequence, e.g. after a timeout
+    pub fn reset(&mut self) -> Vec<KeyChord> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::collections::BTreeMap;
+
+    fn chord(text: &str) -> KeyChord {
+        KeyChord::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        let leader = KeySequence::parse("ctrl+space", None).unwrap();
+        let sequence = KeySequence::parse("leader then Shift+W", Some(&leader)).unwrap();
+        assert_eq!(sequence.to_string(), "ctrl+space then shift+W");
+        assert_eq!(chord("ctrl++").key, "+");
+        assert!(KeyChord::parse("hyper+a").is_err());
+        assert!(KeySequence::parse("leader then c", None).is_err());
+    }
+
+    #[test]
+    fn test_modes_and_chords() {
+        let mut config = Config::default().keybindings;
+        config.leader = Some("ctrl+a".to_string());
+        config.modes = BTreeMap::from([(
+            "normal".to_string(),
+            BTreeMap::from([
+                ("leader then c".to_string(), "new-tab".to_string()),
+                ("leader".to_string(), "select-all".to_string()),
+            ]),
+        )]);
+
+        let mut keymap = Keymap::from_config(&config).unwrap();
+        assert_eq!(keymap.conflicts().len(), 1);
+        assert_eq!(
+            keymap.conflicts()[0].to_string(),
+            "[normal] 'ctrl+a' (select-all) makes 'ctrl+a then c' (new-tab) unreachable"
+        );
+
+        assert_eq!(
+            keymap.feed(Mode::Normal, chord("ctrl+a")),
+            KeyResult::Action("select-all".to_string())
+        );
+        assert_eq!(
+            keymap.feed(Mode::Pager, chord("x")),
+            KeyResult::Unbound(vec![chord("x")])
+        );
+        assert_eq!(
+            keymap.feed(Mode::Normal, chord("ctrl+alt+o")),
+            KeyResult::Action("select-output".to_string())
+        );
+    }
+
+    #[test]
+    fn test_pending_sequence() {
+        let mut config = Config::default().keybindings;
+        config.modes = BTreeMap::from([(
+            "block-navigation".to_string(),
+            BTreeMap::from([("g then g".to_string(), "first-block".to_string())]),
+        )]);
+
+        let mut keymap = Keymap::from_config(&config).unwrap();
+        assert!(keymap.conflicts().is_empty());
+        assert_eq!(keymap.feed(Mode::BlockNavigation, chord("g")), KeyResult::Pending);
+        assert_eq!(
+            keymap.feed(Mode::BlockNavigation, chord("g")),
+            KeyResult::Action("first-block".to_string())
+        );
+        assert_eq!(keymap.list(Some(Mode::BlockNavigation)).len(), 1);
+    }
+}
@@ -4,15 +4,420 @@
 //
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::Path;
 
+pub mod font;
+pub mod keymap;
+pub mod pattern;
+pub mod timestamp;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub theme: String,
+    /// Nudge text towards this WCAG contrast ratio (1.0–21.0) against its
+    /// background; unset leaves program colors untouched
+    #[serde(default)]
+    pub minimum_contrast: Option<f32>,
+    /// Adjust the theme palette for a color vision deficiency
+    #[serde(default)]
+    pub color_vision: Option<ColorVisionConfig>,
     pub font: FontConfig,
     pub terminal: TerminalConfig,
     pub keybindings: KeybindingsConfig,
     pub performance: PerformanceConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub bell: BellConfig,
+    /// Sounds and flashes for terminal events
+    #[serde(default)]
+    pub feedback: FeedbackConfig,
+    #[serde(default)]
+    pub diagnostics: DiagnosticsConfig,
+    #[serde(default)]
+    pub blocks: BlocksConfig,
+    /// Named layout templates, opened with `--layout NAME`
+    #[serde(default)]
+    pub layouts: BTreeMap<String, LayoutPreset>,
+    #[serde(default)]
+    pub ai: AiConfig,
+    #[serde(default)]
+    pub crash_reports: CrashReportConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub timestamps: TimestampConfig,
+    /// Profile and theme overrides for panes inside matching directories
+    #[serde(default)]
+    pub directory_profiles: DirectoryProfilesConfig,
+    #[serde(default)]
+    pub updates: UpdateConfig,
+    /// Limits on what program output can do through escape sequences
+    #[serde(default)]
+    pub security: SecurityConfig,
+    /// When pastes are held for a preview before reaching the shell
+    #[serde(default)]
+    pub paste: PasteConfig,
+    /// Full-text search over the commands and output of past sessions
+    #[serde(default)]
+    pub search_index: SearchIndexConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchIndexConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Oldest blocks are dropped beyond this size
+    #[serde(default = "default_search_index_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Blocks older than this are dropped
+    #[serde(default = "default_search_index_max_age_days")]
+    pub max_age_days: u64,
+}
+
+fn default_search_index_max_size_mb() -> u64 {
+    200
+}
+
+fn default_search_index_max_age_days() -> u64 {
+    90
+}
+
+impl Default for SearchIndexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_size_mb: default_search_index_max_size_mb(),
+            max_age_days: default_search_index_max_age_days(),
+        }
+    }
+}
+
+/// Log levels and where logs are written; `--log-level` and `--log-file`
+/// override `level` and `file`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Level for modules without their own entry: error, warn, info, debug
+    /// or trace
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    /// Per-module levels, e.g. `term::parser: debug`
+    #[serde(default)]
+    pub modules: BTreeMap<String, String>,
+    /// Append logs to this file instead of stderr
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Rotate the log file once it grows past this size
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files kept next to the log file (`voidcli.log.1`, ...)
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_log_max_size_mb() -> u64 {
+    10
+}
+
+fn default_log_max_files() -> usize {
+    5
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            modules: BTreeMap::new(),
+            file: None,
+            max_size_mb: default_log_max_size_mb(),
+            max_files: default_log_max_files(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasteConfig {
+    /// Confirm pastes of more than one line
+    #[serde(default = "default_true")]
+    pub confirm_multiline: bool,
+    /// Confirm pastes ending in a newline, which would run at once
+    #[serde(default = "default_true")]
+    pub confirm_trailing_newline: bool,
+    /// Confirm pastes with control, zero-width or bidi override characters
+    #[serde(default = "default_true")]
+    pub confirm_hidden: bool,
+    /// Skip the line checks when the program enabled bracketed paste, so
+    /// newlines don't run anything; hidden characters are still confirmed
+    #[serde(default)]
+    pub trust_bracketed: bool,
+}
+
+impl Default for PasteConfig {
+    fn default() -> Self {
+        Self {
+            confirm_multiline: true,
+            confirm_trailing_newline: true,
+            confirm_hidden: true,
+            trust_bracketed: false,
+        }
+    }
+}
+
+/// Policy for OSC-driven features, since any program output (a `cat` of an
+/// untrusted file, a remote shell) can send these sequences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Make OSC 8 hyperlinks clickable
+    #[serde(default = "default_true")]
+    pub hyperlinks: bool,
+    /// URI schemes OSC 8 links may open
+    #[serde(default = "default_link_schemes")]
+    pub link_schemes: Vec<String>,
+    /// Show the target and ask before opening an OSC 8 link
+    #[serde(default = "default_true")]
+    pub confirm_links: bool,
+    /// Let programs set the clipboard with OSC 52
+    #[serde(default = "default_true")]
+    pub clipboard_write: bool,
+    /// Largest decoded OSC 52 write accepted
+    #[serde(default = "default_clipboard_max_bytes")]
+    pub clipboard_max_bytes: usize,
+    /// OSC 52 writes accepted per pane and minute
+    #[serde(default = "default_clipboard_writes_per_minute")]
+    pub clipboard_writes_per_minute: usize,
+}
+
+fn default_link_schemes() -> Vec<String> {
+    ["http", "https", "mailto", "file"]
+        .iter()
+        .map(|scheme| scheme.to_string())
+        .collect()
+}
+
+fn default_clipboard_max_bytes() -> usize {
+    100_000
+}
+
+fn default_clipboard_writes_per_minute() -> usize {
+    10
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self {
+            hyperlinks: true,
+            link_schemes: default_link_schemes(),
+            confirm_links: true,
+            clipboard_write: true,
+            clipboard_max_bytes: default_clipboard_max_bytes(),
+            clipboard_writes_per_minute: default_clipboard_writes_per_minute(),
+        }
+    }
+}
+
+/// Release channel followed by the update check and `voidcli self-update`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    /// Pre-releases as well
+    Nightly,
+}
+
+impl UpdateChannel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "stable" => Some(UpdateChannel::Stable),
+            "nightly" => Some(UpdateChannel::Nightly),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateConfig {
+    /// Look for a new release at most once a day on startup; off unless
+    /// enabled since it contacts GitHub
+    #[serde(default)]
+    pub check: bool,
+    #[serde(default)]
+    pub channel: UpdateChannel,
+    /// GitHub repository releases are taken from, as `owner/name`
+    #[serde(default = "default_update_repository")]
+    pub repository: String,
+}
+
+fn default_update_repository() -> String {
+    "deep60/VoidCLI".to_string()
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            check: false,
+            channel: UpdateChannel::default(),
+            repository: default_update_repository(),
+        }
+    }
+}
+
+/// What a crash report written by the panic hook contains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReportConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Keep command lines and output in the recent events; redacted otherwise
+    #[serde(default)]
+    pub include_block_text: bool,
+}
+
+impl Default for CrashReportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            include_block_text: false,
+        }
+    }
+}
+
+/// AI features and what they may send off the machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AiConfig {
+    #[serde(default)]
+    pub context: AiContextConfig,
+    /// Extra token prefixes to redact, on top of the built-in secret rules
+    #[serde(default)]
+    pub redact_prefixes: Vec<String>,
+    /// Index history and block output for meaning-based palette search;
+    /// without an embedding model it matches shared words only
+    #[serde(default)]
+    pub semantic_search: bool,
+    /// Only allow backends on localhost, checked on every request
+    #[serde(default)]
+    pub local_only: bool,
+    #[serde(default)]
+    pub limits: AiLimitsConfig,
+}
+
+/// Allowlist of context attached to AI requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiContextConfig {
+    #[serde(default = "default_true")]
+    pub cwd: bool,
+    #[serde(default = "default_true")]
+    pub os: bool,
+    #[serde(default = "default_true")]
+    pub shell: bool,
+    /// Command lines of recent blocks
+    #[serde(default = "default_true")]
+    pub commands: bool,
+    /// Output of recent blocks
+    #[serde(default = "default_true")]
+    pub output: bool,
+    /// Recent blocks included
+    #[serde(default = "default_ai_max_blocks")]
+    pub max_blocks: usize,
+    /// Upper bound of the whole context in characters
+    #[serde(default = "default_ai_max_chars")]
+    pub max_chars: usize,
+}
+
+fn default_ai_max_blocks() -> usize {
+    5
+}
+
+fn default_ai_max_chars() -> usize {
+    8000
+}
+
+impl Default for AiContextConfig {
+    fn default() -> Self {
+        Self {
+            cwd: true,
+            os: true,
+            shell: true,
+            commands: true,
+            output: true,
+            max_blocks: default_ai_max_blocks(),
+            max_chars: default_ai_max_chars(),
+        }
+    }
+}
+
+/// Budgets for AI provider calls; requests past them are skipped
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiLimitsConfig {
+    #[serde(default = "default_ai_requests_per_minute")]
+    pub requests_per_minute: u32,
+    /// Estimated prompt and response tokens per minute
+    #[serde(default = "default_ai_tokens_per_minute")]
+    pub tokens_per_minute: u64,
+    /// Responses kept for identical prompts; 0 disables the cache
+    #[serde(default = "default_ai_cache_entries")]
+    pub cache_entries: usize,
+}
+
+fn default_ai_requests_per_minute() -> u32 {
+    20
+}
+
+fn default_ai_tokens_per_minute() -> u64 {
+    40_000
+}
+
+fn default_ai_cache_entries() -> usize {
+    100
+}
+
+impl Default for AiLimitsConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: default_ai_requests_per_minute(),
+            tokens_per_minute: default_ai_tokens_per_minute(),
+            cache_entries: default_ai_cache_entries(),
+        }
+    }
+}
+
+/// A pane, or a split of two nested presets when `split` is set
+///
+/// ```yaml
+/// layouts:
+///   dev:
+///     split: horizontal
+///     ratio: 0.7
+///     first: { command: nvim }
+///     second:
+///       split: vertical
+///       first: {}
+///       second: { command: cargo watch }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayoutPreset {
+    /// `horizontal` (side by side) or `vertical` (stacked)
+    #[serde(default)]
+    pub split: Option<String>,
+    /// Share of the space taken by `first`
+    #[serde(default)]
+    pub ratio: Option<f32>,
+    #[serde(default)]
+    pub first: Option<Box<LayoutPreset>>,
+    #[serde(default)]
+    pub second: Option<Box<LayoutPreset>>,
+    /// Working directory of a pane; defaults to the current directory
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Command run in a pane once its shell has started
+    #[serde(default)]
+    pub command: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +425,12 @@ pub struct FontConfig {
     pub name: String,
     pub size: f32,
     pub line_height: f32,
+    /// OpenType features for every font, e.g. `calt off`, `ss01 on`, `zero`
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// Features for a single font family, applied after `features`
+    #[serde(default)]
+    pub family_features: BTreeMap<String, Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,11 +438,462 @@ pub struct TerminalConfig {
     pub shell: String,
     pub scrollback_lines: usize,
     pub cursor_blink: bool,
+    /// Inject the OSC 133/7 shell integration into supported shells
+    #[serde(default = "default_true")]
+    pub shell_integration: bool,
+    /// Template for tab and window titles; supports `{title}`, `{cwd}` and `{shell}`
+    #[serde(default = "default_title_format")]
+    pub title_format: String,
+    #[serde(default)]
+    pub keyboard: KeyboardConfig,
+    /// Treat all input as UTF-8; when false, 8-bit C1 controls are interpreted
+    #[serde(default = "default_true")]
+    pub utf8_only: bool,
+    /// Environment added to spawned processes when a rule matches
+    #[serde(default)]
+    pub env_rules: Vec<EnvRule>,
+    /// Start the shell as a login shell
+    #[serde(default)]
+    pub login_shell: bool,
+    /// Extra arguments passed to the shell
+    #[serde(default)]
+    pub shell_args: Vec<String>,
+    /// Command typed into the shell once it has started
+    #[serde(default)]
+    pub startup_command: Option<String>,
+    /// Program and arguments to run instead of the shell, as given with `-e`
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    /// Keep the pane open after `command` exits
+    #[serde(default)]
+    pub hold: bool,
+    /// What happens to a pane when its shell or command exits
+    #[serde(default)]
+    pub on_exit: OnExit,
+    /// Where new splits and tabs start their shell
+    #[serde(default)]
+    pub new_pane_cwd: NewPaneCwd,
+    /// Reorder right-to-left text (Arabic, Hebrew) for display; sessions and
+    /// programs (BDSM, `CSI 8 l`) can turn it off for themselves
+    #[serde(default = "default_true")]
+    pub bidi: bool,
+    /// Regex matching prompt lines, for jumping between prompts in shells
+    /// that don't send OSC 133 marks
+    #[serde(default)]
+    pub prompt_pattern: Option<String>,
+    /// Handlers for private OSC numbers and APC payloads sent by in-house
+    /// tools
+    #[serde(default)]
+    pub escape_hooks: Vec<EscapeHook>,
+}
+
+/// Maps a custom escape sequence to an action or a script
+///
+/// Set either `osc` or `apc`, and either `action` or `run`. The payload is
+/// everything after the OSC number's `;`, or the whole APC string.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EscapeHook {
+    /// OSC number to claim, e.g. `5522`; numbers the terminal handles
+    /// itself are refused
+    #[serde(default)]
+    pub osc: Option<u32>,
+    /// Prefix of the APC payloads to claim, e.g. `myapp;`
+    #[serde(default)]
+    pub apc: Option<String>,
+    /// Only payloads starting with this, e.g. `set-status;`
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Keybinding action to run, e.g. `toggle_hud`
+    #[serde(default)]
+    pub action: Option<String>,
+    /// Shell command to run with the payload in `$VOIDCLI_PAYLOAD`
+    #[serde(default)]
+    pub run: Option<String>,
+}
+
+/// What a pane does once its process has exited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnExit {
+    #[default]
+    Close,
+    /// Keep the final output on screen with a "process exited" banner
+    Hold,
+    /// Start the shell again
+    Restart,
+    /// Start the shell again only after a non-zero exit code, close otherwise
+    RestartOnFailure,
+}
+
+/// Working directory for shells of new splits and tabs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NewPaneCwd {
+    /// The focused pane's directory, as reported via OSC 7
+    #[default]
+    Current,
+    Home,
+    /// The directory VoidCLI was started in
+    Start,
+}
+
+/// Extra environment for processes spawned under matching conditions
+///
+/// All given conditions must match; a rule without conditions always applies.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvRule {
+    /// Name of the active profile
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Working directory pattern; `~` expands to the home directory and `*`
+    /// matches any run of characters, e.g. `~/work/*`
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Prefix of the spawned command line
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Directories put in front of `PATH`
+    #[serde(default)]
+    pub path_prepend: Vec<String>,
+    /// Directories put at the end of `PATH`
+    #[serde(default)]
+    pub path_append: Vec<String>,
+}
+
+/// Overrides applied while the focused pane matches `cwd` and `context`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DirectoryProfile {
+    /// Directory pattern as in `env_rules`; subdirectories match too, so
+    /// `~/prod-ops` covers `~/prod-ops/db`. Empty matches any directory
+    #[serde(default)]
+    pub cwd: String,
+    /// Only match sessions in this context: `local`, `ssh`, `container` or
+    /// `toolbox`
+    #[serde(default)]
+    pub context: Option<String>,
+    /// Profile to activate, which selects `env_rules` for new processes
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Theme to switch to
+    #[serde(default)]
+    pub theme: Option<String>,
+    /// Accent color replacing the theme's, e.g. `#ff0000`
+    #[serde(default)]
+    pub accent: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryProfilesConfig {
+    /// Checked in order; the first match applies
+    #[serde(default)]
+    pub rules: Vec<DirectoryProfile>,
+    /// How long a directory must stay current before switching, so `cd`
+    /// chains passing through other directories don't flicker
+    #[serde(default = "default_directory_settle_ms")]
+    pub settle_ms: u64,
+}
+
+fn default_directory_settle_ms() -> u64 {
+    300
+}
+
+impl Default for DirectoryProfilesConfig {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            settle_ms: default_directory_settle_ms(),
+        }
+    }
+}
+
+/// How key presses are encoded for the running program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyboardConfig {
+    /// Backspace sends DEL (0x7f); when false it sends BS (0x08)
+    #[serde(default = "default_true")]
+    pub backspace_sends_delete: bool,
+    /// Alt/Option prefixes keys with ESC
+    #[serde(default = "default_true")]
+    pub alt_sends_escape: bool,
+}
+
+impl Default for KeyboardConfig {
+    fn default() -> Self {
+        Self {
+            backspace_sends_delete: true,
+            alt_sends_escape: true,
+        }
+    }
+}
+
+fn default_title_format() -> String {
+    "{title}".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeybindingsConfig {
-    //
+    /// Accept the whole autosuggestion
+    #[serde(default = "default_accept_suggestion")]
+    pub accept_suggestion: String,
+    /// Accept the next word of the autosuggestion
+    #[serde(default = "default_accept_suggestion_word")]
+    pub accept_suggestion_word: String,
+    /// Open the multi-line command editor
+    #[serde(default = "default_compose_multiline")]
+    pub compose_multiline: String,
+    /// Open the focused block's output in the built-in pager
+    #[serde(default = "default_open_pager")]
+    pub open_pager: String,
+    /// Open the context menu of the focused block
+    #[serde(default = "default_block_menu")]
+    pub block_menu: String,
+    #[serde(default = "default_select_output")]
+    pub select_output: String,
+    #[serde(default = "default_select_command")]
+    pub select_command: String,
+    #[serde(default = "default_select_path")]
+    pub select_path: String,
+    #[serde(default = "default_select_word")]
+    pub select_word: String,
+    /// Maximize the focused pane, or restore the split layout
+    #[serde(default = "default_zoom_pane")]
+    pub zoom_pane: String,
+    /// Run the correction offered in the "did you mean" banner
+    #[serde(default = "default_accept_correction")]
+    pub accept_correction: String,
+    /// Link scrolling of the focused pane with the previously focused one
+    #[serde(default = "default_link_scroll")]
+    pub link_scroll: String,
+    /// Alert on new output in the focused pane
+    #[serde(default = "default_monitor_activity")]
+    pub monitor_activity: String,
+    /// Alert when the focused pane has been silent for `silence_secs`
+    #[serde(default = "default_monitor_silence")]
+    pub monitor_silence: String,
+    /// Show or hide the debug overlay with frame and throughput metrics
+    #[serde(default = "default_toggle_debug_hud")]
+    pub toggle_debug_hud: String,
+    /// Scale the whole UI up, down, or back to `ui.scale`
+    #[serde(default = "default_ui_zoom_in")]
+    pub ui_zoom_in: String,
+    #[serde(default = "default_ui_zoom_out")]
+    pub ui_zoom_out: String,
+    #[serde(default = "default_ui_zoom_reset")]
+    pub ui_zoom_reset: String,
+    /// Enter keyboard copy mode over the focused block, or the scrollback
+    #[serde(default = "default_copy_mode")]
+    pub copy_mode: String,
+    /// Key set used inside copy mode
+    #[serde(default)]
+    pub copy_mode_keys: CopyModeKeys,
+    /// Move block focus to the previous or next command block
+    #[serde(default = "default_focus_previous_block")]
+    pub focus_previous_block: String,
+    #[serde(default = "default_focus_next_block")]
+    pub focus_next_block: String,
+    /// Scroll the viewport to the previous or next prompt, with or without
+    /// blocks
+    #[serde(default = "default_previous_prompt")]
+    pub previous_prompt: String,
+    #[serde(default = "default_next_prompt")]
+    pub next_prompt: String,
+    /// Select the output of the last command
+    #[serde(default = "default_select_last_output")]
+    pub select_last_output: String,
+    /// Move focus between the panes of the tab
+    #[serde(default = "default_focus_previous_pane")]
+    pub focus_previous_pane: String,
+    #[serde(default = "default_focus_next_pane")]
+    pub focus_next_pane: String,
+    /// Grow or shrink the focused pane
+    #[serde(default = "default_resize_pane_left")]
+    pub resize_pane_left: String,
+    #[serde(default = "default_resize_pane_right")]
+    pub resize_pane_right: String,
+    #[serde(default = "default_resize_pane_up")]
+    pub resize_pane_up: String,
+    #[serde(default = "default_resize_pane_down")]
+    pub resize_pane_down: String,
+    /// Add a cursor on the line above or below in the command editor
+    #[serde(default = "default_add_cursor_above")]
+    pub add_cursor_above: String,
+    #[serde(default = "default_add_cursor_below")]
+    pub add_cursor_below: String,
+    /// Select every occurrence of the word under the cursor in the command
+    /// editor, so typing replaces them all
+    #[serde(default = "default_edit_all_occurrences")]
+    pub edit_all_occurrences: String,
+    /// Show or hide the escape-sequence inspector for the focused pane
+    #[serde(default = "default_toggle_inspector")]
+    pub toggle_inspector: String,
+    /// Pause or resume the inspected stream (inspector mode)
+    #[serde(default = "default_inspector_pause")]
+    pub inspector_pause: String,
+    /// Apply the next held action while paused (inspector mode)
+    #[serde(default = "default_inspector_step")]
+    pub inspector_step: String,
+    /// Key sequence that `leader` expands to in bindings
+    #[serde(default)]
+    pub leader: Option<String>,
+    /// Extra bindings per mode (`normal`, `search`, `block-navigation`,
+    /// `pager`, `inspector`, `copy`), mapping a sequence like `ctrl+a then c` to an action
+    #[serde(default)]
+    pub modes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+fn default_accept_suggestion() -> String {
+    "right".to_string()
+}
+
+fn default_accept_suggestion_word() -> String {
+    "alt+right".to_string()
+}
+
+fn default_compose_multiline() -> String {
+    "ctrl+shift+enter".to_string()
+}
+
+fn default_open_pager() -> String {
+    "ctrl+shift+l".to_string()
+}
+
+fn default_block_menu() -> String {
+    "menu".to_string()
+}
+
+fn default_select_output() -> String {
+    "ctrl+alt+o".to_string()
+}
+
+fn default_select_command() -> String {
+    "ctrl+alt+c".to_string()
+}
+
+fn default_select_path() -> String {
+    "ctrl+alt+p".to_string()
+}
+
+fn default_select_word() -> String {
+    "ctrl+alt+w".to_string()
+}
+
+fn default_zoom_pane() -> String {
+    "ctrl+shift+z".to_string()
+}
+
+fn default_accept_correction() -> String {
+    "alt+enter".to_string()
+}
+
+fn default_link_scroll() -> String {
+    "ctrl+alt+l".to_string()
+}
+
+fn default_monitor_activity() -> String {
+    "ctrl+alt+a".to_string()
+}
+
+fn default_monitor_silence() -> String {
+    "ctrl+alt+s".to_string()
+}
+
+fn default_toggle_debug_hud() -> String {
+    "ctrl+shift+f12".to_string()
+}
+
+fn default_ui_zoom_in() -> String {
+    "ctrl+alt+=".to_string()
+}
+
+fn default_ui_zoom_out() -> String {
+    "ctrl+alt+-".to_string()
+}
+
+fn default_ui_zoom_reset() -> String {
+    "ctrl+alt+0".to_string()
+}
+
+fn default_copy_mode() -> String {
+    "ctrl+shift+space".to_string()
+}
+
+fn default_focus_previous_block() -> String {
+    "alt+shift+up".to_string()
+}
+
+fn default_focus_next_block() -> String {
+    "alt+shift+down".to_string()
+}
+
+fn default_previous_prompt() -> String {
+    "ctrl+shift+up".to_string()
+}
+
+fn default_next_prompt() -> String {
+    "ctrl+shift+down".to_string()
+}
+
+fn default_select_last_output() -> String {
+    "ctrl+shift+o".to_string()
+}
+
+fn default_focus_previous_pane() -> String {
+    "ctrl+alt+[".to_string()
+}
+
+fn default_focus_next_pane() -> String {
+    "ctrl+alt+]".to_string()
+}
+
+fn default_resize_pane_left() -> String {
+    "ctrl+alt+left".to_string()
+}
+
+fn default_resize_pane_right() -> String {
+    "ctrl+alt+right".to_string()
+}
+
+fn default_resize_pane_up() -> String {
+    "ctrl+alt+up".to_string()
+}
+
+fn default_resize_pane_down() -> String {
+    "ctrl+alt+down".to_string()
+}
+
+/// Keys used in copy mode, as in tmux's `mode-keys`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CopyModeKeys {
+    #[default]
+    Vi,
+    Emacs,
+}
+
+fn default_toggle_inspector() -> String {
+    "ctrl+shift+f11".to_string()
+}
+
+fn default_inspector_pause() -> String {
+    "space".to_string()
+}
+
+fn default_inspector_step() -> String {
+    "n".to_string()
+}
+
+fn default_add_cursor_above() -> String {
+    "ctrl+alt+shift+up".to_string()
+}
+
+fn default_add_cursor_below() -> String {
+    "ctrl+alt+shift+down".to_string()
+}
+
+fn default_edit_all_occurrences() -> String {
+    "ctrl+alt+e".to_string()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,25 +902,531 @@ pub struct PerformanceConfig {
     pub vsync: bool,
 }
 
+/// How block headers, exports and history show when a command ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimestampConfig {
+    #[serde(default)]
+    pub mode: TimestampMode,
+    /// strftime format for absolute times; `%x %X` is the locale's own
+    /// date and time
+    #[serde(default = "default_timestamp_format")]
+    pub format: String,
+    /// Locale such as `de_DE`; defaults to LC_ALL, LC_TIME or LANG
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Show UTC instead of the local time zone
+    #[serde(default)]
+    pub utc: bool,
+    /// Relative times switch to absolute once older than this
+    #[serde(default = "default_relative_max_days")]
+    pub relative_max_days: u64,
+}
+
+/// Relative ("2 min ago", updated live) or absolute times
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampMode {
+    #[default]
+    Relative,
+    Absolute,
+}
+
+fn default_timestamp_format() -> String {
+    "%x %X".to_string()
+}
+
+fn default_relative_max_days() -> u64 {
+    7
+}
+
+impl Default for TimestampConfig {
+    fn default() -> Self {
+        Self {
+            mode: TimestampMode::default(),
+            format: default_timestamp_format(),
+            locale: None,
+            utc: false,
+            relative_max_days: default_relative_max_days(),
+        }
+    }
+}
+
+/// Palette transform for color vision deficiencies
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorVisionConfig {
+    pub deficiency: ColorVisionDeficiency,
+    #[serde(default)]
+    pub mode: ColorVisionMode,
+    /// How far `correct` moves colors, from 0.0 (not at all) to 1.0
+    #[serde(default = "default_color_vision_strength")]
+    pub strength: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorVisionDeficiency {
+    /// No red cones
+    Protanopia,
+    /// No green cones
+    Deuteranopia,
+    /// No blue cones
+    Tritanopia,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorVisionMode {
+    /// Shift colors so they stay distinguishable with the deficiency
+    #[default]
+    Correct,
+    /// Show the palette as it looks with the deficiency, for theme authors
+    Simulate,
+}
+
+fn default_color_vision_strength() -> f32 {
+    1.0
+}
+
+/// Size of the interface around the text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiConfig {
+    /// Scale for paddings, block headers, the tab bar and overlays,
+    /// independent of the font size; the zoom bindings adjust it at runtime
+    #[serde(default = "default_ui_scale")]
+    pub scale: f32,
+    /// Change per press of `ui-zoom-in` / `ui-zoom-out`
+    #[serde(default = "default_ui_scale_step")]
+    pub scale_step: f32,
+}
+
+fn default_ui_scale() -> f32 {
+    1.0
+}
+
+fn default_ui_scale_step() -> f32 {
+    0.1
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            scale: default_ui_scale(),
+            scale_step: default_ui_scale_step(),
+        }
+    }
+}
+
+/// A sound, a flash of the pane border, both or neither
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cue {
+    /// `system` for the platform alert sound, or a path to a sound file
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default)]
+    pub flash: bool,
+}
+
+/// Which cue each event gets; an empty cue (`{}`) silences an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackConfig {
+    /// BEL from a program; unset follows `bell.audible` and `bell.visual`
+    #[serde(default)]
+    pub bell: Option<Cue>,
+    /// A command exited with a non-zero status
+    #[serde(default)]
+    pub command_failed: Cue,
+    /// A command ran for at least `long_command_secs`
+    #[serde(default)]
+    pub long_command: Cue,
+    #[serde(default = "default_long_command_secs")]
+    pub long_command_secs: u64,
+    /// A paste was held back for confirmation
+    #[serde(default = "default_paste_blocked_cue")]
+    pub paste_blocked: Cue,
+}
+
+fn default_long_command_secs() -> u64 {
+    10
+}
+
+fn default_paste_blocked_cue() -> Cue {
+    Cue {
+        sound: None,
+        flash: true,
+    }
+}
+
+impl Default for FeedbackConfig {
+    fn default() -> Self {
+        Self {
+            bell: None,
+            command_failed: Cue::default(),
+            long_command: Cue::default(),
+            long_command_secs: default_long_command_secs(),
+            paste_blocked: default_paste_blocked_cue(),
+        }
+    }
+}
+
+/// How the terminal reacts to BEL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BellConfig {
+    /// Play the system alert sound
+    #[serde(default)]
+    pub audible: bool,
+    /// Flash the pane border
+    #[serde(default = "default_true")]
+    pub visual: bool,
+    /// Duration of the visual flash in milliseconds
+    #[serde(default = "default_bell_flash_ms")]
+    pub flash_duration_ms: u64,
+    /// Set the urgency hint on the window when it is not focused
+    #[serde(default = "default_true")]
+    pub urgency_hint: bool,
+}
+
+fn default_bell_flash_ms() -> u64 {
+    150
+}
+
+impl Default for BellConfig {
+    fn default() -> Self {
+        Self {
+            audible: false,
+            visual: true,
+            flash_duration_ms: default_bell_flash_ms(),
+            urgency_hint: true,
+        }
+    }
+}
+
+/// Rules deciding when a finished command should alert the user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    #[serde(default)]
+    pub rules: Vec<NotificationRule>,
+    /// Seconds without output before a pane monitored for silence alerts
+    #[serde(default = "default_silence_secs")]
+    pub silence_secs: u64,
+    /// Send desktop notifications for pane activity and silence alerts
+    #[serde(default = "default_true")]
+    pub monitor_desktop: bool,
+}
+
+fn default_silence_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationRule {
+    /// Minimum command duration before the rule applies
+    #[serde(default)]
+    pub min_duration_secs: u64,
+    /// Programs the rule applies to (empty means every program)
+    #[serde(default)]
+    pub commands: Vec<String>,
+    /// Programs the rule never applies to
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Only alert when the command exited with a non-zero status
+    #[serde(default)]
+    pub only_on_failure: bool,
+    /// Send a desktop notification
+    #[serde(default = "default_true")]
+    pub desktop: bool,
+    /// Ring the bell / show a toast in the UI
+    #[serde(default = "default_true")]
+    pub bell: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![NotificationRule {
+                min_duration_secs: 30,
+                commands: Vec::new(),
+                exclude: vec!["ssh".to_string(), "vim".to_string(), "watch".to_string()],
+                only_on_failure: false,
+                desktop: true,
+                bell: true,
+            }],
+            silence_secs: default_silence_secs(),
+            monitor_desktop: true,
+        }
+    }
+}
+
+/// How block output is displayed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlocksConfig {
+    /// Show runs of identical lines once with a `× N` counter
+    #[serde(default = "default_true")]
+    pub collapse_repeated_lines: bool,
+    /// Blocks longer than this only show their head and tail until expanded
+    #[serde(default = "default_truncate_after_lines")]
+    pub truncate_after_lines: usize,
+    /// Lines shown at each end of a truncated block
+    #[serde(default = "default_truncated_preview_lines")]
+    pub truncated_preview_lines: usize,
+    #[serde(default)]
+    pub watch: WatchConfig,
+    /// Rules styling or masking parts of the output; replaces the built-in
+    /// sets when given
+    #[serde(default = "default_highlighters")]
+    pub highlighters: Vec<HighlightRuleSet>,
+}
+
+/// Named output highlighting rules, toggled together per session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighlightRuleSet {
+    pub name: String,
+    /// Active in new sessions
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    pub rules: Vec<HighlightRule>,
+}
+
+/// A regex and what to do with its matches; earlier rules win overlaps
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HighlightRule {
+    pub pattern: String,
+    /// Foreground color, e.g. `#ff5555`
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub bold: bool,
+    /// Replace the match with this text, e.g. to hide secrets on screen
+    #[serde(default)]
+    pub mask: Option<String>,
+}
+
+fn default_highlighters() -> Vec<HighlightRuleSet> {
+    let rule = |pattern: &str, color: &str| HighlightRule {
+        pattern: pattern.to_string(),
+        color: Some(color.to_string()),
+        ..HighlightRule::default()
+    };
+    vec![
+        HighlightRuleSet {
+            name: "log-levels".to_string(),
+            enabled: true,
+            rules: vec![
+                HighlightRule {
+                    bold: true,
+                    ..rule(r"\b(ERROR|FATAL|CRITICAL)\b", "#ff5555")
+                },
+                rule(r"\b(WARN|WARNING)\b", "#f1fa8c"),
+                rule(r"\bINFO\b", "#8be9fd"),
+                rule(r"\b(DEBUG|TRACE)\b", "#6272a4"),
+            ],
+        },
+        HighlightRuleSet {
+            name: "ip-addresses".to_string(),
+            enabled: true,
+            rules: vec![rule(r"\b(?:\d{1,3}\.){3}\d{1,3}(?::\d{1,5})?\b", "#bd93f9")],
+        },
+        HighlightRuleSet {
+            name: "card-numbers".to_string(),
+            enabled: false,
+            rules: vec![HighlightRule {
+                pattern: r"\b(?:\d[ -]?){12,15}\d\b".to_string(),
+                mask: Some("•••• •••• •••• ••••".to_string()),
+                ..HighlightRule::default()
+            }],
+        },
+    ]
+}
+
+/// Defaults of the block "Watch" action
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    /// Seconds between runs when no paths are watched
+    #[serde(default = "default_watch_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub output: WatchOutput,
+    /// Highlight what changed since the previous run, like `watch -d`
+    #[serde(default = "default_true")]
+    pub highlight_changes: bool,
+}
+
+fn default_watch_interval_secs() -> u64 {
+    2
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_watch_interval_secs(),
+            output: WatchOutput::default(),
+            highlight_changes: true,
+        }
+    }
+}
+
+/// What a watched block shows after each run
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchOutput {
+    /// Only the latest run
+    #[default]
+    Replace,
+    /// Every run, one after the other
+    Append,
+}
+
+fn default_truncate_after_lines() -> usize {
+    5000
+}
+
+fn default_truncated_preview_lines() -> usize {
+    100
+}
+
+impl Default for BlocksConfig {
+    fn default() -> Self {
+        Self {
+            collapse_repeated_lines: true,
+            truncate_after_lines: default_truncate_after_lines(),
+            truncated_preview_lines: default_truncated_preview_lines(),
+            watch: WatchConfig::default(),
+            highlighters: default_highlighters(),
+        }
+    }
+}
+
+/// Parsing of compiler and linter diagnostics out of block output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Extra errorformat-style patterns, tried before the built-in ones
+    #[serde(default)]
+    pub formats: Vec<ErrorFormatConfig>,
+    /// Editor used to open a location; falls back to `$VISUAL`/`$EDITOR`
+    #[serde(default)]
+    pub editor: Option<String>,
+}
+
+/// An errorformat-style pattern: `%f` file, `%l` line, `%c` column,
+/// `%s` severity, `%m` message, `%*` anything
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorFormatConfig {
+    pub name: String,
+    pub pattern: String,
+    /// Severity for patterns without `%s`
+    #[serde(default)]
+    pub severity: Option<String>,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            formats: Vec::new(),
+            editor: None,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             theme: "dark".to_string(),
+            minimum_contrast: None,
+            color_vision: None,
             font: FontConfig {
                 name: "JetBrains Mono".to_string(),
                 size: 14.0,
                 line_height: 1.2,
+                features: Vec::new(),
+                family_features: BTreeMap::new(),
             },
             terminal: TerminalConfig {
                 shell: std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string()),
                 scrollback_lines: 10000,
                 cursor_blink: true,
+                shell_integration: true,
+                title_format: default_title_format(),
+                keyboard: KeyboardConfig::default(),
+                utf8_only: true,
+                env_rules: Vec::new(),
+                login_shell: false,
+                shell_args: Vec::new(),
+                startup_command: None,
+                command: None,
+                hold: false,
+                on_exit: OnExit::default(),
+                new_pane_cwd: NewPaneCwd::default(),
+                bidi: true,
+                prompt_pattern: None,
+                escape_hooks: Vec::new(),
+            },
+            keybindings: KeybindingsConfig {
+                accept_suggestion: default_accept_suggestion(),
+                accept_suggestion_word: default_accept_suggestion_word(),
+                compose_multiline: default_compose_multiline(),
+                open_pager: default_open_pager(),
+                block_menu: default_block_menu(),
+                select_output: default_select_output(),
+                select_command: default_select_command(),
+                select_path: default_select_path(),
+                select_word: default_select_word(),
+                zoom_pane: default_zoom_pane(),
+                accept_correction: default_accept_correction(),
+                link_scroll: default_link_scroll(),
+                monitor_activity: default_monitor_activity(),
+                monitor_silence: default_monitor_silence(),
+                toggle_debug_hud: default_toggle_debug_hud(),
+                ui_zoom_in: default_ui_zoom_in(),
+                ui_zoom_out: default_ui_zoom_out(),
+                ui_zoom_reset: default_ui_zoom_reset(),
+                copy_mode: default_copy_mode(),
+                copy_mode_keys: CopyModeKeys::default(),
+                focus_previous_block: default_focus_previous_block(),
+                focus_next_block: default_focus_next_block(),
+                previous_prompt: default_previous_prompt(),
+                next_prompt: default_next_prompt(),
+                select_last_output: default_select_last_output(),
+                focus_previous_pane: default_focus_previous_pane(),
+                focus_next_pane: default_focus_next_pane(),
+                resize_pane_left: default_resize_pane_left(),
+                resize_pane_right: default_resize_pane_right(),
+                resize_pane_up: default_resize_pane_up(),
+                resize_pane_down: default_resize_pane_down(),
+                add_cursor_above: default_add_cursor_above(),
+                add_cursor_below: default_add_cursor_below(),
+                edit_all_occurrences: default_edit_all_occurrences(),
+                toggle_inspector: default_toggle_inspector(),
+                inspector_pause: default_inspector_pause(),
+                inspector_step: default_inspector_step(),
+                leader: None,
+                modes: BTreeMap::new(),
             },
-            keybindings: KeybindingsConfig {},
             performance: PerformanceConfig {
                 gpu_acceleration: true,
                 vsync: true,
             },
+            notifications: NotificationConfig::default(),
+            bell: BellConfig::default(),
+            feedback: FeedbackConfig::default(),
+            diagnostics: DiagnosticsConfig::default(),
+            blocks: BlocksConfig::default(),
+            layouts: BTreeMap::new(),
+            ai: AiConfig::default(),
+            crash_reports: CrashReportConfig::default(),
+            logging: LoggingConfig::default(),
+            ui: UiConfig::default(),
+            timestamps: TimestampConfig::default(),
+            directory_profiles: DirectoryProfilesConfig::default(),
+            updates: UpdateConfig::default(),
+            security: SecurityConfig::default(),
+            paste: PasteConfig::default(),
+            search_index: SearchIndexConfig::default(),
         }
     }
 }
@@ -67,6 +1435,8 @@ impl Config {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let contents = std::fs::read_to_string(path)?;
         let config: Config = serde_yaml::from_str(&contents)?;
+        config.font.validate_features()?;
+        config.timestamps.validate()?;
         Ok(config)
     }
 }
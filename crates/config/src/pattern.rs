@@ -0,0 +1,50 @@
+// Path patterns shared by `env_rules` and `directory_profiles`
+
+use std::path::Path;
+
+/// Replace a leading `~` with the home directory
+pub fn expand_home(pattern: &str, home: Option<&Path>) -> String {
+    match (pattern.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.display(), rest)
+        }
+        _ => pattern.to_string(),
+    }
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("/a/*/c", "/a/b/c"));
+        assert!(glob_match("/a/*", "/a/b/c"));
+        assert!(!glob_match("/a/*/c", "/a/b/d"));
+        assert!(glob_match("/a", "/a"));
+        assert!(!glob_match("/a", "/ab"));
+        assert_eq!(expand_home("~/src", Some(Path::new("/home/me"))), "/home/me/src");
+        assert_eq!(expand_home("~me/src", Some(Path::new("/home/me"))), "~me/src");
+    }
+}
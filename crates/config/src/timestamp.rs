@@ -0,0 +1,201 @@
+// Timestamps for block headers, exports and history
+//
+// Absolute times use a strftime format with the user's locale (LC_TIME,
+// then LC_ALL and LANG) and local time zone; relative times ("2 min ago")
+// are refreshed by the UI at the interval `next_refresh` returns.
+
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Locale, Utc};
+
+use crate::{TimestampConfig, TimestampMode};
+
+/// Format used when the configured one is invalid
+const FALLBACK_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// Formats times as configured under `timestamps`
+#[derive(Debug, Clone)]
+pub struct TimestampFormatter {
+    mode: TimestampMode,
+    format: String,
+    locale: Locale,
+    utc: bool,
+    relative_max_secs: i64,
+}
+
+impl TimestampFormatter {
+    pub fn new(config: &TimestampConfig) -> Self {
+        let format = if is_valid_format(&config.format) {
+            config.format.clone()
+        } else {
+            FALLBACK_FORMAT.to_string()
+        };
+        let locale = config
+            .locale
+            .as_deref()
+            .and_then(parse_locale)
+            .or_else(system_locale)
+            .unwrap_or(Locale::POSIX);
+        Self {
+            mode: config.mode,
+            format,
+            locale,
+            utc: config.utc,
+            relative_max_secs: config.relative_max_days as i64 * DAY,
+        }
+    }
+
+    /// Time for a block header or history row: relative while recent in
+    /// relative mode, absolute otherwise
+    pub fn format(&self, at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+        match self.mode {
+            TimestampMode::Relative => {
+                let elapsed = (now - at).num_seconds();
+                match relative(elapsed).filter(|_| elapsed < self.relative_max_secs) {
+                    Some(text) => text,
+                    None => self.absolute(at),
+                }
+            }
+            TimestampMode::Absolute => self.absolute(at),
+        }
+    }
+
+    /// Time in the configured format, always absolute, as used in exports
+    pub fn absolute(&self, at: DateTime<Utc>) -> String {
+        let mut text = String::new();
+        let written = if self.utc {
+            write!(text, "{}", at.format_localized(&self.format, self.locale))
+        } else {
+            let local = at.with_timezone(&Local);
+            write!(text, "{}", local.format_localized(&self.format, self.locale))
+        };
+        match written {
+            Ok(()) => text,
+            Err(_) => at.format(FALLBACK_FORMAT).to_string(),
+        }
+    }
+
+    /// `format` for Unix timestamps in seconds, as stored in history
+    pub fn format_unix(&self, at: u64, now: u64) -> String {
+        self.format(from_unix(at), from_unix(now))
+    }
+
+    /// How long until the text for `at` changes, for live-updating headers;
+    /// `None` when it never changes
+    pub fn next_refresh(&self, at: DateTime<Utc>, now: DateTime<Utc>) -> Option<Duration> {
+        if self.mode == TimestampMode::Absolute {
+            return None;
+        }
+        let elapsed = (now - at).num_seconds().max(0);
+        if elapsed >= self.relative_max_secs {
+            return None;
+        }
+        let unit = match elapsed {
+            e if e < MINUTE => 1,
+            e if e < HOUR => MINUTE,
+            e if e < DAY => HOUR,
+            _ => DAY,
+        };
+        Some(Duration::from_secs((unit - elapsed % unit) as u64))
+    }
+}
+
+impl Default for TimestampFormatter {
+    fn default() -> Self {
+        Self::new(&TimestampConfig::default())
+    }
+}
+
+/// "just now", "45 s ago", "2 min ago", "3 h ago" or "5 d ago"; `None` for
+/// times in the future
+pub fn relative(elapsed_secs: i64) -> Option<String> {
+    match elapsed_secs {
+        e if e < 0 => None,
+        e if e < 5 => Some("just now".to_string()),
+        e if e < MINUTE => Some(format!("{} s ago", e)),
+        e if e < HOUR => Some(format!("{} min ago", e / MINUTE)),
+        e if e < DAY => Some(format!("{} h ago", e / HOUR)),
+        e => Some(format!("{} d ago", e / DAY)),
+    }
+}
+
+pub fn from_unix(secs: u64) -> DateTime<Utc> {
+    DateTime::<Utc>::from_timestamp(secs.min(i64::MAX as u64) as i64, 0).unwrap_or_default()
+}
+
+fn is_valid_format(format: &str) -> bool {
+    StrftimeItems::new(format).all(|item| item != Item::Error)
+}
+
+/// `de_DE.UTF-8@euro` -> `de_DE`
+fn parse_locale(name: &str) -> Option<Locale> {
+    let name = name.split(['.', '@']).next()?;
+    Locale::try_from(name).ok()
+}
+
+fn system_locale() -> Option<Locale> {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| parse_locale(&value))
+}
+
+impl TimestampConfig {
+    /// Check the format string and locale
+    pub fn validate(&self) -> Result<()> {
+        if !is_valid_format(&self.format) {
+            return Err(anyhow!("Invalid timestamp format '{}'", self.format));
+        }
+        if let Some(locale) = &self.locale {
+            parse_locale(locale).ok_or_else(|| anyhow!("Unknown locale '{}'", locale))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative() {
+        let timestamps = TimestampFormatter::default();
+        assert_eq!(timestamps.format_unix(1000, 1002), "just now");
+        assert_eq!(timestamps.format_unix(1000, 1045), "45 s ago");
+        assert_eq!(timestamps.format_unix(1000, 1000 + 150), "2 min ago");
+        assert_eq!(timestamps.format_unix(1000, 1000 + 3 * 3600 + 5), "3 h ago");
+
+        let now = from_unix(1000 + 150);
+        assert_eq!(
+            timestamps.next_refresh(from_unix(1000), now),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn test_absolute_format() {
+        let config = TimestampConfig {
+            mode: TimestampMode::Absolute,
+            format: "%Y-%m-%d %H:%M".to_string(),
+            utc: true,
+            ..TimestampConfig::default()
+        };
+        let timestamps = TimestampFormatter::new(&config);
+        assert_eq!(timestamps.format_unix(86_400 * 365, 0), "1971-01-01 00:00");
+        assert_eq!(timestamps.next_refresh(from_unix(0), from_unix(10)), None);
+
+        let bad = TimestampConfig {
+            format: "%Q".to_string(),
+            ..TimestampConfig::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+}
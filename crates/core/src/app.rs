@@ -1,10 +1,16 @@
 use anyhow::Result;
-use log::info;
+use tracing::{info, warn};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::{mpsc, Mutex};
 
 use config::Config;
+use term::HostedPane;
+use crate::directory_profiles::{DirectoryProfiles, ProfileSwitch};
 use crate::events::{Event, EventLoop};
+use crate::layout::{PaneId, Rect, SessionLayout};
+use crate::preferences::Preferences;
 use crate::state::AppState;
 
 // Terminal implementation using alacritty_terminal
@@ -49,6 +55,15 @@ impl BlockManager {
     }
 }
 
+/// Tab size in cells that restored panes are laid out in until the window
+/// reports its own
+const RESTORE_AREA: Rect = Rect {
+    x: 0,
+    y: 0,
+    width: 80,
+    height: 24,
+};
+
 pub struct VoidCLI {
     _config: Config,
     _state: Arc<Mutex<AppState>>,
@@ -56,6 +71,11 @@ pub struct VoidCLI {
     renderer: Renderer,
     _block_manager: BlockManager,
     event_loop: EventLoop,
+    layout: SessionLayout,
+    preferences: Preferences,
+    directory_profiles: DirectoryProfiles,
+    /// Shells of the panes, by pane id
+    panes: HashMap<PaneId, HostedPane>,
 }
 
 impl VoidCLI {
@@ -65,7 +85,9 @@ impl VoidCLI {
         let terminal = Terminal::new(&config, event_tx.clone());
         let renderer = Renderer::new(&config);
         let block_manager = BlockManager::new(state.clone());
-        let event_loop = EventLoop::new(state.clone(), event_rx);
+        let event_loop = EventLoop::new(&config, state.clone(), event_rx);
+        let directory_profiles =
+            DirectoryProfiles::new(&config.directory_profiles, dirs::home_dir());
 
         Self {
             _config: config,
@@ -74,10 +96,63 @@ impl VoidCLI {
             renderer,
             _block_manager: block_manager,
             event_loop,
+            layout: SessionLayout::default(),
+            preferences: Preferences::default(),
+            directory_profiles,
+            panes: HashMap::new(),
+        }
+    }
+
+    /// Start from a saved layout instead of a single shell
+    pub fn with_layout(mut self, layout: SessionLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Current windows, tabs and panes, for saving on exit
+    pub fn layout(&self) -> &SessionLayout {
+        &self.layout
+    }
+
+    /// Start with preferences saved by an earlier run
+    pub fn with_preferences(mut self, preferences: Preferences) -> Self {
+        self.preferences = preferences;
+        self
+    }
+
+    /// Runtime preferences, for saving on exit
+    pub fn preferences(&self) -> &Preferences {
+        &self.preferences
+    }
+
+    /// Handle `ui-zoom-in` (positive `steps`), `ui-zoom-out` (negative) and
+    /// `ui-zoom-reset` (zero), returning the new UI scale
+    pub fn zoom_ui(&mut self, steps: i32) -> f32 {
+        if steps == 0 {
+            self.preferences.reset_ui_zoom();
+            return self.preferences.ui_scale(&self._config.ui);
         }
+        self.preferences.zoom_ui(&self._config.ui, steps)
     }
 
-    pub async fn run(&self) -> Result<()> {
+    /// The focused pane reported a new directory, or focus moved to a pane
+    /// in another directory
+    pub fn focused_cwd_changed(&mut self, cwd: &str) {
+        self.directory_profiles.cwd_changed(cwd, Instant::now());
+    }
+
+    /// The focused pane entered or left SSH or a container; `context` is a
+    /// `SessionContext::name`
+    pub fn focused_context_changed(&mut self, context: &str) {
+        self.directory_profiles.context_changed(context, Instant::now());
+    }
+
+    /// Directory profile to switch to, once the focused directory settled
+    pub fn poll_directory_profile(&mut self) -> Option<ProfileSwitch> {
+        self.directory_profiles.poll(Instant::now())
+    }
+
+    pub async fn run(&mut self) -> Result<()> {
         info!("Initializing application components");
 
         //Initializing the renderer
@@ -86,9 +161,38 @@ impl VoidCLI {
         //initialize the terminal
         self.terminal.initialize().await?;
 
+        let fallback_cwd = dirs::home_dir()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "/".to_string());
+        for pane in self.layout.restore_plan(&fallback_cwd, RESTORE_AREA) {
+            info!("Restoring pane {} in {}", pane.id, pane.cwd);
+            // Directories are user data and stay out of crash reports
+            crate::crash::record_event("restore pane", None);
+            let (cols, rows) = (pane.rect.width.max(1), pane.rect.height.max(1));
+            match HostedPane::spawn_in(&self._config, Some(&pane.cwd), cols, rows).await {
+                Ok(hosted) => {
+                    self.panes.insert(pane.id, hosted);
+                }
+                Err(e) => warn!("Failed to restore pane {}: {:#}", pane.id, e),
+            }
+        }
+        let focused = self
+            .layout
+            .windows
+            .first()
+            .and_then(|window| window.tabs.get(window.active_tab))
+            .map(|tab| tab.focused);
+        if let Some(pane) = focused.and_then(|id| self.panes.get_mut(&id)) {
+            let context = pane.session_context();
+            self.focused_context_changed(context.name());
+        }
+
         //start the event loop
-        self.event_loop.run().await?;
+        let result = self.event_loop.run().await;
 
-        Ok(())
+        for pane in self.panes.values_mut() {
+            let _ = pane.kill().await;
+        }
+        result
     }
 }
@@ -0,0 +1,270 @@
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fs;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use config::Config;
+
+/// Events kept for the next crash report
+const RECENT_EVENTS: usize = 50;
+
+/// Characters of the panic message kept when block text is excluded
+const PANIC_MESSAGE_CHARS: usize = 120;
+
+/// Marker holding the newest report already announced on launch
+const NOTIFIED_MARKER: &str = ".notified";
+
+/// State captured into a crash report
+struct CrashState {
+    config_summary: String,
+    include_block_text: bool,
+    recent_events: VecDeque<String>,
+    scrollback_lines: usize,
+}
+
+static STATE: Mutex<CrashState> = Mutex::new(CrashState {
+    config_summary: String::new(),
+    include_block_text: false,
+    recent_events: VecDeque::new(),
+    scrollback_lines: 0,
+});
+
+/// ~/.local/share/voidcli/crashes
+pub fn crash_dir() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("voidcli").join("crashes"))
+}
+
+/// Install the panic hook writing crash reports, unless disabled in config
+pub fn install(config: &Config) {
+    if !config.crash_reports.enabled {
+        return;
+    }
+    if let Ok(mut state) = STATE.lock() {
+        state.config_summary = config_summary(config);
+        state.include_block_text = config.crash_reports.include_block_text;
+    }
+
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(dir) = crash_dir() {
+            match write_report(&dir, info) {
+                Ok(path) => eprintln!("VoidCLI crashed; report written to {}", path.display()),
+                Err(e) => eprintln!("VoidCLI crashed; failed to write report: {:#}", e),
+            }
+        }
+        previous(info);
+    }));
+}
+
+/// Remember an event for the crash report; `text` (command lines, output)
+/// is redacted unless `crash_reports.include_block_text` is set
+pub fn record_event(kind: &str, text: Option<&str>) {
+    let Ok(mut state) = STATE.lock() else {
+        return;
+    };
+    let entry = match text {
+        Some(text) if state.include_block_text => format!("{}: {}", kind, text),
+        Some(text) => format!("{}: [{} chars redacted]", kind, text.chars().count()),
+        None => kind.to_string(),
+    };
+    if state.recent_events.len() == RECENT_EVENTS {
+        state.recent_events.pop_front();
+    }
+    state.recent_events.push_back(entry);
+}
+
+/// Lines held in scrollback across all panes
+pub fn set_scrollback_lines(lines: usize) {
+    if let Ok(mut state) = STATE.lock() {
+        state.scrollback_lines = lines;
+    }
+}
+
+/// Settings useful for triage, without paths or environment
+fn config_summary(config: &Config) -> String {
+    format!(
+        "theme: {}\nfont: {} {}\nshell: {}\nscrollback_lines: {}\ngpu_acceleration: {}\n",
+        config.theme,
+        config.font.name,
+        config.font.size,
+        config.terminal.shell,
+        config.terminal.scrollback_lines,
+        config.performance.gpu_acceleration,
+    )
+}
+
+fn write_report(dir: &Path, info: &PanicHookInfo) -> Result<PathBuf> {
+    let message = match info.payload().downcast_ref::<&str>() {
+        Some(message) => message.to_string(),
+        None => match info.payload().downcast_ref::<String>() {
+            Some(message) => message.clone(),
+            None => "unknown panic payload".to_string(),
+        },
+    };
+    let location = info
+        .location()
+        .map(|location| location.to_string())
+        .unwrap_or_default();
+    let backtrace = Backtrace::force_capture().to_string();
+
+    // The panic may have happened while the state was locked
+    let report = match STATE.try_lock() {
+        Ok(state) => render_report(&message, &location, &backtrace, &state),
+        Err(_) => render_report(
+            &message,
+            &location,
+            &backtrace,
+            &CrashState {
+                config_summary: "(unavailable)\n".to_string(),
+                include_block_text: false,
+                recent_events: VecDeque::new(),
+                scrollback_lines: 0,
+            },
+        ),
+    };
+
+    fs::create_dir_all(dir).context("Failed to create crash directory")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = dir.join(format!("crash-{}.txt", now));
+    fs::write(&path, report).context("Failed to write crash report")?;
+    Ok(path)
+}
+
+/// The panic message as reported: unless block text is included, only the
+/// start of its first line, before any ": " that usually leads into values
+/// such as paths, command lines or output
+fn report_message(message: &str, include_block_text: bool) -> String {
+    if include_block_text {
+        return message.to_string();
+    }
+    let first_line = message.lines().next().unwrap_or_default();
+    let head = first_line.split(": ").next().unwrap_or_default();
+    let kept: String = head.chars().take(PANIC_MESSAGE_CHARS).collect();
+    match message.chars().count() - kept.chars().count() {
+        0 => kept,
+        dropped => format!("{} [{} chars redacted]", kept.trim_end(), dropped),
+    }
+}
+
+fn render_report(message: &str, location: &str, backtrace: &str, state: &CrashState) -> String {
+    let message = report_message(message, state.include_block_text);
+    let mut report = format!(
+        "VoidCLI {} crash report\n\npanic: {}\nat: {}\n\n[config]\n{}scrollback: {} lines\n\n",
+        env!("CARGO_PKG_VERSION"),
+        message,
+        location,
+        state.config_summary,
+        state.scrollback_lines,
+    );
+    report.push_str("[recent events]\n");
+    for event in &state.recent_events {
+        report.push_str(event);
+        report.push('\n');
+    }
+    report.push_str("\n[backtrace]\n");
+    report.push_str(backtrace);
+    report
+}
+
+/// Crash reports in `dir`, oldest first
+pub fn reports(dir: &Path) -> Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut reports: Vec<PathBuf> = fs::read_dir(dir)
+        .context("Failed to read crash directory")?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("crash-") && name.ends_with(".txt"))
+        })
+        .collect();
+    // Millisecond timestamps of equal width sort by name
+    reports.sort_by_key(|path| (path.as_os_str().len(), path.clone()));
+    Ok(reports)
+}
+
+/// Reports written since the last launch, marking them as announced
+pub fn take_new_reports(dir: &Path) -> Result<Vec<PathBuf>> {
+    let reports = reports(dir)?;
+    let marker = dir.join(NOTIFIED_MARKER);
+    let notified = fs::read_to_string(&marker).unwrap_or_default();
+    let notified = notified.trim();
+    let seen = reports
+        .iter()
+        .position(|path| !notified.is_empty() && path.ends_with(notified));
+    let new: Vec<PathBuf> = match seen {
+        Some(seen) => reports[seen + 1..].to_vec(),
+        None => reports,
+    };
+    if let Some(name) = new.last().and_then(|path| path.file_name()) {
+        fs::write(&marker, name.to_string_lossy().as_bytes())
+            .context("Failed to update crash marker")?;
+    }
+    Ok(new)
+}
+
+/// Concatenate every report in `dir` into one file to attach to an issue
+pub fn bundle_reports(dir: &Path, output: &Path) -> Result<usize> {
+    let reports = reports(dir)?;
+    let mut bundle = String::new();
+    for path in &reports {
+        let report = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        bundle.push_str(&format!("===== {} =====\n{}\n", name, report));
+    }
+    fs::write(output, bundle).context("Failed to write report bundle")?;
+    Ok(reports.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_reports_and_bundle() {
+        let dir = std::env::temp_dir().join(format!("voidcli-crash-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["crash-100.txt", "crash-200.txt", "other.txt"] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        assert_eq!(take_new_reports(&dir).unwrap().len(), 2);
+        assert!(take_new_reports(&dir).unwrap().is_empty());
+        fs::write(dir.join("crash-300.txt"), "x").unwrap();
+        assert_eq!(take_new_reports(&dir).unwrap(), vec![dir.join("crash-300.txt")]);
+
+        let output = dir.join("bundle.txt");
+        assert_eq!(bundle_reports(&dir, &output).unwrap(), 3);
+        assert!(fs::read_to_string(&output).unwrap().contains("===== crash-200.txt ====="));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_render_report_redacts() {
+        let state = CrashState {
+            config_summary: "theme: dark\n".to_string(),
+            include_block_text: false,
+            recent_events: VecDeque::from(["command: [12 chars redacted]".to_string()]),
+            scrollback_lines: 4000,
+        };
+        let report = render_report("boom", "src/main.rs:1:1", "frames", &state);
+        assert!(report.contains("panic: boom"));
+        let message = "called `Result::unwrap()` on an `Err` value: \"/home/me/secret.txt\"";
+        let report = render_report(message, "src/main.rs:1:1", "frames", &state);
+        assert!(report.contains("panic: called `Result::unwrap()` on an `Err` value [23 chars"));
+        assert!(!report.contains("secret"));
+        assert!(report.contains("scrollback: 4000 lines"));
+        assert!(report.contains("command: [12 chars redacted]\n"));
+    }
+}
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use config::pattern::{expand_home, glob_match};
+use config::{DirectoryProfile, DirectoryProfilesConfig};
+
+/// A change of the directory profile to apply
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileSwitch {
+    /// Apply these overrides, replacing those of any previous profile
+    Enter(DirectoryProfile),
+    /// Back to the configured profile and theme
+    Revert,
+}
+
+/// Follows the focused pane's directory (OSC 7) and session context, and
+/// switches between `directory_profiles` once they have been current for
+/// `settle_ms`
+pub struct DirectoryProfiles {
+    config: DirectoryProfilesConfig,
+    home: Option<PathBuf>,
+    cwd: String,
+    /// `SessionContext::name` of the focused pane
+    context: String,
+    /// Index of the applied rule
+    active: Option<usize>,
+    /// Rule the directory matches, and since when, if it differs from `active`
+    pending: Option<(Option<usize>, Instant)>,
+}
+
+impl DirectoryProfiles {
+    pub fn new(config: &DirectoryProfilesConfig, home: Option<PathBuf>) -> Self {
+        Self {
+            config: config.clone(),
+            home,
+            cwd: String::new(),
+            context: "local".to_string(),
+            active: None,
+            pending: None,
+        }
+    }
+
+    fn matching(&self) -> Option<usize> {
+        let cwd = self.cwd.trim_end_matches('/');
+        self.config.rules.iter().position(|rule| {
+            let context_matches = rule.context.as_ref().is_none_or(|c| *c == self.context);
+            let pattern = expand_home(rule.cwd.trim_end_matches('/'), self.home.as_deref());
+            let cwd_matches = rule.cwd.is_empty()
+                || glob_match(&pattern, cwd)
+                || glob_match(&format!("{}/*", pattern), cwd);
+            context_matches && cwd_matches
+        })
+    }
+
+    /// The applied profile, if any
+    pub fn active(&self) -> Option<&DirectoryProfile> {
+        self.active.map(|index| &self.config.rules[index])
+    }
+
+    /// Record a new directory for the focused pane
+    pub fn cwd_changed(&mut self, cwd: &str, now: Instant) {
+        self.cwd = cwd.to_string();
+        self.retarget(now);
+    }
+
+    /// Record the focused pane entering or leaving SSH or a container
+    pub fn context_changed(&mut self, context: &str, now: Instant) {
+        self.context = context.to_string();
+        self.retarget(now);
+    }
+
+    fn retarget(&mut self, now: Instant) {
+        let target = self.matching();
+        if target == self.active {
+            self.pending = None;
+            return;
+        }
+        match self.pending {
+            Some((pending, _)) if pending == target => {}
+            _ => self.pending = Some((target, now)),
+        }
+    }
+
+    /// Switch once the directory has settled
+    pub fn poll(&mut self, now: Instant) -> Option<ProfileSwitch> {
+        let (target, since) = self.pending?;
+        if now.duration_since(since) < self.settle() {
+            return None;
+        }
+        self.pending = None;
+        self.active = target;
+        Some(match target {
+            Some(index) => ProfileSwitch::Enter(self.config.rules[index].clone()),
+            None => ProfileSwitch::Revert,
+        })
+    }
+
+    /// Time until `poll` can switch, for scheduling a wakeup
+    pub fn next_poll(&self, now: Instant) -> Option<Duration> {
+        let (_, since) = self.pending?;
+        Some(self.settle().saturating_sub(now.duration_since(since)))
+    }
+
+    fn settle(&self) -> Duration {
+        Duration::from_millis(self.config.settle_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_switch_after_settling() {
+        let config = DirectoryProfilesConfig {
+            rules: vec![DirectoryProfile {
+                cwd: "~/prod-ops".to_string(),
+                accent: Some("#ff0000".to_string()),
+                ..DirectoryProfile::default()
+            }],
+            settle_ms: 300,
+        };
+        let mut profiles = DirectoryProfiles::new(&config, Some(PathBuf::from("/home/me")));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // Passing through on the way elsewhere doesn't switch
+        profiles.cwd_changed("/home/me/prod-ops/db", at(0));
+        profiles.cwd_changed("/tmp", at(100));
+        assert_eq!(profiles.poll(at(400)), None);
+
+        profiles.cwd_changed("/home/me/prod-ops", at(500));
+        assert_eq!(profiles.poll(at(600)), None);
+        assert_eq!(profiles.next_poll(at(600)), Some(Duration::from_millis(200)));
+        assert_eq!(
+            profiles.poll(at(800)),
+            Some(ProfileSwitch::Enter(config.rules[0].clone()))
+        );
+        assert!(profiles.active().is_some());
+
+        profiles.cwd_changed("/home/me/prod-opsx", at(900));
+        assert_eq!(profiles.poll(at(1200)), Some(ProfileSwitch::Revert));
+        assert!(profiles.active().is_none());
+    }
+
+    #[test]
+    fn test_context_rule() {
+        let config = DirectoryProfilesConfig {
+            rules: vec![DirectoryProfile {
+                context: Some("ssh".to_string()),
+                theme: Some("red-alert".to_string()),
+                ..DirectoryProfile::default()
+            }],
+            settle_ms: 0,
+        };
+        let mut profiles = DirectoryProfiles::new(&config, None);
+        let now = Instant::now();
+
+        profiles.cwd_changed("/srv", now);
+        assert_eq!(profiles.poll(now), None);
+        profiles.context_changed("ssh", now);
+        assert_eq!(profiles.poll(now), Some(ProfileSwitch::Enter(config.rules[0].clone())));
+        profiles.context_changed("local", now);
+        assert_eq!(profiles.poll(now), Some(ProfileSwitch::Revert));
+    }
+}
@@ -1,22 +1,58 @@
 use std::sync::Arc;
 use tokio::sync::{mpsc, Mutex};
 use anyhow::Result;
+use config::{Config, Cue};
 
+use crate::feedback::{Feedback, FeedbackEvent};
+use crate::monitor::MonitorAlert;
+use crate::protocol::Message;
 use crate::state::AppState;
 
 pub enum Event {
     // Define your events here
     Quit,
+    /// A session rang the bell; the UI flashes the pane border and/or beeps
+    Bell { session_id: usize },
+    /// A pane monitored for activity or silence wants attention; shown in
+    /// the tab bar and, if enabled, as a desktop notification
+    PaneAlert(MonitorAlert),
+}
+
+impl Event {
+    /// The event a message from a `voidcli host` raises, if any
+    pub fn from_message(message: &Message) -> Option<Self> {
+        match message {
+            Message::Bell { pane } => Some(Event::Bell { session_id: *pane }),
+            _ => None,
+        }
+    }
 }
 
 pub struct EventLoop {
     _state: Arc<Mutex<AppState>>,
     _event_rx: mpsc::Receiver<Event>,
+    feedback: Feedback,
 }
 
 impl EventLoop {
-    pub fn new(state: Arc<Mutex<AppState>>, event_rx: mpsc::Receiver<Event>) -> Self {
-        Self { _state: state, _event_rx: event_rx }
+    pub fn new(
+        config: &Config,
+        state: Arc<Mutex<AppState>>,
+        event_rx: mpsc::Receiver<Event>,
+    ) -> Self {
+        Self {
+            _state: state,
+            _event_rx: event_rx,
+            feedback: Feedback::new(config),
+        }
+    }
+
+    /// Sound and flash for `event`, for the UI's `BellState::ring`
+    pub fn cue(&self, event: &Event) -> Option<Cue> {
+        match event {
+            Event::Bell { .. } => self.feedback.cue(FeedbackEvent::Bell),
+            _ => None,
+        }
     }
 
     pub async fn run(&self) -> Result<()> {
@@ -0,0 +1,100 @@
+use std::time::Duration;
+
+use config::{Config, Cue, FeedbackConfig};
+
+/// Something the user may want to hear or see
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedbackEvent {
+    /// BEL from a program
+    Bell,
+    CommandFinished { exit_code: i32, duration: Duration },
+    /// A paste was held back for confirmation
+    PasteBlocked,
+}
+
+/// Maps events to the sounds and flashes configured under `feedback`
+pub struct Feedback {
+    config: FeedbackConfig,
+    bell: Cue,
+}
+
+impl Feedback {
+    pub fn new(config: &Config) -> Self {
+        let bell = config.feedback.bell.clone().unwrap_or_else(|| Cue {
+            sound: config.bell.audible.then(|| "system".to_string()),
+            flash: config.bell.visual,
+        });
+        Self {
+            config: config.feedback.clone(),
+            bell,
+        }
+    }
+
+    /// Cue to play for `event`, or `None` when the event is silent
+    pub fn cue(&self, event: FeedbackEvent) -> Option<Cue> {
+        let cue = match event {
+            FeedbackEvent::Bell => self.bell.clone(),
+            FeedbackEvent::CommandFinished {
+                exit_code,
+                duration,
+            } => {
+                let mut cue = Cue::default();
+                if exit_code != 0 {
+                    cue = merge(cue, &self.config.command_failed);
+                }
+                if duration >= Duration::from_secs(self.config.long_command_secs) {
+                    cue = merge(cue, &self.config.long_command);
+                }
+                cue
+            }
+            FeedbackEvent::PasteBlocked => self.config.paste_blocked.clone(),
+        };
+        (cue.flash || cue.sound.is_some()).then_some(cue)
+    }
+}
+
+/// Both cues at once; the first sound wins since only one can play
+fn merge(cue: Cue, other: &Cue) -> Cue {
+    Cue {
+        sound: cue.sound.or_else(|| other.sound.clone()),
+        flash: cue.flash || other.flash,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_map() {
+        let mut config = Config::default();
+        config.bell.audible = true;
+        config.bell.visual = false;
+        config.feedback.command_failed = Cue {
+            sound: Some("/sounds/fail.wav".to_string()),
+            flash: false,
+        };
+        config.feedback.long_command = Cue {
+            sound: Some("system".to_string()),
+            flash: true,
+        };
+        config.feedback.paste_blocked = Cue::default();
+        let feedback = Feedback::new(&config);
+
+        let bell = feedback.cue(FeedbackEvent::Bell).unwrap();
+        assert_eq!(bell.sound.as_deref(), Some("system"));
+        assert!(!bell.flash);
+        assert_eq!(feedback.cue(FeedbackEvent::PasteBlocked), None);
+
+        let finished = |exit_code, secs| {
+            feedback.cue(FeedbackEvent::CommandFinished {
+                exit_code,
+                duration: Duration::from_secs(secs),
+            })
+        };
+        assert_eq!(finished(0, 1), None);
+        let slow_failure = finished(1, 60).unwrap();
+        assert_eq!(slow_failure.sound.as_deref(), Some("/sounds/fail.wav"));
+        assert!(slow_failure.flash);
+    }
+}
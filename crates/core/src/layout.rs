@@ -0,0 +1,752 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use config::{LayoutPreset, NewPaneCwd};
+use serde::{Deserialize, Serialize};
+
+pub type PaneId = usize;
+
+/// Version of the saved layout format
+const LAYOUT_VERSION: u32 = 1;
+
+/// Smallest share of a split either side can be resized down to
+const MIN_RATIO: f32 = 0.05;
+
+/// How a split arranges its two children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SplitDirection {
+    /// Side by side
+    Horizontal,
+    /// One above the other
+    Vertical,
+}
+
+/// What is known about a pane's shell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaneState {
+    pub id: PaneId,
+    /// Working directory as last reported by the shell
+    pub cwd: String,
+    /// Foreground command when the layout was saved, offered again on restore
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+impl PaneState {
+    pub fn new(id: PaneId, cwd: &str) -> Self {
+        Self {
+            id,
+            cwd: cwd.to_string(),
+            command: None,
+        }
+    }
+}
+
+/// Directory a new split or tab starts in
+///
+/// `current` is the focused pane's directory; it is used only if it still exists.
+pub fn new_pane_cwd(policy: NewPaneCwd, current: Option<&str>, start: &str) -> String {
+    let home = || {
+        dirs::home_dir()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_else(|| start.to_string())
+    };
+    match policy {
+        NewPaneCwd::Current => current
+            .filter(|dir| Path::new(dir).is_dir())
+            .map(str::to_string)
+            .unwrap_or_else(|| start.to_string()),
+        NewPaneCwd::Home => home(),
+        NewPaneCwd::Start => start.to_string(),
+    }
+}
+
+/// Area of the tab in cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Tree of panes within a tab
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PaneNode {
+    Pane(PaneState),
+    Split {
+        direction: SplitDirection,
+        /// Share of the space taken by `first`, in `0.0..=1.0`
+        ratio: f32,
+        first: Box<PaneNode>,
+        second: Box<PaneNode>,
+    },
+}
+
+impl PaneNode {
+    /// Panes in layout order
+    pub fn panes(&self) -> Vec<&PaneState> {
+        match self {
+            PaneNode::Pane(pane) => vec![pane],
+            PaneNode::Split { first, second, .. } => {
+                let mut panes = first.panes();
+                panes.extend(second.panes());
+                panes
+            }
+        }
+    }
+
+    pub fn pane(&self, id: PaneId) -> Option<&PaneState> {
+        self.panes().into_iter().find(|pane| pane.id == id)
+    }
+
+    pub fn pane_mut(&mut self, id: PaneId) -> Option<&mut PaneState> {
+        match self {
+            PaneNode::Pane(pane) => (pane.id == id).then_some(pane),
+            PaneNode::Split { first, second, .. } => {
+                first.pane_mut(id).or_else(|| second.pane_mut(id))
+            }
+        }
+    }
+
+    /// Split pane `target`, putting `pane` after it
+    pub fn split(&mut self, target: PaneId, direction: SplitDirection, pane: PaneState) -> bool {
+        match self {
+            PaneNode::Pane(existing) if existing.id == target => {
+                let existing = PaneNode::Pane(existing.clone());
+                *self = PaneNode::Split {
+                    direction,
+                    ratio: 0.5,
+                    first: Box::new(existing),
+                    second: Box::new(PaneNode::Pane(pane)),
+                };
+                true
+            }
+            PaneNode::Pane(_) => false,
+            PaneNode::Split { first, second, .. } => {
+                if first.pane(target).is_some() {
+                    first.split(target, direction, pane)
+                } else {
+                    second.split(target, direction, pane)
+                }
+            }
+        }
+    }
+
+    /// Area of each pane when the tree fills `area`
+    pub fn pane_rects(&self, area: Rect) -> Vec<(PaneId, Rect)> {
+        match self {
+            PaneNode::Pane(pane) => vec![(pane.id, area)],
+            PaneNode::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let (first_area, second_area) = match direction {
+                    SplitDirection::Horizontal => {
+                        let width = (area.width as f32 * ratio.clamp(0.0, 1.0)).round() as u16;
+                        (
+                            Rect { width, ..area },
+                            Rect {
+                                x: area.x + width,
+                                width: area.width - width,
+                                ..area
+                            },
+                        )
+                    }
+                    SplitDirection::Vertical => {
+                        let height = (area.height as f32 * ratio.clamp(0.0, 1.0)).round() as u16;
+                        (
+                            Rect { height, ..area },
+                            Rect {
+                                y: area.y + height,
+                                height: area.height - height,
+                                ..area
+                            },
+                        )
+                    }
+                };
+                let mut rects = first.pane_rects(first_area);
+                rects.extend(second.pane_rects(second_area));
+                rects
+            }
+        }
+    }
+
+    /// Grow pane `id` by `delta` of the nearest enclosing split in
+    /// `direction`; a negative `delta` shrinks it
+    ///
+    /// Returns false when no such split encloses the pane.
+    pub fn resize(&mut self, id: PaneId, direction: SplitDirection, delta: f32) -> bool {
+        let PaneNode::Split {
+            direction: split,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return false;
+        };
+
+        let delta = if first.pane(id).is_some() {
+            if first.resize(id, direction, delta) {
+                return true;
+            }
+            delta
+        } else if second.pane(id).is_some() {
+            if second.resize(id, direction, delta) {
+                return true;
+            }
+            -delta
+        } else {
+            return false;
+        };
+        if *split != direction {
+            return false;
+        }
+        *ratio = (*ratio + delta).clamp(MIN_RATIO, 1.0 - MIN_RATIO);
+        true
+    }
+
+    /// Remove pane `id`, letting its sibling take its space
+    ///
+    /// Returns false when `id` is not found or is the only pane.
+    pub fn close(&mut self, id: PaneId) -> bool {
+        let PaneNode::Split { first, second, .. } = self else {
+            return false;
+        };
+
+        let sibling = match (first.as_ref(), second.as_ref()) {
+            (PaneNode::Pane(pane), _) if pane.id == id => second.as_ref().clone(),
+            (_, PaneNode::Pane(pane)) if pane.id == id => first.as_ref().clone(),
+            _ => return first.close(id) || second.close(id),
+        };
+        *self = sibling;
+        true
+    }
+}
+
+/// A tab and its pane tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabLayout {
+    #[serde(default)]
+    pub title: Option<String>,
+    pub root: PaneNode,
+    pub focused: PaneId,
+    /// Pane temporarily filling the whole tab; the tree is kept as is
+    #[serde(default)]
+    pub zoomed: Option<PaneId>,
+    /// Pairs of panes that scroll together
+    #[serde(default)]
+    pub scroll_links: Vec<(PaneId, PaneId)>,
+}
+
+/// Scroll position of a pane, in lines from the top of its history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrollPosition {
+    pub offset: usize,
+    /// Largest possible offset
+    pub max: usize,
+}
+
+impl ScrollPosition {
+    /// Position as a fraction of the scrollable range
+    pub fn fraction(&self) -> f64 {
+        if self.max == 0 {
+            1.0
+        } else {
+            self.offset.min(self.max) as f64 / self.max as f64
+        }
+    }
+
+    /// The same fraction applied to this pane's range
+    pub fn follow(&self, leader: ScrollPosition) -> ScrollPosition {
+        ScrollPosition {
+            offset: (leader.fraction() * self.max as f64).round() as usize,
+            max: self.max,
+        }
+    }
+}
+
+impl TabLayout {
+    pub fn new(pane: PaneState) -> Self {
+        Self {
+            title: None,
+            focused: pane.id,
+            root: PaneNode::Pane(pane),
+            zoomed: None,
+            scroll_links: Vec::new(),
+        }
+    }
+
+    /// Link or unlink scrolling between two panes; returns whether they are
+    /// now linked
+    pub fn toggle_scroll_link(&mut self, a: PaneId, b: PaneId) -> bool {
+        if a == b || self.root.pane(a).is_none() || self.root.pane(b).is_none() {
+            return false;
+        }
+        let before = self.scroll_links.len();
+        self.scroll_links.retain(|&link| link != (a, b) && link != (b, a));
+        if self.scroll_links.len() != before {
+            return false;
+        }
+        self.scroll_links.push((a, b));
+        true
+    }
+
+    /// Panes that scroll along with `pane`
+    pub fn scroll_linked(&self, pane: PaneId) -> Vec<PaneId> {
+        self.scroll_links
+            .iter()
+            .filter_map(|&(a, b)| {
+                if a == pane {
+                    Some(b)
+                } else if b == pane {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Zoom the focused pane, or restore the layout when a pane is zoomed
+    pub fn toggle_zoom(&mut self) {
+        self.zoomed = match self.zoomed {
+            Some(_) => None,
+            None if self.root.panes().len() > 1 => Some(self.focused),
+            None => None,
+        };
+    }
+
+    /// Visible panes and their areas; only the zoomed pane while zoomed
+    pub fn pane_rects(&self, area: Rect) -> Vec<(PaneId, Rect)> {
+        match self.zoomed.filter(|id| self.root.pane(*id).is_some()) {
+            Some(id) => vec![(id, area)],
+            None => self.root.pane_rects(area),
+        }
+    }
+
+    /// Record the directory a pane's shell reported via OSC 7
+    pub fn set_cwd(&mut self, pane: PaneId, cwd: &str) {
+        if let Some(pane) = self.root.pane_mut(pane) {
+            pane.cwd = cwd.to_string();
+        }
+    }
+
+    /// Directory of the focused pane
+    pub fn focused_cwd(&self) -> Option<&str> {
+        self.root.pane(self.focused).map(|pane| pane.cwd.as_str())
+    }
+
+    /// Split the focused pane; unzooms first so the new pane is visible
+    pub fn split_focused(&mut self, direction: SplitDirection, pane: PaneState) -> bool {
+        self.zoomed = None;
+        let id = pane.id;
+        let split = self.root.split(self.focused, direction, pane);
+        if split {
+            self.focused = id;
+        }
+        split
+    }
+
+    /// Grow or shrink the focused pane from the keyboard
+    pub fn resize_focused(&mut self, direction: SplitDirection, delta: f32) -> bool {
+        self.root.resize(self.focused, direction, delta)
+    }
+
+    /// Move focus to the next or previous pane in layout order, wrapping
+    /// around; unzooms so the newly focused pane is visible
+    pub fn focus_next(&mut self, forward: bool) {
+        let ids: Vec<PaneId> = self.root.panes().iter().map(|pane| pane.id).collect();
+        let Some(index) = ids.iter().position(|id| *id == self.focused) else {
+            return;
+        };
+        let next = if forward {
+            (index + 1) % ids.len()
+        } else {
+            (index + ids.len() - 1) % ids.len()
+        };
+        self.zoomed = None;
+        self.focused = ids[next];
+    }
+
+    /// Close a pane, moving focus to the first remaining one if needed
+    pub fn close(&mut self, id: PaneId) -> bool {
+        if !self.root.close(id) {
+            return false;
+        }
+        if self.zoomed == Some(id) {
+            self.zoomed = None;
+        }
+        self.scroll_links.retain(|&(a, b)| a != id && b != id);
+        if self.focused == id {
+            self.focused = self.root.panes()[0].id;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowLayout {
+    pub tabs: Vec<TabLayout>,
+    #[serde(default)]
+    pub active_tab: usize,
+}
+
+/// Builds pane trees for config presets and the scripting layer, e.g.
+/// `LayoutBuilder::hsplit(0.7, LayoutBuilder::pane().command("nvim"), LayoutBuilder::pane())`
+#[derive(Debug, Clone)]
+pub enum LayoutBuilder {
+    Pane {
+        cwd: Option<String>,
+        command: Option<String>,
+    },
+    Split {
+        direction: SplitDirection,
+        ratio: f32,
+        first: Box<LayoutBuilder>,
+        second: Box<LayoutBuilder>,
+    },
+}
+
+impl LayoutBuilder {
+    pub fn pane() -> Self {
+        LayoutBuilder::Pane {
+            cwd: None,
+            command: None,
+        }
+    }
+
+    pub fn hsplit(ratio: f32, first: LayoutBuilder, second: LayoutBuilder) -> Self {
+        Self::split(SplitDirection::Horizontal, ratio, first, second)
+    }
+
+    pub fn vsplit(ratio: f32, first: LayoutBuilder, second: LayoutBuilder) -> Self {
+        Self::split(SplitDirection::Vertical, ratio, first, second)
+    }
+
+    fn split(direction: SplitDirection, ratio: f32, first: Self, second: Self) -> Self {
+        LayoutBuilder::Split {
+            direction,
+            ratio: ratio.clamp(0.05, 0.95),
+            first: Box::new(first),
+            second: Box::new(second),
+        }
+    }
+
+    /// Working directory of a pane
+    pub fn cwd(mut self, dir: &str) -> Self {
+        if let LayoutBuilder::Pane { cwd, .. } = &mut self {
+            *cwd = Some(dir.to_string());
+        }
+        self
+    }
+
+    /// Command run in a pane once its shell has started
+    pub fn command(mut self, text: &str) -> Self {
+        if let LayoutBuilder::Pane { command, .. } = &mut self {
+            *command = Some(text.to_string());
+        }
+        self
+    }
+
+    pub fn from_preset(preset: &LayoutPreset) -> Result<Self> {
+        let Some(split) = preset.split.as_deref() else {
+            let mut pane = Self::pane();
+            if let Some(cwd) = &preset.cwd {
+                pane = pane.cwd(cwd);
+            }
+            if let Some(command) = &preset.command {
+                pane = pane.command(command);
+            }
+            return Ok(pane);
+        };
+
+        let direction = match split {
+            "horizontal" => SplitDirection::Horizontal,
+            "vertical" => SplitDirection::Vertical,
+            other => return Err(anyhow!("Unknown split direction '{}'", other)),
+        };
+        let child = |child: &Option<Box<LayoutPreset>>| match child {
+            Some(child) => Self::from_preset(child),
+            None => Ok(Self::pane()),
+        };
+        Ok(Self::split(
+            direction,
+            preset.ratio.unwrap_or(0.5),
+            child(&preset.first)?,
+            child(&preset.second)?,
+        ))
+    }
+
+    /// Create the tab, numbering panes from `next_id`; the first pane is focused
+    pub fn build(self, next_id: &mut PaneId, default_cwd: &str) -> TabLayout {
+        let root = self.build_node(next_id, default_cwd);
+        let mut tab = TabLayout::new(root.panes()[0].clone());
+        tab.root = root;
+        tab
+    }
+
+    fn build_node(self, next_id: &mut PaneId, default_cwd: &str) -> PaneNode {
+        match self {
+            LayoutBuilder::Pane { cwd, command } => {
+                let id = *next_id;
+                *next_id += 1;
+                PaneNode::Pane(PaneState {
+                    id,
+                    cwd: cwd.unwrap_or_else(|| default_cwd.to_string()),
+                    command,
+                })
+            }
+            LayoutBuilder::Split {
+                direction,
+                ratio,
+                first,
+                second,
+            } => PaneNode::Split {
+                direction,
+                ratio,
+                first: Box::new(first.build_node(next_id, default_cwd)),
+                second: Box::new(second.build_node(next_id, default_cwd)),
+            },
+        }
+    }
+}
+
+/// A pane to re-create when restoring a layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoredPane {
+    pub id: PaneId,
+    pub cwd: String,
+    /// Area of the pane within its tab
+    pub rect: Rect,
+    /// Command that was running, for the user to re-run
+    pub command_hint: Option<String>,
+}
+
+/// The whole workspace: windows, tabs and pane trees
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionLayout {
+    pub version: u32,
+    pub windows: Vec<WindowLayout>,
+}
+
+impl Default for SessionLayout {
+    fn default() -> Self {
+        Self {
+            version: LAYOUT_VERSION,
+            windows: Vec::new(),
+        }
+    }
+}
+
+impl SessionLayout {
+    /// Default location of the saved layout
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("voidcli").join("layout.json"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read layout {}", path.display()))?;
+        let layout: Self = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid layout {}", path.display()))?;
+        if layout.version > LAYOUT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Layout {} was saved by a newer version",
+                path.display()
+            ));
+        }
+        Ok(layout)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// All panes in window, tab and layout order
+    pub fn panes(&self) -> impl Iterator<Item = &PaneState> {
+        self.windows
+            .iter()
+            .flat_map(|window| &window.tabs)
+            .flat_map(|tab| tab.root.panes())
+    }
+
+    /// Panes to spawn shells for, laid out in a tab of size `area`;
+    /// directories that no longer exist fall back to `fallback_cwd`
+    pub fn restore_plan(&self, fallback_cwd: &str, area: Rect) -> Vec<RestoredPane> {
+        self.windows
+            .iter()
+            .flat_map(|window| &window.tabs)
+            .flat_map(|tab| {
+                // Zoomed-out panes get shells too, sized as in the split
+                tab.root
+                    .pane_rects(area)
+                    .into_iter()
+                    .filter_map(|(id, rect)| tab.root.pane(id).map(|pane| (pane, rect)))
+            })
+            .map(|(pane, rect)| RestoredPane {
+                id: pane.id,
+                cwd: if Path::new(&pane.cwd).is_dir() {
+                    pane.cwd.clone()
+                } else {
+                    fallback_cwd.to_string()
+                },
+                rect,
+                command_hint: pane.command.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_close() {
+        let mut root = PaneNode::Pane(PaneState::new(0, "/"));
+        assert!(root.split(0, SplitDirection::Horizontal, PaneState::new(1, "/tmp")));
+        assert!(root.split(1, SplitDirection::Vertical, PaneState::new(2, "/tmp")));
+        let ids: Vec<PaneId> = root.panes().iter().map(|pane| pane.id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        assert!(root.close(1));
+        let ids: Vec<PaneId> = root.panes().iter().map(|pane| pane.id).collect();
+        assert_eq!(ids, vec![0, 2]);
+        assert!(root.close(0));
+        assert_eq!(root, PaneNode::Pane(PaneState::new(2, "/tmp")));
+        assert!(!root.close(2));
+    }
+
+    #[test]
+    fn test_zoom() {
+        let area = Rect { x: 0, y: 0, width: 80, height: 24 };
+        let mut tab = TabLayout::new(PaneState::new(0, "/"));
+        tab.split_focused(SplitDirection::Horizontal, PaneState::new(1, "/"));
+        assert_eq!(
+            tab.pane_rects(area),
+            vec![
+                (0, Rect { width: 40, ..area }),
+                (1, Rect { x: 40, width: 40, ..area }),
+            ]
+        );
+
+        let tree = tab.root.clone();
+        tab.toggle_zoom();
+        assert_eq!(tab.pane_rects(area), vec![(1, area)]);
+        tab.toggle_zoom();
+        assert_eq!(tab.pane_rects(area).len(), 2);
+        assert_eq!(tab.root, tree);
+    }
+
+    #[test]
+    fn test_keyboard_focus_and_resize() {
+        let area = Rect { x: 0, y: 0, width: 100, height: 40 };
+        let mut tab = TabLayout::new(PaneState::new(0, "/"));
+        tab.split_focused(SplitDirection::Horizontal, PaneState::new(1, "/"));
+        tab.split_focused(SplitDirection::Vertical, PaneState::new(2, "/"));
+
+        // Pane 2 sits in a vertical split, so width comes from the outer one
+        assert!(tab.resize_focused(SplitDirection::Horizontal, 0.2));
+        assert_eq!(tab.pane_rects(area)[0].1.width, 30);
+        assert!(tab.resize_focused(SplitDirection::Vertical, 0.25));
+        assert_eq!(tab.pane_rects(area)[2].1.height, 30);
+
+        tab.focus_next(true);
+        assert_eq!(tab.focused, 0);
+        assert!(!tab.resize_focused(SplitDirection::Vertical, 0.1));
+        assert!(tab.resize_focused(SplitDirection::Horizontal, 1.0));
+        assert_eq!(tab.pane_rects(area)[0].1.width, 95);
+        tab.focus_next(false);
+        assert_eq!(tab.focused, 2);
+    }
+
+    #[test]
+    fn test_scroll_links() {
+        let mut tab = TabLayout::new(PaneState::new(0, "/"));
+        tab.split_focused(SplitDirection::Horizontal, PaneState::new(1, "/"));
+        assert!(tab.toggle_scroll_link(0, 1));
+        assert_eq!(tab.scroll_linked(1), vec![0]);
+
+        let leader = ScrollPosition { offset: 50, max: 100 };
+        let follower = ScrollPosition { offset: 0, max: 400 };
+        assert_eq!(follower.follow(leader).offset, 200);
+
+        assert!(!tab.toggle_scroll_link(1, 0));
+        assert!(tab.scroll_linked(0).is_empty());
+        assert!(!tab.toggle_scroll_link(0, 7));
+    }
+
+    #[test]
+    fn test_layout_builder() {
+        let preset = LayoutPreset {
+            split: Some("horizontal".to_string()),
+            ratio: Some(0.7),
+            first: Some(Box::new(LayoutPreset {
+                command: Some("nvim".to_string()),
+                ..LayoutPreset::default()
+            })),
+            second: Some(Box::new(LayoutPreset {
+                split: Some("vertical".to_string()),
+                ..LayoutPreset::default()
+            })),
+            ..LayoutPreset::default()
+        };
+
+        let mut next_id = 5;
+        let tab = LayoutBuilder::from_preset(&preset).unwrap().build(&mut next_id, "/work");
+        assert_eq!(next_id, 8);
+        assert_eq!(tab.focused, 5);
+
+        let area = Rect { x: 0, y: 0, width: 100, height: 40 };
+        let rects = tab.pane_rects(area);
+        assert_eq!(rects[0], (5, Rect { width: 70, ..area }));
+        assert_eq!(rects[2], (7, Rect { x: 70, y: 20, width: 30, height: 20 }));
+        assert_eq!(tab.root.pane(5).unwrap().command.as_deref(), Some("nvim"));
+
+        let bad = LayoutPreset {
+            split: Some("diagonal".to_string()),
+            ..LayoutPreset::default()
+        };
+        assert!(LayoutBuilder::from_preset(&bad).is_err());
+    }
+
+    #[test]
+    fn test_new_pane_cwd() {
+        let mut tab = TabLayout::new(PaneState::new(0, "/start"));
+        tab.set_cwd(0, "/tmp");
+        let current = tab.focused_cwd();
+        assert_eq!(new_pane_cwd(NewPaneCwd::Current, current, "/start"), "/tmp");
+        assert_eq!(new_pane_cwd(NewPaneCwd::Start, current, "/start"), "/start");
+        assert_eq!(
+            new_pane_cwd(NewPaneCwd::Current, Some("/no/such/dir"), "/start"),
+            "/start"
+        );
+    }
+
+    #[test]
+    fn test_restore_plan() {
+        let mut tab = TabLayout::new(PaneState::new(0, "/"));
+        let mut gone = PaneState::new(1, "/no/such/dir");
+        gone.command = Some("cargo watch".to_string());
+        tab.root.split(0, SplitDirection::Horizontal, gone);
+
+        let layout = SessionLayout {
+            windows: vec![WindowLayout { tabs: vec![tab], active_tab: 0 }],
+            ..SessionLayout::default()
+        };
+        let area = Rect { x: 0, y: 0, width: 80, height: 24 };
+        let plan = layout.restore_plan("/home", area);
+        assert_eq!(plan[0].cwd, "/");
+        assert_eq!(plan[1].cwd, "/home");
+        assert_eq!(plan[1].rect, Rect { x: 40, width: 40, ..area });
+        assert_eq!(plan[1].command_hint.as_deref(), Some("cargo watch"));
+    }
+}
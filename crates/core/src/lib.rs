@@ -1,4 +1,14 @@
 pub mod app;
+pub mod crash;
+pub mod directory_profiles;
 pub mod error;
 pub mod events;
+pub mod feedback;
+pub mod layout;
+pub mod logging;
+pub mod monitor;
+pub mod preferences;
+pub mod protocol;
+pub mod session;
 pub mod state;
+pub mod update;
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::layout::PaneId;
+
+/// Why a monitored pane wants attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonitorAlert {
+    /// New output in a pane watched for activity
+    Activity { pane: PaneId },
+    /// No output for the configured time in a pane watched for silence
+    Silence { pane: PaneId, idle: Duration },
+}
+
+impl MonitorAlert {
+    pub fn pane(&self) -> PaneId {
+        match self {
+            MonitorAlert::Activity { pane } | MonitorAlert::Silence { pane, .. } => *pane,
+        }
+    }
+
+    /// Notification text
+    pub fn message(&self) -> String {
+        match self {
+            MonitorAlert::Activity { pane } => format!("Activity in pane {}", pane),
+            MonitorAlert::Silence { pane, idle } => {
+                format!("Pane {} silent for {}s", pane, idle.as_secs())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PaneMonitor {
+    activity: bool,
+    silence: Option<Duration>,
+    last_output: Instant,
+    /// Alert raised and not yet seen; shown in the tab bar
+    pending: Option<MonitorAlert>,
+    /// Silence already reported for the current quiet period
+    silence_reported: bool,
+}
+
+/// Per-pane activity and silence monitoring
+#[derive(Debug, Default)]
+pub struct PaneMonitors {
+    panes: HashMap<PaneId, PaneMonitor>,
+}
+
+impl PaneMonitors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn monitor(&mut self, pane: PaneId, now: Instant) -> &mut PaneMonitor {
+        self.panes.entry(pane).or_insert(PaneMonitor {
+            activity: false,
+            silence: None,
+            last_output: now,
+            pending: None,
+            silence_reported: false,
+        })
+    }
+
+    /// Toggle alerting on new output; returns whether it is now on
+    pub fn toggle_activity(&mut self, pane: PaneId, now: Instant) -> bool {
+        let monitor = self.monitor(pane, now);
+        monitor.activity = !monitor.activity;
+        monitor.activity
+    }
+
+    /// Toggle alerting after `after` without output; returns whether it is now on
+    pub fn toggle_silence(&mut self, pane: PaneId, after: Duration, now: Instant) -> bool {
+        let monitor = self.monitor(pane, now);
+        monitor.silence = match monitor.silence {
+            Some(_) => None,
+            None => Some(after),
+        };
+        monitor.last_output = now;
+        monitor.silence_reported = false;
+        monitor.silence.is_some()
+    }
+
+    /// Record output of a pane; returns an activity alert for watched
+    /// panes that are not focused
+    pub fn record_output(
+        &mut self,
+        pane: PaneId,
+        focused: bool,
+        now: Instant,
+    ) -> Option<MonitorAlert> {
+        let monitor = self.panes.get_mut(&pane)?;
+        monitor.last_output = now;
+        monitor.silence_reported = false;
+
+        if !monitor.activity || focused || monitor.pending.is_some() {
+            return None;
+        }
+        let alert = MonitorAlert::Activity { pane };
+        monitor.pending = Some(alert);
+        Some(alert)
+    }
+
+    /// Silence alerts due at `now`, each reported once per quiet period
+    pub fn poll(&mut self, now: Instant) -> Vec<MonitorAlert> {
+        let mut alerts = Vec::new();
+        for (&pane, monitor) in &mut self.panes {
+            let Some(after) = monitor.silence else {
+                continue;
+            };
+            let idle = now.saturating_duration_since(monitor.last_output);
+            if idle >= after && !monitor.silence_reported {
+                let alert = MonitorAlert::Silence { pane, idle };
+                monitor.silence_reported = true;
+                monitor.pending = Some(alert);
+                alerts.push(alert);
+            }
+        }
+        alerts.sort_by_key(MonitorAlert::pane);
+        alerts
+    }
+
+    /// Alert to show in the tab bar for a pane, until it is focused
+    pub fn pending(&self, pane: PaneId) -> Option<MonitorAlert> {
+        self.panes.get(&pane).and_then(|monitor| monitor.pending)
+    }
+
+    /// The user looked at the pane
+    pub fn acknowledge(&mut self, pane: PaneId) {
+        if let Some(monitor) = self.panes.get_mut(&pane) {
+            monitor.pending = None;
+        }
+    }
+
+    pub fn remove(&mut self, pane: PaneId) {
+        self.panes.remove(&pane);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity_and_silence() {
+        let start = Instant::now();
+        let mut monitors = PaneMonitors::new();
+
+        assert_eq!(monitors.record_output(0, false, start), None);
+        assert!(monitors.toggle_activity(0, start));
+        assert_eq!(monitors.record_output(0, true, start), None);
+        assert_eq!(
+            monitors.record_output(0, false, start),
+            Some(MonitorAlert::Activity { pane: 0 })
+        );
+        assert_eq!(monitors.record_output(0, false, start), None);
+        monitors.acknowledge(0);
+        assert_eq!(monitors.pending(0), None);
+
+        assert!(monitors.toggle_silence(1, Duration::from_secs(30), start));
+        assert!(monitors.poll(start + Duration::from_secs(10)).is_empty());
+        let alerts = monitors.poll(start + Duration::from_secs(31));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].message(), "Pane 1 silent for 31s");
+        assert!(monitors.poll(start + Duration::from_secs(60)).is_empty());
+
+        monitors.record_output(1, false, start + Duration::from_secs(61));
+        assert_eq!(monitors.poll(start + Duration::from_secs(95)).len(), 1);
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use config::UiConfig;
+use serde::{Deserialize, Serialize};
+
+/// Limits of the UI zoom, whatever the config says
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+/// Settings changed at runtime and kept across restarts, separate from the
+/// user's config file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preferences {
+    /// UI zoom picked with the zoom bindings; replaces `ui.scale`
+    #[serde(default)]
+    pub ui_scale: Option<f32>,
+}
+
+impl Preferences {
+    /// ~/.local/share/voidcli/preferences.json
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("voidcli").join("preferences.json"))
+    }
+
+    /// Load saved preferences; a missing file gives the defaults
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read preferences {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid preferences {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Current UI scale factor
+    pub fn ui_scale(&self, config: &UiConfig) -> f32 {
+        clamp_ui_scale(self.ui_scale.unwrap_or(config.scale))
+    }
+
+    /// Zoom the UI in (positive) or out (negative) by `steps` of
+    /// `ui.scale_step`, returning the new scale
+    pub fn zoom_ui(&mut self, config: &UiConfig, steps: i32) -> f32 {
+        let scale = clamp_ui_scale(self.ui_scale(config) + steps as f32 * config.scale_step);
+        // Round away float drift so repeated steps land on e.g. 1.2, not 1.2000001
+        let scale = (scale * 100.0).round() / 100.0;
+        self.ui_scale = Some(scale);
+        scale
+    }
+
+    /// Go back to the configured `ui.scale`
+    pub fn reset_ui_zoom(&mut self) {
+        self.ui_scale = None;
+    }
+}
+
+fn clamp_ui_scale(scale: f32) -> f32 {
+    if scale.is_finite() {
+        scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+    } else {
+        1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ui_zoom() {
+        let config = UiConfig {
+            scale: 1.5,
+            ..UiConfig::default()
+        };
+        let mut preferences = Preferences::default();
+        assert_eq!(preferences.ui_scale(&config), 1.5);
+        assert_eq!(preferences.zoom_ui(&config, 2), 1.7);
+        assert_eq!(preferences.zoom_ui(&config, -3), 1.4);
+        assert_eq!(preferences.zoom_ui(&config, 100), MAX_UI_SCALE);
+        assert_eq!(preferences.ui_scale, Some(MAX_UI_SCALE));
+
+        preferences.reset_ui_zoom();
+        assert_eq!(preferences.ui_scale(&config), 1.5);
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::layout::PaneId;
+
+/// Bumped on incompatible changes; client and host must agree
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Frames larger than this are rejected instead of allocated
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// A cell as sent over the wire
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WireCell {
+    /// Empty for the right half of a wide character
+    pub text: String,
+    /// ANSI color index, or `0x1RRGGBB` for an RGB color; `None` for the
+    /// default color
+    #[serde(default)]
+    pub fg: Option<u32>,
+    #[serde(default)]
+    pub bg: Option<u32>,
+    /// `WireCell::BOLD`, `WireCell::ITALIC`, ... as a bit set
+    #[serde(default)]
+    pub flags: u16,
+}
+
+impl WireCell {
+    pub const BOLD: u16 = 1;
+    pub const ITALIC: u16 = 1 << 1;
+    pub const UNDERLINE: u16 = 1 << 2;
+    pub const BLINK: u16 = 1 << 3;
+    pub const REVERSE: u16 = 1 << 4;
+    pub const HIDDEN: u16 = 1 << 5;
+    pub const STRIKETHROUGH: u16 = 1 << 6;
+}
+
+/// New contents of one screen row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowUpdate {
+    pub row: u16,
+    pub cells: Vec<WireCell>,
+}
+
+/// Changes to a pane's screen since the last frame the client acknowledged
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenDiff {
+    pub pane: PaneId,
+    /// Sequence number the client acknowledges with `Ack`
+    pub seq: u64,
+    pub cols: u16,
+    pub rows: u16,
+    pub cursor: (u16, u16),
+    pub updates: Vec<RowUpdate>,
+}
+
+impl ScreenDiff {
+    /// Rows of `new` that differ from `old`; all rows when the size changed
+    pub fn between(
+        pane: PaneId,
+        seq: u64,
+        old: &[Vec<WireCell>],
+        new: &[Vec<WireCell>],
+        cursor: (u16, u16),
+    ) -> Self {
+        let cols = new.first().map_or(0, Vec::len) as u16;
+        let resized = old.len() != new.len() || old.first().map_or(0, Vec::len) != cols as usize;
+        let updates = new
+            .iter()
+            .enumerate()
+            .filter(|(row, cells)| resized || old.get(*row) != Some(cells))
+            .map(|(row, cells)| RowUpdate {
+                row: row as u16,
+                cells: cells.clone(),
+            })
+            .collect();
+
+        Self {
+            pane,
+            seq,
+            cols,
+            rows: new.len() as u16,
+            cursor,
+            updates,
+        }
+    }
+
+    /// Apply the diff to a client-side copy of the screen
+    pub fn apply(&self, screen: &mut Vec<Vec<WireCell>>) {
+        let blank = WireCell {
+            text: " ".to_string(),
+            fg: None,
+            bg: None,
+            flags: 0,
+        };
+        screen.resize(self.rows as usize, Vec::new());
+        for row in screen.iter_mut() {
+            row.resize(self.cols as usize, blank.clone());
+        }
+        for update in &self.updates {
+            if let Some(row) = screen.get_mut(update.row as usize) {
+                *row = update.cells.clone();
+            }
+        }
+    }
+}
+
+/// Messages between a UI client and a `voidcli host`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Message {
+    /// First message in both directions
+    Hello { version: u32, client: String },
+    /// Start a shell for a new pane; the host answers with `Screen` diffs
+    Open { pane: PaneId, cols: u16, rows: u16 },
+    /// Keyboard or paste input for a pane
+    Input { pane: PaneId, data: Vec<u8> },
+    Resize {
+        pane: PaneId,
+        cols: u16,
+        rows: u16,
+        /// Width and height of a cell in pixels, if the client knows them
+        #[serde(default)]
+        cell_size: Option<(u16, u16)>,
+    },
+    /// The pane gained or lost focus in the client
+    Focus { pane: PaneId, focused: bool },
+    Screen(ScreenDiff),
+    /// The client has applied the diff with this sequence number
+    Ack { pane: PaneId, seq: u64 },
+    PaneExited { pane: PaneId, code: i32 },
+    /// The pane's program rang the bell
+    Bell { pane: PaneId },
+    /// The pane's program set a new window title
+    Title { pane: PaneId, title: String },
+    /// The pane's session entered or left SSH or a container; `name` is
+    /// `local`, `ssh`, `container` or `toolbox`
+    Context {
+        pane: PaneId,
+        name: String,
+        /// Block header badge such as `ssh prod-1`; `None` when local
+        badge: Option<String>,
+    },
+    /// A command in the pane finished; CPU times in milliseconds, peak
+    /// memory in KiB
+    CommandUsage {
+        pane: PaneId,
+        user_ms: u64,
+        system_ms: u64,
+        max_rss_kb: u64,
+    },
+    /// Stop or resume sending `Bell` for a pane
+    SuppressBell { pane: PaneId, suppressed: bool },
+    /// Ask the host for its runtime metrics
+    MetricsRequest,
+    /// Counter and histogram bucket values by name
+    Metrics { counters: BTreeMap<String, u64> },
+    Error { message: String },
+}
+
+/// Write a message as a big-endian length-prefixed JSON frame
+pub fn write_message(writer: &mut impl Write, message: &Message) -> Result<()> {
+    let payload = serde_json::to_vec(message)?;
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_message(reader: &mut impl Read) -> Result<Message> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).context("Connection closed")?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(anyhow!("Frame of {} bytes exceeds the limit", len));
+    }
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    serde_json::from_slice(&payload).context("Invalid protocol message")
+}
+
+/// Exchange `Hello` messages and check that both sides speak the same version
+pub fn handshake(stream: &mut (impl Read + Write), name: &str) -> Result<String> {
+    write_message(
+        stream,
+        &Message::Hello {
+            version: PROTOCOL_VERSION,
+            client: name.to_string(),
+        },
+    )?;
+    match read_message(stream)? {
+        Message::Hello { version, client } if version == PROTOCOL_VERSION => Ok(client),
+        Message::Hello { version, .. } => Err(anyhow!(
+            "Protocol version mismatch: peer speaks {}, we speak {}",
+            version,
+            PROTOCOL_VERSION
+        )),
+        Message::Error { message } => Err(anyhow!("{}", message)),
+        other => Err(anyhow!("Expected hello, got {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(text: &str) -> Vec<WireCell> {
+        text.chars()
+            .map(|c| WireCell {
+                text: c.to_string(),
+                fg: None,
+                bg: None,
+                flags: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_screen_diff() {
+        let old = vec![row("ab"), row("cd"), row("ef")];
+        let new = vec![row("ab"), row("xd"), row("ef")];
+
+        let diff = ScreenDiff::between(0, 1, &old, &new, (1, 1));
+        assert_eq!(diff.updates.len(), 1);
+        assert_eq!(diff.updates[0].row, 1);
+
+        let mut screen = old.clone();
+        diff.apply(&mut screen);
+        assert_eq!(screen, new);
+
+        let resized = ScreenDiff::between(0, 2, &old, &[row("abc")], (0, 0));
+        assert_eq!(resized.updates.len(), 1);
+        resized.apply(&mut screen);
+        assert_eq!(screen, vec![row("abc")]);
+    }
+}
@@ -0,0 +1,637 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::Shutdown;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use config::Config;
+use serde::{Deserialize, Serialize};
+use term::{HostedPane, TermEvent};
+use tokio::runtime::Handle;
+use tracing::{info, warn};
+
+use crate::layout::SessionLayout;
+
+/// Metadata of a daemon session, as listed by `voidcli ls`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: usize,
+    pub name: String,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub windows: usize,
+    pub attached_clients: usize,
+}
+
+/// A session kept alive by the daemon
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: usize,
+    pub name: String,
+    pub created_at: u64,
+    pub layout: SessionLayout,
+    pub attached_clients: usize,
+    /// The shell the daemon runs for the session, if it runs one
+    shell: Option<SessionShell>,
+}
+
+impl Session {
+    pub fn info(&self) -> SessionInfo {
+        SessionInfo {
+            id: self.id,
+            name: self.name.clone(),
+            created_at: self.created_at,
+            windows: self.layout.windows.len(),
+            attached_clients: self.attached_clients,
+        }
+    }
+}
+
+/// Requests a client sends to the daemon, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "kebab-case")]
+pub enum Request {
+    List,
+    /// Create a session; the daemon picks a name when none is given
+    New { name: Option<String> },
+    /// Attach to a session; when the daemon runs a shell for it, the
+    /// connection then carries the shell's output instead of responses
+    Attach { name: String },
+    Detach { name: String },
+    Rename { from: String, to: String },
+    /// Keyboard input for the attached session's shell; no response
+    Input { data: Vec<u8> },
+    /// New size of the attached client's terminal; no response
+    Resize { cols: u16, rows: u16 },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "kebab-case")]
+pub enum Response {
+    Sessions { sessions: Vec<SessionInfo> },
+    Session { session: SessionInfo },
+    Error { message: String },
+}
+
+/// What a session's shell task is asked to do
+#[derive(Debug)]
+enum ShellCommand {
+    Input(Vec<u8>),
+    Resize { cols: u16, rows: u16 },
+    /// Paint the screen for a newly attached client, then send it the output
+    Attach(mpsc::Sender<Vec<u8>>),
+}
+
+/// Handle on the shell the daemon runs for a session; the session ends
+/// when the shell exits
+#[derive(Debug, Clone)]
+struct SessionShell {
+    commands: tokio::sync::mpsc::UnboundedSender<ShellCommand>,
+}
+
+impl SessionShell {
+    /// Start the configured shell on a PTY, kept by a task on `runtime`
+    fn spawn(config: &Config, runtime: &Handle, name: &str) -> Self {
+        let (commands, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let config = config.clone();
+        let name = name.to_string();
+        runtime.spawn(async move {
+            match run_shell(&config, receiver).await {
+                Ok(()) => info!("Shell of session {} exited", name),
+                Err(e) => warn!("Shell of session {} failed: {:#}", name, e),
+            }
+        });
+        Self { commands }
+    }
+
+    fn is_running(&self) -> bool {
+        !self.commands.is_closed()
+    }
+
+    fn send(&self, command: ShellCommand) -> Result<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| anyhow!("The session's shell has exited"))
+    }
+}
+
+/// Run a session's shell until it exits, forwarding its output to the
+/// attached clients
+async fn run_shell(
+    config: &Config,
+    mut commands: tokio::sync::mpsc::UnboundedReceiver<ShellCommand>,
+) -> Result<()> {
+    let mut pane = HostedPane::spawn(config, 80, 24).await?;
+    let mut clients: Vec<mpsc::Sender<Vec<u8>>> = Vec::new();
+    loop {
+        tokio::select! {
+            events = pane.next_events() => {
+                for event in events? {
+                    match event {
+                        TermEvent::Output(data) => {
+                            clients.retain(|client| client.send(data.clone()).is_ok());
+                        }
+                        TermEvent::ProcessExit(_) => {
+                            // Marks the session ended before the clients'
+                            // streams close
+                            commands.close();
+                            return Ok(());
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            command = commands.recv() => match command {
+                Some(ShellCommand::Input(data)) => pane.write(&data).await?,
+                Some(ShellCommand::Resize { cols, rows }) => pane.resize(cols, rows).await?,
+                Some(ShellCommand::Attach(client)) => {
+                    if client.send(pane.screen().redraw()).is_ok() {
+                        clients.push(client);
+                    }
+                }
+                None => return pane.kill().await,
+            },
+        }
+    }
+}
+
+/// Sessions of the daemon, addressed by unique name
+#[derive(Debug, Default)]
+pub struct SessionRegistry {
+    sessions: Vec<Session>,
+    next_id: usize,
+    /// Config and runtime for the shells of new sessions, when the registry
+    /// runs them
+    shells: Option<(Config, Handle)>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the configured shell in each new session, on the current tokio
+    /// runtime, so clients attaching to it reconnect to a live program
+    pub fn with_shells(mut self, config: &Config) -> Self {
+        self.shells = Some((config.clone(), Handle::current()));
+        self
+    }
+
+    pub fn create(&mut self, name: Option<&str>) -> Result<&Session> {
+        let name = match name {
+            Some(name) => {
+                validate_name(name)?;
+                if self.get(name).is_some() {
+                    return Err(anyhow!("Session '{}' already exists", name));
+                }
+                name.to_string()
+            }
+            None => (self.next_id..)
+                .map(|n| n.to_string())
+                .find(|name| self.get(name).is_none())
+                .unwrap_or_default(),
+        };
+
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let shell = self
+            .shells
+            .as_ref()
+            .map(|(config, runtime)| SessionShell::spawn(config, runtime, &name));
+        self.sessions.push(Session {
+            id: self.next_id,
+            name,
+            created_at,
+            layout: SessionLayout::default(),
+            attached_clients: 0,
+            shell,
+        });
+        self.next_id += 1;
+        Ok(self.sessions.last().unwrap())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Session> {
+        self.sessions.iter().find(|session| session.name == name)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut Session> {
+        self.sessions
+            .iter_mut()
+            .find(|session| session.name == name)
+            .ok_or_else(|| anyhow!("No session named '{}'", name))
+    }
+
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<&Session> {
+        validate_name(to)?;
+        if from != to && self.get(to).is_some() {
+            return Err(anyhow!("Session '{}' already exists", to));
+        }
+        let session = self.get_mut(from)?;
+        session.name = to.to_string();
+        Ok(session)
+    }
+
+    /// Drop one client from a session, found by id since it may have been
+    /// renamed
+    fn detach_id(&mut self, id: usize) {
+        if let Some(session) = self.sessions.iter_mut().find(|session| session.id == id) {
+            session.attached_clients = session.attached_clients.saturating_sub(1);
+        }
+    }
+
+    /// The shell of a session, found by id
+    fn shell(&self, id: usize) -> Option<SessionShell> {
+        self.sessions
+            .iter()
+            .find(|session| session.id == id)
+            .and_then(|session| session.shell.clone())
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        self.sessions.iter().map(Session::info).collect()
+    }
+
+    /// Apply a client request
+    pub fn handle(&mut self, request: Request) -> Response {
+        // Sessions end with their shell
+        self.sessions
+            .retain(|session| session.shell.as_ref().is_none_or(SessionShell::is_running));
+
+        let result = match request {
+            Request::List => {
+                return Response::Sessions {
+                    sessions: self.list(),
+                }
+            }
+            Request::New { name } => self.create(name.as_deref()).map(Session::info),
+            Request::Attach { name } => self.get_mut(&name).map(|session| {
+                session.attached_clients += 1;
+                session.info()
+            }),
+            Request::Detach { name } => self.get_mut(&name).map(|session| {
+                session.attached_clients = session.attached_clients.saturating_sub(1);
+                session.info()
+            }),
+            Request::Rename { from, to } => self.rename(&from, &to).map(Session::info),
+            Request::Input { .. } | Request::Resize { .. } => {
+                Err(anyhow!("Not attached to a session with a shell"))
+            }
+        };
+
+        match result {
+            Ok(session) => Response::Session { session },
+            Err(e) => Response::Error {
+                message: e.to_string(),
+            },
+        }
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(anyhow!("Invalid session name '{}'", name));
+    }
+    Ok(())
+}
+
+/// Socket the daemon listens on
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .or_else(dirs::cache_dir)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("voidcli")
+        .join("daemon.sock")
+}
+
+/// A client's connection to the daemon
+///
+/// Sessions attached through a connection are detached when it closes, so
+/// `attached_clients` stays right when a client exits without detaching.
+/// Once attached to a session with a shell, reading the connection yields
+/// the shell's output.
+pub struct DaemonConnection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl DaemonConnection {
+    pub fn connect(socket: &Path) -> Result<Self> {
+        let writer = UnixStream::connect(socket)
+            .with_context(|| format!("No VoidCLI daemon listening on {}", socket.display()))?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self { reader, writer })
+    }
+
+    /// Send one request and wait for its response
+    pub fn request(&mut self, request: &Request) -> Result<Response> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        let mut reply = String::new();
+        if self.reader.read_line(&mut reply)? == 0 {
+            return Err(anyhow!("The daemon closed the connection"));
+        }
+        serde_json::from_str(&reply).context("Invalid response from daemon")
+    }
+
+    /// Send a request that gets no response, like `Input` once attached
+    pub fn send(&mut self, request: &Request) -> Result<()> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// A second handle for sending on this connection, e.g. input from
+    /// another thread while this one reads output
+    pub fn try_clone(&self) -> Result<Self> {
+        let writer = self.writer.try_clone()?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Self { reader, writer })
+    }
+}
+
+impl Read for DaemonConnection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+/// Send one request to the daemon on a fresh connection
+pub fn send_request(socket: &Path, request: &Request) -> Result<Response> {
+    DaemonConnection::connect(socket)?.request(request)
+}
+
+/// Run the daemon: serve `registry` to clients on `socket` until listening
+/// fails
+///
+/// The socket's directory is created private to the user, and a socket
+/// left behind by a daemon that is no longer running is replaced.
+pub fn serve(socket: &Path, registry: SessionRegistry) -> Result<()> {
+    if let Some(dir) = socket.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(anyhow!("A VoidCLI daemon is already listening on {}", socket.display()));
+        }
+        fs::remove_file(socket).context("Failed to remove stale daemon socket")?;
+    }
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+
+    let registry = Arc::new(Mutex::new(registry));
+    for stream in listener.incoming() {
+        let stream = stream.context("Failed to accept a daemon client")?;
+        let registry = Arc::clone(&registry);
+        std::thread::spawn(move || {
+            if let Err(e) = serve_client(stream, &registry) {
+                warn!("Daemon client failed: {:#}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Answer one client until it disconnects, then detach it from the
+/// sessions it is still attached to
+fn serve_client(stream: UnixStream, registry: &Mutex<SessionRegistry>) -> Result<()> {
+    let mut attached = Vec::new();
+    let result = answer_requests(stream, registry, &mut attached);
+    let mut registry = registry.lock().unwrap();
+    for id in attached {
+        registry.detach_id(id);
+    }
+    result
+}
+
+/// Reply to requests, one JSON line each, tracking the ids of sessions
+/// attached through this connection in `attached`; attaching to a session
+/// with a shell turns the connection into that shell's stream
+fn answer_requests(
+    stream: UnixStream,
+    registry: &Mutex<SessionRegistry>,
+    attached: &mut Vec<usize>,
+) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    while let Some(line) = lines.next() {
+        let mut shell = None;
+        let response = match serde_json::from_str::<Request>(&line?) {
+            Ok(request) => {
+                let mut registry = registry.lock().unwrap();
+                let response = registry.handle(request.clone());
+                if let Response::Session { session } = &response {
+                    match request {
+                        Request::Attach { .. } => {
+                            attached.push(session.id);
+                            shell = registry.shell(session.id);
+                        }
+                        Request::Detach { .. } => {
+                            if let Some(i) = attached.iter().position(|id| *id == session.id) {
+                                attached.remove(i);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                response
+            }
+            Err(e) => Response::Error {
+                message: format!("Invalid request: {}", e),
+            },
+        };
+        let mut reply = serde_json::to_string(&response)?;
+        reply.push('\n');
+        writer.write_all(reply.as_bytes())?;
+        if let Some(shell) = shell {
+            return stream_shell(lines, writer, &shell);
+        }
+    }
+    Ok(())
+}
+
+/// Stream a shell to an attached client: its output goes out raw, and the
+/// client's `Input` and `Resize` requests go to the shell until the client
+/// detaches or disconnects
+fn stream_shell(
+    lines: impl Iterator<Item = io::Result<String>>,
+    mut writer: UnixStream,
+    shell: &SessionShell,
+) -> Result<()> {
+    let (output_tx, output) = mpsc::channel::<Vec<u8>>();
+    shell.send(ShellCommand::Attach(output_tx))?;
+    let connection = writer.try_clone()?;
+    std::thread::spawn(move || {
+        for data in output {
+            if writer.write_all(&data).is_err() {
+                break;
+            }
+        }
+        // The shell exited or the client went away
+        let _ = writer.shutdown(Shutdown::Both);
+    });
+
+    let result = forward_requests(lines, shell);
+    let _ = connection.shutdown(Shutdown::Both);
+    result
+}
+
+/// Pass an attached client's requests to its shell until it detaches
+fn forward_requests(
+    lines: impl Iterator<Item = io::Result<String>>,
+    shell: &SessionShell,
+) -> Result<()> {
+    for line in lines {
+        match serde_json::from_str::<Request>(&line?) {
+            Ok(Request::Input { data }) => shell.send(ShellCommand::Input(data))?,
+            Ok(Request::Resize { cols, rows }) => shell.send(ShellCommand::Resize { cols, rows })?,
+            Ok(Request::Detach { .. }) => break,
+            _ => warn!("Ignoring a request from an attached client"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = SessionRegistry::new();
+        registry.create(Some("work")).unwrap();
+        assert_eq!(registry.create(None).unwrap().name, "1");
+        assert!(registry.create(Some("work")).is_err());
+
+        let response = registry.handle(Request::Attach {
+            name: "work".to_string(),
+        });
+        let Response::Session { session } = response else {
+            panic!("unexpected response");
+        };
+        assert_eq!(session.attached_clients, 1);
+
+        registry.rename("1", "scratch").unwrap();
+        assert!(registry.rename("scratch", "work").is_err());
+        let names: Vec<String> = registry.list().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["work", "scratch"]);
+
+        assert!(matches!(
+            registry.handle(Request::Attach { name: "nope".to_string() }),
+            Response::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_serve() {
+        let dir = std::env::temp_dir().join(format!("voidcli-daemon-{}", std::process::id()));
+        let socket = dir.join("daemon.sock");
+        let _ = fs::remove_dir_all(&dir);
+        let path = socket.clone();
+        std::thread::spawn(move || serve(&path, SessionRegistry::new()));
+        let mut client = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                DaemonConnection::connect(&socket).ok()
+            })
+            .unwrap();
+
+        let new = Request::New {
+            name: Some("work".to_string()),
+        };
+        assert!(matches!(client.request(&new).unwrap(), Response::Session { .. }));
+        let attach = Request::Attach {
+            name: "work".to_string(),
+        };
+        client.request(&attach).unwrap();
+        let Response::Sessions { sessions } = send_request(&socket, &Request::List).unwrap() else {
+            panic!("unexpected response");
+        };
+        assert_eq!(sessions[0].attached_clients, 1);
+
+        // Closing the connection detaches its client
+        drop(client);
+        let detached = (0..100).any(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            matches!(
+                send_request(&socket, &Request::List),
+                Ok(Response::Sessions { sessions }) if sessions[0].attached_clients == 0
+            )
+        });
+        assert!(detached);
+        assert!(serve(&socket, SessionRegistry::new()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_attach() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let _entered = runtime.enter();
+        let mut config = Config::default();
+        config.terminal.shell = "/bin/sh".to_string();
+        config.terminal.shell_integration = false;
+        let registry = SessionRegistry::new().with_shells(&config);
+        std::thread::spawn(move || runtime.block_on(std::future::pending::<()>()));
+
+        let dir = std::env::temp_dir().join(format!("voidcli-attach-{}", std::process::id()));
+        let socket = dir.join("daemon.sock");
+        let _ = fs::remove_dir_all(&dir);
+        let path = socket.clone();
+        std::thread::spawn(move || serve(&path, registry));
+        let mut client = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                DaemonConnection::connect(&socket).ok()
+            })
+            .unwrap();
+        let new = Request::New {
+            name: Some("work".to_string()),
+        };
+        client.request(&new).unwrap();
+        let attach = Request::Attach {
+            name: "work".to_string(),
+        };
+        assert!(matches!(client.request(&attach).unwrap(), Response::Session { .. }));
+
+        // The connection now carries the shell's output, starting with a
+        // repaint of its screen
+        let input = |data: &[u8]| Request::Input {
+            data: data.to_vec(),
+        };
+        client.send(&input(b"echo $((6 * 7))\r")).unwrap();
+        let mut output = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !String::from_utf8_lossy(&output).contains("42") {
+            let n = client.read(&mut buffer).unwrap();
+            assert!(n > 0, "connection closed early");
+            output.extend_from_slice(&buffer[..n]);
+        }
+        // The stream opens with a redraw of the screen
+        assert!(output.starts_with(b"\x1b[0m\x1b[r"));
+
+        // The session ends with its shell, closing the stream
+        client.send(&input(b"exit\r")).unwrap();
+        while client.read(&mut buffer).unwrap() > 0 {}
+        let Response::Sessions { sessions } = send_request(&socket, &Request::List).unwrap() else {
+            panic!("unexpected response");
+        };
+        assert!(sessions.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
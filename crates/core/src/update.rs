@@ -0,0 +1,324 @@
+// Update check and `voidcli self-update` against GitHub releases
+//
+// Downloads go through `curl`, restricted to HTTPS. A release must ship a
+// `SHA256SUMS` file listing the binary; the download is only installed when
+// its hash matches, and replaces the running executable with a rename.
+
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use config::{UpdateChannel, UpdateConfig};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info};
+
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Release asset listing the hashes of the other assets
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+const CHECK_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// A release version like `v1.2.3` or `1.3.0-nightly.20261015`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+}
+
+impl Version {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().trim_start_matches('v');
+        let (core, pre) = match text.split_once('-') {
+            Some((core, pre)) => (core, Some(pre.to_string())),
+            None => (text, None),
+        };
+        let mut numbers = core.split('.').map(|n| n.parse::<u64>());
+        let version = Self {
+            major: numbers.next()?.ok()?,
+            minor: numbers.next()?.ok()?,
+            patch: numbers.next().unwrap_or(Ok(0)).ok()?,
+            pre,
+        };
+        numbers.next().is_none().then_some(version)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            // A pre-release comes before the release itself
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+/// A release as returned by the GitHub API
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    pub fn version(&self) -> Option<Version> {
+        Version::parse(&self.tag_name)
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// Newest release on `channel` that is newer than `current`
+pub fn newest_release<'a>(
+    releases: &'a [Release],
+    channel: UpdateChannel,
+    current: &Version,
+) -> Option<&'a Release> {
+    releases
+        .iter()
+        .filter(|release| !release.draft)
+        .filter(|release| channel == UpdateChannel::Nightly || !release.prerelease)
+        .filter_map(|release| release.version().map(|version| (version, release)))
+        .filter(|(version, _)| version > current)
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+}
+
+/// Name of the release asset for this platform, e.g. `voidcli-x86_64-linux`
+pub fn asset_name() -> String {
+    format!("voidcli-{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+}
+
+/// Hash listed for `name` in a `sha256sum` style file
+pub fn expected_sha256(checksums: &str, name: &str) -> Option<String> {
+    checksums.lines().find_map(|line| {
+        let (hash, file) = line.split_once(char::is_whitespace)?;
+        // `*` marks binary mode in sha256sum output
+        let file = file.trim_start().trim_start_matches('*');
+        (file == name).then(|| hash.to_lowercase())
+    })
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn download(url: &str) -> Result<Vec<u8>> {
+    debug!("Downloading {}", url);
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args(["--proto", "=https", "--max-time", "120"])
+        .args(["--header", "Accept: application/vnd.github+json"])
+        .args(["--user-agent", &format!("voidcli/{}", CURRENT_VERSION)])
+        .arg(url)
+        .output()
+        .context("Failed to run curl")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Download of {} failed: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+pub fn fetch_releases(config: &UpdateConfig) -> Result<Vec<Release>> {
+    let url = format!("https://api.github.com/repos/{}/releases", config.repository);
+    let body = download(&url)?;
+    serde_json::from_slice(&body).context("Invalid release list from GitHub")
+}
+
+/// The newest release on the configured channel, if newer than this build
+pub fn check(config: &UpdateConfig, channel: UpdateChannel) -> Result<Option<Release>> {
+    let current = Version::parse(CURRENT_VERSION)
+        .ok_or_else(|| anyhow!("Invalid version {}", CURRENT_VERSION))?;
+    let releases = fetch_releases(config)?;
+    Ok(newest_release(&releases, channel, &current).cloned())
+}
+
+fn stamp_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("voidcli").join("last-update-check"))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Whether a day has passed since the check recorded in `stamp`
+fn check_due(stamp: &Path, now: u64) -> bool {
+    let last = std::fs::read_to_string(stamp)
+        .ok()
+        .and_then(|text| text.trim().parse::<u64>().ok());
+    match last {
+        Some(last) => now.saturating_sub(last) >= CHECK_INTERVAL_SECS,
+        None => true,
+    }
+}
+
+/// Run the opt-in update check on a background thread, printing a one-line
+/// notice when a newer release exists
+pub fn check_in_background(config: &UpdateConfig) {
+    if !config.check {
+        return;
+    }
+    let Some(stamp) = stamp_path() else {
+        return;
+    };
+    let now = unix_now();
+    if !check_due(&stamp, now) {
+        return;
+    }
+    let config = config.clone();
+    std::thread::spawn(move || {
+        if let Some(dir) = stamp.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = std::fs::write(&stamp, now.to_string());
+        match check(&config, config.channel) {
+            Ok(Some(release)) => eprintln!(
+                "VoidCLI {} is available; run `voidcli self-update` to install it.",
+                release.tag_name
+            ),
+            Ok(None) => debug!("VoidCLI is up to date"),
+            Err(e) => debug!("Update check failed: {:#}", e),
+        }
+    });
+}
+
+/// Download, verify and install the newest release on `channel`, returning
+/// its version, or `None` when already up to date
+pub fn self_update(config: &UpdateConfig, channel: UpdateChannel) -> Result<Option<String>> {
+    let Some(release) = check(config, channel)? else {
+        return Ok(None);
+    };
+    let name = asset_name();
+    let asset = release
+        .asset(&name)
+        .ok_or_else(|| anyhow!("Release {} has no build for {}", release.tag_name, name))?;
+    let checksums = release.asset(CHECKSUMS_ASSET).ok_or_else(|| {
+        anyhow!("Release {} has no {}; not installing", release.tag_name, CHECKSUMS_ASSET)
+    })?;
+    let checksums = String::from_utf8(download(&checksums.browser_download_url)?)
+        .context("Invalid checksum file")?;
+    let expected = expected_sha256(&checksums, &name)
+        .ok_or_else(|| anyhow!("{} does not list {}", CHECKSUMS_ASSET, name))?;
+
+    let binary = download(&asset.browser_download_url)?;
+    let actual = sha256_hex(&binary);
+    if actual != expected {
+        return Err(anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            name,
+            expected,
+            actual
+        ));
+    }
+
+    install(&binary)?;
+    info!("Installed VoidCLI {}", release.tag_name);
+    Ok(Some(release.tag_name))
+}
+
+/// Replace the running executable; the rename is atomic, so a failure
+/// leaves the old binary in place
+fn install(binary: &[u8]) -> Result<()> {
+    let exe = std::env::current_exe().context("Cannot locate the running executable")?;
+    let staged = exe.with_extension("update");
+    std::fs::write(&staged, binary)
+        .with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    std::fs::rename(&staged, &exe).with_context(|| format!("Failed to replace {}", exe.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, prerelease: bool) -> Release {
+        Release {
+            tag_name: tag.to_string(),
+            prerelease,
+            draft: false,
+            assets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_newest_release() {
+        let current = Version::parse("0.1.0").unwrap();
+        let nightly = Version::parse("v0.2.0-nightly.20261015").unwrap();
+        assert!(nightly < Version::parse("0.2.0").unwrap());
+        assert_eq!(Version::parse("1.2"), Version::parse("1.2.0"));
+        assert_eq!(Version::parse("1.x.0"), None);
+
+        let releases = vec![
+            release("v0.1.0", false),
+            release("v0.2.0", false),
+            release("v0.3.0-nightly.20261015", true),
+            release("nightly", true),
+        ];
+        let newest = |channel| newest_release(&releases, channel, &current).map(|r| &r.tag_name);
+        assert_eq!(newest(UpdateChannel::Stable).unwrap(), "v0.2.0");
+        assert_eq!(newest(UpdateChannel::Nightly).unwrap(), "v0.3.0-nightly.20261015");
+        let latest = Version::parse("0.2.0").unwrap();
+        assert!(newest_release(&releases, UpdateChannel::Stable, &latest).is_none());
+    }
+
+    #[test]
+    fn test_expected_sha256() {
+        let sums = "AB12  voidcli-x86_64-linux\ncd34 *voidcli-aarch64-macos\n";
+        assert_eq!(expected_sha256(sums, "voidcli-x86_64-linux").as_deref(), Some("ab12"));
+        assert_eq!(expected_sha256(sums, "voidcli-aarch64-macos").as_deref(), Some("cd34"));
+        assert_eq!(expected_sha256(sums, "voidcli"), None);
+    }
+}
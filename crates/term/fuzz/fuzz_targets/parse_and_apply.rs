@@ -0,0 +1,11 @@
+// Any panic in the parser or VT on arbitrary bytes is a bug; the first two
+// bytes pick the screen size, so degenerate sizes are covered too
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let [cols, rows, rest @ ..] = data {
+        let _ = term::parse_and_apply(rest, *cols as usize, *rows as usize);
+    }
+});
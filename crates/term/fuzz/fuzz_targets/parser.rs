@@ -0,0 +1,13 @@
+// Any panic in the parser on arbitrary bytes is a bug
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use term::TerminalParser;
+
+fuzz_target!(|data: &[u8]| {
+    let mut parser = TerminalParser::new();
+    // Split the input so sequences straddle reads, as they do from a PTY
+    let (first, second) = data.split_at(data.len() / 2);
+    let _ = parser.parse(first);
+    let _ = parser.parse(second);
+});
@@ -0,0 +1,21 @@
+# VoidCLI shell integration for bash
+# Emits OSC 133 prompt/command marks and OSC 7 working directory reports.
+
+if [[ -z "$VOIDCLI_SHELL_INTEGRATION_LOADED" ]]; then
+    VOIDCLI_SHELL_INTEGRATION_LOADED=1
+    __voidcli_first_prompt=1
+
+    __voidcli_precmd() {
+        local ret=$?
+        if [[ -z "$__voidcli_first_prompt" ]]; then
+            printf '\e]133;D;%s\a' "$ret"
+        fi
+        __voidcli_first_prompt=
+        printf '\e]7;file://%s%s\a' "$HOSTNAME" "$PWD"
+        printf '\e]133;A\a'
+    }
+
+    PROMPT_COMMAND="__voidcli_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+    PS1="$PS1"'\[\e]133;B\a\]'
+    PS0="$PS0"$'\e]133;C\a'
+fi
@@ -0,0 +1,21 @@
+# VoidCLI shell integration for fish
+# Emits OSC 133 prompt/command marks and OSC 7 working directory reports.
+
+if not set -q VOIDCLI_SHELL_INTEGRATION_LOADED
+    set -g VOIDCLI_SHELL_INTEGRATION_LOADED 1
+
+    function __voidcli_prompt --on-event fish_prompt
+        set -l ret $status
+        if set -q __voidcli_cmd_running
+            printf '\e]133;D;%s\a' $ret
+            set -e __voidcli_cmd_running
+        end
+        printf '\e]7;file://%s%s\a' (hostname) $PWD
+        printf '\e]133;A\a'
+    end
+
+    function __voidcli_preexec --on-event fish_preexec
+        set -g __voidcli_cmd_running 1
+        printf '\e]133;C\a'
+    end
+end
@@ -0,0 +1,268 @@
+use std::fmt::{self, Write as _};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::headless::HeadlessTerminal;
+use crate::parser::TerminalParser;
+use crate::vt::VirtualTerminal;
+
+/// Bytes handed to the parser at once, like a PTY read
+const CHUNK: usize = 4096;
+
+/// Output between two frames at 60 Hz on a fast program
+const BYTES_PER_FRAME: usize = 64 * 1024;
+
+/// Synthetic output the benchmark replays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Workload {
+    /// Full-screen redraws on the alternate screen, like a busy TUI
+    TuiChurn,
+    /// Plain lines scrolling past, like `cat` of a large log
+    MassiveScroll,
+    /// Short runs of text, each with its own 256-color or RGB attributes
+    SgrHeavy,
+    /// Lines scrolling between a fixed header and status line, like a
+    /// pager or chat client
+    RegionScroll,
+}
+
+impl Workload {
+    pub const ALL: [Workload; 4] = [
+        Workload::TuiChurn,
+        Workload::MassiveScroll,
+        Workload::SgrHeavy,
+        Workload::RegionScroll,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Workload::TuiChurn => "tui-churn",
+            Workload::MassiveScroll => "massive-scroll",
+            Workload::SgrHeavy => "sgr-heavy",
+            Workload::RegionScroll => "region-scroll",
+        }
+    }
+
+    /// Deterministic output of at least `size` bytes for a `cols`x`rows` screen
+    pub fn generate(self, cols: usize, rows: usize, size: usize) -> Vec<u8> {
+        let mut out = String::with_capacity(size + 1024);
+        let mut n = 0usize;
+        match self {
+            Workload::TuiChurn => {
+                out.push_str("\x1b[?1049h\x1b[?25l");
+                while out.len() < size {
+                    out.push_str("\x1b[H");
+                    for row in 1..=rows {
+                        let color = (n + row) % 8;
+                        let _ = write!(out, "\x1b[{};1H\x1b[3{}m", row, color);
+                        let text =
+                            format!("{:>6} | proc {:<5} cpu {:>3}% ", n, row * 7, (n * row) % 100);
+                        out.extend(text.chars().cycle().take(cols));
+                    }
+                    let _ = write!(out, "\x1b[{};1H\x1b[7m status {} \x1b[0m", rows, n);
+                    n += 1;
+                }
+                out.push_str("\x1b[?25h\x1b[?1049l");
+            }
+            Workload::MassiveScroll => {
+                while out.len() < size {
+                    let _ = write!(
+                        out,
+                        "{:>8} INFO request handled path=/api/v1/items/{} ms={}\r\n",
+                        n,
+                        n % 997,
+                        n % 250
+                    );
+                    n += 1;
+                }
+            }
+            Workload::SgrHeavy => {
+                while out.len() < size {
+                    let _ = match n % 3 {
+                        0 => write!(out, "\x1b[38;5;{}mword{} ", n % 256, n % 10),
+                        1 => write!(
+                            out,
+                            "\x1b[1;38;2;{};{};{}mword{} ",
+                            n % 256,
+                            (n * 7) % 256,
+                            (n * 13) % 256,
+                            n % 10
+                        ),
+                        _ => write!(out, "\x1b[0;4;48;5;{}mword{}\x1b[0m ", n % 256, n % 10),
+                    };
+                    if n % 12 == 11 {
+                        out.push_str("\r\n");
+                    }
+                    n += 1;
+                }
+            }
+            Workload::RegionScroll => {
+                let bottom = rows.max(3) - 1;
+                let _ = write!(out, "\x1b[H\x1b[7m header \x1b[0m\x1b[2;{}r", bottom);
+                while out.len() < size {
+                    if n.is_multiple_of(50) {
+                        let _ = write!(
+                            out,
+                            "\x1b[{};1H\x1b[7m line {} \x1b[0m\x1b[{};1H",
+                            bottom + 1,
+                            n,
+                            bottom
+                        );
+                    }
+                    let _ = write!(out, "<user{}> message number {} in the log\r\n", n % 17, n);
+                    n += 1;
+                }
+                out.push_str("\x1b[r");
+            }
+        }
+        out.into_bytes()
+    }
+}
+
+/// Timings of one workload
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub workload: Workload,
+    pub bytes: usize,
+    pub actions: usize,
+    pub lines: usize,
+    pub parse: Duration,
+    pub apply: Duration,
+    pub frames: usize,
+    /// Total time spent taking a screen snapshot per frame
+    pub frame: Duration,
+}
+
+impl BenchResult {
+    pub fn parse_mb_per_sec(&self) -> f64 {
+        per_sec(self.bytes as f64 / (1024.0 * 1024.0), self.parse)
+    }
+
+    pub fn actions_per_sec(&self) -> f64 {
+        per_sec(self.actions as f64, self.apply)
+    }
+
+    pub fn lines_per_sec(&self) -> f64 {
+        per_sec(self.lines as f64, self.parse + self.apply)
+    }
+
+    pub fn mean_frame(&self) -> Duration {
+        self.frame / self.frames.max(1) as u32
+    }
+}
+
+fn per_sec(amount: f64, elapsed: Duration) -> f64 {
+    amount / elapsed.as_secs_f64().max(f64::EPSILON)
+}
+
+/// Run `workload` of about `size` bytes through the parser and VT
+pub fn run(workload: Workload, cols: usize, rows: usize, size: usize) -> Result<BenchResult> {
+    let data = workload.generate(cols, rows, size);
+    let mut parser = TerminalParser::new();
+    let mut vt = VirtualTerminal::new(cols, rows);
+    let mut screen = HeadlessTerminal::new(cols, rows);
+    let mut result = BenchResult {
+        workload,
+        bytes: data.len(),
+        actions: 0,
+        lines: data.iter().filter(|byte| **byte == b'\n').count(),
+        parse: Duration::ZERO,
+        apply: Duration::ZERO,
+        frames: 0,
+        frame: Duration::ZERO,
+    };
+
+    let mut actions = Vec::new();
+    for chunk in data.chunks(CHUNK) {
+        actions.clear();
+        let started = Instant::now();
+        parser.parse_into(chunk, &mut actions)?;
+        result.parse += started.elapsed();
+
+        let started = Instant::now();
+        for action in &actions {
+            vt.process_action(action)?;
+        }
+        result.apply += started.elapsed();
+        result.actions += actions.len();
+    }
+
+    // Frame preparation: feed a frame's worth of output, then read the grid
+    for frame in data.chunks(BYTES_PER_FRAME) {
+        screen.feed(frame)?;
+        let started = Instant::now();
+        std::hint::black_box(screen.snapshot());
+        result.frame += started.elapsed();
+        result.frames += 1;
+    }
+    Ok(result)
+}
+
+/// Results of every workload on the same screen size
+pub struct BenchReport {
+    pub cols: usize,
+    pub rows: usize,
+    pub results: Vec<BenchResult>,
+}
+
+/// Run every workload with about `size` bytes each
+pub fn run_all(cols: usize, rows: usize, size: usize) -> Result<BenchReport> {
+    let results = Workload::ALL
+        .iter()
+        .map(|workload| run(*workload, cols, rows, size))
+        .collect::<Result<_>>()?;
+    Ok(BenchReport { cols, rows, results })
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "voidcli {} bench, {}x{}",
+            env!("CARGO_PKG_VERSION"),
+            self.cols,
+            self.rows
+        )?;
+        writeln!(
+            f,
+            "{:<16} {:>8} {:>12} {:>14} {:>12} {:>10}",
+            "workload", "MB", "parse MB/s", "apply act/s", "lines/s", "frame us"
+        )?;
+        for result in &self.results {
+            writeln!(
+                f,
+                "{:<16} {:>8.1} {:>12.1} {:>14.0} {:>12.0} {:>10.1}",
+                result.workload.name(),
+                result.bytes as f64 / (1024.0 * 1024.0),
+                result.parse_mb_per_sec(),
+                result.actions_per_sec(),
+                result.lines_per_sec(),
+                result.mean_frame().as_secs_f64() * 1_000_000.0,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workloads_are_deterministic() {
+        for workload in Workload::ALL {
+            let data = workload.generate(80, 24, 10_000);
+            assert!(data.len() >= 10_000);
+            assert_eq!(data, workload.generate(80, 24, 10_000));
+        }
+    }
+
+    #[test]
+    fn test_run_counts() {
+        let result = run(Workload::MassiveScroll, 80, 24, 100_000).unwrap();
+        assert!(result.actions > 0);
+        assert!(result.lines > 1000);
+        assert_eq!(result.frames, 2);
+    }
+}
@@ -0,0 +1,271 @@
+// Bidirectional text (UAX #9) for display of a single terminal row
+//
+// Implements the implicit part of the algorithm, following the "BiDi in
+// terminal emulators" recommendations: no embeddings or isolates, each row
+// is its own paragraph with its direction taken from the first strong
+// character, and trailing whitespace stays at the paragraph level.
+
+/// Bidi character classes used by the implicit rules, named as in UAX #9
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    L,
+    R,
+    AL,
+    EN,
+    ES,
+    ET,
+    AN,
+    CS,
+    NSM,
+    WS,
+    ON,
+}
+
+fn class(c: char) -> Class {
+    match c as u32 {
+        0x30..=0x39 => Class::EN,
+        0x660..=0x669 | 0x66B | 0x66C | 0x6F0..=0x6F9 => Class::AN,
+        0x300..=0x36F
+        | 0x591..=0x5BD
+        | 0x5BF
+        | 0x5C1
+        | 0x5C2
+        | 0x5C4
+        | 0x5C5
+        | 0x5C7
+        | 0x610..=0x61A
+        | 0x64B..=0x65F
+        | 0x670
+        | 0x6D6..=0x6DC
+        | 0x6DF..=0x6E4
+        | 0x6E7
+        | 0x6E8
+        | 0x6EA..=0x6ED => Class::NSM,
+        0x590..=0x5FF | 0x7C0..=0x85F | 0xFB1D..=0xFB4F => Class::R,
+        0x600..=0x7BF | 0x860..=0x8FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Class::AL,
+        _ if c.is_whitespace() => Class::WS,
+        _ => match c {
+            '+' | '-' => Class::ES,
+            '#' | '$' | '%' | '°' | '€' | '£' | '¥' => Class::ET,
+            ',' | '.' | ':' | '/' => Class::CS,
+            _ if c.is_alphanumeric() => Class::L,
+            _ => Class::ON,
+        },
+    }
+}
+
+/// Whether any character on the row may need reordering
+fn has_rtl(line: &[char]) -> bool {
+    line.iter()
+        .any(|c| matches!(class(*c), Class::R | Class::AL | Class::AN))
+}
+
+/// Mirrored glyph for characters drawn in a right-to-left run
+fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        _ => c,
+    }
+}
+
+/// Resolved embedding level of each character
+fn levels(line: &[char]) -> Vec<u8> {
+    let mut classes: Vec<Class> = line.iter().map(|c| class(*c)).collect();
+
+    // P2/P3: paragraph direction from the first strong character
+    let base: u8 = match classes
+        .iter()
+        .find(|class| matches!(class, Class::L | Class::R | Class::AL))
+    {
+        Some(Class::R | Class::AL) => 1,
+        _ => 0,
+    };
+    let sor = if base == 1 { Class::R } else { Class::L };
+
+    // W1: marks take the class of what they attach to
+    let mut previous = sor;
+    for class in classes.iter_mut() {
+        if *class == Class::NSM {
+            *class = previous;
+        }
+        previous = *class;
+    }
+
+    // W2/W3: numbers after Arabic letters are Arabic numbers
+    let mut strong = sor;
+    for class in classes.iter_mut() {
+        match *class {
+            Class::L | Class::R => strong = *class,
+            Class::AL => {
+                strong = Class::AL;
+                *class = Class::R;
+            }
+            Class::EN if strong == Class::AL => *class = Class::AN,
+            _ => {}
+        }
+    }
+
+    // W4: a single separator between two numbers of the same kind
+    for i in 1..classes.len().saturating_sub(1) {
+        let (before, after) = (classes[i - 1], classes[i + 1]);
+        classes[i] = match (before, classes[i], after) {
+            (Class::EN, Class::ES | Class::CS, Class::EN) => Class::EN,
+            (Class::AN, Class::CS, Class::AN) => Class::AN,
+            (_, class, _) => class,
+        };
+    }
+
+    // W5: terminators next to European numbers join them
+    let mut i = 0;
+    while i < classes.len() {
+        if classes[i] != Class::ET {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < classes.len() && classes[i] == Class::ET {
+            i += 1;
+        }
+        let touches_number = (start > 0 && classes[start - 1] == Class::EN)
+            || classes.get(i) == Some(&Class::EN);
+        if touches_number {
+            classes[start..i].fill(Class::EN);
+        }
+    }
+
+    // W6/W7: leftover separators are neutral; numbers in left-to-right text
+    // are left-to-right
+    let mut strong = sor;
+    for class in classes.iter_mut() {
+        match *class {
+            Class::ES | Class::ET | Class::CS => *class = Class::ON,
+            Class::L | Class::R => strong = *class,
+            Class::EN if strong == Class::L => *class = Class::L,
+            _ => {}
+        }
+    }
+
+    // N1/N2: neutrals between text of one direction take that direction,
+    // otherwise the paragraph direction
+    let direction = |class: Class| match class {
+        Class::L => Some(Class::L),
+        Class::R | Class::EN | Class::AN => Some(Class::R),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < classes.len() {
+        if direction(classes[i]).is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < classes.len() && direction(classes[i]).is_none() {
+            i += 1;
+        }
+        let before = match start {
+            0 => sor,
+            _ => direction(classes[start - 1]).unwrap_or(sor),
+        };
+        let after = classes.get(i).and_then(|class| direction(*class)).unwrap_or(sor);
+        let resolved = if before == after { before } else { sor };
+        classes[start..i].fill(resolved);
+    }
+
+    // I1/I2
+    let mut levels: Vec<u8> = classes
+        .iter()
+        .map(|class| match (base, class) {
+            (0, Class::R) => 1,
+            (0, Class::AN | Class::EN) => 2,
+            (1, Class::L | Class::EN | Class::AN) => 2,
+            _ => base,
+        })
+        .collect();
+
+    // L1: trailing whitespace goes back to the paragraph level
+    for (level, c) in levels.iter_mut().zip(line).rev() {
+        if !c.is_whitespace() {
+            break;
+        }
+        *level = base;
+    }
+    levels
+}
+
+/// Display order of a row: for each visual column, the logical column it
+/// shows and the character to draw there (mirrored inside right-to-left
+/// runs). Rows without right-to-left text come back unchanged.
+pub fn visual_order(line: &[char]) -> Vec<(usize, char)> {
+    if !has_rtl(line) {
+        return line.iter().copied().enumerate().collect();
+    }
+
+    let levels = levels(line);
+    let mut order: Vec<usize> = (0..line.len()).collect();
+
+    // L2: reverse every run at or above each odd level, highest first
+    let highest = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd = levels
+        .iter()
+        .copied()
+        .filter(|level| level % 2 == 1)
+        .min()
+        .unwrap_or(highest + 1);
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+
+    order
+        .into_iter()
+        .map(|index| {
+            let c = line[index];
+            // L4
+            let c = if levels[index] % 2 == 1 { mirror(c) } else { c };
+            (index, c)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(text: &str) -> String {
+        let line: Vec<char> = text.chars().collect();
+        visual_order(&line).into_iter().map(|(_, c)| c).collect()
+    }
+
+    #[test]
+    fn test_left_to_right_untouched() {
+        assert_eq!(display("ls -la (1.5 MB)  "), "ls -la (1.5 MB)  ");
+    }
+
+    #[test]
+    fn test_hebrew_and_numbers() {
+        // "shalom" in a left-to-right row, and a right-to-left row with a
+        // number and brackets
+        assert_eq!(display("say שלום now"), "say םולש now");
+        assert_eq!(display("אב (12) ג  "), "  ג (12) בא");
+    }
+}
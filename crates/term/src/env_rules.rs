@@ -0,0 +1,114 @@
+use std::path::Path;
+
+use config::pattern::{expand_home, glob_match};
+use config::EnvRule;
+
+/// What a spawn is matched against
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnContext<'a> {
+    pub profile: Option<&'a str>,
+    pub cwd: &'a str,
+    pub command: &'a str,
+}
+
+fn matches(rule: &EnvRule, context: &SpawnContext, home: Option<&Path>) -> bool {
+    let profile = match &rule.profile {
+        Some(profile) => context.profile == Some(profile.as_str()),
+        None => true,
+    };
+    let cwd = match &rule.cwd {
+        Some(pattern) => glob_match(&expand_home(pattern, home), context.cwd),
+        None => true,
+    };
+    let command = match &rule.command {
+        Some(prefix) => context.command.trim_start().starts_with(prefix.as_str()),
+        None => true,
+    };
+    profile && cwd && command
+}
+
+/// Environment contributed by the matching rules, in rule order
+///
+/// `PATH` entries are combined with `base_path` into a single `PATH` value.
+pub fn resolve_env(
+    rules: &[EnvRule],
+    context: &SpawnContext,
+    home: Option<&Path>,
+    base_path: Option<&str>,
+) -> Vec<(String, String)> {
+    let mut env: Vec<(String, String)> = Vec::new();
+    let mut prepend: Vec<String> = Vec::new();
+    let mut append: Vec<String> = Vec::new();
+
+    for rule in rules.iter().filter(|rule| matches(rule, context, home)) {
+        for (key, value) in &rule.env {
+            env.retain(|(k, _)| k != key);
+            env.push((key.clone(), value.clone()));
+        }
+        prepend.extend(rule.path_prepend.iter().map(|dir| expand_home(dir, home)));
+        append.extend(rule.path_append.iter().map(|dir| expand_home(dir, home)));
+    }
+
+    if !prepend.is_empty() || !append.is_empty() {
+        // A rule's own PATH value replaces the inherited one
+        let base = env
+            .iter()
+            .position(|(key, _)| key == "PATH")
+            .map(|index| env.remove(index).1)
+            .or_else(|| base_path.map(str::to_string));
+        let path: Vec<String> = prepend
+            .into_iter()
+            .chain(base.filter(|base| !base.is_empty()))
+            .chain(append)
+            .collect();
+        env.push(("PATH".to_string(), path.join(":")));
+    }
+
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(cwd: Option<&str>, command: Option<&str>, env: &[(&str, &str)]) -> EnvRule {
+        EnvRule {
+            cwd: cwd.map(str::to_string),
+            command: command.map(str::to_string),
+            env: env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..EnvRule::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_env() {
+        let home = Path::new("/home/me");
+        let mut tools = rule(None, Some("bash"), &[]);
+        tools.path_prepend = vec!["~/work/bin".to_string()];
+        let rules = vec![
+            rule(Some("~/work/*"), None, &[("AWS_PROFILE", "work")]),
+            rule(Some("~/play/*"), None, &[("AWS_PROFILE", "play")]),
+            tools,
+        ];
+
+        let context = SpawnContext {
+            profile: None,
+            cwd: "/home/me/work/api",
+            command: "bash -l",
+        };
+        assert_eq!(
+            resolve_env(&rules, &context, Some(home), Some("/usr/bin")),
+            vec![
+                ("AWS_PROFILE".to_string(), "work".to_string()),
+                ("PATH".to_string(), "/home/me/work/bin:/usr/bin".to_string()),
+            ]
+        );
+
+        let context = SpawnContext {
+            profile: None,
+            cwd: "/home/me/work",
+            command: "zsh",
+        };
+        assert!(resolve_env(&rules, &context, Some(home), None).is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+// Screen grid storage
+// Every cell of the screen lives in one buffer, row after row. Screen rows
+// map onto buffer rows through a ring, so scrolling the whole screen moves
+// the ring's start instead of copying cells.
+
+use std::ops::{Index, IndexMut};
+
+use crate::vt::{CellWidth, LineAttributes, TerminalCell};
+
+/// The cells and line attributes of one screen
+#[derive(Debug, Clone)]
+pub struct Grid {
+    cells: Vec<TerminalCell>,
+    /// Attributes of each buffer row
+    attrs: Vec<LineAttributes>,
+    cols: usize,
+    rows: usize,
+    /// Buffer row holding screen row 0
+    start: usize,
+}
+
+impl Grid {
+    /// A blank grid, at least 1x1
+    pub fn new(cols: usize, rows: usize) -> Self {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        Self {
+            cells: vec![TerminalCell::default(); cols * rows],
+            attrs: vec![LineAttributes::default(); rows],
+            cols,
+            rows,
+            start: 0,
+        }
+    }
+
+    fn buffer_row(&self, row: usize) -> usize {
+        debug_assert!(row < self.rows, "row {} out of {}", row, self.rows);
+        (self.start + row) % self.rows
+    }
+
+    /// Attributes of a screen row
+    pub fn attrs(&self, row: usize) -> &LineAttributes {
+        &self.attrs[self.buffer_row(row)]
+    }
+
+    pub fn attrs_mut(&mut self, row: usize) -> &mut LineAttributes {
+        let row = self.buffer_row(row);
+        &mut self.attrs[row]
+    }
+
+    /// Set every cell to `cell` and reset the line attributes
+    pub fn fill(&mut self, cell: &TerminalCell) {
+        self.cells.fill(cell.clone());
+        self.attrs.fill(LineAttributes::default());
+    }
+
+    /// Rotate the rows `top..=bottom` up by `n`, like `slice::rotate_left`:
+    /// the first `n` end up at the bottom, cells and attributes unchanged
+    pub fn rotate_up(&mut self, top: usize, bottom: usize, n: usize) {
+        let len = bottom + 1 - top;
+        let n = n % len;
+        if n == 0 {
+            return;
+        }
+        // Moving the ring start the short way round shifts every row; the
+        // rows outside the region and those wrapping round the region are
+        // then swapped into place
+        let (shift, wrapped) = if n <= len - n {
+            (n, n)
+        } else {
+            (self.rows - (len - n), len - n)
+        };
+        if self.rows - len + wrapped < len {
+            self.start = (self.start + shift) % self.rows;
+            self.place_after_shift(top, bottom, n, shift);
+            return;
+        }
+        // Three reversals move each row once per reversal, without cloning
+        self.reverse_rows(top, top + n);
+        self.reverse_rows(top + n, bottom + 1);
+        self.reverse_rows(top, bottom + 1);
+    }
+
+    /// After moving the ring start by `shift` rows, swap rows so that only
+    /// `top..=bottom` ends up rotated up by `n`
+    fn place_after_shift(&mut self, top: usize, bottom: usize, n: usize, shift: usize) {
+        let (rows, len) = (self.rows, bottom + 1 - top);
+        // Where the row at screen row `p` belongs: it was at `p + shift`
+        let target = move |p: usize| {
+            let q = (p + shift) % rows;
+            if (top..=bottom).contains(&q) {
+                top + (q - top + len - n) % len
+            } else {
+                q
+            }
+        };
+        for p in 0..rows {
+            // Follow each cycle of misplaced rows once, from its first row
+            let mut t = target(p);
+            while t > p {
+                t = target(t);
+            }
+            if t < p {
+                continue;
+            }
+            let mut t = target(p);
+            while t != p {
+                self.swap_rows(p, t);
+                t = target(t);
+            }
+        }
+    }
+
+    /// Rotate the rows `top..=bottom` down by `n`, like `slice::rotate_right`
+    pub fn rotate_down(&mut self, top: usize, bottom: usize, n: usize) {
+        let len = bottom + 1 - top;
+        self.rotate_up(top, bottom, len - n % len);
+    }
+
+    fn reverse_rows(&mut self, start: usize, end: usize) {
+        let (mut a, mut b) = (start, end);
+        while a + 1 < b {
+            b -= 1;
+            self.swap_rows(a, b);
+            a += 1;
+        }
+    }
+
+    fn swap_rows(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.buffer_row(a), self.buffer_row(b));
+        if a == b {
+            return;
+        }
+        let (low, high) = (a.min(b) * self.cols, a.max(b) * self.cols);
+        let (before, after) = self.cells.split_at_mut(high);
+        before[low..low + self.cols].swap_with_slice(&mut after[..self.cols]);
+        self.attrs.swap(a, b);
+    }
+
+    /// Resize in place, keeping the top-left cells
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        if (cols, rows) == (self.cols, self.rows) {
+            return;
+        }
+        let mut resized = Grid::new(cols, rows);
+        for row in 0..rows.min(self.rows) {
+            let kept = cols.min(self.cols);
+            resized[row][..kept].clone_from_slice(&self[row][..kept]);
+            // A wide character cut in half by a narrower width
+            if resized[row][cols - 1].width == CellWidth::Wide {
+                resized[row][cols - 1] = TerminalCell::default();
+            }
+            *resized.attrs_mut(row) = *self.attrs(row);
+        }
+        *self = resized;
+    }
+}
+
+/// The cells of a screen row
+impl Index<usize> for Grid {
+    type Output = [TerminalCell];
+
+    fn index(&self, row: usize) -> &[TerminalCell] {
+        let start = self.buffer_row(row) * self.cols;
+        &self.cells[start..start + self.cols]
+    }
+}
+
+impl IndexMut<usize> for Grid {
+    fn index_mut(&mut self, row: usize) -> &mut [TerminalCell] {
+        let start = self.buffer_row(row) * self.cols;
+        &mut self.cells[start..start + self.cols]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(grid: &mut Grid) {
+        for row in 0..grid.rows {
+            grid[row][0].character = char::from(b'a' + row as u8);
+        }
+    }
+
+    fn column(grid: &Grid) -> String {
+        (0..grid.rows).map(|row| grid[row][0].character).collect()
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut grid = Grid::new(3, 5);
+        mark(&mut grid);
+        grid.attrs_mut(0).wrapped = true;
+        grid.rotate_up(0, 4, 2);
+        assert_eq!(column(&grid), "cdeab");
+        assert!(grid.attrs(3).wrapped);
+        grid.rotate_down(1, 3, 1);
+        assert_eq!(column(&grid), "cadeb");
+        assert!(grid.attrs(1).wrapped);
+
+        // Every region and distance, against rotating a slice
+        for rows in 1..=7 {
+            for top in 0..rows {
+                for bottom in top..rows {
+                    for n in 0..=bottom + 1 - top {
+                        let mut grid = Grid::new(1, rows);
+                        mark(&mut grid);
+                        let mut expected: Vec<char> = column(&grid).chars().collect();
+                        grid.rotate_up(0, rows - 1, top);
+                        grid.rotate_down(0, rows - 1, top);
+                        grid.rotate_up(top, bottom, n);
+                        expected[top..=bottom].rotate_left(n);
+                        assert_eq!(column(&grid), String::from_iter(expected));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut grid = Grid::new(4, 3);
+        mark(&mut grid);
+        grid.rotate_up(0, 2, 1);
+        grid[0][3].width = CellWidth::Wide;
+
+        grid.resize(4, 2);
+        assert_eq!(column(&grid), "bc");
+        grid.resize(2, 4);
+        assert_eq!(column(&grid), "bc  ");
+        assert_eq!(grid[0].len(), 2);
+    }
+}
@@ -0,0 +1,523 @@
+use anyhow::Result;
+use tracing::trace_span;
+
+use crate::hooks::{ExtensionHooks, ExtensionSequence};
+use crate::inspector::Inspector;
+use crate::keyboard::KeyboardModes;
+use crate::metrics;
+use crate::mouse::{MouseEvent, MouseModes};
+use crate::parser::{TerminalAction, TerminalParser};
+use crate::prompts::PromptMarks;
+use crate::vt::{
+    CellAttributes, CellWidth, ClipboardRequest, ImagePlacement, LineAttributes, LineSize,
+    TerminalCell, VirtualTerminal,
+};
+
+/// Parser and virtual terminal without a window or GPU device
+///
+/// Used by `--headless`, integration tests and the remote host, so they all
+/// go through the same escape handling as the renderer.
+pub struct HeadlessTerminal {
+    parser: TerminalParser,
+    vt: VirtualTerminal,
+    bells: usize,
+    /// A bell rang since the last `take_bell`
+    bell_pending: bool,
+    inspector: Option<Inspector>,
+    /// Action buffer reused between feeds
+    actions: Vec<TerminalAction>,
+}
+
+/// Visible state of a headless terminal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenSnapshot {
+    pub cols: usize,
+    pub rows: usize,
+    /// Row and column of the cursor
+    pub cursor: (usize, usize),
+    pub title: String,
+    /// Each row's text with trailing blanks removed
+    pub lines: Vec<String>,
+}
+
+impl ScreenSnapshot {
+    /// Rows joined with newlines, without trailing empty rows
+    pub fn text(&self) -> String {
+        let used = self
+            .lines
+            .iter()
+            .rposition(|line| !line.is_empty())
+            .map_or(0, |last| last + 1);
+        let mut text = self.lines[..used].join("\n");
+        if used > 0 {
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl HeadlessTerminal {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        Self {
+            parser: TerminalParser::new(),
+            vt: VirtualTerminal::new(cols, rows),
+            bells: 0,
+            bell_pending: false,
+            inspector: None,
+            actions: Vec::new(),
+        }
+    }
+
+    /// Parse program output and apply it to the screen
+    pub fn feed(&mut self, data: &[u8]) -> Result<()> {
+        let mut actions = std::mem::take(&mut self.actions);
+        self.parser.parse_into(data, &mut actions)?;
+        let parsed = actions.len();
+        if let Some(inspector) = &mut self.inspector {
+            actions = inspector.record(data, actions, self.parser.take_unhandled());
+        }
+        {
+            let _span = trace_span!("apply", actions = actions.len()).entered();
+            self.apply(&actions)?;
+        }
+        metrics::global().record_parse(data.len(), parsed);
+        actions.clear();
+        self.actions = actions;
+        Ok(())
+    }
+
+    fn apply(&mut self, actions: &[TerminalAction]) -> Result<()> {
+        for action in actions {
+            self.vt.process_action(action)?;
+        }
+        if self.vt.take_bell() {
+            self.bells += 1;
+            self.bell_pending = true;
+        }
+        Ok(())
+    }
+
+    /// Start or stop inspecting the decoded stream; stopping applies any
+    /// actions held while paused
+    pub fn set_inspecting(&mut self, inspecting: bool) -> Result<()> {
+        self.parser.set_record_unhandled(inspecting);
+        match (inspecting, self.inspector.take()) {
+            (true, inspector) => self.inspector = Some(inspector.unwrap_or_default()),
+            (false, Some(mut inspector)) => self.apply(&inspector.resume())?,
+            (false, None) => {}
+        }
+        Ok(())
+    }
+
+    pub fn inspector(&self) -> Option<&Inspector> {
+        self.inspector.as_ref()
+    }
+
+    /// Pause the inspected stream, or resume it and apply what was held
+    pub fn toggle_inspector_pause(&mut self) -> Result<()> {
+        let Some(inspector) = &mut self.inspector else {
+            return Ok(());
+        };
+        if inspector.is_paused() {
+            let held = inspector.resume();
+            self.apply(&held)?;
+        } else {
+            inspector.pause();
+        }
+        Ok(())
+    }
+
+    /// Apply the next action held while paused; false if there is none
+    pub fn inspector_step(&mut self) -> Result<bool> {
+        match self.inspector.as_mut().and_then(Inspector::step) {
+            Some(action) => {
+                self.apply(std::slice::from_ref(&action))?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Treat all output as UTF-8, or interpret 8-bit C1 controls when false
+    pub fn with_utf8_only(mut self, utf8_only: bool) -> Self {
+        self.parser.set_utf8_only(utf8_only);
+        self
+    }
+
+    /// Keep up to `lines` rows scrolled off the top of the screen
+    pub fn with_scrollback(mut self, lines: usize) -> Self {
+        self.vt.set_scrollback_limit(lines);
+        self
+    }
+
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        self.vt.resize(cols, rows);
+    }
+
+    /// Size of a cell in pixels, which sixel images are laid out by
+    pub fn set_cell_pixel_size(&mut self, width: usize, height: usize) {
+        self.vt.set_cell_pixel_size(width, height);
+    }
+
+    /// Rows the view is scrolled back; 0 shows the live screen
+    pub fn display_offset(&self) -> usize {
+        self.vt.display_offset()
+    }
+
+    /// Scroll the view back into the scrollback (positive) or towards the
+    /// live screen (negative)
+    pub fn scroll_display(&mut self, lines: isize) {
+        self.vt.scroll_display(lines);
+    }
+
+    pub fn scroll_display_to_bottom(&mut self) {
+        self.vt.scroll_display_to_bottom();
+    }
+
+    /// Text of the rows the view shows, trailing blanks removed
+    pub fn display_lines(&self) -> Vec<String> {
+        (0..self.vt.rows)
+            .map(|row| {
+                line_text((0..self.vt.cols).filter_map(|col| self.vt.display_cell(row, col)))
+            })
+            .collect()
+    }
+
+    /// Text of the scrollback rows, oldest first, trailing blanks removed
+    pub fn scrollback_lines(&self) -> Vec<String> {
+        (0..self.vt.scrollback_len())
+            .filter_map(|index| self.vt.scrollback_line(index))
+            .map(|line| line_text(line.cells.iter()))
+            .collect()
+    }
+
+    /// Bidi reordering chosen for this session, if it overrides the config
+    pub fn bidi(&self) -> Option<bool> {
+        self.vt.bidi()
+    }
+
+    pub fn set_bidi(&mut self, bidi: Option<bool>) {
+        self.vt.set_bidi(bidi);
+    }
+
+    /// The window title, if it changed since the last call
+    pub fn take_title_change(&mut self) -> Option<String> {
+        self.vt.take_title_change()
+    }
+
+    /// Feeds that rang the bell
+    pub fn bells(&self) -> usize {
+        self.bells
+    }
+
+    /// Returns whether a bell rang since the last call, clearing it
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::replace(&mut self.bell_pending, false)
+    }
+
+    /// Suppress or re-enable the bell for this session
+    pub fn set_bell_suppressed(&mut self, suppressed: bool) {
+        self.vt.set_bell_suppressed(suppressed);
+        if suppressed {
+            self.bell_pending = false;
+        }
+    }
+
+    pub fn prompt_marks(&self) -> &PromptMarks {
+        self.vt.prompt_marks()
+    }
+
+    pub fn lines_scrolled(&self) -> usize {
+        self.vt.lines_scrolled()
+    }
+
+    pub fn bracketed_paste(&self) -> bool {
+        self.vt.bracketed_paste()
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.vt.cursor_visible()
+    }
+
+    pub fn cursor_blink(&self) -> Option<bool> {
+        self.vt.cursor_blink()
+    }
+
+    pub fn keyboard_modes(&self) -> KeyboardModes {
+        self.vt.keyboard_modes()
+    }
+
+    pub fn mouse_modes(&self) -> MouseModes {
+        self.vt.mouse_modes()
+    }
+
+    pub fn encode_mouse(&self, event: &MouseEvent) -> Option<Vec<u8>> {
+        self.vt.encode_mouse(event)
+    }
+
+    pub fn focus_events(&self) -> bool {
+        self.vt.focus_events()
+    }
+
+    pub fn encode_focus(&self, focused: bool) -> Option<&'static [u8]> {
+        self.vt.encode_focus(focused)
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.vt.is_alternate_screen()
+    }
+
+    pub fn autowrap(&self) -> bool {
+        self.vt.autowrap()
+    }
+
+    pub fn hyperlink_at(&self, row: usize, col: usize) -> Option<&str> {
+        self.vt.hyperlink_at(row, col)
+    }
+
+    pub fn working_directory(&self) -> Option<(&str, &str)> {
+        self.vt.working_directory()
+    }
+
+    /// Report the sequences `hooks` claim, for `take_extension_sequences`
+    pub fn set_extensions(&mut self, hooks: &ExtensionHooks) {
+        self.parser.set_extensions(hooks);
+    }
+
+    pub fn take_extension_sequences(&mut self) -> Vec<ExtensionSequence> {
+        self.vt.take_extension_sequences()
+    }
+
+    pub fn take_clipboard_requests(&mut self) -> Vec<ClipboardRequest> {
+        self.vt.take_clipboard_requests()
+    }
+
+    pub fn take_responses(&mut self) -> Vec<Vec<u8>> {
+        self.vt.take_responses()
+    }
+
+    /// Whether a synchronized update (mode 2026) is holding back drawing
+    pub fn is_synchronized(&self) -> bool {
+        self.vt.is_synchronized()
+    }
+
+    pub fn take_frame_ready(&mut self) -> bool {
+        self.vt.take_frame_ready()
+    }
+
+    pub fn images(&self) -> impl Iterator<Item = &ImagePlacement> {
+        self.vt.images()
+    }
+
+    /// Columns and rows of the screen
+    pub fn size(&self) -> (usize, usize) {
+        (self.vt.cols, self.vt.rows)
+    }
+
+    /// Row and column of the cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        self.vt.get_cursor_position()
+    }
+
+    pub fn origin_mode(&self) -> bool {
+        self.vt.origin_mode()
+    }
+
+    /// First and last row of the scroll region
+    pub fn scroll_region(&self) -> (usize, usize) {
+        self.vt.scroll_region()
+    }
+
+    /// Soft wrap and double width or height of a screen row
+    pub fn line_attributes(&self, row: usize) -> Option<LineAttributes> {
+        self.vt.line_attributes(row).copied()
+    }
+
+    /// Text and attributes of each cell of a screen row; the right half of
+    /// a wide character has no text
+    pub fn row_cells(&self, row: usize) -> Vec<(String, CellAttributes)> {
+        (0..self.vt.cols)
+            .filter_map(|col| self.vt.get_cell(row, col))
+            .map(|cell| {
+                let mut text = String::new();
+                if cell.width != CellWidth::Spacer {
+                    cell.push_text(&mut text);
+                }
+                (text, cell.attributes.clone())
+            })
+            .collect()
+    }
+
+    /// Escape sequences that paint this screen, renditions and cursor
+    /// included, onto a real terminal, for a client attaching to a running
+    /// session
+    pub fn redraw(&self) -> Vec<u8> {
+        // Rows are painted with absolute addressing and the full screen as
+        // the scroll region; the program's modes are restored afterwards
+        let mut out = String::from("\x1b[0m\x1b[r\x1b[?6l\x1b[?7h\x1b[H\x1b[2J");
+        for row in 0..self.vt.rows {
+            out.push_str(&format!("\x1b[{};1H", row + 1));
+            let size = self.line_attributes(row).unwrap_or_default().size;
+            out.push_str(match size {
+                LineSize::Single => "",
+                LineSize::DoubleWidth => "\x1b#6",
+                LineSize::DoubleHeightTop => "\x1b#3",
+                LineSize::DoubleHeightBottom => "\x1b#4",
+            });
+            // Double-size rows hold half as many cells
+            let cols = match size {
+                LineSize::Single => self.vt.cols,
+                _ => self.vt.cols / 2,
+            };
+            let mut current = CellAttributes::default().sgr();
+            for (text, attributes) in self.row_cells(row).into_iter().take(cols) {
+                let sgr = attributes.sgr();
+                if sgr != current {
+                    out.push_str(&format!("\x1b[{}m", sgr));
+                    current = sgr;
+                }
+                out.push_str(&text);
+            }
+            out.push_str("\x1b[0m");
+        }
+        let (top, bottom) = self.scroll_region();
+        out.push_str(&format!("\x1b[{};{}r", top + 1, bottom + 1));
+        if self.origin_mode() {
+            out.push_str("\x1b[?6h");
+        }
+        if !self.autowrap() {
+            out.push_str("\x1b[?7l");
+        }
+        let (mut row, col) = self.cursor();
+        if self.origin_mode() {
+            row -= top;
+        }
+        out.push_str(&format!("\x1b[{};{}H", row + 1, col + 1));
+        out.push_str(if self.cursor_visible() { "\x1b[?25h" } else { "\x1b[?25l" });
+        match self.cursor_blink() {
+            Some(true) => out.push_str("\x1b[?12h"),
+            Some(false) => out.push_str("\x1b[?12l"),
+            None => {}
+        }
+        out.into_bytes()
+    }
+
+    pub fn snapshot(&self) -> ScreenSnapshot {
+        let lines = (0..self.vt.rows)
+            .map(|row| line_text((0..self.vt.cols).filter_map(|col| self.vt.get_cell(row, col))))
+            .collect();
+        ScreenSnapshot {
+            cols: self.vt.cols,
+            rows: self.vt.rows,
+            cursor: self.vt.get_cursor_position(),
+            title: self.vt.title.clone(),
+            lines,
+        }
+    }
+}
+
+/// Text of a row's cells with trailing blanks removed
+fn line_text<'a>(cells: impl Iterator<Item = &'a TerminalCell>) -> String {
+    let mut line = String::new();
+    for cell in cells.filter(|cell| cell.width != CellWidth::Spacer) {
+        cell.push_text(&mut line);
+    }
+    line.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headless_snapshot() {
+        let mut terminal = HeadlessTerminal::new(10, 4);
+        terminal.feed(b"\x1b]2;build\x07hello\r\nwor").unwrap();
+        terminal.feed(b"ld\x1b[2;1H\x1b[2K\x07ok").unwrap();
+
+        let snapshot = terminal.snapshot();
+        assert_eq!(snapshot.title, "build");
+        assert_eq!(snapshot.cursor, (1, 2));
+        assert_eq!(snapshot.text(), "hello\nok\n");
+        assert_eq!(terminal.display_lines(), snapshot.lines);
+        assert_eq!(terminal.bells(), 1);
+        assert!(terminal.take_bell() && !terminal.take_bell());
+        terminal.set_bell_suppressed(true);
+        terminal.feed(b"\x07").unwrap();
+        assert!(!terminal.take_bell());
+
+        let mut terminal = HeadlessTerminal::new(10, 2).with_scrollback(100);
+        terminal.feed(b"one\r\ntwo\r\nthree").unwrap();
+        assert_eq!(terminal.scrollback_lines(), vec!["one"]);
+
+        // An 8-bit CSI only moves the cursor when C1 controls are enabled
+        let mut terminal = HeadlessTerminal::new(10, 2).with_utf8_only(false);
+        terminal.feed(b"ab\x9b2;1Hc").unwrap();
+        assert_eq!(terminal.snapshot().text(), "ab\nc\n");
+    }
+
+    #[test]
+    fn test_redraw() {
+        let mut terminal = HeadlessTerminal::new(12, 3);
+        terminal.feed("\x1b[1;31mred\x1b[0m 字\r\n".as_bytes()).unwrap();
+        terminal.feed(b"\x1b[44mblue\x1b[0m\x1b#6\x1b[?25l\x1b[?12h").unwrap();
+        terminal.feed(b"\x1b[2;3r\x1b[?6h\x1b[2;5H").unwrap();
+
+        // Painting the redraw onto a fresh screen reproduces it
+        let mut copy = HeadlessTerminal::new(12, 3);
+        copy.feed(&terminal.redraw()).unwrap();
+        assert_eq!(copy.snapshot(), terminal.snapshot());
+        assert_eq!(copy.line_attributes(1), terminal.line_attributes(1));
+        assert_eq!(copy.line_attributes(1).unwrap().size, LineSize::DoubleWidth);
+        assert_eq!(copy.cursor(), (2, 4));
+        assert!(copy.origin_mode());
+        assert_eq!(copy.scroll_region(), (1, 2));
+        assert!(!copy.cursor_visible());
+        assert_eq!(copy.cursor_blink(), Some(true));
+        for row in 0..3 {
+            assert_eq!(copy.row_cells(row), terminal.row_cells(row));
+        }
+    }
+
+    #[test]
+    fn test_cursor_and_tab_sequences() {
+        let text = |data: &[u8]| {
+            let mut terminal = HeadlessTerminal::new(20, 4);
+            terminal.feed(data).unwrap();
+            terminal.snapshot()
+        };
+
+        // CHA and VPA
+        assert_eq!(text(b"abc\x1b[5GX").lines[0], "abc X");
+        assert_eq!(text(b"ab\x1b[3dX").lines[2], "  X");
+        // CNL and CPL go to the first column
+        assert_eq!(text(b"ab\x1b[2EX").lines[2], "X");
+        assert_eq!(text(b"\n\n  ab\x1b[FX").lines[1], "X");
+        // REP repeats the last character
+        assert_eq!(text(b"a-\x1b[3b").lines[0], "a----");
+        // CBT back to the previous stop, HTS sets one, TBC clears them
+        assert_eq!(text(b"\x1b[12G\x1b[ZX").lines[0], "        X");
+        assert_eq!(text(b"\x1b[4G\x1bH\rx\ty").lines[0], "x  y");
+        assert_eq!(text(b"\x1b[9G\x1b[g\r\tX").lines[0], format!("{:16}X", ""));
+        assert_eq!(text(b"\x1b[3g\tX").lines[0], format!("{:19}X", ""));
+    }
+
+    #[test]
+    fn test_inspector_pause_and_step() {
+        let mut terminal = HeadlessTerminal::new(10, 2);
+        terminal.set_inspecting(true).unwrap();
+        terminal.feed(b"a\x1b[5x").unwrap();
+        terminal.toggle_inspector_pause().unwrap();
+        terminal.feed(b"bc").unwrap();
+        assert_eq!(terminal.snapshot().text(), "a\n");
+
+        assert!(terminal.inspector_step().unwrap());
+        assert_eq!(terminal.snapshot().text(), "ab\n");
+        terminal.toggle_inspector_pause().unwrap();
+        assert_eq!(terminal.snapshot().text(), "abc\n");
+
+        let inspector = terminal.inspector().unwrap();
+        assert_eq!(inspector.entries().count(), 2);
+        assert_eq!(inspector.unhandled_counts(), vec![("\\u{1b}[5x", 1)]);
+    }
+}
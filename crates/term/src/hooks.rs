@@ -0,0 +1,245 @@
+// Extension hooks for private escape sequences
+//
+// In-house tools can talk to the terminal through OSC numbers it doesn't use
+// and APC strings. The parser only reports the sequences a hook claimed, so
+// everything else is still ignored as unhandled; the matching hooks map each
+// one to a keybinding action, a script or a plugin callback.
+
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use config::EscapeHook;
+use tracing::debug;
+
+/// OSC numbers the terminal handles or reserves; hooks can't claim them
+const RESERVED_OSC: &[u32] = &[0, 1, 2, 4, 7, 8, 9, 10, 11, 12, 52, 104, 110, 111, 112, 133, 777];
+
+/// A private sequence claimed by a hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtensionSequence {
+    /// `OSC number ; payload`
+    Osc { number: u32, payload: String },
+    /// `ESC _ payload ST`
+    Apc(String),
+}
+
+/// What a hook wants done with a sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookOutcome {
+    /// Run a keybinding action, e.g. `toggle_hud`
+    Action(String),
+    /// Run a shell command with the payload, see `run_script`
+    Run { command: String, payload: String },
+    /// A callback dealt with it
+    Handled,
+}
+
+/// Which sequences a hook claims
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookMatch {
+    Osc(u32),
+    /// APC payloads starting with this
+    Apc(String),
+}
+
+type Callback = Arc<dyn Fn(&str) -> Option<HookOutcome> + Send + Sync>;
+
+struct Hook {
+    matcher: HookMatch,
+    /// Only payloads starting with this
+    prefix: String,
+    callback: Callback,
+}
+
+/// Hooks from `terminal.escape_hooks` and plugins
+#[derive(Default)]
+pub struct ExtensionHooks {
+    hooks: Vec<Hook>,
+}
+
+impl ExtensionHooks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hooks for `terminal.escape_hooks`
+    pub fn from_config(hooks: &[EscapeHook]) -> Result<Self> {
+        let mut registry = Self::new();
+        for hook in hooks {
+            let matcher = match (hook.osc, &hook.apc) {
+                (Some(number), None) => HookMatch::Osc(number),
+                (None, Some(prefix)) => HookMatch::Apc(prefix.clone()),
+                _ => return Err(anyhow!("Escape hook needs exactly one of `osc` and `apc`")),
+            };
+            let outcome = match (&hook.action, &hook.run) {
+                (Some(action), None) => HookOutcome::Action(action.clone()),
+                (None, Some(command)) => HookOutcome::Run {
+                    command: command.clone(),
+                    payload: String::new(),
+                },
+                _ => return Err(anyhow!("Escape hook needs exactly one of `action` and `run`")),
+            };
+            let prefix = hook.prefix.clone().unwrap_or_default();
+            registry.register(matcher, &prefix, move |payload| {
+                Some(match &outcome {
+                    HookOutcome::Run { command, .. } => HookOutcome::Run {
+                        command: command.clone(),
+                        payload: payload.to_string(),
+                    },
+                    other => other.clone(),
+                })
+            })?;
+        }
+        Ok(registry)
+    }
+
+    /// Add a handler, e.g. from a plugin, for payloads starting with
+    /// `prefix`; it returns `None` to leave the sequence to other hooks
+    pub fn register<F>(&mut self, matcher: HookMatch, prefix: &str, callback: F) -> Result<()>
+    where
+        F: Fn(&str) -> Option<HookOutcome> + Send + Sync + 'static,
+    {
+        match &matcher {
+            HookMatch::Osc(number) if RESERVED_OSC.contains(number) => {
+                return Err(anyhow!("OSC {} is handled by the terminal", number));
+            }
+            HookMatch::Apc(prefix) if prefix.is_empty() => {
+                return Err(anyhow!("APC hooks need a prefix"));
+            }
+            _ => {}
+        }
+        self.hooks.push(Hook {
+            matcher,
+            prefix: prefix.to_string(),
+            callback: Arc::new(callback),
+        });
+        Ok(())
+    }
+
+    /// OSC numbers for `TerminalParser::set_extensions`
+    pub fn osc_numbers(&self) -> Vec<u32> {
+        let mut numbers: Vec<u32> = self
+            .hooks
+            .iter()
+            .filter_map(|hook| match hook.matcher {
+                HookMatch::Osc(number) => Some(number),
+                HookMatch::Apc(_) => None,
+            })
+            .collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+    }
+
+    /// Whether any hook wants APC strings
+    pub fn wants_apc(&self) -> bool {
+        self.hooks
+            .iter()
+            .any(|hook| matches!(hook.matcher, HookMatch::Apc(_)))
+    }
+
+    /// Outcomes of the hooks matching a sequence, in registration order
+    pub fn dispatch(&self, sequence: &ExtensionSequence) -> Vec<HookOutcome> {
+        let outcomes: Vec<HookOutcome> = self
+            .hooks
+            .iter()
+            .filter(|hook| match (&hook.matcher, sequence) {
+                (HookMatch::Osc(number), ExtensionSequence::Osc { number: n, .. }) => number == n,
+                (HookMatch::Apc(prefix), ExtensionSequence::Apc(payload)) => {
+                    payload.starts_with(prefix.as_str())
+                }
+                _ => false,
+            })
+            .filter_map(|hook| {
+                let payload = match sequence {
+                    ExtensionSequence::Osc { payload, .. } => payload,
+                    ExtensionSequence::Apc(payload) => payload,
+                };
+                payload
+                    .starts_with(hook.prefix.as_str())
+                    .then(|| (hook.callback)(payload))
+                    .flatten()
+            })
+            .collect();
+        if outcomes.is_empty() {
+            debug!("No hook handled {:?}", sequence);
+        }
+        outcomes
+    }
+}
+
+/// Start a hook's shell command in the background with the payload in
+/// `$VOIDCLI_PAYLOAD`
+pub fn run_script(command: &str, payload: &str) -> Result<()> {
+    Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("VOIDCLI_PAYLOAD", payload)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run escape hook: {}", command))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessTerminal;
+
+    #[test]
+    fn test_osc_hook() {
+        let config = vec![EscapeHook {
+            osc: Some(5522),
+            prefix: Some("set-status;".to_string()),
+            run: Some("notify-send \"$VOIDCLI_PAYLOAD\"".to_string()),
+            ..EscapeHook::default()
+        }];
+        let hooks = ExtensionHooks::from_config(&config).unwrap();
+        let mut terminal = HeadlessTerminal::new(20, 2);
+        terminal.set_extensions(&hooks);
+        terminal
+            .feed(b"\x1b]5522;set-status;deploying\x07\x1b]5523;x\x07\x1b]5522;other\x07")
+            .unwrap();
+
+        let sequences = terminal.take_extension_sequences();
+        assert_eq!(sequences.len(), 2);
+        assert_eq!(
+            hooks.dispatch(&sequences[0]),
+            vec![HookOutcome::Run {
+                command: "notify-send \"$VOIDCLI_PAYLOAD\"".to_string(),
+                payload: "set-status;deploying".to_string(),
+            }]
+        );
+        assert!(hooks.dispatch(&sequences[1]).is_empty());
+
+        let reserved = EscapeHook {
+            osc: Some(52),
+            action: Some("toggle_hud".to_string()),
+            ..EscapeHook::default()
+        };
+        assert!(ExtensionHooks::from_config(&[reserved]).is_err());
+    }
+
+    #[test]
+    fn test_apc_hook() {
+        let mut hooks = ExtensionHooks::new();
+        hooks
+            .register(HookMatch::Apc("build;".to_string()), "", |payload| {
+                Some(HookOutcome::Action(format!("seen {}", payload)))
+            })
+            .unwrap();
+        let mut terminal = HeadlessTerminal::new(20, 2);
+        terminal.set_extensions(&hooks);
+        terminal.feed(b"a\x1b_build;done\x1b\\b").unwrap();
+
+        assert_eq!(terminal.snapshot().lines[0], "ab");
+        let sequences = terminal.take_extension_sequences();
+        assert_eq!(sequences, vec![ExtensionSequence::Apc("build;done".to_string())]);
+        assert_eq!(
+            hooks.dispatch(&sequences[0]),
+            vec![HookOutcome::Action("seen build;done".to_string())]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::parser::TerminalAction;
+
+/// Reads kept for display
+const MAX_ENTRIES: usize = 200;
+
+/// One read from the program, as the parser saw it
+#[derive(Debug, Clone)]
+pub struct InspectorEntry {
+    pub bytes: Vec<u8>,
+    /// Parsed actions, formatted for display
+    pub actions: Vec<String>,
+    /// Sequences the parser ignored, escaped
+    pub unhandled: Vec<String>,
+}
+
+impl InspectorEntry {
+    /// Raw bytes with control characters escaped, e.g. `\u{1b}[1m`
+    pub fn escaped_bytes(&self) -> String {
+        String::from_utf8_lossy(&self.bytes).escape_debug().to_string()
+    }
+}
+
+/// Live view of the decoded stream of a pane, for debugging rendering bugs
+///
+/// While paused, parsed actions are held back from the screen and released
+/// one at a time with `step`.
+#[derive(Default)]
+pub struct Inspector {
+    entries: VecDeque<InspectorEntry>,
+    unhandled_counts: BTreeMap<String, usize>,
+    paused: bool,
+    held: VecDeque<TerminalAction>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a read and return the actions to apply now
+    pub(crate) fn record(
+        &mut self,
+        bytes: &[u8],
+        actions: Vec<TerminalAction>,
+        unhandled: Vec<String>,
+    ) -> Vec<TerminalAction> {
+        for sequence in &unhandled {
+            *self.unhandled_counts.entry(sequence.clone()).or_default() += 1;
+        }
+        if self.entries.len() == MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(InspectorEntry {
+            bytes: bytes.to_vec(),
+            actions: actions.iter().map(|action| format!("{:?}", action)).collect(),
+            unhandled,
+        });
+
+        if self.paused {
+            self.held.extend(actions);
+            Vec::new()
+        } else {
+            actions
+        }
+    }
+
+    /// Hold back actions until `step` or `resume`
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Stop holding actions back, returning the held ones in order
+    pub(crate) fn resume(&mut self) -> Vec<TerminalAction> {
+        self.paused = false;
+        self.held.drain(..).collect()
+    }
+
+    /// Release the next held action
+    pub(crate) fn step(&mut self) -> Option<TerminalAction> {
+        self.held.pop_front()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Actions waiting while paused
+    pub fn held(&self) -> usize {
+        self.held.len()
+    }
+
+    /// Next action `step` would apply, formatted for display
+    pub fn next_action(&self) -> Option<String> {
+        self.held.front().map(|action| format!("{:?}", action))
+    }
+
+    /// Recent reads, oldest first
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &InspectorEntry> {
+        self.entries.iter()
+    }
+
+    /// Ignored sequences by how often they were seen, most frequent first
+    pub fn unhandled_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts: Vec<(&str, usize)> = self
+            .unhandled_counts
+            .iter()
+            .map(|(sequence, count)| (sequence.as_str(), *count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.unhandled_counts.clear();
+    }
+}
@@ -0,0 +1,271 @@
+// Keyboard input encoder
+// Translates key presses into the byte sequences programs expect, based on the
+// terminal's current keyboard modes
+
+/// A key independent of the windowing library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Insert,
+    Delete,
+    /// Function key F1..=F12
+    F(u8),
+    /// Numeric keypad key: digits, `.`, `+`, `-`, `*`, `/` or `\r` for keypad enter
+    Keypad(char),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+    pub super_key: bool,
+}
+
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        !(self.shift || self.alt || self.ctrl || self.super_key)
+    }
+
+    /// xterm modifier parameter: 1 + shift + 2*alt + 4*ctrl + 8*super
+    fn param(&self) -> u8 {
+        1 + self.shift as u8 + 2 * self.alt as u8 + 4 * self.ctrl as u8 + 8 * self.super_key as u8
+    }
+}
+
+/// Keyboard-related modes set by the running program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyboardModes {
+    /// DECCKM: cursor keys send SS3 instead of CSI
+    pub application_cursor: bool,
+    /// DECKPAM: keypad sends application sequences
+    pub application_keypad: bool,
+    /// xterm modifyOtherKeys level (0, 1 or 2)
+    pub modify_other_keys: u32,
+    /// Active kitty keyboard protocol flags
+    pub kitty_flags: u32,
+}
+
+/// Kitty flag: disambiguate escape codes
+const KITTY_DISAMBIGUATE: u32 = 0b1;
+
+/// Encodes keys according to user preferences and terminal modes
+#[derive(Debug, Clone)]
+pub struct KeyEncoder {
+    /// Backspace sends DEL (0x7f) rather than BS (0x08)
+    pub backspace_sends_delete: bool,
+    /// Alt prefixes the key with ESC
+    pub alt_sends_escape: bool,
+}
+
+impl Default for KeyEncoder {
+    fn default() -> Self {
+        Self {
+            backspace_sends_delete: true,
+            alt_sends_escape: true,
+        }
+    }
+}
+
+impl KeyEncoder {
+    pub fn new(config: &config::KeyboardConfig) -> Self {
+        Self {
+            backspace_sends_delete: config.backspace_sends_delete,
+            alt_sends_escape: config.alt_sends_escape,
+        }
+    }
+
+    /// Encode a key press, or `None` if it produces no input
+    pub fn encode(&self, key: Key, mods: Modifiers, modes: &KeyboardModes) -> Option<Vec<u8>> {
+        match key {
+            Key::Char(c) => self.encode_char(c, mods, modes),
+            Key::Enter => Some(self.with_alt(b"\r", mods)),
+            Key::Tab if mods.shift => Some(b"\x1b[Z".to_vec()),
+            Key::Tab => Some(self.with_alt(b"\t", mods)),
+            Key::Backspace => {
+                // Ctrl inverts the configured backspace byte
+                let delete = self.backspace_sends_delete != mods.ctrl;
+                Some(self.with_alt(if delete { b"\x7f" } else { b"\x08" }, mods))
+            }
+            Key::Escape => {
+                if modes.kitty_flags & KITTY_DISAMBIGUATE != 0 {
+                    Some(csi_u(27, mods))
+                } else {
+                    Some(self.with_alt(b"\x1b", mods))
+                }
+            }
+            Key::Up => Some(cursor_key(b'A', mods, modes)),
+            Key::Down => Some(cursor_key(b'B', mods, modes)),
+            Key::Right => Some(cursor_key(b'C', mods, modes)),
+            Key::Left => Some(cursor_key(b'D', mods, modes)),
+            Key::Home => Some(cursor_key(b'H', mods, modes)),
+            Key::End => Some(cursor_key(b'F', mods, modes)),
+            Key::Insert => Some(tilde_key(2, mods)),
+            Key::Delete => Some(tilde_key(3, mods)),
+            Key::PageUp => Some(tilde_key(5, mods)),
+            Key::PageDown => Some(tilde_key(6, mods)),
+            Key::F(n) => function_key(n, mods),
+            Key::Keypad(c) => Some(self.encode_keypad(c, mods, modes)),
+        }
+    }
+
+    fn encode_char(&self, c: char, mods: Modifiers, modes: &KeyboardModes) -> Option<Vec<u8>> {
+        let has_extra_mods = mods.ctrl || mods.alt || mods.super_key;
+
+        // Kitty disambiguation reports modified keys as `CSI code ; mods u`
+        if modes.kitty_flags & KITTY_DISAMBIGUATE != 0 && has_extra_mods {
+            return Some(csi_u(c as u32, mods));
+        }
+
+        // modifyOtherKeys level 2 reports every modified key unambiguously
+        if modes.modify_other_keys >= 2 && has_extra_mods {
+            return Some(format!("\x1b[27;{};{}~", mods.param(), c as u32).into_bytes());
+        }
+
+        if mods.ctrl {
+            if let Some(byte) = ctrl_byte(c) {
+                return Some(self.with_alt(&[byte], mods));
+            }
+            if modes.modify_other_keys == 1 {
+                return Some(format!("\x1b[27;{};{}~", mods.param(), c as u32).into_bytes());
+            }
+        }
+
+        let mut buf = [0u8; 4];
+        Some(self.with_alt(c.encode_utf8(&mut buf).as_bytes(), mods))
+    }
+
+    fn encode_keypad(&self, c: char, mods: Modifiers, modes: &KeyboardModes) -> Vec<u8> {
+        if modes.application_keypad && mods.is_empty() {
+            let final_byte = match c {
+                '0'..='9' => Some(b'p' + (c as u8 - b'0')),
+                '.' => Some(b'n'),
+                '+' => Some(b'k'),
+                '-' => Some(b'm'),
+                '*' => Some(b'j'),
+                '/' => Some(b'o'),
+                '\r' => Some(b'M'),
+                _ => None,
+            };
+            if let Some(final_byte) = final_byte {
+                return vec![0x1b, b'O', final_byte];
+            }
+        }
+
+        let mut buf = [0u8; 4];
+        c.encode_utf8(&mut buf).as_bytes().to_vec()
+    }
+
+    fn with_alt(&self, bytes: &[u8], mods: Modifiers) -> Vec<u8> {
+        let mut out = Vec::with_capacity(bytes.len() + 1);
+        if mods.alt && self.alt_sends_escape {
+            out.push(0x1b);
+        }
+        out.extend_from_slice(bytes);
+        out
+    }
+}
+
+/// Control byte for ctrl+key, e.g. ctrl+c -> 0x03
+fn ctrl_byte(c: char) -> Option<u8> {
+    match c {
+        'a'..='z' => Some(c as u8 - b'a' + 1),
+        'A'..='Z' => Some(c as u8 - b'A' + 1),
+        ' ' | '@' | '2' => Some(0x00),
+        '[' | '3' => Some(0x1b),
+        '\\' | '4' => Some(0x1c),
+        ']' | '5' => Some(0x1d),
+        '^' | '6' => Some(0x1e),
+        '_' | '-' | '7' => Some(0x1f),
+        '8' | '?' => Some(0x7f),
+        _ => None,
+    }
+}
+
+/// Arrow, Home and End keys: SS3 in application cursor mode, CSI otherwise,
+/// and `CSI 1 ; mods X` when modified
+fn cursor_key(final_byte: u8, mods: Modifiers, modes: &KeyboardModes) -> Vec<u8> {
+    if !mods.is_empty() {
+        return format!("\x1b[1;{}{}", mods.param(), final_byte as char).into_bytes();
+    }
+
+    if modes.application_cursor {
+        vec![0x1b, b'O', final_byte]
+    } else {
+        vec![0x1b, b'[', final_byte]
+    }
+}
+
+/// Keys encoded as `CSI n ~`, with `CSI n ; mods ~` when modified
+fn tilde_key(code: u8, mods: Modifiers) -> Vec<u8> {
+    if mods.is_empty() {
+        format!("\x1b[{}~", code).into_bytes()
+    } else {
+        format!("\x1b[{};{}~", code, mods.param()).into_bytes()
+    }
+}
+
+fn function_key(n: u8, mods: Modifiers) -> Option<Vec<u8>> {
+    match n {
+        1..=4 => {
+            let final_byte = (b'P' + n - 1) as char;
+            if mods.is_empty() {
+                Some(format!("\x1bO{}", final_byte).into_bytes())
+            } else {
+                Some(format!("\x1b[1;{}{}", mods.param(), final_byte).into_bytes())
+            }
+        }
+        5..=12 => {
+            const CODES: [u8; 8] = [15, 17, 18, 19, 20, 21, 23, 24];
+            Some(tilde_key(CODES[(n - 5) as usize], mods))
+        }
+        _ => None,
+    }
+}
+
+fn csi_u(code: u32, mods: Modifiers) -> Vec<u8> {
+    if mods.is_empty() {
+        format!("\x1b[{}u", code).into_bytes()
+    } else {
+        format!("\x1b[{};{}u", code, mods.param()).into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_keys() {
+        let encoder = KeyEncoder::default();
+        let none = Modifiers::default();
+        let ctrl = Modifiers { ctrl: true, ..Default::default() };
+        let mut modes = KeyboardModes::default();
+
+        assert_eq!(encoder.encode(Key::Char('c'), ctrl, &modes), Some(vec![0x03]));
+        assert_eq!(encoder.encode(Key::Backspace, none, &modes), Some(vec![0x7f]));
+        assert_eq!(encoder.encode(Key::Up, none, &modes), Some(b"\x1b[A".to_vec()));
+        assert_eq!(encoder.encode(Key::Up, ctrl, &modes), Some(b"\x1b[1;5A".to_vec()));
+        assert_eq!(encoder.encode(Key::F(5), none, &modes), Some(b"\x1b[15~".to_vec()));
+
+        modes.application_cursor = true;
+        modes.application_keypad = true;
+        assert_eq!(encoder.encode(Key::Up, none, &modes), Some(b"\x1bOA".to_vec()));
+        assert_eq!(encoder.encode(Key::Keypad('5'), none, &modes), Some(b"\x1bOu".to_vec()));
+
+        modes.kitty_flags = KITTY_DISAMBIGUATE;
+        assert_eq!(encoder.encode(Key::Char('c'), ctrl, &modes), Some(b"\x1b[99;5u".to_vec()));
+    }
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use config::{OnExit, TerminalConfig};
+
+/// Processes exiting sooner than this are held instead of restarted, so a
+/// shell that fails on startup doesn't respawn in a loop
+const MIN_UPTIME_FOR_RESTART: Duration = Duration::from_secs(1);
+
+/// How the pane's process is started
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LaunchOptions {
+    /// Start the shell as a login shell (`-l`)
+    pub login: bool,
+    /// Extra arguments for the shell
+    pub shell_args: Vec<String>,
+    /// Typed into the shell once it has initialized
+    pub startup_command: Option<String>,
+    /// Run this program instead of the shell (`-e`)
+    pub command: Option<Vec<String>>,
+    /// Keep the pane open after a `-e` command exits
+    pub hold: bool,
+    /// Configured behavior when the process exits
+    pub on_exit: OnExit,
+}
+
+/// What to do with a pane whose process has exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitAction {
+    Close,
+    /// Keep the output and show `exit_message` until a key is pressed
+    Hold,
+    /// Spawn the process again in the same pane
+    Restart,
+}
+
+impl LaunchOptions {
+    pub fn from_config(config: &TerminalConfig) -> Self {
+        Self {
+            login: config.login_shell,
+            shell_args: config.shell_args.clone(),
+            startup_command: config.startup_command.clone(),
+            command: config.command.clone(),
+            hold: config.hold,
+            on_exit: config.on_exit,
+        }
+    }
+
+    /// Program and arguments to execute
+    ///
+    /// `injected` are the shell integration arguments; they come first since
+    /// bash requires long options before single-letter ones.
+    pub fn command_line(&self, shell: &str, injected: &[String]) -> (String, Vec<String>) {
+        if let Some((program, args)) = self.command.as_ref().and_then(|c| c.split_first()) {
+            return (program.clone(), args.to_vec());
+        }
+
+        let mut args = injected.to_vec();
+        if self.login {
+            args.push("-l".to_string());
+        }
+        args.extend(self.shell_args.iter().cloned());
+        (shell.to_string(), args)
+    }
+
+    /// Whether a shell runs, as opposed to a `-e` command
+    pub fn runs_shell(&self) -> bool {
+        !matches!(&self.command, Some(command) if !command.is_empty())
+    }
+
+    /// Keep the pane after the process exits
+    pub fn holds(&self) -> bool {
+        (self.hold && !self.runs_shell()) || self.on_exit == OnExit::Hold
+    }
+
+    /// What to do once the process exited with `code` after running for
+    /// `uptime`
+    pub fn exit_action(&self, code: i32, uptime: Duration) -> ExitAction {
+        let restart = match self.on_exit {
+            OnExit::Restart => true,
+            OnExit::RestartOnFailure => code != 0,
+            OnExit::Close | OnExit::Hold => false,
+        };
+        if restart {
+            if uptime >= MIN_UPTIME_FOR_RESTART {
+                return ExitAction::Restart;
+            }
+            return ExitAction::Hold;
+        }
+        if self.holds() {
+            ExitAction::Hold
+        } else {
+            ExitAction::Close
+        }
+    }
+}
+
+/// Line shown in a held pane once its command has exited
+pub fn exit_message(code: i32) -> String {
+    format!("\r\n[Process exited with code {} — press any key to close]", code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let injected = vec!["--rcfile".to_string(), "/tmp/rc".to_string()];
+        let options = LaunchOptions {
+            login: true,
+            shell_args: vec!["--noprofile".to_string()],
+            ..LaunchOptions::default()
+        };
+        assert_eq!(
+            options.command_line("/bin/bash", &injected),
+            (
+                "/bin/bash".to_string(),
+                vec!["--rcfile", "/tmp/rc", "-l", "--noprofile"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect()
+            )
+        );
+
+        let options = LaunchOptions {
+            command: Some(vec!["htop".to_string(), "-d".to_string(), "5".to_string()]),
+            hold: true,
+            ..LaunchOptions::default()
+        };
+        assert_eq!(
+            options.command_line("/bin/bash", &injected),
+            ("htop".to_string(), vec!["-d".to_string(), "5".to_string()])
+        );
+        assert!(options.holds());
+    }
+
+    #[test]
+    fn test_exit_action() {
+        let uptime = Duration::from_secs(60);
+        let options = |on_exit| LaunchOptions {
+            on_exit,
+            ..LaunchOptions::default()
+        };
+        assert_eq!(options(OnExit::Close).exit_action(1, uptime), ExitAction::Close);
+        assert_eq!(options(OnExit::Hold).exit_action(0, uptime), ExitAction::Hold);
+        assert_eq!(options(OnExit::Restart).exit_action(0, uptime), ExitAction::Restart);
+
+        let on_failure = options(OnExit::RestartOnFailure);
+        assert_eq!(on_failure.exit_action(0, uptime), ExitAction::Close);
+        assert_eq!(on_failure.exit_action(2, uptime), ExitAction::Restart);
+        // Failing right away would respawn forever
+        assert_eq!(
+            on_failure.exit_action(2, Duration::from_millis(100)),
+            ExitAction::Hold
+        );
+    }
+}
@@ -2,15 +2,47 @@
 //
 // This module handles terminal emulation, PTY handling, and terminal state management.
 
+pub mod bench;
+pub mod bidi;
+mod env_rules;
+mod grid;
+pub mod headless;
+pub mod hooks;
+pub mod inspector;
+pub mod keyboard;
+pub mod launch;
+pub mod metrics;
+pub mod mouse;
 mod parser;
+pub mod paste;
 mod process;
+pub mod prompts;
 mod pty;
+pub mod remote;
+pub mod replay;
+pub mod security;
+pub mod sixel;
+pub mod usage;
 mod vt;
+pub mod shell_integration;
+
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use config::Config;
 use tokio::sync::mpsc;
 
+use headless::{HeadlessTerminal, ScreenSnapshot};
+use keyboard::{Key, KeyEncoder, Modifiers};
+use launch::{exit_message, ExitAction, LaunchOptions};
+use process::ProcessManager;
+use prompts::PromptMark;
+use remote::SessionContext;
+use usage::{CommandUsage, UsageSampler, SAMPLE_INTERVAL};
+
+pub use parser::{TerminalAction, TerminalParser};
+pub use vt::{CellAttributes, ClipboardRequest, ImagePlacement, LineAttributes, LineSize};
+
 /// Represents a terminal instance
 pub struct Terminal {
     config: Config,
@@ -22,6 +54,14 @@ pub enum TermEvent {
     Resize(u16, u16),
     ProcessExit(i32),
     Error(String),
+    /// The program rang the bell
+    Bell,
+    /// The window title set via OSC 0/2 or the title stack changed
+    TitleChanged(String),
+    /// The session entered or left SSH or a container
+    ContextChanged(SessionContext),
+    /// A command marked by OSC 133 C and D finished, with what it used
+    CommandUsage(CommandUsage),
 }
 
 impl Terminal {
@@ -47,3 +87,338 @@ impl Terminal {
         Ok(())
     }
 }
+
+/// A shell or command run by `run_headless`
+#[derive(Debug, Clone)]
+pub struct HeadlessRun {
+    pub screen: ScreenSnapshot,
+    /// Everything the program wrote, escape sequences included
+    pub output: Vec<u8>,
+    pub exit_code: i32,
+    pub duration: Duration,
+}
+
+/// Spawn the configured shell or `-e` command on a PTY, with a headless
+/// screen set up from `config.terminal`
+async fn spawn_headless(
+    config: &Config,
+    cwd: Option<&str>,
+    cols: u16,
+    rows: u16,
+    event_tx: mpsc::UnboundedSender<TermEvent>,
+) -> Result<(ProcessManager, HeadlessTerminal)> {
+    let terminal_config = &config.terminal;
+    let mut process = ProcessManager::new(&terminal_config.shell, event_tx, cwd, Vec::new())
+        .with_env_rules(terminal_config.env_rules.clone(), None)
+        .with_launch(LaunchOptions::from_config(terminal_config));
+    if terminal_config.shell_integration {
+        if let Some(dir) = shell_integration::default_install_dir() {
+            process = process.with_shell_integration(dir);
+        }
+    }
+    process.spawn().await?;
+    process.resize(cols, rows).await?;
+
+    let screen = HeadlessTerminal::new(cols as usize, rows as usize)
+        .with_utf8_only(terminal_config.utf8_only)
+        .with_scrollback(terminal_config.scrollback_lines);
+    Ok((process, screen))
+}
+
+/// Run the configured shell or `-e` command on a PTY without a window
+pub async fn run_headless(config: &Config, cols: u16, rows: u16) -> Result<HeadlessRun> {
+    let started = Instant::now();
+    let (event_tx, mut events) = mpsc::unbounded_channel();
+    let (mut process, mut screen) = spawn_headless(config, None, cols, rows, event_tx).await?;
+    let mut output = Vec::new();
+    let exit_code = loop {
+        let event = tokio::select! {
+            event = events.recv() => event,
+            code = process.wait() => Some(TermEvent::ProcessExit(code?)),
+        };
+        match event {
+            Some(TermEvent::Output(data)) => {
+                screen.feed(&data)?;
+                output.extend_from_slice(&data);
+                for response in screen.take_responses() {
+                    process.write(&response).await?;
+                }
+                if let Some(input) = process.take_startup_input() {
+                    process.write(&input).await?;
+                }
+            }
+            Some(TermEvent::ProcessExit(code)) => {
+                // `wait` returns once the output has been read, so what is
+                // left is already queued
+                while let Ok(event) = events.try_recv() {
+                    if let TermEvent::Output(data) = event {
+                        screen.feed(&data)?;
+                        output.extend_from_slice(&data);
+                    }
+                }
+                // The final screen shows what a held window would
+                if process.holds_on_exit() {
+                    screen.feed(exit_message(code).as_bytes())?;
+                }
+                break code;
+            }
+            Some(_) => {}
+            None => break -1,
+        }
+    };
+    Ok(HeadlessRun {
+        screen: screen.snapshot(),
+        output,
+        exit_code,
+        duration: started.elapsed(),
+    })
+}
+
+/// How often a pane that keeps writing output is checked for SSH or
+/// container sessions
+const CONTEXT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A shell on a PTY whose screen is kept by a headless terminal, for
+/// `voidcli host` to stream to remote clients
+pub struct HostedPane {
+    process: ProcessManager,
+    events: mpsc::UnboundedReceiver<TermEvent>,
+    screen: HeadlessTerminal,
+    keys: KeyEncoder,
+    /// Exit code of a process kept on screen by `terminal.on_exit` until a
+    /// key is pressed
+    held: Option<i32>,
+    /// A key was pressed in the held pane; reported as `ProcessExit` next
+    closed: bool,
+    /// Context last reported with `ContextChanged`
+    context: SessionContext,
+    context_checked: Instant,
+    /// Prompt mark last seen, to notice commands starting and finishing
+    last_mark: Option<(usize, PromptMark)>,
+    /// Samples the running command, if any
+    usage: Option<UsageSampler>,
+    usage_sampled: Instant,
+}
+
+impl HostedPane {
+    pub async fn spawn(config: &Config, cols: u16, rows: u16) -> Result<Self> {
+        Self::spawn_in(config, None, cols, rows).await
+    }
+
+    /// Spawn in `cwd` instead of the current directory
+    pub async fn spawn_in(
+        config: &Config,
+        cwd: Option<&str>,
+        cols: u16,
+        rows: u16,
+    ) -> Result<Self> {
+        let (event_tx, events) = mpsc::unbounded_channel();
+        let (process, screen) = spawn_headless(config, cwd, cols, rows, event_tx).await?;
+        Ok(Self {
+            process,
+            events,
+            screen,
+            keys: KeyEncoder::new(&config.terminal.keyboard),
+            held: None,
+            closed: false,
+            context: SessionContext::Local,
+            context_checked: Instant::now(),
+            last_mark: None,
+            usage: None,
+            usage_sampled: Instant::now(),
+        })
+    }
+
+    /// Send input to the program; any key closes a held pane
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        if self.held.is_some() {
+            self.closed = true;
+            return Ok(());
+        }
+        // Typing returns to the live screen
+        self.screen.scroll_display_to_bottom();
+        self.process.write(data).await
+    }
+
+    /// Send a key press, encoded for the program's keyboard modes
+    pub async fn press_key(&mut self, key: Key, mods: Modifiers) -> Result<()> {
+        let modes = self.screen.keyboard_modes();
+        match self.keys.encode(key, mods, &modes) {
+            Some(data) => self.write(&data).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Report the pane gaining or losing focus, if the program asked
+    pub async fn set_focused(&mut self, focused: bool) -> Result<()> {
+        match self.screen.encode_focus(focused) {
+            Some(report) if self.held.is_none() => self.process.write(report).await,
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        self.screen.resize(cols as usize, rows as usize);
+        self.process.resize(cols, rows).await
+    }
+
+    /// Size of a cell in the client's window, in pixels, for images
+    pub fn set_cell_pixel_size(&mut self, width: u16, height: u16) {
+        self.screen.set_cell_pixel_size(width as usize, height as usize);
+    }
+
+    /// Apply the output that arrived since the last call, answering the
+    /// program's queries; returns that output, `Bell` if the program rang
+    /// it, `TitleChanged`, `ContextChanged` when it entered or left SSH or
+    /// a container, `CommandUsage` when a command finished, and
+    /// `ProcessExit` once it has exited and is not restarted or held
+    pub async fn poll(&mut self) -> Result<Vec<TermEvent>> {
+        let mut events = Vec::new();
+        let mut exit_code = None;
+        while let Ok(event) = self.events.try_recv() {
+            self.apply(event, &mut events, &mut exit_code).await?;
+        }
+        let exit_code = exit_code.or_else(|| self.process.try_exit_code());
+        self.finish(events, exit_code).await
+    }
+
+    /// Like `poll`, but waits for the program to write output or exit
+    pub async fn next_events(&mut self) -> Result<Vec<TermEvent>> {
+        let first = if self.closed {
+            None
+        } else {
+            tokio::select! {
+                event = self.events.recv() => event,
+                code = self.process.wait(), if self.held.is_none() => {
+                    Some(TermEvent::ProcessExit(code?))
+                }
+                _ = tokio::time::sleep(SAMPLE_INTERVAL), if self.usage.is_some() => None,
+            }
+        };
+        let mut events = Vec::new();
+        let mut exit_code = None;
+        if let Some(event) = first {
+            self.apply(event, &mut events, &mut exit_code).await?;
+        }
+        while let Ok(event) = self.events.try_recv() {
+            self.apply(event, &mut events, &mut exit_code).await?;
+        }
+        self.finish(events, exit_code).await
+    }
+
+    /// Feed output to the screen and pass it on, or note the exit code
+    async fn apply(
+        &mut self,
+        event: TermEvent,
+        events: &mut Vec<TermEvent>,
+        exit_code: &mut Option<i32>,
+    ) -> Result<()> {
+        match event {
+            TermEvent::Output(data) => {
+                self.screen.feed(&data)?;
+                for response in self.screen.take_responses() {
+                    self.process.write(&response).await?;
+                }
+                if let Some(input) = self.process.take_startup_input() {
+                    self.process.write(&input).await?;
+                }
+                events.push(TermEvent::Output(data));
+            }
+            TermEvent::ProcessExit(code) => *exit_code = Some(code),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Add the bell, title and context changes and command usage, and
+    /// apply `terminal.on_exit` to an exit
+    async fn finish(
+        &mut self,
+        mut events: Vec<TermEvent>,
+        exit_code: Option<i32>,
+    ) -> Result<Vec<TermEvent>> {
+        if self.screen.take_bell() {
+            events.push(TermEvent::Bell);
+        }
+        if let Some(title) = self.screen.take_title_change() {
+            events.push(TermEvent::TitleChanged(title));
+        }
+        self.track_usage(&mut events);
+        // Entering or leaving a session always prints something
+        let output = events.iter().any(|event| matches!(event, TermEvent::Output(_)));
+        if output && self.context_checked.elapsed() >= CONTEXT_INTERVAL {
+            self.context_checked = Instant::now();
+            let context = self.session_context();
+            if context != self.context {
+                self.context = context.clone();
+                events.push(TermEvent::ContextChanged(context));
+            }
+        }
+        if let Some(code) = self.held.filter(|_| self.closed) {
+            events.push(TermEvent::ProcessExit(code));
+        } else if let Some(code) = exit_code.filter(|_| self.held.is_none()) {
+            match self.process.handle_exit(code).await? {
+                ExitAction::Close => events.push(TermEvent::ProcessExit(code)),
+                // The exit banner follows as output
+                ExitAction::Hold => self.held = Some(code),
+                ExitAction::Restart => {
+                    let (cols, rows) = self.screen.size();
+                    self.process.resize(cols as u16, rows as u16).await?;
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Sample commands from OSC 133 C until the next mark, then report
+    /// their usage
+    fn track_usage(&mut self, events: &mut Vec<TermEvent>) {
+        let mark = self.screen.prompt_marks().last();
+        if mark != self.last_mark {
+            self.last_mark = mark;
+            if let Some(mut sampler) = self.usage.take() {
+                events.push(TermEvent::CommandUsage(sampler.finish()));
+            }
+            if let Some((_, PromptMark::OutputStart)) = mark {
+                self.usage = self.process.usage_sampler();
+            }
+        }
+        if let Some(sampler) = &mut self.usage {
+            if self.usage_sampled.elapsed() >= SAMPLE_INTERVAL {
+                self.usage_sampled = Instant::now();
+                sampler.sample();
+            }
+        }
+    }
+
+    /// Suppress or re-enable the bell for this pane
+    pub fn set_bell_suppressed(&mut self, suppressed: bool) {
+        self.screen.set_bell_suppressed(suppressed);
+    }
+
+    pub fn screen(&self) -> &HeadlessTerminal {
+        &self.screen
+    }
+
+    /// Whether the session is local, over SSH or in a container
+    pub fn session_context(&mut self) -> SessionContext {
+        // Without the environment the other sources still apply
+        let _ = self.process.refresh_environment();
+        let host = self.screen.working_directory().map(|(host, _)| host);
+        self.process.session_context(host)
+    }
+
+    pub async fn kill(&mut self) -> Result<()> {
+        self.process.kill().await
+    }
+}
+
+/// Parse arbitrary bytes and apply them to a fresh `cols`x`rows` screen
+///
+/// Entry point for fuzzing (see `fuzz/`): malformed input may be ignored but
+/// must never panic.
+pub fn parse_and_apply(data: &[u8], cols: usize, rows: usize) -> Result<ScreenSnapshot> {
+    let mut terminal = HeadlessTerminal::new(cols, rows);
+    terminal.feed(data)?;
+    Ok(terminal.snapshot())
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds of the frame time histogram buckets, in microseconds;
+/// a last bucket collects everything slower
+pub const FRAME_BUCKETS_US: [u64; 8] = [
+    1_000, 2_000, 4_000, 8_000, 16_667, 33_333, 50_000, 100_000,
+];
+
+/// A frame slower than this misses a 60 Hz refresh
+const FRAME_BUDGET_US: u64 = 16_667;
+
+/// Process-wide runtime counters, cheap enough to update on every read and
+/// frame
+pub struct Metrics {
+    bytes_parsed: AtomicU64,
+    actions_applied: AtomicU64,
+    frames: AtomicU64,
+    dropped_frames: AtomicU64,
+    frame_time_total_us: AtomicU64,
+    frame_buckets: [AtomicU64; FRAME_BUCKETS_US.len() + 1],
+    /// Rectangles redrawn in the last frame
+    damage_rects: AtomicU64,
+    /// Events waiting in the app's queue
+    event_queue_depth: AtomicU64,
+    memory_used: AtomicU64,
+    memory_budget: AtomicU64,
+}
+
+static METRICS: Metrics = Metrics::new();
+
+/// The metrics shared by the parser, renderer, IPC server and debug overlay
+pub fn global() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    pub const fn new() -> Self {
+        Self {
+            bytes_parsed: AtomicU64::new(0),
+            actions_applied: AtomicU64::new(0),
+            frames: AtomicU64::new(0),
+            dropped_frames: AtomicU64::new(0),
+            frame_time_total_us: AtomicU64::new(0),
+            frame_buckets: [const { AtomicU64::new(0) }; FRAME_BUCKETS_US.len() + 1],
+            damage_rects: AtomicU64::new(0),
+            event_queue_depth: AtomicU64::new(0),
+            memory_used: AtomicU64::new(0),
+            memory_budget: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_parse(&self, bytes: usize, actions: usize) {
+        self.bytes_parsed.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.actions_applied.fetch_add(actions as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_frame(&self, frame_time: Duration) {
+        let micros = frame_time.as_micros().min(u64::MAX as u128) as u64;
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.frame_time_total_us.fetch_add(micros, Ordering::Relaxed);
+        if micros > FRAME_BUDGET_US {
+            self.dropped_frames.fetch_add(1, Ordering::Relaxed);
+        }
+        let bucket = FRAME_BUCKETS_US
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(FRAME_BUCKETS_US.len());
+        self.frame_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_damage_rects(&self, rects: usize) {
+        self.damage_rects.store(rects as u64, Ordering::Relaxed);
+    }
+
+    pub fn set_event_queue_depth(&self, depth: usize) {
+        self.event_queue_depth.store(depth as u64, Ordering::Relaxed);
+    }
+
+    /// Bytes held by grids and scrollback, against the configured budget
+    pub fn set_memory(&self, used: u64, budget: u64) {
+        self.memory_used.store(used, Ordering::Relaxed);
+        self.memory_budget.store(budget, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            bytes_parsed: self.bytes_parsed.load(Ordering::Relaxed),
+            actions_applied: self.actions_applied.load(Ordering::Relaxed),
+            frames: self.frames.load(Ordering::Relaxed),
+            dropped_frames: self.dropped_frames.load(Ordering::Relaxed),
+            frame_time_total_us: self.frame_time_total_us.load(Ordering::Relaxed),
+            frame_buckets: std::array::from_fn(|i| self.frame_buckets[i].load(Ordering::Relaxed)),
+            damage_rects: self.damage_rects.load(Ordering::Relaxed),
+            event_queue_depth: self.event_queue_depth.load(Ordering::Relaxed),
+            memory_used: self.memory_used.load(Ordering::Relaxed),
+            memory_budget: self.memory_budget.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counter values at one point in time
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    pub bytes_parsed: u64,
+    pub actions_applied: u64,
+    pub frames: u64,
+    pub dropped_frames: u64,
+    pub frame_time_total_us: u64,
+    pub frame_buckets: [u64; FRAME_BUCKETS_US.len() + 1],
+    pub damage_rects: u64,
+    pub event_queue_depth: u64,
+    pub memory_used: u64,
+    pub memory_budget: u64,
+}
+
+impl MetricsSnapshot {
+    /// Mean frame time, if any frame was drawn
+    pub fn mean_frame_time(&self) -> Option<Duration> {
+        (self.frames > 0).then(|| Duration::from_micros(self.frame_time_total_us / self.frames))
+    }
+
+    /// Upper bound of the bucket holding the `quantile` (0.0-1.0) frame,
+    /// `None` when it is in the overflow bucket or no frame was drawn
+    pub fn frame_time_quantile(&self, quantile: f64) -> Option<Duration> {
+        let target = (self.frames as f64 * quantile).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, bound) in self.frame_buckets.iter().zip(FRAME_BUCKETS_US) {
+            seen += bucket;
+            if seen >= target {
+                return Some(Duration::from_micros(bound));
+            }
+        }
+        None
+    }
+
+    /// Actions applied per second between an earlier snapshot and this one
+    pub fn actions_per_sec(&self, earlier: &MetricsSnapshot, elapsed: Duration) -> f64 {
+        if elapsed.is_zero() {
+            return 0.0;
+        }
+        self.actions_applied.saturating_sub(earlier.actions_applied) as f64
+            / elapsed.as_secs_f64()
+    }
+
+    /// Flat name → value map, as sent over IPC
+    pub fn to_map(&self) -> BTreeMap<String, u64> {
+        let mut map = BTreeMap::from([
+            ("bytes_parsed".to_string(), self.bytes_parsed),
+            ("actions_applied".to_string(), self.actions_applied),
+            ("frames".to_string(), self.frames),
+            ("dropped_frames".to_string(), self.dropped_frames),
+            ("frame_time_total_us".to_string(), self.frame_time_total_us),
+            ("damage_rects".to_string(), self.damage_rects),
+            ("event_queue_depth".to_string(), self.event_queue_depth),
+            ("memory_used".to_string(), self.memory_used),
+            ("memory_budget".to_string(), self.memory_budget),
+        ]);
+        for (i, count) in self.frame_buckets.iter().enumerate() {
+            let name = match FRAME_BUCKETS_US.get(i) {
+                Some(bound) => format!("frame_time_le_{}us", bound),
+                None => "frame_time_le_inf".to_string(),
+            };
+            map.insert(name, *count);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics() {
+        let metrics = Metrics::new();
+        metrics.record_parse(100, 10);
+        metrics.record_frame(Duration::from_millis(5));
+        metrics.record_frame(Duration::from_millis(40));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.dropped_frames, 1);
+        assert_eq!(snapshot.frame_buckets[3], 1);
+        assert_eq!(snapshot.mean_frame_time(), Some(Duration::from_micros(22_500)));
+        assert_eq!(snapshot.to_map()["frame_time_le_50000us"], 1);
+        assert_eq!(snapshot.frame_time_quantile(0.5), Some(Duration::from_millis(8)));
+
+        let later = MetricsSnapshot {
+            actions_applied: 30,
+            ..snapshot
+        };
+        assert_eq!(later.actions_per_sec(&snapshot, Duration::from_secs(2)), 10.0);
+    }
+}
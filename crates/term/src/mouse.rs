@@ -0,0 +1,248 @@
+// Mouse reporting
+// Which mouse events the running program asked for, and encoding those events
+// into the reports written back to the PTY
+
+use crate::keyboard::Modifiers;
+
+/// Mouse events reported to the program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MouseTracking {
+    #[default]
+    Off,
+    /// Presses only (mode 9)
+    X10,
+    /// Presses and releases (mode 1000)
+    Normal,
+    /// Also motion while a button is held (mode 1002)
+    ButtonEvent,
+    /// Also motion with no button held (mode 1003)
+    AnyEvent,
+}
+
+/// How mouse reports encode buttons and coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MouseEncoding {
+    /// `CSI M` followed by three bytes, limited to 223 columns
+    #[default]
+    Default,
+    /// `CSI M` with UTF-8 encoded coordinates (mode 1005)
+    Utf8,
+    /// `CSI < b ; x ; y M/m` (mode 1006)
+    Sgr,
+    /// `CSI b ; x ; y M` (mode 1015)
+    Urxvt,
+}
+
+/// Mouse-related modes set by the running program
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MouseModes {
+    pub tracking: MouseTracking,
+    pub encoding: MouseEncoding,
+}
+
+impl MouseModes {
+    /// Apply a DECSET/DECRST mode; false if `mode` is not a mouse mode
+    pub fn set(&mut self, mode: u32, enable: bool) -> bool {
+        let tracking = match mode {
+            9 => Some(MouseTracking::X10),
+            1000 => Some(MouseTracking::Normal),
+            1002 => Some(MouseTracking::ButtonEvent),
+            1003 => Some(MouseTracking::AnyEvent),
+            _ => None,
+        };
+        let encoding = match mode {
+            1005 => Some(MouseEncoding::Utf8),
+            1006 => Some(MouseEncoding::Sgr),
+            1015 => Some(MouseEncoding::Urxvt),
+            _ => None,
+        };
+        match (tracking, encoding) {
+            // Like xterm, resetting any tracking mode turns reporting off
+            (Some(tracking), _) => {
+                self.tracking = if enable { tracking } else { MouseTracking::Off };
+            }
+            (_, Some(encoding)) => {
+                if enable {
+                    self.encoding = encoding;
+                } else if self.encoding == encoding {
+                    self.encoding = MouseEncoding::Default;
+                }
+            }
+            (None, None) => return false,
+        }
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+    WheelLeft,
+    WheelRight,
+}
+
+impl MouseButton {
+    fn is_wheel(self) -> bool {
+        !matches!(self, MouseButton::Left | MouseButton::Middle | MouseButton::Right)
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::WheelUp => 64,
+            MouseButton::WheelDown => 65,
+            MouseButton::WheelLeft => 66,
+            MouseButton::WheelRight => 67,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEventKind {
+    Press,
+    Release,
+    /// The pointer moved to another cell; `button` is the one held, if any
+    Motion,
+}
+
+/// A mouse event from the UI, on a zero-based cell of the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub button: Option<MouseButton>,
+    pub row: usize,
+    pub col: usize,
+    pub modifiers: Modifiers,
+}
+
+impl MouseModes {
+    /// Encode `event` for the program, or `None` if it did not ask for it
+    pub fn encode(&self, event: &MouseEvent) -> Option<Vec<u8>> {
+        let wanted = match (self.tracking, event.kind) {
+            (MouseTracking::Off, _) => false,
+            (_, MouseEventKind::Press) => event.button.is_some(),
+            (MouseTracking::X10, _) => false,
+            // Wheel "buttons" are never released
+            (_, MouseEventKind::Release) => !event.button.is_some_and(MouseButton::is_wheel),
+            (MouseTracking::Normal, MouseEventKind::Motion) => false,
+            (MouseTracking::ButtonEvent, MouseEventKind::Motion) => event.button.is_some(),
+            (MouseTracking::AnyEvent, MouseEventKind::Motion) => true,
+        };
+        if !wanted {
+            return None;
+        }
+
+        // Button code: 3 for a release or motion without a button, except
+        // that SGR reports which button was released
+        let mut code = match event.button {
+            Some(button)
+                if event.kind != MouseEventKind::Release
+                    || self.encoding == MouseEncoding::Sgr =>
+            {
+                button.code()
+            }
+            _ => 3,
+        };
+        if event.kind == MouseEventKind::Motion {
+            code += 32;
+        }
+        if self.tracking != MouseTracking::X10 {
+            let mods = event.modifiers;
+            code += 4 * mods.shift as u32 + 8 * mods.alt as u32 + 16 * mods.ctrl as u32;
+        }
+        let (x, y) = (event.col as u32 + 1, event.row as u32 + 1);
+
+        match self.encoding {
+            MouseEncoding::Sgr => {
+                let last = if event.kind == MouseEventKind::Release { 'm' } else { 'M' };
+                Some(format!("\x1b[<{};{};{}{}", code, x, y, last).into_bytes())
+            }
+            MouseEncoding::Urxvt => Some(format!("\x1b[{};{};{}M", code + 32, x, y).into_bytes()),
+            MouseEncoding::Default => {
+                // Positions past 223 do not fit in a byte; xterm drops them
+                let bytes = [code + 32, x + 32, y + 32];
+                if bytes.iter().any(|&b| b > 0xff) {
+                    return None;
+                }
+                let mut report = b"\x1b[M".to_vec();
+                report.extend(bytes.iter().map(|&b| b as u8));
+                Some(report)
+            }
+            MouseEncoding::Utf8 => {
+                let mut report = String::from("\x1b[M");
+                for value in [code + 32, x + 32, y + 32] {
+                    report.push(char::from_u32(value).filter(|_| value < 0x800)?);
+                }
+                Some(report.into_bytes())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mouse_modes() {
+        let mut modes = MouseModes::default();
+        assert!(modes.set(1002, true));
+        assert!(modes.set(1006, true));
+        assert_eq!(modes.tracking, MouseTracking::ButtonEvent);
+        assert_eq!(modes.encoding, MouseEncoding::Sgr);
+
+        assert!(modes.set(1015, false));
+        assert_eq!(modes.encoding, MouseEncoding::Sgr);
+        assert!(modes.set(1000, false));
+        assert_eq!(modes.tracking, MouseTracking::Off);
+        assert!(!modes.set(25, true));
+    }
+
+    #[test]
+    fn test_encode() {
+        let press = MouseEvent {
+            kind: MouseEventKind::Press,
+            button: Some(MouseButton::Left),
+            row: 4,
+            col: 9,
+            modifiers: Modifiers::default(),
+        };
+        let release = MouseEvent {
+            kind: MouseEventKind::Release,
+            ..press
+        };
+        let drag = MouseEvent {
+            kind: MouseEventKind::Motion,
+            modifiers: Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            },
+            ..press
+        };
+
+        let mut modes = MouseModes::default();
+        assert_eq!(modes.encode(&press), None);
+
+        modes.set(1000, true);
+        assert_eq!(modes.encode(&press).unwrap(), b"\x1b[M *%");
+        assert_eq!(modes.encode(&release).unwrap(), b"\x1b[M#*%");
+        assert_eq!(modes.encode(&drag), None);
+
+        modes.set(1002, true);
+        modes.set(1006, true);
+        assert_eq!(modes.encode(&drag).unwrap(), b"\x1b[<48;10;5M");
+        assert_eq!(modes.encode(&release).unwrap(), b"\x1b[<0;10;5m");
+
+        let wheel = MouseEvent {
+            button: Some(MouseButton::WheelDown),
+            ..press
+        };
+        assert_eq!(modes.encode(&wheel).unwrap(), b"\x1b[<65;10;5M");
+    }
+}
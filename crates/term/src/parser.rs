@@ -1,198 +1,921 @@
 // Terminal parser implementation
 // Handles parsing of terminal output data and escape sequences
+//
+// The state machine follows the DEC-compatible parser described at
+// https://vt100.net/emu/dec_ansi_parser: a table maps each state and byte to
+// an action and the next state, so intermediates, parameters, C0 controls
+// inside sequences and 8-bit C1 controls are handled the way xterm does.
+
+use std::sync::Arc;
 
 use anyhow::Result;
+use tracing::{debug, trace_span};
+
+use crate::hooks::{ExtensionHooks, ExtensionSequence};
+use crate::prompts::PromptMark;
+use crate::remote::parse_osc7;
+use crate::sixel::{SixelDecoder, SixelImage};
+use crate::vt::{Charset, LineSize};
+
+/// Primary device attributes (DA1): a VT220 with sixel graphics and ANSI color
+const PRIMARY_DEVICE_ATTRIBUTES: &[u8] = b"\x1b[?62;4;22c";
+
+/// Most parameters and sub-parameters kept for one sequence; longer
+/// sequences are ignored
+const MAX_PARAMS: usize = 32;
+
+/// Most intermediate bytes kept for one sequence
+const MAX_INTERMEDIATES: usize = 2;
 
 /// Terminal parser that processes and interprets escape sequences
 pub struct TerminalParser {
     // Parser state
-    state: ParserState,
-    // Current escape sequence being built
-    escape_buffer: Vec<u8>,
-    // Max size of escape buffer to prevent overflow
+    state: State,
+    /// The sequence being parsed in its 7-bit form, for string data and
+    /// for logging unhandled sequences
+    buffer: Vec<u8>,
+    // Max size of the buffer to prevent overflow
     max_escape_len: usize,
+    /// The sequence outgrew `buffer` or the parameter limits; it is ignored
+    overflow: bool,
+    /// Parameters and sub-parameters, in order
+    params: Vec<u32>,
+    /// Bit `i` is set when `params[i]` is a sub-parameter (after `:`)
+    subparams: u32,
+    /// Private marker, e.g. `?` in `CSI ? 1 h`
+    prefix: Option<u8>,
+    intermediates: [u8; MAX_INTERMEDIATES],
+    intermediate_len: usize,
+    /// Treat 0x80-0x9F as UTF-8 continuation bytes rather than C1 controls
+    utf8_only: bool,
+    /// Unhandled sequences since the last `take_unhandled`, when recording
+    unhandled: Option<Vec<String>>,
+    /// OSC numbers claimed by extension hooks
+    extension_osc: Vec<u32>,
+    /// Report APC strings for extension hooks instead of ignoring them
+    extension_apc: bool,
+    /// The current SOS/PM/APC string is an APC for extension hooks
+    apc: bool,
+    /// What receives the data of the current DCS string
+    dcs: DcsHandler,
+    /// Where the data of a DCS query starts in `buffer`
+    dcs_data_start: usize,
+    /// Bytes of a UTF-8 sequence received so far, with `utf8_len` in use
+    utf8_buffer: [u8; 4],
+    utf8_len: usize,
+    /// Length of the UTF-8 sequence being received
+    utf8_expected: usize,
 }
 
-/// Enum representing different parser states
-enum ParserState {
-    /// Normal processing state
-    Normal,
-    /// Processing an escape sequence
+/// Parser states, named as in the DEC parser description
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Ground,
     Escape,
-    /// Processing an OSC (Operating System Command)
-    Osc,
-    /// Processing a CSI (Control Sequence Introducer)
-    Csi,
+    EscapeIntermediate,
+    CsiEntry,
+    CsiParam,
+    CsiIntermediate,
+    CsiIgnore,
+    DcsEntry,
+    DcsParam,
+    DcsIntermediate,
+    DcsPassthrough,
+    DcsIgnore,
+    OscString,
+    SosPmApcString,
+}
+
+const STATE_COUNT: usize = 14;
+
+const STATES: [State; STATE_COUNT] = [
+    State::Ground,
+    State::Escape,
+    State::EscapeIntermediate,
+    State::CsiEntry,
+    State::CsiParam,
+    State::CsiIntermediate,
+    State::CsiIgnore,
+    State::DcsEntry,
+    State::DcsParam,
+    State::DcsIntermediate,
+    State::DcsPassthrough,
+    State::DcsIgnore,
+    State::OscString,
+    State::SosPmApcString,
+];
+
+/// What to do with a byte; entering and leaving states does the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Ignore,
+    Print,
+    /// Run a C0 or C1 control
+    Execute,
+    /// Keep an intermediate byte or private marker
+    Collect,
+    /// Add a digit or separator to the parameters
+    Param,
+    EscDispatch,
+    CsiDispatch,
+    /// Pass string data to the OSC, DCS or APC being received
+    Put,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    action: Action,
+    /// The state to enter, `None` to stay without leaving the current one
+    next: Option<State>,
+}
+
+type Table = [[Transition; 256]; STATE_COUNT];
+
+/// Transitions when 0x80-0xFF are UTF-8
+static UTF8_TABLE: Table = build_table(false);
+/// Transitions when 0x80-0x9F are C1 controls
+static C1_TABLE: Table = build_table(true);
+
+const fn build_table(c1: bool) -> Table {
+    let mut table = [[Transition {
+        action: Action::Ignore,
+        next: None,
+    }; 256]; STATE_COUNT];
+    let mut state = 0;
+    while state < STATE_COUNT {
+        let mut byte = 0;
+        while byte < 256 {
+            table[state][byte] = transition(STATES[state], byte as u8, c1);
+            byte += 1;
+        }
+        state += 1;
+    }
+    table
+}
+
+const fn stay(action: Action) -> Transition {
+    Transition { action, next: None }
+}
+
+const fn enter(action: Action, state: State) -> Transition {
+    Transition {
+        action,
+        next: Some(state),
+    }
+}
+
+const fn transition(state: State, byte: u8, c1: bool) -> Transition {
+    use Action::*;
+
+    // Transitions from any state
+    match byte {
+        // CAN and SUB abort a sequence
+        0x18 | 0x1A => return enter(Execute, State::Ground),
+        0x1B => return enter(Ignore, State::Escape),
+        0x80..=0x9F if c1 => {
+            return match byte {
+                0x90 => enter(Ignore, State::DcsEntry),
+                0x9B => enter(Ignore, State::CsiEntry),
+                // ST
+                0x9C => enter(Ignore, State::Ground),
+                0x9D => enter(Ignore, State::OscString),
+                0x98 | 0x9E | 0x9F => enter(Ignore, State::SosPmApcString),
+                _ => enter(Execute, State::Ground),
+            };
+        }
+        _ => {}
+    }
+
+    // From 0x80, bytes are characters or string data; they end control
+    // sequences, which are ASCII only
+    match state {
+        State::Ground => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x7F => stay(Ignore),
+            _ => stay(Print),
+        },
+        State::Escape => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x20..=0x2F => enter(Collect, State::EscapeIntermediate),
+            b'[' => enter(Ignore, State::CsiEntry),
+            b']' => enter(Ignore, State::OscString),
+            b'P' => enter(Ignore, State::DcsEntry),
+            b'X' | b'^' | b'_' => enter(Ignore, State::SosPmApcString),
+            0x30..=0x7E => enter(EscDispatch, State::Ground),
+            0x7F => stay(Ignore),
+            _ => enter(Ignore, State::Ground),
+        },
+        State::EscapeIntermediate => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x20..=0x2F => stay(Collect),
+            0x30..=0x7E => enter(EscDispatch, State::Ground),
+            0x7F => stay(Ignore),
+            _ => enter(Ignore, State::Ground),
+        },
+        State::CsiEntry => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x20..=0x2F => enter(Collect, State::CsiIntermediate),
+            0x30..=0x3B => enter(Param, State::CsiParam),
+            0x3C..=0x3F => enter(Collect, State::CsiParam),
+            0x40..=0x7E => enter(CsiDispatch, State::Ground),
+            0x7F => stay(Ignore),
+            _ => enter(Ignore, State::Ground),
+        },
+        State::CsiParam => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x20..=0x2F => enter(Collect, State::CsiIntermediate),
+            0x30..=0x3B => stay(Param),
+            0x3C..=0x3F => enter(Ignore, State::CsiIgnore),
+            0x40..=0x7E => enter(CsiDispatch, State::Ground),
+            0x7F => stay(Ignore),
+            _ => enter(Ignore, State::Ground),
+        },
+        State::CsiIntermediate => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x20..=0x2F => stay(Collect),
+            0x30..=0x3F => enter(Ignore, State::CsiIgnore),
+            0x40..=0x7E => enter(CsiDispatch, State::Ground),
+            0x7F => stay(Ignore),
+            _ => enter(Ignore, State::Ground),
+        },
+        State::CsiIgnore => match byte {
+            0x00..=0x1F => stay(Execute),
+            0x40..=0x7E => enter(Ignore, State::Ground),
+            0x80..=0xFF => enter(Ignore, State::Ground),
+            _ => stay(Ignore),
+        },
+        State::DcsEntry => match byte {
+            0x20..=0x2F => enter(Collect, State::DcsIntermediate),
+            0x30..=0x39 | b';' => enter(Param, State::DcsParam),
+            b':' => enter(Ignore, State::DcsIgnore),
+            0x3C..=0x3F => enter(Collect, State::DcsParam),
+            0x40..=0x7E => enter(Ignore, State::DcsPassthrough),
+            0x80..=0xFF => enter(Ignore, State::DcsIgnore),
+            _ => stay(Ignore),
+        },
+        State::DcsParam => match byte {
+            0x20..=0x2F => enter(Collect, State::DcsIntermediate),
+            0x30..=0x39 | b';' => stay(Param),
+            b':' | 0x3C..=0x3F => enter(Ignore, State::DcsIgnore),
+            0x40..=0x7E => enter(Ignore, State::DcsPassthrough),
+            0x80..=0xFF => enter(Ignore, State::DcsIgnore),
+            _ => stay(Ignore),
+        },
+        State::DcsIntermediate => match byte {
+            0x20..=0x2F => stay(Collect),
+            0x30..=0x3F => enter(Ignore, State::DcsIgnore),
+            0x40..=0x7E => enter(Ignore, State::DcsPassthrough),
+            0x80..=0xFF => enter(Ignore, State::DcsIgnore),
+            _ => stay(Ignore),
+        },
+        State::DcsPassthrough => match byte {
+            0x7F => stay(Ignore),
+            _ => stay(Put),
+        },
+        State::DcsIgnore => stay(Ignore),
+        // Like xterm, BEL also ends OSC and APC strings
+        State::OscString | State::SosPmApcString => match byte {
+            0x07 => enter(Ignore, State::Ground),
+            0x00..=0x1F => stay(Ignore),
+            _ => stay(Put),
+        },
+    }
+}
+
+/// What receives the data of a DCS string
+enum DcsHandler {
+    /// Unsupported; the data is dropped
+    Ignore,
+    /// Decoding a sixel image
+    Sixel(SixelDecoder),
+    /// Collecting a query answered once the string ends
+    Query(DcsQuery),
+}
+
+/// DCS strings answered once their data is complete
+#[derive(Clone, Copy)]
+enum DcsQuery {
+    /// DECRQSS, `DCS $ q Pt ST`
+    Setting,
+    /// XTGETTCAP, `DCS + q Pt ST` with hex encoded capability names
+    Termcap,
 }
 
 impl TerminalParser {
     /// Create a new terminal parser
     pub fn new() -> Self {
         Self {
-            state: ParserState::Normal,
-            escape_buffer: Vec::with_capacity(128),
+            state: State::Ground,
+            buffer: Vec::with_capacity(128),
             max_escape_len: 1024,
+            overflow: false,
+            params: Vec::with_capacity(MAX_PARAMS),
+            subparams: 0,
+            prefix: None,
+            intermediates: [0; MAX_INTERMEDIATES],
+            intermediate_len: 0,
+            utf8_only: true,
+            unhandled: None,
+            extension_osc: Vec::new(),
+            extension_apc: false,
+            apc: false,
+            dcs: DcsHandler::Ignore,
+            dcs_data_start: 0,
+            utf8_buffer: [0; 4],
+            utf8_len: 0,
+            utf8_expected: 0,
         }
     }
 
+    /// Report the sequences `hooks` claim as `TerminalAction::Extension`
+    pub fn set_extensions(&mut self, hooks: &ExtensionHooks) {
+        self.extension_osc = hooks.osc_numbers();
+        self.extension_apc = hooks.wants_apc();
+    }
+
+    /// Keep the unhandled sequences for `take_unhandled`, for the inspector
+    pub fn set_record_unhandled(&mut self, record: bool) {
+        self.unhandled = record.then(Vec::new);
+    }
+
+    /// Unhandled sequences, escaped, seen since the last call
+    pub fn take_unhandled(&mut self) -> Vec<String> {
+        self.unhandled.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Choose between UTF-8-only mode and interpreting 8-bit C1 controls
+    pub fn set_utf8_only(&mut self, utf8_only: bool) {
+        self.utf8_only = utf8_only;
+    }
+
     /// Parse terminal output data
     /// Returns processed data and actions to perform
     pub fn parse(&mut self, data: &[u8]) -> Result<Vec<TerminalAction>> {
         let mut actions = Vec::new();
+        self.parse_into(data, &mut actions)?;
+        Ok(actions)
+    }
+
+    /// Like `parse`, appending to `actions` so its allocation can be reused
+    /// across reads
+    pub fn parse_into(&mut self, data: &[u8], actions: &mut Vec<TerminalAction>) -> Result<()> {
+        let _span = trace_span!("parse", bytes = data.len()).entered();
+        let table = if self.utf8_only { &UTF8_TABLE } else { &C1_TABLE };
 
         for &byte in data {
-            match self.state {
-                ParserState::Normal => {
-                    match byte {
-                        // ESC character
-                        0x1b => {
-                            self.escape_buffer.clear();
-                            self.escape_buffer.push(byte);
-                            self.state = ParserState::Escape;
-                        }
-                        // Handle other control characters
-                        0x07 => actions.push(TerminalAction::Bell),
-                        0x08 => actions.push(TerminalAction::Backspace),
-                        0x09 => actions.push(TerminalAction::Tab),
-                        0x0A => actions.push(TerminalAction::LineFeed),
-                        0x0D => actions.push(TerminalAction::CarriageReturn),
-                        // Normal printable character
-                        _ => actions.push(TerminalAction::Print(byte)),
+            // Text skips the table
+            if self.state == State::Ground {
+                if self.utf8_len > 0 {
+                    // In C1 mode 0x80-0x9F are controls even mid-sequence
+                    let continuation =
+                        matches!(byte, 0x80..=0xBF) && (self.utf8_only || byte >= 0xA0);
+                    if continuation {
+                        actions.extend(self.continue_utf8(byte));
+                        continue;
                     }
+                    // Truncated sequence
+                    self.utf8_len = 0;
+                    actions.push(TerminalAction::Print(char::REPLACEMENT_CHARACTER));
                 }
-                ParserState::Escape => {
-                    self.escape_buffer.push(byte);
-                    match byte {
-                        // OSC - Operating System Command
-                        b']' => {
-                            self.state = ParserState::Osc;
-                        }
-                        // CSI - Control Sequence Introducer
-                        b'[' => {
-                            self.state = ParserState::Csi;
-                        }
-                        // Other escape sequences
-                        _ => {
-                            // Process simple escape sequence
-                            if let Some(action) = self.process_simple_escape_sequence() {
-                                actions.push(action);
-                            }
-                            self.state = ParserState::Normal;
-                        }
+                match byte {
+                    0x20..=0x7E => {
+                        actions.push(TerminalAction::Print(byte as char));
+                        continue;
+                    }
+                    0x80..=0x9F if !self.utf8_only => {}
+                    0x80..=0xFF => {
+                        actions.extend(self.start_utf8(byte));
+                        continue;
                     }
+                    _ => {}
                 }
-                ParserState::Csi => {
-                    self.escape_buffer.push(byte);
+            }
 
-                    // End of CSI sequence
-                    if byte >= 0x40 && byte <= 0x7E {
-                        if let Some(action) = self.process_csi_sequence() {
-                            actions.push(action);
-                        }
-                        self.state = ParserState::Normal;
-                    }
+            let transition = table[self.state as usize][byte as usize];
+            if transition.next.is_some() {
+                self.leave_state(byte, actions);
+            }
+            self.perform(transition.action, byte, actions);
+            if let Some(next) = transition.next {
+                self.state = next;
+                self.enter_state(byte);
+            }
+        }
 
-                    // Safety check for malformed sequences
-                    if self.escape_buffer.len() > self.max_escape_len {
-                        self.state = ParserState::Normal;
-                    }
+        Ok(())
+    }
+
+    /// Begin a multi-byte UTF-8 sequence at its lead byte; bytes that cannot
+    /// start one print as U+FFFD
+    fn start_utf8(&mut self, byte: u8) -> Option<TerminalAction> {
+        self.utf8_expected = match byte {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Some(TerminalAction::Print(char::REPLACEMENT_CHARACTER)),
+        };
+        self.utf8_buffer[0] = byte;
+        self.utf8_len = 1;
+        None
+    }
+
+    /// Add a continuation byte, printing the character once it is complete;
+    /// overlong forms and surrogates print as U+FFFD
+    fn continue_utf8(&mut self, byte: u8) -> Option<TerminalAction> {
+        self.utf8_buffer[self.utf8_len] = byte;
+        self.utf8_len += 1;
+        if self.utf8_len < self.utf8_expected {
+            return None;
+        }
+        let sequence = &self.utf8_buffer[..std::mem::take(&mut self.utf8_len)];
+        let c = std::str::from_utf8(sequence)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        Some(TerminalAction::Print(c))
+    }
+
+    fn perform(&mut self, action: Action, byte: u8, actions: &mut Vec<TerminalAction>) {
+        match action {
+            Action::Ignore => {}
+            Action::Print => actions.push(TerminalAction::Print(byte as char)),
+            Action::Execute => self.execute(byte, actions),
+            Action::Collect => self.collect(byte),
+            Action::Param => self.param(byte),
+            Action::EscDispatch => self.esc_dispatch(byte, actions),
+            Action::CsiDispatch => {
+                self.push_buffer(byte);
+                match self.process_csi_sequence(byte) {
+                    Some(action) if !self.overflow => actions.push(action),
+                    _ => self.note_unhandled("CSI"),
+                }
+            }
+            Action::Put => match (self.state, &mut self.dcs) {
+                (State::DcsPassthrough, DcsHandler::Sixel(sixel)) => sixel.feed(byte),
+                (State::DcsPassthrough, DcsHandler::Ignore) => {}
+                (State::SosPmApcString, _) if !self.apc => {}
+                _ => self.push_buffer(byte),
+            },
+        }
+    }
+
+    /// Start a sequence or string on entering its first state
+    fn enter_state(&mut self, byte: u8) {
+        match self.state {
+            State::Escape => self.clear(b"\x1b"),
+            State::CsiEntry => self.clear(b"\x1b["),
+            State::DcsEntry => self.clear(b"\x1bP"),
+            State::OscString => self.clear(b"\x1b]"),
+            State::SosPmApcString => {
+                let introducer = seven_bit(byte);
+                self.clear(&[0x1b, introducer]);
+                self.apc = introducer == b'_' && self.extension_apc;
+                // SOS, PM and APC strings are not supported otherwise
+                if !self.apc {
+                    self.note_unhandled("string");
+                }
+            }
+            State::DcsPassthrough => self.hook(byte),
+            State::CsiIgnore => {
+                self.push_buffer(byte);
+                self.note_unhandled("CSI");
+            }
+            State::DcsIgnore => {
+                self.push_buffer(byte);
+                self.note_unhandled("DCS");
+            }
+            _ => {}
+        }
+    }
+
+    /// Finish a string on leaving its state; CAN and SUB cancel it instead
+    fn leave_state(&mut self, byte: u8, actions: &mut Vec<TerminalAction>) {
+        let cancelled = matches!(byte, 0x18 | 0x1A);
+        match self.state {
+            State::OscString if !cancelled => {
+                match self.process_osc_sequence().filter(|_| !self.overflow) {
+                    Some(action) => actions.push(action),
+                    None => self.note_unhandled("OSC"),
+                }
+            }
+            State::SosPmApcString if self.apc && !cancelled => {
+                if self.overflow {
+                    // Too long for a hook
+                    self.note_unhandled("APC");
+                } else {
+                    let payload = String::from_utf8_lossy(&self.buffer[2..]).into_owned();
+                    actions.push(TerminalAction::Extension(ExtensionSequence::Apc(payload)));
                 }
-                ParserState::Osc => {
-                    self.escape_buffer.push(byte);
-
-                    // End of OSC sequence (BEL or ST)
-                    if byte == 0x07
-                        || (byte == 0x5c
-                            && self.escape_buffer.len() >= 2
-                            && self.escape_buffer[self.escape_buffer.len() - 2] == 0x1b)
-                    {
-                        if let Some(action) = self.process_osc_sequence() {
-                            actions.push(action);
+            }
+            State::DcsPassthrough => {
+                match std::mem::replace(&mut self.dcs, DcsHandler::Ignore) {
+                    DcsHandler::Sixel(sixel) if !cancelled => {
+                        if let Some(image) = sixel.finish() {
+                            actions.push(TerminalAction::SixelImage(Arc::new(image)));
                         }
-                        self.state = ParserState::Normal;
                     }
-
-                    // Safety check for malformed sequences
-                    if self.escape_buffer.len() > self.max_escape_len {
-                        self.state = ParserState::Normal;
+                    DcsHandler::Query(_) if self.overflow => self.note_unhandled("DCS"),
+                    DcsHandler::Query(query) if !cancelled => {
+                        actions.push(self.process_dcs_query(query));
                     }
+                    _ => {}
                 }
             }
+            _ => {}
         }
+    }
 
-        Ok(actions)
+    /// Forget the previous sequence; `introducer` starts the new one
+    fn clear(&mut self, introducer: &[u8]) {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(introducer);
+        self.overflow = false;
+        self.params.clear();
+        self.subparams = 0;
+        self.prefix = None;
+        self.intermediate_len = 0;
     }
 
-    fn process_simple_escape_sequence(&self) -> Option<TerminalAction> {
-        if self.escape_buffer.len() < 2 {
-            return None;
+    fn push_buffer(&mut self, byte: u8) {
+        if self.buffer.len() < self.max_escape_len {
+            self.buffer.push(byte);
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn execute(&mut self, byte: u8, actions: &mut Vec<TerminalAction>) {
+        let action = match byte {
+            0x07 => TerminalAction::Bell,
+            0x08 => TerminalAction::Backspace,
+            0x09 => TerminalAction::Tab,
+            // VT and FF act like LF
+            0x0A..=0x0C => TerminalAction::LineFeed,
+            0x0D => TerminalAction::CarriageReturn,
+            0x0E => TerminalAction::ShiftOut,
+            0x0F => TerminalAction::ShiftIn,
+            // 8-bit C1 controls act like their 7-bit `ESC Fe` equivalents
+            0x80..=0x9F => {
+                self.clear(b"\x1b");
+                self.esc_dispatch(byte - 0x40, actions);
+                return;
+            }
+            _ => return,
+        };
+        actions.push(action);
+    }
+
+    fn collect(&mut self, byte: u8) {
+        self.push_buffer(byte);
+        if matches!(byte, 0x3C..=0x3F) {
+            self.prefix = Some(byte);
+        } else if self.intermediate_len < MAX_INTERMEDIATES {
+            self.intermediates[self.intermediate_len] = byte;
+            self.intermediate_len += 1;
+        } else {
+            self.overflow = true;
+        }
+    }
+
+    fn param(&mut self, byte: u8) {
+        self.push_buffer(byte);
+        if self.params.is_empty() {
+            self.params.push(0);
+        }
+        match byte {
+            b';' | b':' => {
+                if self.params.len() == MAX_PARAMS {
+                    self.overflow = true;
+                    return;
+                }
+                if byte == b':' {
+                    self.subparams |= 1 << self.params.len();
+                }
+                self.params.push(0);
+            }
+            _ => {
+                let param = self.params.last_mut().expect("a parameter was pushed");
+                *param = param.saturating_mul(10).saturating_add((byte - b'0') as u32);
+            }
+        }
+    }
+
+    fn intermediates(&self) -> &[u8] {
+        &self.intermediates[..self.intermediate_len]
+    }
+
+    /// Parameters without their sub-parameters
+    fn top_params(&self) -> Vec<u32> {
+        self.params
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.subparams & (1 << i) == 0)
+            .map(|(_, &param)| param)
+            .collect()
+    }
+
+    /// SGR parameters with sub-parameters flattened into the forms the
+    /// screen understands, e.g. `38:2::255:0:0` as `38;2;255;0;0`
+    fn sgr_params(&self) -> Vec<u32> {
+        let mut flat = Vec::with_capacity(self.params.len());
+        let mut start = 0;
+        while start < self.params.len() {
+            let mut end = start + 1;
+            while end < self.params.len() && self.subparams & (1 << end) != 0 {
+                end += 1;
+            }
+            match self.params[start..end] {
+                [param] => flat.push(param),
+                // Underline styles; any style but 0 (none) is a plain underline
+                [4, style] => flat.push(if style == 0 { 24 } else { 4 }),
+                [color @ (38 | 48), 5, index] => flat.extend([color, 5, index]),
+                // The color space id before the components is optional
+                [color @ (38 | 48), 2, _, r, g, b] | [color @ (38 | 48), 2, r, g, b] => {
+                    flat.extend([color, 2, r, g, b])
+                }
+                // Other sub-parameters, e.g. underline colors, are not supported
+                _ => {}
+            }
+            start = end;
+        }
+        flat
+    }
+
+    fn esc_dispatch(&mut self, byte: u8, actions: &mut Vec<TerminalAction>) {
+        self.push_buffer(byte);
+        // The ST ending a string already handled, e.g. sixel
+        if byte == b'\\' && self.intermediate_len == 0 {
+            return;
+        }
+        match self.process_simple_escape_sequence(byte) {
+            Some(action) if !self.overflow => actions.push(action),
+            _ => self.note_unhandled("ESC"),
+        }
+    }
+
+    /// Choose what receives the data of a DCS string from its final byte
+    fn hook(&mut self, byte: u8) {
+        self.push_buffer(byte);
+        self.dcs = match (self.prefix, self.intermediates(), byte) {
+            _ if self.overflow => DcsHandler::Ignore,
+            (None, [], b'q') => DcsHandler::Sixel(SixelDecoder::new(&self.top_params())),
+            (None, [b'$'], b'q') if self.params.is_empty() => DcsHandler::Query(DcsQuery::Setting),
+            (None, [b'+'], b'q') if self.params.is_empty() => DcsHandler::Query(DcsQuery::Termcap),
+            _ => DcsHandler::Ignore,
+        };
+        if let DcsHandler::Ignore = self.dcs {
+            self.note_unhandled("DCS");
+        }
+        self.dcs_data_start = self.buffer.len();
+    }
+
+    /// Answer a complete DECRQSS or XTGETTCAP query
+    fn process_dcs_query(&self, query: DcsQuery) -> TerminalAction {
+        let data = String::from_utf8_lossy(&self.buffer[self.dcs_data_start..]).into_owned();
+        match query {
+            DcsQuery::Setting => TerminalAction::RequestSetting(data),
+            DcsQuery::Termcap => {
+                let mut response = Vec::new();
+                for name in data.split(';') {
+                    let reply = match termcap_value(name) {
+                        Some(value) => format!("\x1bP1+r{}={}\x1b\\", name, hex_encode(value)),
+                        None => format!("\x1bP0+r{}\x1b\\", name),
+                    };
+                    response.extend_from_slice(reply.as_bytes());
+                }
+                TerminalAction::Response(response)
+            }
         }
+    }
 
-        match self.escape_buffer[1] {
-            b'A' => Some(TerminalAction::CursorUp(1)),
-            b'B' => Some(TerminalAction::CursorDown(1)),
-            b'C' => Some(TerminalAction::CursorForward(1)),
-            b'D' => Some(TerminalAction::CursorBackward(1)),
-            b'E' => Some(TerminalAction::CursorNextLine(1)),
-            b'F' => Some(TerminalAction::CursorPreviousLine(1)),
-            b'H' => Some(TerminalAction::CursorPosition(1, 1)),
-            b'J' => Some(TerminalAction::EraseInDisplay(0)),
-            b'K' => Some(TerminalAction::EraseInLine(0)),
-            b'M' => Some(TerminalAction::ScrollUp(1)),
-            b'c' => Some(TerminalAction::Reset),
+    fn process_simple_escape_sequence(&self, final_byte: u8) -> Option<TerminalAction> {
+        match (self.intermediates(), final_byte) {
+            ([], b'D') => Some(TerminalAction::Index),
+            ([], b'E') => Some(TerminalAction::NextLine),
+            // HTS; `ESC H` is only cursor home in VT52 mode, which is not
+            // supported
+            ([], b'H') => Some(TerminalAction::SetTabStop),
+            ([], b'M') => Some(TerminalAction::ReverseIndex),
+            ([], b'7') => Some(TerminalAction::SaveCursor),
+            ([], b'8') => Some(TerminalAction::RestoreCursor),
+            ([], b'c') => Some(TerminalAction::Reset),
+            ([], b'=') => Some(TerminalAction::ApplicationKeypad(true)),
+            ([], b'>') => Some(TerminalAction::ApplicationKeypad(false)),
+            ([b'#'], b'3') => Some(TerminalAction::SetLineSize(LineSize::DoubleHeightTop)),
+            ([b'#'], b'4') => Some(TerminalAction::SetLineSize(LineSize::DoubleHeightBottom)),
+            ([b'#'], b'5') => Some(TerminalAction::SetLineSize(LineSize::Single)),
+            ([b'#'], b'6') => Some(TerminalAction::SetLineSize(LineSize::DoubleWidth)),
+            ([b'#'], b'8') => Some(TerminalAction::ScreenAlignmentTest),
+            // SCS for G0 and G1
+            ([slot @ (b'(' | b')')], _) => {
+                let charset = match final_byte {
+                    b'B' => Charset::Ascii,
+                    b'0' => Charset::DecSpecialGraphics,
+                    b'A' => Charset::Uk,
+                    _ => return None,
+                };
+                Some(TerminalAction::DesignateCharset(slot - b'(', charset))
+            }
             _ => None,
         }
     }
 
-    fn process_csi_sequence(&self) -> Option<TerminalAction> {
-        if self.escape_buffer.len() < 3 {
-            return None;
+    /// Log a sequence the parser does not handle, so it can be debugged,
+    /// and keep it for the inspector when recording
+    fn note_unhandled(&mut self, kind: &str) {
+        let sequence = String::from_utf8_lossy(&self.buffer)
+            .escape_debug()
+            .to_string();
+        debug!("Ignoring unhandled {} sequence: {}", kind, sequence);
+        if let Some(unhandled) = &mut self.unhandled {
+            unhandled.push(sequence);
         }
+    }
 
-        let final_byte = *self.escape_buffer.last()?;
-        let params_str =
-            String::from_utf8_lossy(&self.escape_buffer[2..(self.escape_buffer.len() - 1)]);
-        let params: Vec<u32> = params_str
-            .split(';')
-            .filter_map(|s| s.parse::<u32>().ok())
-            .collect();
+    fn process_csi_sequence(&self, final_byte: u8) -> Option<TerminalAction> {
+        if final_byte == b'm' && self.prefix.is_none() && self.intermediate_len == 0 {
+            return Some(TerminalAction::SetGraphicsRendition(self.sgr_params()));
+        }
+        let params = self.top_params();
+
+        // Intermediate bytes before the final byte, e.g. `"` in `CSI 1 " q`
+        if self.intermediate_len > 0 {
+            return match (self.prefix, self.intermediates(), final_byte) {
+                // DECSCA: 1 protects, 0 and 2 unprotect
+                (None, [b'"'], b'q') => Some(TerminalAction::SetProtection(
+                    params.first().copied() == Some(1),
+                )),
+                // DECRQM for a DEC private mode
+                (Some(b'?'), [b'$'], b'p') => Some(TerminalAction::RequestPrivateMode(
+                    params.first().copied().unwrap_or(0),
+                )),
+                _ => None,
+            };
+        }
+
+        if let Some(prefix) = self.prefix {
+            return self.process_private_csi_sequence(prefix, final_byte, params);
+        }
 
         match final_byte {
-            b'm' => Some(TerminalAction::SetGraphicsRendition(params)),
             b'H' | b'f' => {
-                let row = params.get(0).copied().unwrap_or(1);
+                let row = params.first().copied().unwrap_or(1);
                 let col = params.get(1).copied().unwrap_or(1);
                 Some(TerminalAction::CursorPosition(row, col))
             }
             b'J' => Some(TerminalAction::EraseInDisplay(
-                params.get(0).copied().unwrap_or(0),
+                params.first().copied().unwrap_or(0),
             )),
             b'K' => Some(TerminalAction::EraseInLine(
-                params.get(0).copied().unwrap_or(0),
+                params.first().copied().unwrap_or(0),
             )),
             b'A' => Some(TerminalAction::CursorUp(
-                params.get(0).copied().unwrap_or(1),
+                params.first().copied().unwrap_or(1),
             )),
             b'B' => Some(TerminalAction::CursorDown(
-                params.get(0).copied().unwrap_or(1),
+                params.first().copied().unwrap_or(1),
             )),
             b'C' => Some(TerminalAction::CursorForward(
-                params.get(0).copied().unwrap_or(1),
+                params.first().copied().unwrap_or(1),
             )),
             b'D' => Some(TerminalAction::CursorBackward(
-                params.get(0).copied().unwrap_or(1),
+                params.first().copied().unwrap_or(1),
+            )),
+            // CNL/CPL
+            b'E' => Some(TerminalAction::CursorNextLine(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'F' => Some(TerminalAction::CursorPreviousLine(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            // CHA/HPA and VPA, 1-based
+            b'G' | b'`' => Some(TerminalAction::CursorColumn(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'd' => Some(TerminalAction::CursorRow(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            // CHT/CBT
+            b'I' => Some(TerminalAction::ForwardTab(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'Z' => Some(TerminalAction::BackwardTab(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            // TBC: 0 clears the stop at the cursor, 3 clears all
+            b'g' => Some(TerminalAction::ClearTabStop(
+                params.first().copied().unwrap_or(0),
+            )),
+            // REP
+            b'b' => Some(TerminalAction::RepeatChar(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'S' => Some(TerminalAction::ScrollUp(
+                params.first().copied().unwrap_or(1),
+            )),
+            b'T' => Some(TerminalAction::ScrollDown(
+                params.first().copied().unwrap_or(1),
+            )),
+            // SCOSC/SCORC; `CSI s` with parameters would set left and
+            // right margins, which are not supported
+            b's' if params.is_empty() => Some(TerminalAction::SaveCursor),
+            b'u' if params.is_empty() => Some(TerminalAction::RestoreCursor),
+            b'r' => Some(TerminalAction::SetScrollRegion(
+                params.first().copied().unwrap_or(0),
+                params.get(1).copied().unwrap_or(0),
+            )),
+            b'L' => Some(TerminalAction::InsertLines(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'M' => Some(TerminalAction::DeleteLines(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'@' => Some(TerminalAction::InsertChars(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'P' => Some(TerminalAction::DeleteChars(
+                params.first().copied().unwrap_or(1).max(1),
+            )),
+            b'X' => Some(TerminalAction::EraseChars(
+                params.first().copied().unwrap_or(1).max(1),
             )),
+            // BDSM: set is implicit bidi, reset leaves reordering to the program
+            b'h' | b'l' if params == [8] => {
+                Some(TerminalAction::SetImplicitBidi(final_byte == b'h'))
+            }
+            // DSR: status and cursor position reports
+            b'n' => match params.first().copied() {
+                Some(5) => Some(TerminalAction::Response(b"\x1b[0n".to_vec())),
+                Some(6) => Some(TerminalAction::ReportCursorPosition { extended: false }),
+                _ => None,
+            },
+            // DA1
+            b'c' if params.first().copied().unwrap_or(0) == 0 => {
+                Some(TerminalAction::Response(PRIMARY_DEVICE_ATTRIBUTES.to_vec()))
+            }
+            // XTWINOPS: only the title stack operations are supported
+            b't' => match params.first().copied() {
+                Some(22) => Some(TerminalAction::PushTitle),
+                Some(23) => Some(TerminalAction::PopTitle),
+                _ => None,
+            },
             _ => None,
         }
     }
 
-    fn process_osc_sequence(&self) -> Option<TerminalAction> {
-        if self.escape_buffer.len() < 4 {
-            return None;
+    /// Handle CSI sequences with a private parameter prefix (`?`, `<`, `=`, `>`)
+    fn process_private_csi_sequence(
+        &self,
+        prefix: u8,
+        final_byte: u8,
+        params: Vec<u32>,
+    ) -> Option<TerminalAction> {
+        match (prefix, final_byte) {
+            (b'?', b'h') => Some(TerminalAction::DecPrivateModeSet(params)),
+            (b'?', b'l') => Some(TerminalAction::DecPrivateModeReset(params)),
+            // DECSED/DECSEL: erase that skips protected cells
+            (b'?', b'J') => Some(TerminalAction::SelectiveEraseInDisplay(
+                params.first().copied().unwrap_or(0),
+            )),
+            (b'?', b'K') => Some(TerminalAction::SelectiveEraseInLine(
+                params.first().copied().unwrap_or(0),
+            )),
+            // DECXCPR: cursor position report including the page
+            (b'?', b'n') if params.first() == Some(&6) => {
+                Some(TerminalAction::ReportCursorPosition { extended: true })
+            }
+            // DA2: terminal type 0, the version and no ROM cartridge
+            (b'>', b'c') if params.first().copied().unwrap_or(0) == 0 => {
+                let response = format!("\x1b[>0;{};0c", version_number());
+                Some(TerminalAction::Response(response.into_bytes()))
+            }
+            // XTVERSION: name and version
+            (b'>', b'q') if params.first().copied().unwrap_or(0) == 0 => {
+                let response = format!("\x1bP>|VoidCLI {}\x1b\\", env!("CARGO_PKG_VERSION"));
+                Some(TerminalAction::Response(response.into_bytes()))
+            }
+            // XTMODKEYS: only modifyOtherKeys (resource 4) is tracked
+            (b'>', b'm') if params.first() == Some(&4) => Some(TerminalAction::SetModifyOtherKeys(
+                params.get(1).copied().unwrap_or(0),
+            )),
+            // Kitty keyboard protocol flag stack
+            (b'>', b'u') => Some(TerminalAction::PushKeyboardFlags(
+                params.first().copied().unwrap_or(0),
+            )),
+            (b'<', b'u') => Some(TerminalAction::PopKeyboardFlags(
+                params.first().copied().unwrap_or(1),
+            )),
+            (b'=', b'u') => Some(TerminalAction::SetKeyboardFlags(
+                params.first().copied().unwrap_or(0),
+                params.get(1).copied().unwrap_or(1),
+            )),
+            _ => None,
         }
+    }
 
-        let osc_data =
-            String::from_utf8_lossy(&self.escape_buffer[2..(self.escape_buffer.len() - 1)]);
+
+    fn process_osc_sequence(&self) -> Option<TerminalAction> {
+        let osc_data = String::from_utf8_lossy(&self.buffer[2..]);
 
         if let Some(semicolon_pos) = osc_data.find(';') {
             let cmd = &osc_data[..semicolon_pos];
@@ -211,7 +934,34 @@ impl TerminalParser {
                     }
                     None
                 }
-                _ => None,
+                "7" => {
+                    let (host, path) = parse_osc7(args)?;
+                    Some(TerminalAction::SetWorkingDirectory { host, path })
+                }
+                "8" => {
+                    // OSC 8 ; params ; uri — an empty uri ends the link
+                    let (_params, uri) = args.split_once(';')?;
+                    let uri = (!uri.is_empty()).then(|| uri.to_string());
+                    Some(TerminalAction::SetHyperlink(uri))
+                }
+                "52" => {
+                    // Clipboard reads (`?`) are never answered
+                    let (selection, data) = args.split_once(';')?;
+                    (data != "?").then(|| TerminalAction::ClipboardWrite {
+                        selection: selection.to_string(),
+                        data: data.to_string(),
+                    })
+                }
+                "133" => PromptMark::parse(args).map(TerminalAction::SemanticPrompt),
+                _ => {
+                    let number = cmd.parse::<u32>().ok()?;
+                    self.extension_osc.contains(&number).then(|| {
+                        TerminalAction::Extension(ExtensionSequence::Osc {
+                            number,
+                            payload: args.to_string(),
+                        })
+                    })
+                }
             }
         } else {
             None
@@ -223,7 +973,7 @@ impl TerminalParser {
 #[derive(Debug)]
 pub enum TerminalAction {
     /// Print a character to the terminal
-    Print(u8),
+    Print(char),
     /// Bell (alert) signal
     Bell,
     /// Backspace
@@ -248,20 +998,167 @@ pub enum TerminalAction {
     CursorPreviousLine(u32),
     /// Move cursor to position (row, column)
     CursorPosition(u32, u32),
+    /// Move cursor to a 1-based column on the current row (CHA, HPA)
+    CursorColumn(u32),
+    /// Move cursor to a 1-based row in the current column (VPA)
+    CursorRow(u32),
+    /// Move cursor forward n tab stops (CHT)
+    ForwardTab(u32),
+    /// Move cursor back n tab stops (CBT)
+    BackwardTab(u32),
+    /// Set a tab stop at the cursor column (HTS, `ESC H`)
+    SetTabStop,
+    /// Clear the tab stop at the cursor (0) or all tab stops (3) (TBC)
+    ClearTabStop(u32),
+    /// Print the last printed character n more times (REP)
+    RepeatChar(u32),
     /// Erase in display (0=below, 1=above, 2=all, 3=saved lines)
     EraseInDisplay(u32),
     /// Erase in line (0=to right, 1=to left, 2=all)
     EraseInLine(u32),
+    /// Erase in display, skipping protected cells (DECSED)
+    SelectiveEraseInDisplay(u32),
+    /// Erase in line, skipping protected cells (DECSEL)
+    SelectiveEraseInLine(u32),
+    /// Mark subsequently printed cells as protected or not (DECSCA)
+    SetProtection(bool),
+    /// Set the size of the cursor line (DECDHL, DECSWL, DECDWL)
+    SetLineSize(LineSize),
     /// Set graphics rendition (colors, styles)
     SetGraphicsRendition(Vec<u32>),
     /// Reset terminal state
     Reset,
     /// Scroll up by n lines
     ScrollUp(u32),
+    /// Scroll down by n lines
+    ScrollDown(u32),
+    /// Insert n blank lines at the cursor within the scroll region (IL)
+    InsertLines(u32),
+    /// Delete n lines at the cursor within the scroll region (DL)
+    DeleteLines(u32),
+    /// Insert n blank characters at the cursor, shifting the rest right (ICH)
+    InsertChars(u32),
+    /// Delete n characters at the cursor, shifting the rest left (DCH)
+    DeleteChars(u32),
+    /// Blank n characters from the cursor without moving it (ECH)
+    EraseChars(u32),
+    /// Move cursor down one line, scrolling at the bottom margin (IND)
+    Index,
+    /// Move cursor up one line, scrolling down at the top margin (RI)
+    ReverseIndex,
+    /// Set the top and bottom margins, 1-based with 0 for the screen edge
+    /// (DECSTBM)
+    SetScrollRegion(u32, u32),
+    /// Move cursor to the start of the next line, scrolling at the bottom margin (NEL)
+    NextLine,
+    /// Save the cursor position, attributes and origin mode (DECSC, `ESC 7`,
+    /// `CSI s`)
+    SaveCursor,
+    /// Restore what `SaveCursor` saved (DECRC, `ESC 8`, `CSI u`)
+    RestoreCursor,
+    /// Designate a character set into G0 (0) or G1 (1) (SCS, `ESC ( F`,
+    /// `ESC ) F`)
+    DesignateCharset(u8, Charset),
+    /// Use G1 for printing (SO)
+    ShiftOut,
+    /// Use G0 for printing (SI)
+    ShiftIn,
+    /// Fill the screen with `E` for alignment testing (DECALN)
+    ScreenAlignmentTest,
     /// Set window title
     SetWindowTitle(String),
+    /// Save the window title on the title stack (XTWINOPS 22)
+    PushTitle,
+    /// Restore the window title from the title stack (XTWINOPS 23)
+    PopTitle,
+    /// Enable DEC private modes (DECSET, `CSI ? Pm h`)
+    DecPrivateModeSet(Vec<u32>),
+    /// Disable DEC private modes (DECRST, `CSI ? Pm l`)
+    DecPrivateModeReset(Vec<u32>),
+    /// Report whether a DEC private mode is set (DECRQM, `CSI ? Ps $ p`)
+    RequestPrivateMode(u32),
+    /// Report a setting such as `m` or `r` (DECRQSS, `DCS $ q Pt ST`)
+    RequestSetting(String),
+    /// Application (true) or numeric (false) keypad mode (DECKPAM/DECKPNM)
+    ApplicationKeypad(bool),
+    /// Set the xterm modifyOtherKeys level (XTMODKEYS)
+    SetModifyOtherKeys(u32),
+    /// Push kitty keyboard protocol flags
+    PushKeyboardFlags(u32),
+    /// Pop n entries from the kitty keyboard flag stack
+    PopKeyboardFlags(u32),
+    /// Set kitty keyboard flags (flags, mode: 1=set, 2=or, 3=and-not)
+    SetKeyboardFlags(u32, u32),
     /// Set color palette entry
     SetColorPalette(u8, String),
+    /// Reorder right-to-left text for display (true) or leave it to the
+    /// program (false) (BDSM, `CSI 8 h` / `CSI 8 l`)
+    SetImplicitBidi(bool),
+    /// Shell integration mark for prompts, commands and output (OSC 133)
+    SemanticPrompt(PromptMark),
+    /// Start (`Some(uri)`) or end (`None`) a hyperlink over the following
+    /// text (OSC 8)
+    SetHyperlink(Option<String>),
+    /// Set the clipboard to base64 `data` (OSC 52); `selection` names the
+    /// clipboards, e.g. `c` or `p`
+    ClipboardWrite { selection: String, data: String },
+    /// The shell's host and current directory, from `file://host/path`
+    /// (OSC 7)
+    SetWorkingDirectory { host: String, path: String },
+    /// A private OSC or APC sequence claimed by an extension hook
+    Extension(ExtensionSequence),
+    /// A sixel image (`DCS P1 ; P2 ; P3 q ... ST`) to place at the cursor
+    SixelImage(Arc<SixelImage>),
+    /// Bytes to write back to the program, answering a query
+    Response(Vec<u8>),
+    /// Report the cursor position (CPR, `CSI 6 n`), with the page number
+    /// when `extended` (DECXCPR, `CSI ? 6 n`)
+    ReportCursorPosition { extended: bool },
+}
+
+/// The 7-bit form of an ESC-prefixed byte that may have arrived as its
+/// 8-bit C1 equivalent
+fn seven_bit(byte: u8) -> u8 {
+    if byte >= 0x80 {
+        byte - 0x40
+    } else {
+        byte
+    }
+}
+
+/// Terminfo capabilities answered for XTGETTCAP, by hex encoded name
+fn termcap_value(hex_name: &str) -> Option<&'static str> {
+    let name = hex_decode(hex_name)?;
+    match name.as_str() {
+        "TN" | "name" => Some("xterm-256color"),
+        "Co" | "colors" => Some("256"),
+        "RGB" => Some("8/8/8"),
+        _ => None,
+    }
+}
+
+fn hex_encode(text: &str) -> String {
+    text.bytes().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<String> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// The crate version as one number for DA2, e.g. 1.2.3 as 10203
+fn version_number() -> u32 {
+    env!("CARGO_PKG_VERSION")
+        .split('.')
+        .take(3)
+        .map(|part| part.parse::<u32>().unwrap_or(0).min(99))
+        .fold(0, |number, part| number * 100 + part)
 }
 
 impl Default for TerminalParser {
@@ -280,7 +1177,7 @@ mod tests {
         let actions = parser.parse(b"Hello").unwrap();
 
         assert_eq!(actions.len(), 5);
-        if let TerminalAction::Print(b'H') = actions[0] {
+        if let TerminalAction::Print('H') = actions[0] {
             // Good
         } else {
             panic!("Expected Print('H') action");
@@ -300,4 +1197,157 @@ mod tests {
             panic!("Expected SetGraphicsRendition action");
         }
     }
+
+    #[test]
+    fn test_c1_controls() {
+        let mut parser = TerminalParser::new();
+        parser.set_utf8_only(false);
+        let actions = parser.parse(b"\x9b2J\x9d2;title\x9c\x90|ignored\x9cA").unwrap();
+
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(actions[0], TerminalAction::EraseInDisplay(2)));
+        assert!(matches!(&actions[1], TerminalAction::SetWindowTitle(t) if t == "title"));
+        assert!(matches!(actions[2], TerminalAction::Print('A')));
+
+        // DCS bodies are swallowed rather than printed in 7-bit form too
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1bP|data\x1b\\B").unwrap();
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0], TerminalAction::Print('B')));
+    }
+
+    #[test]
+    fn test_escape_sequences() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1bD\x1bE\x1b#8\x1b[3S\x1b[T").unwrap();
+
+        assert!(matches!(actions[0], TerminalAction::Index));
+        assert!(matches!(actions[1], TerminalAction::NextLine));
+        assert!(matches!(actions[2], TerminalAction::ScreenAlignmentTest));
+        assert!(matches!(actions[3], TerminalAction::ScrollUp(3)));
+        assert!(matches!(actions[4], TerminalAction::ScrollDown(1)));
+
+        let actions = parser.parse(b"\x1bM\x1b[2;5r\x1b[r").unwrap();
+        assert!(matches!(actions[0], TerminalAction::ReverseIndex));
+        assert!(matches!(actions[1], TerminalAction::SetScrollRegion(2, 5)));
+        assert!(matches!(actions[2], TerminalAction::SetScrollRegion(0, 0)));
+
+        // HTS, not the VT52 cursor home; other VT52 sequences are ignored
+        let actions = parser.parse(b"\x1bH\x1bA\x1bJ").unwrap();
+        assert_eq!(actions.len(), 1);
+        assert!(matches!(actions[0], TerminalAction::SetTabStop));
+    }
+
+    #[test]
+    fn test_protection_and_line_size() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1b[1\"q\x1b[?2K\x1b[\"q\x1b#3\x1b[2 q").unwrap();
+
+        assert_eq!(actions.len(), 4);
+        assert!(matches!(actions[0], TerminalAction::SetProtection(true)));
+        assert!(matches!(actions[1], TerminalAction::SelectiveEraseInLine(2)));
+        assert!(matches!(actions[2], TerminalAction::SetProtection(false)));
+        assert!(matches!(
+            actions[3],
+            TerminalAction::SetLineSize(LineSize::DoubleHeightTop)
+        ));
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut parser = TerminalParser::new();
+        assert!(parser.parse(b"\x1bP0;1;0q\"1;1;2;6#0~").unwrap().is_empty());
+        let actions = parser.parse(b"~\x1b\\A\x1bP|m\x1b\\B").unwrap();
+
+        assert_eq!(actions.len(), 3);
+        match &actions[0] {
+            TerminalAction::SixelImage(image) => assert_eq!((image.width, image.height), (2, 6)),
+            other => panic!("Expected a sixel image, got {:?}", other),
+        }
+        assert!(matches!(actions[1], TerminalAction::Print('A')));
+        assert!(matches!(actions[2], TerminalAction::Print('B')));
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1b[5n\x1b[6n\x1b[c\x1b[>c\x1b[?6n").unwrap();
+
+        assert_eq!(actions.len(), 5);
+        assert!(matches!(&actions[0], TerminalAction::Response(r) if r == b"\x1b[0n"));
+        assert!(matches!(
+            actions[1],
+            TerminalAction::ReportCursorPosition { extended: false }
+        ));
+        assert!(matches!(&actions[2], TerminalAction::Response(r) if r == b"\x1b[?62;4;22c"));
+        assert!(matches!(&actions[3], TerminalAction::Response(r) if r.starts_with(b"\x1b[>0;")));
+        assert!(matches!(
+            actions[4],
+            TerminalAction::ReportCursorPosition { extended: true }
+        ));
+    }
+
+    #[test]
+    fn test_utf8() {
+        let mut parser = TerminalParser::new();
+        // "é" and "→" split across reads
+        let mut actions = parser.parse(b"\xc3").unwrap();
+        actions.extend(parser.parse(b"\xa9\xe2\x86").unwrap());
+        actions.extend(parser.parse(b"\x92").unwrap());
+        let printed: String = actions
+            .iter()
+            .map(|action| match action {
+                TerminalAction::Print(c) => *c,
+                other => panic!("Expected Print, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(printed, "é→");
+
+        // A stray continuation, an overlong form and a truncated sequence
+        let actions = parser.parse(b"\x80\xc0\xaf\xe2\x86A").unwrap();
+        assert_eq!(actions.len(), 5);
+        assert!(actions[..4]
+            .iter()
+            .all(|action| matches!(action, TerminalAction::Print(char::REPLACEMENT_CHARACTER))));
+        assert!(matches!(actions[4], TerminalAction::Print('A')));
+    }
+
+    #[test]
+    fn test_subparams_and_controls() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1b[4:3;38:2::255:0:0m\x1b[4:0;58:5:1m").unwrap();
+        assert!(matches!(
+            &actions[0],
+            TerminalAction::SetGraphicsRendition(p) if p == &[4, 38, 2, 255, 0, 0]
+        ));
+        assert!(matches!(&actions[1], TerminalAction::SetGraphicsRendition(p) if p == &[24]));
+
+        // Empty parameters, a C0 control inside a sequence, CAN cancelling one
+        let actions = parser.parse(b"\x1b[;5H\x1b[1\n2A\x1b[31\x18m").unwrap();
+        assert_eq!(actions.len(), 4);
+        assert!(matches!(actions[0], TerminalAction::CursorPosition(0, 5)));
+        assert!(matches!(actions[1], TerminalAction::LineFeed));
+        assert!(matches!(actions[2], TerminalAction::CursorUp(12)));
+        assert!(matches!(actions[3], TerminalAction::Print('m')));
+
+        // An 8-bit CSI interrupts a sequence; 8-bit IND acts like ESC D
+        parser.set_utf8_only(false);
+        let actions = parser.parse(b"\x1b[1\x9b2J\x84").unwrap();
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(actions[0], TerminalAction::EraseInDisplay(2)));
+        assert!(matches!(actions[1], TerminalAction::Index));
+    }
+
+    #[test]
+    fn test_dcs_queries() {
+        let mut parser = TerminalParser::new();
+        let actions = parser.parse(b"\x1bP$qm\x1b\\\x1bP+q436F;5858\x1b\\").unwrap();
+
+        assert_eq!(actions.len(), 2);
+        assert!(matches!(&actions[0], TerminalAction::RequestSetting(s) if s == "m"));
+        assert!(matches!(
+            &actions[1],
+            TerminalAction::Response(r) if r == b"\x1bP1+r436F=323536\x1b\\\x1bP0+r5858\x1b\\"
+        ));
+    }
 }
@@ -0,0 +1,138 @@
+// Paste protection
+//
+// Pasted text is checked before it reaches the PTY: several lines or a
+// trailing newline would run commands straight away, and control, zero-width
+// or bidi override characters can hide what actually runs. Risky pastes are
+// held for a preview according to the `paste` config.
+
+use config::PasteConfig;
+
+const BRACKETED_START: &str = "\x1b[200~";
+const BRACKETED_END: &str = "\x1b[201~";
+
+/// What makes a paste risky
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasteReport {
+    pub lines: usize,
+    pub trailing_newline: bool,
+    /// Hidden characters, in order of appearance
+    pub hidden: Vec<char>,
+}
+
+impl PasteReport {
+    pub fn analyze(text: &str) -> Self {
+        let text = normalize_newlines(text);
+        Self {
+            lines: text.trim_end_matches('\n').split('\n').count(),
+            trailing_newline: text.ends_with('\n'),
+            hidden: text.chars().filter(|c| is_hidden(*c)).collect(),
+        }
+    }
+
+    /// Whether the paste must be confirmed before sending
+    pub fn needs_confirmation(&self, config: &PasteConfig, bracketed: bool) -> bool {
+        let check_lines = !(bracketed && config.trust_bracketed);
+        (check_lines && config.confirm_multiline && self.lines > 1)
+            || (check_lines && config.confirm_trailing_newline && self.trailing_newline)
+            || (config.confirm_hidden && !self.hidden.is_empty())
+    }
+
+    /// e.g. "3 lines, ends with a newline, 1 hidden character"
+    pub fn summary(&self) -> String {
+        let mut parts = vec![match self.lines {
+            1 => "1 line".to_string(),
+            n => format!("{} lines", n),
+        }];
+        if self.trailing_newline {
+            parts.push("ends with a newline".to_string());
+        }
+        match self.hidden.len() {
+            0 => {}
+            1 => parts.push("1 hidden character".to_string()),
+            n => parts.push(format!("{} hidden characters", n)),
+        }
+        parts.join(", ")
+    }
+}
+
+/// Controls other than newline and tab, zero-width characters and bidi
+/// overrides
+pub fn is_hidden(c: char) -> bool {
+    (c.is_control() && c != '\n' && c != '\t')
+        || matches!(c, '\u{200b}'..='\u{200f}' | '\u{202a}'..='\u{202e}')
+        || matches!(c, '\u{2066}'..='\u{2069}' | '\u{feff}')
+}
+
+/// Text with hidden characters made visible, for the preview: C0 controls
+/// as control pictures (`␛`), others as `<U+202E>`
+pub fn reveal(text: &str) -> String {
+    normalize_newlines(text)
+        .chars()
+        .map(|c| match c {
+            c if !is_hidden(c) => c.to_string(),
+            '\0'..='\x1f' => char::from_u32(0x2400 + c as u32).unwrap_or('?').to_string(),
+            '\x7f' => '\u{2421}'.to_string(),
+            c => format!("<U+{:04X}>", c as u32),
+        })
+        .collect()
+}
+
+pub fn strip_hidden(text: &str) -> String {
+    normalize_newlines(text).chars().filter(|c| !is_hidden(*c)).collect()
+}
+
+fn normalize_newlines(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Bytes to write to the PTY: newlines become CR, and with bracketed paste
+/// the text is wrapped in the paste markers. Escapes are dropped from
+/// bracketed text so that no end marker, however nested, lets it escape them
+pub fn encode(text: &str, bracketed: bool) -> Vec<u8> {
+    let text = normalize_newlines(text).replace('\n', "\r");
+    if bracketed {
+        let text: String = text.chars().filter(|c| !matches!(c, '\x1b' | '\u{9b}')).collect();
+        format!("{}{}{}", BRACKETED_START, text, BRACKETED_END).into_bytes()
+    } else {
+        text.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paste_report() {
+        let config = PasteConfig::default();
+        let safe = PasteReport::analyze("ls -la");
+        assert!(!safe.needs_confirmation(&config, false));
+
+        let report = PasteReport::analyze("rm -rf build\r\nmake\r\n");
+        assert_eq!(report.lines, 2);
+        assert!(report.trailing_newline);
+        assert!(report.needs_confirmation(&config, true));
+        let trusting = PasteConfig {
+            trust_bracketed: true,
+            ..PasteConfig::default()
+        };
+        assert!(!report.needs_confirmation(&trusting, true));
+
+        let hidden = PasteReport::analyze("echo hi\u{202e}\x1b[2K");
+        assert_eq!(hidden.hidden, vec!['\u{202e}', '\x1b']);
+        assert!(hidden.needs_confirmation(&trusting, true));
+        assert_eq!(hidden.summary(), "1 line, 2 hidden characters");
+        assert_eq!(reveal("a\x1bb\u{202e}"), "a\u{241b}b<U+202E>");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("a\nb", false), b"a\rb");
+        assert_eq!(encode("x\x1b[201~y", true), b"\x1b[200~x[201~y\x1b[201~");
+        // Removing a nested marker must not leave a live one behind
+        assert_eq!(
+            encode("\x1b[20\x1b[201~1~", true),
+            b"\x1b[200~[20[201~1~\x1b[201~"
+        );
+    }
+}
@@ -1,18 +1,29 @@
 use std::{
+    io::{Read, Write},
+    path::PathBuf,
     process::Stdio,
-    sync::mpsc,
+    time::{Duration, Instant},
     os::unix::io::{AsRawFd, OwnedFd, FromRawFd},
 };
 
 use anyhow::{Context, Result};
 use tokio::{
     process::Command as TokioCommand,
-    io::{AsyncReadExt, AsyncWriteExt},
-    sync::oneshot,
+    sync::{mpsc, oneshot},
 };
-use log::info;
+use tracing::info;
 
-use crate::{TermEvent, pty::PtyPair};
+use config::EnvRule;
+
+use crate::{
+    env_rules::{resolve_env, SpawnContext},
+    launch::{exit_message, ExitAction, LaunchOptions},
+    pty::{PtyMaster, PtyPair},
+    remote::{self, SessionContext},
+    shell_integration::Shell,
+    usage::UsageSampler,
+    TermEvent,
+};
 
 // manages a terminal process
 pub struct ProcessManager {
@@ -21,18 +32,40 @@ pub struct ProcessManager {
     /// The shell command to run
     shell: String,
     /// Event Sender for process events
-    event_sender: mpsc::Sender<TermEvent>,
+    event_sender: mpsc::UnboundedSender<TermEvent>,
     /// Working directory
     working_directory: String,
     ///Environment variables
     env_vars: Vec<(String, String)>,
+    /// Directory to install shell integration scripts into, if enabled
+    shell_integration_dir: Option<PathBuf>,
+    /// Environment of the running child, as last observed
+    environment: Vec<(String, String)>,
+    /// Config rules adding environment depending on profile, cwd and command
+    env_rules: Vec<EnvRule>,
+    /// Active profile name, matched by `env_rules`
+    profile: Option<String>,
+    launch: LaunchOptions,
+    /// Startup command not yet typed into the shell
+    pending_startup: Option<String>,
+    /// When the current child was spawned
+    started_at: Option<Instant>,
+    /// PTY master of the current child, for input and resizing; the child's
+    /// stdin is the slave side
+    pty: Option<PtyMaster>,
+    /// Fires once the current child's output has all been read
+    output_closed: Option<oneshot::Receiver<()>>,
 }
 
+/// How long `wait` lets background jobs that inherited the PTY keep it
+/// open after the process itself exited
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
 impl ProcessManager {
     // Create a new process manager
     pub fn new(
         shell: &str,
-        event_sender: mpsc::Sender<TermEvent>,
+        event_sender: mpsc::UnboundedSender<TermEvent>,
         working_directory: Option<&str>,
         env_vars: Vec<(String, String)>,
     ) -> Self {
@@ -48,16 +81,62 @@ impl ProcessManager {
             event_sender,
             working_directory,
             env_vars,
+            shell_integration_dir: None,
+            environment: Vec::new(),
+            env_rules: Vec::new(),
+            profile: None,
+            launch: LaunchOptions::default(),
+            pending_startup: None,
+            started_at: None,
+            pty: None,
+            output_closed: None,
         }
     }
 
+    /// Inject the OSC 133/7 shell integration when spawning a supported shell
+    pub fn with_shell_integration(mut self, dir: PathBuf) -> Self {
+        self.shell_integration_dir = Some(dir);
+        self
+    }
+
+    /// Apply environment injection rules from the config on spawn
+    pub fn with_env_rules(mut self, rules: Vec<EnvRule>, profile: Option<String>) -> Self {
+        self.env_rules = rules;
+        self.profile = profile;
+        self
+    }
+
+    /// Login shell, extra arguments, startup command and `-e` command
+    pub fn with_launch(mut self, launch: LaunchOptions) -> Self {
+        self.launch = launch;
+        self
+    }
+
     /// Spawn a new process
     pub async fn spawn(&mut self) -> Result<()> {
         // Create a pseudo-terminal
         let pty = PtyPair::new()?;
 
+        // Load shell integration without requiring changes to the user's rc files
+        let mut injected_args = Vec::new();
+        let mut injected_env = Vec::new();
+        let integration_dir = self
+            .shell_integration_dir
+            .as_ref()
+            .filter(|_| self.launch.runs_shell());
+        if let Some(dir) = integration_dir {
+            if let Some(shell) = Shell::detect(&self.shell) {
+                let injection = shell.install(dir)?;
+                injected_args = injection.args;
+                injected_env = injection.env;
+                info!("Injected {} shell integration", shell.name());
+            }
+        }
+
         // Set up the command
-        let mut command = TokioCommand::new(&self.shell);
+        let (program, args) = self.launch.command_line(&self.shell, &injected_args);
+        let mut command = TokioCommand::new(&program);
+        command.args(&args);
         command.current_dir(&self.working_directory);
 
         // Add environment variables
@@ -65,9 +144,39 @@ impl ProcessManager {
             command.env(key, value);
         }
 
+        // Rules matching the profile, working directory and command
+        let command_line = std::iter::once(program.as_str())
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let context = SpawnContext {
+            profile: self.profile.as_deref(),
+            cwd: &self.working_directory,
+            command: &command_line,
+        };
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let base_path = std::env::var("PATH").ok();
+        let injected = resolve_env(
+            &self.env_rules,
+            &context,
+            home.as_deref(),
+            base_path.as_deref(),
+        );
+        for (key, value) in injected {
+            command.env(key, value);
+        }
+
         // Standard environment variables
         command.env("TERM", "xterm-256color");
 
+        for (key, value) in &injected_env {
+            command.env(key, value);
+        }
+
+        if self.launch.runs_shell() {
+            self.pending_startup = self.launch.startup_command.clone();
+        }
+
         // Connect the command to our pty
         #[cfg(unix)]
         {
@@ -89,22 +198,37 @@ impl ProcessManager {
         // Spawn the process
         let mut child = command.spawn().context("Failed to spawn process")?;
 
+        // Record the environment the child starts with
+        self.environment = std::env::vars().collect();
+        for (key, value) in command.as_std().get_envs() {
+            let key = key.to_string_lossy().to_string();
+            self.environment.retain(|(k, _)| *k != key);
+            if let Some(value) = value {
+                self.environment.push((key, value.to_string_lossy().to_string()));
+            }
+        }
+        self.environment.sort();
+
         // Set up output handling
         let mut master = pty.master;
+        self.pty = Some(master.try_clone()?);
         let event_sender = self.event_sender.clone();
 
-        // Create a channel for process status
-        let (status_tx, status_rx) = oneshot::channel();
+        // Fires once the reader has seen the end of the output
+        let (closed_tx, closed_rx) = oneshot::channel();
+        self.output_closed = Some(closed_rx);
 
         // Store the child process first
         self.child = Some(child);
+        self.started_at = Some(Instant::now());
 
-        // Spawn a task to handle process output
-        tokio::spawn(async move {
+        // Reads block, so they get a thread of their own instead of a
+        // runtime worker
+        tokio::task::spawn_blocking(move || {
             let mut buffer = vec![0u8; 4096];
 
             loop {
-                match master.read(&mut buffer).await {
+                match master.read(&mut buffer) {
                     Ok(0) => {
                         // EOF - process has terminated
                         break;
@@ -112,10 +236,12 @@ impl ProcessManager {
                     Ok(n) => {
                         // Send the output to the event handler
                         let output_data = buffer[0..n].to_vec();
-                        if let Err(_) = event_sender.send(TermEvent::Output(output_data)) {
+                        if event_sender.send(TermEvent::Output(output_data)).is_err() {
                             break;
                         }
                     }
+                    // Linux reports the slave side closing as EIO
+                    Err(e) if e.raw_os_error() == Some(libc::EIO) => break,
                     Err(e) => {
                         let error_msg = format!("Error reading from process: {}", e);
                         let _ = event_sender.send(TermEvent::Error(error_msg));
@@ -124,39 +250,80 @@ impl ProcessManager {
                 }
             }
 
-            // Process has terminated
-            let _ = status_tx.send(());
-        });
-
-        // Wait for the process to exit in the background
-        tokio::spawn(async move {
-            if let Ok(()) = status_rx.await {
-                info!("Process output stream closed");
-            }
+            info!("Process output stream closed");
+            let _ = closed_tx.send(());
         });
 
         Ok(())
     }
 
+    /// Input for the startup command, returned once after the shell's first prompt
+    pub fn take_startup_input(&mut self) -> Option<Vec<u8>> {
+        self.pending_startup
+            .take()
+            .map(|command| format!("{}\r", command).into_bytes())
+    }
+
+    /// Exit code once the process has exited; -1 if it was killed by a signal
+    pub fn try_exit_code(&mut self) -> Option<i32> {
+        let child = self.child.as_mut()?;
+        match child.try_wait() {
+            Ok(Some(status)) => Some(status.code().unwrap_or(-1)),
+            _ => None,
+        }
+    }
+
+    /// Wait for the process to exit and for the output it left behind to be
+    /// read; the exit code, -1 if it was killed by a signal
+    pub async fn wait(&mut self) -> Result<i32> {
+        let child = self.child.as_mut().context("No process running")?;
+        let status = child.wait().await?;
+        if let Some(closed) = &mut self.output_closed {
+            let _ = tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, closed).await;
+            self.output_closed = None;
+        }
+        Ok(status.code().unwrap_or(-1))
+    }
+
+    /// Keep the pane open after the process exits
+    pub fn holds_on_exit(&self) -> bool {
+        self.launch.holds()
+    }
+
+    /// Apply `terminal.on_exit` after the process exited with `code`:
+    /// respawns for a restart, and sends the exit banner as output when
+    /// holding
+    pub async fn handle_exit(&mut self, code: i32) -> Result<ExitAction> {
+        let uptime = self.started_at.map(|t| t.elapsed()).unwrap_or_default();
+        let action = self.launch.exit_action(code, uptime);
+        match action {
+            ExitAction::Restart => {
+                info!("Process exited with code {}, restarting", code);
+                self.child = None;
+                self.spawn().await?;
+            }
+            ExitAction::Hold => {
+                let banner = exit_message(code).into_bytes();
+                let _ = self.event_sender.send(TermEvent::Output(banner));
+            }
+            ExitAction::Close => {}
+        }
+        Ok(action)
+    }
+
     /// Write data to the process
     pub async fn write(&mut self, data: &[u8]) -> Result<()> {
-        if let Some(child) = &mut self.child {
-            if let Some(stdin) = &mut child.stdin {
-                stdin.write_all(data).await?;
-                stdin.flush().await?;
-            }
+        if let Some(pty) = &mut self.pty {
+            pty.write_all(data).context("Failed to write to the PTY")?;
         }
 
         Ok(())
     }
 
     // Resize the terminal
-    pub async fn resize(&mut self, _cols: u16, _rows: u16) -> Result<()> {
-        // This would use the PTY resize functionality
-        // A placeholder for now
-        #[cfg(unix)]
-        if let Some(_) = &mut self.child {
-            // Here we would use the winsize struct from libc
+    pub async fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        if let Some(pty) = &self.pty {
+            pty.resize(rows, cols)?;
         }
 
         Ok(())
@@ -193,4 +360,54 @@ impl ProcessManager {
     pub fn add_env_var(&mut self, key: &str, value: &str) {
         self.env_vars.push((key.to_string(), value.to_string()));
     }
+
+    /// Whether the session is local, over SSH or in a container, given the
+    /// host of the shell's last OSC 7 report
+    pub fn session_context(&self, osc7_host: Option<&str>) -> SessionContext {
+        let pid = self.child.as_ref().and_then(|child| child.id());
+        let foreground = pid.and_then(remote::foreground_command);
+        SessionContext::detect(
+            foreground.as_deref(),
+            osc7_host,
+            &self.environment,
+            &remote::local_hostname(),
+        )
+    }
+
+    /// Sampler for the resources of the commands the shell runs
+    pub fn usage_sampler(&self) -> Option<UsageSampler> {
+        let pid = self.child.as_ref()?.id()?;
+        Some(UsageSampler::new(pid))
+    }
+
+    /// Re-read the child's environment from the OS where supported
+    #[cfg(target_os = "linux")]
+    pub fn refresh_environment(&mut self) -> Result<()> {
+        let pid = match self.child.as_ref().and_then(|child| child.id()) {
+            Some(pid) => pid,
+            None => return Ok(()),
+        };
+
+        let raw = std::fs::read(format!("/proc/{}/environ", pid))
+            .context("Failed to read process environment")?;
+
+        self.environment = raw
+            .split(|&b| b == 0)
+            .filter_map(|entry| {
+                let entry = String::from_utf8_lossy(entry);
+                entry
+                    .split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+            })
+            .collect();
+        self.environment.sort();
+
+        Ok(())
+    }
+
+    /// Re-read the child's environment from the OS where supported
+    #[cfg(not(target_os = "linux"))]
+    pub fn refresh_environment(&mut self) -> Result<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,181 @@
+// Prompt positions for jumping between commands without the block UI
+//
+// Shells running the OSC 133 integration mark where prompts, commands and
+// output start; for other shells `terminal.prompt_pattern` recognizes prompt
+// lines instead. Lines are absolute: 0 is the first line the session ever
+// printed, so positions stay valid as the screen scrolls.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use anyhow::{Context, Result};
+use config::TerminalConfig;
+use regex::Regex;
+
+/// Marks kept per session; older prompts can no longer be jumped to
+const MAX_MARKS: usize = 4096;
+
+/// A semantic prompt mark (OSC 133)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptMark {
+    /// `A`: a prompt starts
+    PromptStart,
+    /// `B`: the prompt ends and the user types the command
+    CommandStart,
+    /// `C`: the command runs and its output starts
+    OutputStart,
+    /// `D`: the command finished, with its exit status if reported
+    CommandFinished(Option<i32>),
+}
+
+impl PromptMark {
+    /// Parse the arguments of OSC 133, e.g. `A`, `D;1` or `A;cl=m`
+    pub fn parse(args: &str) -> Option<Self> {
+        let mut fields = args.split(';');
+        match fields.next()? {
+            "A" => Some(PromptMark::PromptStart),
+            "B" => Some(PromptMark::CommandStart),
+            "C" => Some(PromptMark::OutputStart),
+            "D" => Some(PromptMark::CommandFinished(
+                fields.next().and_then(|status| status.parse().ok()),
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Prompt marks of a session with the absolute line they were received on
+#[derive(Debug, Clone, Default)]
+pub struct PromptMarks {
+    marks: VecDeque<(usize, PromptMark)>,
+}
+
+impl PromptMarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, line: usize, mark: PromptMark) {
+        if self.marks.len() == MAX_MARKS {
+            self.marks.pop_front();
+        }
+        self.marks.push_back((line, mark));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// The mark received most recently
+    pub fn last(&self) -> Option<(usize, PromptMark)> {
+        self.marks.back().copied()
+    }
+
+    /// Lines where prompts start, in order
+    pub fn prompts(&self) -> Vec<usize> {
+        let mut prompts: Vec<usize> = self
+            .marks
+            .iter()
+            .filter(|(_, mark)| *mark == PromptMark::PromptStart)
+            .map(|(line, _)| *line)
+            .collect();
+        prompts.dedup();
+        prompts
+    }
+
+    /// Output lines of the last finished command, end exclusive
+    pub fn last_output(&self) -> Option<Range<usize>> {
+        let mut end = None;
+        for (line, mark) in self.marks.iter().rev() {
+            match mark {
+                PromptMark::CommandFinished(_) | PromptMark::PromptStart => end = Some(*line),
+                PromptMark::OutputStart => {
+                    if let Some(end) = end {
+                        return Some(*line..end.max(*line));
+                    }
+                }
+                PromptMark::CommandStart => {}
+            }
+        }
+        None
+    }
+}
+
+/// `terminal.prompt_pattern`, compiled
+pub fn prompt_pattern(config: &TerminalConfig) -> Result<Option<Regex>> {
+    config
+        .prompt_pattern
+        .as_deref()
+        .map(|pattern| {
+            Regex::new(pattern).with_context(|| format!("Invalid prompt pattern '{}'", pattern))
+        })
+        .transpose()
+}
+
+/// Absolute lines of `lines` that look like prompts, the first of them being
+/// line `first_line`
+pub fn match_prompts<'a>(
+    lines: impl IntoIterator<Item = &'a str>,
+    first_line: usize,
+    pattern: &Regex,
+) -> Vec<usize> {
+    lines
+        .into_iter()
+        .enumerate()
+        .filter(|(_, line)| pattern.is_match(line))
+        .map(|(index, _)| first_line + index)
+        .collect()
+}
+
+/// Output between the last two prompts, for shells without OSC 133
+pub fn output_between_prompts(prompts: &[usize]) -> Option<Range<usize>> {
+    match prompts {
+        [.., previous, last] => Some(previous + 1..*last),
+        _ => None,
+    }
+}
+
+/// Nearest prompt above `line`
+pub fn previous_prompt(prompts: &[usize], line: usize) -> Option<usize> {
+    prompts.iter().rev().find(|prompt| **prompt < line).copied()
+}
+
+/// Nearest prompt below `line`
+pub fn next_prompt(prompts: &[usize], line: usize) -> Option<usize> {
+    prompts.iter().find(|prompt| **prompt > line).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessTerminal;
+
+    #[test]
+    fn test_marks_survive_scrolling() {
+        let mut terminal = HeadlessTerminal::new(20, 3);
+        terminal.feed(b"\x1b]133;A\x07$ \x1b]133;B\x07ls\r\n").unwrap();
+        terminal.feed(b"\x1b]133;C\x07a\r\nb\r\nc\r\n\x1b]133;D;0\x07").unwrap();
+        terminal.feed(b"\x1b]133;A\x07$ ").unwrap();
+
+        let marks = terminal.prompt_marks();
+        assert_eq!(marks.prompts(), vec![0, 4]);
+        assert_eq!(marks.last_output(), Some(1..4));
+        assert_eq!(marks.last(), Some((4, PromptMark::PromptStart)));
+        assert_eq!(terminal.lines_scrolled(), 2);
+
+        let prompts = marks.prompts();
+        assert_eq!(previous_prompt(&prompts, 4), Some(0));
+        assert_eq!(next_prompt(&prompts, 0), Some(4));
+        assert_eq!(next_prompt(&prompts, 4), None);
+    }
+
+    #[test]
+    fn test_prompt_pattern_fallback() {
+        let pattern = Regex::new(r"^\S*\s?[$#%] ").unwrap();
+        let lines = ["~ $ make", "cc main.c", "ok", "~ $ "];
+        let prompts = match_prompts(lines, 10, &pattern);
+        assert_eq!(prompts, vec![10, 13]);
+        assert_eq!(output_between_prompts(&prompts), Some(11..13));
+        assert_eq!(PromptMark::parse("D;2"), Some(PromptMark::CommandFinished(Some(2))));
+    }
+}
@@ -65,6 +65,12 @@ impl PtyPair {
 
 #[cfg(unix)]
 impl PtyMaster {
+    /// Another handle to the same PTY, e.g. for writing while a thread reads
+    pub fn try_clone(&self) -> Result<Self> {
+        let fd = nix::unistd::dup(self.fd).context("Failed to duplicate the PTY")?;
+        Ok(Self { fd })
+    }
+
     /// Resize the PTY
     pub fn resize(&self, rows: u16, cols: u16) -> Result<()> {
         let ws = Winsize {
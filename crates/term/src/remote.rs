@@ -0,0 +1,256 @@
+// Where a pane's session runs: locally, over SSH, or in a container
+//
+// Detected from the pane's foreground command, then the host of its OSC 7
+// reports, then the environment its shell started with; the foreground
+// process is the most current of these. Local-only features such as opening
+// paths are disabled for anything but `Local`.
+
+#[cfg(target_os = "linux")]
+use crate::usage::ProcStat;
+
+/// Session metadata for block badges, theme rules and feature gating
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum SessionContext {
+    #[default]
+    Local,
+    Ssh { host: Option<String> },
+    /// Docker, Podman, Kubernetes or LXC
+    Container { runtime: String, name: Option<String> },
+    /// Toolbox or Distrobox
+    Toolbox { name: Option<String> },
+}
+
+/// ssh options taking a value, so the host can be told apart from it
+const SSH_VALUE_OPTIONS: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+impl SessionContext {
+    /// `local`, `ssh`, `container` or `toolbox`, as used in config rules
+    pub fn name(&self) -> &'static str {
+        match self {
+            SessionContext::Local => "local",
+            SessionContext::Ssh { .. } => "ssh",
+            SessionContext::Container { .. } => "container",
+            SessionContext::Toolbox { .. } => "toolbox",
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        *self == SessionContext::Local
+    }
+
+    /// Block header badge such as `ssh prod-1` or `docker web`; `None` when
+    /// local
+    pub fn badge(&self) -> Option<String> {
+        let (kind, name) = match self {
+            SessionContext::Local => return None,
+            SessionContext::Ssh { host } => ("ssh", host),
+            SessionContext::Container { runtime, name } => (runtime.as_str(), name),
+            SessionContext::Toolbox { name } => ("toolbox", name),
+        };
+        Some(match name {
+            Some(name) => format!("{} {}", kind, name),
+            None => kind.to_string(),
+        })
+    }
+
+    /// Context entered by a foreground command like `ssh -p 22 me@host`
+    pub fn from_command(command_line: &str) -> Option<Self> {
+        let words: Vec<&str> = command_line.split_whitespace().collect();
+        let (program, args) = words.split_first()?;
+        let program = program.rsplit('/').next().unwrap_or(program);
+        let first_operand = |args: &[&str]| {
+            args.iter()
+                .find(|arg| !arg.starts_with('-'))
+                .map(|arg| arg.to_string())
+        };
+        match (program, args) {
+            ("ssh" | "mosh" | "autossh", _) => Some(SessionContext::Ssh {
+                host: ssh_host(args),
+            }),
+            ("docker" | "podman" | "nerdctl", [sub @ ("exec" | "attach" | "run"), rest @ ..]) => {
+                Some(SessionContext::Container {
+                    runtime: program.to_string(),
+                    name: (*sub != "run").then(|| first_operand(rest)).flatten(),
+                })
+            }
+            ("kubectl", ["exec" | "attach", rest @ ..]) => Some(SessionContext::Container {
+                runtime: "kubernetes".to_string(),
+                name: first_operand(rest),
+            }),
+            ("lxc", ["exec" | "shell", rest @ ..]) => Some(SessionContext::Container {
+                runtime: "lxc".to_string(),
+                name: first_operand(rest),
+            }),
+            ("toolbox" | "distrobox", ["enter" | "run", rest @ ..]) => {
+                Some(SessionContext::Toolbox {
+                    name: first_operand(rest),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Context the shell itself started in
+    pub fn from_env(env: &[(String, String)]) -> Option<Self> {
+        let get = |key: &str| {
+            env.iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.as_str())
+                .filter(|value| !value.is_empty())
+        };
+        if get("TOOLBOX_PATH").is_some() || get("DISTROBOX_ENTER_PATH").is_some() {
+            return Some(SessionContext::Toolbox { name: None });
+        }
+        if let Some(runtime) = get("container") {
+            return Some(SessionContext::Container {
+                runtime: runtime.to_string(),
+                name: None,
+            });
+        }
+        if get("SSH_CONNECTION").is_some() || get("SSH_TTY").is_some() {
+            return Some(SessionContext::Ssh { host: None });
+        }
+        None
+    }
+
+    /// A shell on another machine, going by the host of its OSC 7 reports
+    pub fn from_host(host: &str, local_host: &str) -> Option<Self> {
+        let remote = !host.is_empty()
+            && !host.eq_ignore_ascii_case("localhost")
+            && !host.eq_ignore_ascii_case(local_host);
+        remote.then(|| SessionContext::Ssh {
+            host: Some(host.to_string()),
+        })
+    }
+
+    pub fn detect(
+        foreground_command: Option<&str>,
+        osc7_host: Option<&str>,
+        env: &[(String, String)],
+        local_host: &str,
+    ) -> Self {
+        foreground_command
+            .and_then(Self::from_command)
+            .or_else(|| osc7_host.and_then(|host| Self::from_host(host, local_host)))
+            .or_else(|| Self::from_env(env))
+            .unwrap_or_default()
+    }
+}
+
+/// Host operand of an ssh command line, without the user
+fn ssh_host(args: &[&str]) -> Option<String> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix('-') {
+            // `-p 22`, but not `-p22` or `-v`
+            Some(option) if option.len() == 1 && SSH_VALUE_OPTIONS.contains(option) => {
+                args.next();
+            }
+            Some(_) => {}
+            None => {
+                let host = arg.rsplit('@').next().unwrap_or(arg);
+                return Some(host.to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Host and path of an OSC 7 `file://host/path` report
+pub fn parse_osc7(uri: &str) -> Option<(String, String)> {
+    let rest = uri.strip_prefix("file://")?;
+    let slash = rest.find('/')?;
+    Some((rest[..slash].to_string(), percent_decode(&rest[slash..])))
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// This machine's host name, as shells put it in OSC 7
+pub fn local_hostname() -> String {
+    nix::unistd::gethostname()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Command line of the foreground process on the terminal of `shell_pid`,
+/// from `/proc`
+#[cfg(target_os = "linux")]
+pub fn foreground_command(shell_pid: u32) -> Option<String> {
+    let foreground = ProcStat::read(shell_pid)?.tpgid;
+    if foreground <= 0 || foreground as u32 == shell_pid {
+        return None;
+    }
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", foreground)).ok()?;
+    let words: Vec<String> = cmdline
+        .split(|byte| *byte == 0)
+        .filter(|word| !word.is_empty())
+        .map(|word| String::from_utf8_lossy(word).into_owned())
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Command line of the foreground process on the terminal of `shell_pid`
+#[cfg(not(target_os = "linux"))]
+pub fn foreground_command(_shell_pid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_command() {
+        let ssh = SessionContext::from_command("/usr/bin/ssh -p 2222 -v deploy@prod-1 uptime");
+        assert_eq!(ssh.as_ref().and_then(SessionContext::badge).unwrap(), "ssh prod-1");
+        assert_eq!(
+            SessionContext::from_command("docker exec -it web bash"),
+            Some(SessionContext::Container {
+                runtime: "docker".to_string(),
+                name: Some("web".to_string()),
+            })
+        );
+        assert_eq!(
+            SessionContext::from_command("toolbox enter"),
+            Some(SessionContext::Toolbox { name: None })
+        );
+        assert_eq!(SessionContext::from_command("docker ps"), None);
+        assert_eq!(SessionContext::from_command("vim ssh.txt"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let env = vec![("SSH_CONNECTION".to_string(), "10.0.0.2 5 10.0.0.1 22".to_string())];
+        let context = SessionContext::detect(None, Some("box"), &[], "box");
+        assert!(context.is_local());
+        let context = SessionContext::detect(None, Some("db-1"), &env, "box");
+        assert_eq!(context.badge().unwrap(), "ssh db-1");
+        assert_eq!(SessionContext::detect(None, None, &env, "box").name(), "ssh");
+
+        assert_eq!(
+            parse_osc7("file://db-1/home/me/my%20dir"),
+            Some(("db-1".to_string(), "/home/me/my dir".to_string()))
+        );
+    }
+}
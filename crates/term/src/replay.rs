@@ -0,0 +1,241 @@
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::headless::{HeadlessTerminal, ScreenSnapshot};
+
+/// Something that happened on the PTY during a recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayEvent {
+    Output(Vec<u8>),
+    Resize { cols: usize, rows: usize },
+}
+
+/// A captured PTY stream with virtual timestamps
+///
+/// Fixture files hold one event per line, `#` starting a comment:
+///
+/// ```text
+/// 0.000 o \e]2;build\a
+/// 0.120 r 40x10
+/// 0.250 o hello\r\n
+/// ```
+///
+/// Output payloads use `\e`, `\a`, `\r`, `\n`, `\t`, `\\` and `\xNN` escapes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub events: Vec<(Duration, ReplayEvent)>,
+}
+
+impl Recording {
+    /// A raw byte capture, replayed as a single output at time zero
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Self {
+            events: vec![(Duration::ZERO, ReplayEvent::Output(data.to_vec()))],
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording {}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut recording = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let event = parse_line(line).with_context(|| format!("Line {}", index + 1))?;
+            recording.events.push(event);
+        }
+        Ok(recording)
+    }
+
+    /// Append output, e.g. from a live recorder
+    pub fn push_output(&mut self, at: Duration, data: &[u8]) {
+        self.events.push((at, ReplayEvent::Output(data.to_vec())));
+    }
+
+    /// Fixture text readable by [`Recording::parse`]
+    pub fn to_fixture(&self) -> String {
+        let mut text = String::new();
+        for (at, event) in &self.events {
+            let at = at.as_secs_f64();
+            match event {
+                ReplayEvent::Output(data) => {
+                    text.push_str(&format!("{:.3} o {}\n", at, escape(data)));
+                }
+                ReplayEvent::Resize { cols, rows } => {
+                    text.push_str(&format!("{:.3} r {}x{}\n", at, cols, rows));
+                }
+            }
+        }
+        text
+    }
+}
+
+fn parse_line(line: &str) -> Result<(Duration, ReplayEvent)> {
+    let mut parts = line.splitn(3, ' ');
+    let at: f64 = parts
+        .next()
+        .and_then(|at| at.parse().ok())
+        .filter(|at: &f64| at.is_finite() && *at >= 0.0)
+        .ok_or_else(|| anyhow!("Invalid timestamp"))?;
+    let at = Duration::from_secs_f64(at);
+    let kind = parts.next().ok_or_else(|| anyhow!("Missing event kind"))?;
+    let payload = parts.next().unwrap_or_default();
+
+    match kind {
+        "o" => Ok((at, ReplayEvent::Output(unescape(payload)?))),
+        "r" => {
+            let (cols, rows) = payload
+                .split_once('x')
+                .and_then(|(cols, rows)| Some((cols.parse().ok()?, rows.parse().ok()?)))
+                .ok_or_else(|| anyhow!("Invalid size: {}", payload))?;
+            Ok((at, ReplayEvent::Resize { cols, rows }))
+        }
+        _ => Err(anyhow!("Unknown event kind: {}", kind)),
+    }
+}
+
+fn unescape(payload: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(payload.len());
+    let mut chars = payload.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('e') => bytes.push(0x1b),
+            Some('a') => bytes.push(0x07),
+            Some('r') => bytes.push(b'\r'),
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| anyhow!("Invalid \\x escape: {}", hex))?;
+                bytes.push(byte);
+            }
+            other => return Err(anyhow!("Invalid escape: \\{}", other.unwrap_or(' '))),
+        }
+    }
+    Ok(bytes)
+}
+
+fn escape(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\x1b' => text.push_str("\\e"),
+                '\x07' => text.push_str("\\a"),
+                '\r' => text.push_str("\\r"),
+                '\n' => text.push_str("\\n"),
+                '\t' => text.push_str("\\t"),
+                '\\' => text.push_str("\\\\"),
+                c if c.is_control() && (c as u32) < 0x100 => {
+                    text.push_str(&format!("\\x{:02x}", c as u32))
+                }
+                c => text.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            text.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    text
+}
+
+/// Feeds a recording through the parser and VT on a virtual clock
+pub struct Replay {
+    recording: Recording,
+    terminal: HeadlessTerminal,
+    next: usize,
+    clock: Duration,
+}
+
+impl Replay {
+    pub fn new(recording: Recording, cols: usize, rows: usize) -> Self {
+        Self {
+            recording,
+            terminal: HeadlessTerminal::new(cols, rows),
+            next: 0,
+            clock: Duration::ZERO,
+        }
+    }
+
+    /// Apply every event up to and including `to`
+    pub fn advance_to(&mut self, to: Duration) -> Result<()> {
+        while let Some((at, event)) = self.recording.events.get(self.next) {
+            if *at > to {
+                break;
+            }
+            match event {
+                ReplayEvent::Output(data) => self.terminal.feed(data)?,
+                ReplayEvent::Resize { cols, rows } => self.terminal.resize(*cols, *rows),
+            }
+            self.next += 1;
+        }
+        self.clock = self.clock.max(to);
+        Ok(())
+    }
+
+    /// Apply the rest of the recording
+    pub fn finish(&mut self) -> Result<ScreenSnapshot> {
+        self.advance_to(Duration::MAX)?;
+        Ok(self.snapshot())
+    }
+
+    pub fn clock(&self) -> Duration {
+        self.clock
+    }
+
+    pub fn snapshot(&self) -> ScreenSnapshot {
+        self.terminal.snapshot()
+    }
+}
+
+/// Final screen of a recording replayed at the given size
+pub fn replay(recording: &Recording, cols: usize, rows: usize) -> Result<ScreenSnapshot> {
+    Replay::new(recording.clone(), cols, rows).finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Golden fixtures: recording and expected final screen text
+    const GOLDEN: &[(&str, &str, &str)] = &[(
+        "prompt_and_redraw",
+        include_str!("../fixtures/prompt_and_redraw.vtrec"),
+        include_str!("../fixtures/prompt_and_redraw.screen"),
+    )];
+
+    #[test]
+    fn test_golden_replays() {
+        for (name, recording, expected) in GOLDEN {
+            let recording = Recording::parse(recording).unwrap();
+            assert_eq!(Recording::parse(&recording.to_fixture()).unwrap(), recording);
+
+            let screen = replay(&recording, 20, 5).unwrap();
+            assert_eq!(screen.text(), *expected, "fixture {}", name);
+        }
+    }
+
+    #[test]
+    fn test_virtual_timing() {
+        let recording = Recording::parse("0.0 o one\n1.5 o \\r\\ntwo\n").unwrap();
+        let mut replay = Replay::new(recording, 10, 3);
+        replay.advance_to(Duration::from_secs(1)).unwrap();
+        assert_eq!(replay.snapshot().text(), "one\n");
+        assert_eq!(replay.finish().unwrap().text(), "one\ntwo\n");
+    }
+}
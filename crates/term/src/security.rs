@@ -0,0 +1,166 @@
+// Policy for escape sequences that reach outside the terminal
+//
+// OSC 8 links and OSC 52 clipboard writes come from whatever a pane prints,
+// including untrusted files and remote hosts. Links are checked when
+// activated, clipboard writes when the UI takes them from the VT; anything
+// refused is logged.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use config::SecurityConfig;
+use tracing::warn;
+
+use crate::vt::ClipboardRequest;
+
+/// What to do when the user activates an OSC 8 link
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkDecision {
+    Open,
+    /// Show the target and open only once confirmed
+    Confirm,
+    Blocked(String),
+}
+
+/// Applies `security` to one pane's output
+pub struct EscapePolicy {
+    config: SecurityConfig,
+    /// Times of recently accepted clipboard writes
+    clipboard_writes: VecDeque<Instant>,
+}
+
+impl EscapePolicy {
+    pub fn new(config: &SecurityConfig) -> Self {
+        Self {
+            config: config.clone(),
+            clipboard_writes: VecDeque::new(),
+        }
+    }
+
+    /// Decide on opening `uri`, the target of an OSC 8 link
+    pub fn check_link(&self, uri: &str) -> LinkDecision {
+        let decision = if !self.config.hyperlinks {
+            LinkDecision::Blocked("hyperlinks are disabled".to_string())
+        } else {
+            match uri.split_once(':') {
+                Some((scheme, _))
+                    if self
+                        .config
+                        .link_schemes
+                        .iter()
+                        .any(|allowed| allowed.eq_ignore_ascii_case(scheme)) =>
+                {
+                    if self.config.confirm_links {
+                        LinkDecision::Confirm
+                    } else {
+                        LinkDecision::Open
+                    }
+                }
+                Some((scheme, _)) => {
+                    LinkDecision::Blocked(format!("scheme '{}' is not allowed", scheme))
+                }
+                None => LinkDecision::Blocked("no scheme".to_string()),
+            }
+        };
+        if let LinkDecision::Blocked(reason) = &decision {
+            warn!("Blocked hyperlink to {}: {}", uri, reason);
+        }
+        decision
+    }
+
+    /// Decoded text of an OSC 52 write, or why it was refused
+    pub fn clipboard_write(&mut self, request: &ClipboardRequest, now: Instant) -> Result<String> {
+        let result = self.check_clipboard_write(request, now);
+        if let Err(e) = &result {
+            warn!("Refused clipboard write ({}): {}", request.selection, e);
+        }
+        result
+    }
+
+    fn check_clipboard_write(
+        &mut self,
+        request: &ClipboardRequest,
+        now: Instant,
+    ) -> Result<String> {
+        if !self.config.clipboard_write {
+            return Err(anyhow!("clipboard writes are disabled"));
+        }
+        // Base64 is 4/3 of the decoded size; refuse before decoding
+        let max = self.config.clipboard_max_bytes;
+        if request.data.len() / 4 * 3 > max + 3 {
+            return Err(anyhow!("over the {} byte limit", max));
+        }
+        let data = STANDARD
+            .decode(request.data.trim())
+            .map_err(|e| anyhow!("invalid base64: {}", e))?;
+        if data.len() > max {
+            return Err(anyhow!("over the {} byte limit", max));
+        }
+
+        while let Some(&at) = self.clipboard_writes.front() {
+            if now.duration_since(at) < Duration::from_secs(60) {
+                break;
+            }
+            self.clipboard_writes.pop_front();
+        }
+        if self.clipboard_writes.len() >= self.config.clipboard_writes_per_minute {
+            return Err(anyhow!("more than {} writes a minute", self.clipboard_writes.len()));
+        }
+        self.clipboard_writes.push_back(now);
+
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::HeadlessTerminal;
+
+    #[test]
+    fn test_link_policy() {
+        let mut terminal = HeadlessTerminal::new(20, 2);
+        terminal
+            .feed(b"\x1b]8;;https://example.com\x07site\x1b[0m\x1b]8;;\x07 x")
+            .unwrap();
+        assert_eq!(terminal.hyperlink_at(0, 3), Some("https://example.com"));
+        assert_eq!(terminal.hyperlink_at(0, 5), None);
+
+        let mut config = SecurityConfig::default();
+        let policy = EscapePolicy::new(&config);
+        assert_eq!(policy.check_link("https://example.com"), LinkDecision::Confirm);
+        assert!(matches!(policy.check_link("javascript:alert(1)"), LinkDecision::Blocked(_)));
+
+        config.confirm_links = false;
+        let policy = EscapePolicy::new(&config);
+        assert_eq!(policy.check_link("HTTPS://example.com"), LinkDecision::Open);
+    }
+
+    #[test]
+    fn test_clipboard_limits() {
+        let config = SecurityConfig {
+            clipboard_max_bytes: 8,
+            clipboard_writes_per_minute: 2,
+            ..SecurityConfig::default()
+        };
+        let mut policy = EscapePolicy::new(&config);
+        let request = |data: &str| ClipboardRequest {
+            selection: "c".to_string(),
+            data: data.to_string(),
+        };
+        let start = Instant::now();
+
+        // "hello" and "hello world!"
+        assert_eq!(policy.clipboard_write(&request("aGVsbG8="), start).unwrap(), "hello");
+        assert!(policy.clipboard_write(&request("aGVsbG8gd29ybGQh"), start).is_err());
+        assert!(policy.clipboard_write(&request("%%%"), start).is_err());
+
+        assert!(policy.clipboard_write(&request("aGVsbG8="), start).is_ok());
+        assert!(policy.clipboard_write(&request("aGVsbG8="), start).is_err());
+        let later = start + Duration::from_secs(61);
+        assert!(policy.clipboard_write(&request("aGVsbG8="), later).is_ok());
+    }
+}
@@ -0,0 +1,218 @@
+// Shell integration scripts and injection
+// Makes bash/zsh/fish emit OSC 133 prompt marks and OSC 7 cwd reports
+
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+};
+
+const BASH_SCRIPT: &str = include_str!("../shell/voidcli.bash");
+const ZSH_SCRIPT: &str = include_str!("../shell/voidcli.zsh");
+const FISH_SCRIPT: &str = include_str!("../shell/voidcli.fish");
+
+/// Switch ZDOTDIR to the user's, unset when they had none
+const ZSH_USER_ZDOTDIR: &str = concat!(
+    "if [[ -n \"$VOIDCLI_USER_ZDOTDIR\" ]]; then\n",
+    "    export ZDOTDIR=\"$VOIDCLI_USER_ZDOTDIR\"\n",
+    "else\n",
+    "    unset ZDOTDIR\n",
+    "fi\n",
+);
+
+/// Note where the user's files left ZDOTDIR, and point it back here
+const ZSH_OUR_ZDOTDIR: &str = concat!(
+    "export VOIDCLI_USER_ZDOTDIR=\"${ZDOTDIR-}\"\n",
+    "export ZDOTDIR=\"$VOIDCLI_ZDOTDIR\"\n",
+);
+
+/// Shells with a bundled integration script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Arguments and environment that load the integration when spawning a shell
+#[derive(Debug, Clone, Default)]
+pub struct Injection {
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+}
+
+impl Shell {
+    /// Parse a shell name as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+
+    /// Detect the shell from the executable path, e.g. `/usr/bin/zsh`
+    pub fn detect(shell_path: &str) -> Option<Self> {
+        let name = Path::new(shell_path).file_name()?.to_str()?;
+        // Login shells are sometimes reported as `-zsh`
+        Self::from_name(name.trim_start_matches('-'))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+        }
+    }
+
+    /// The integration snippet, suitable for sourcing from the user's rc file
+    pub fn script(&self) -> &'static str {
+        match self {
+            Shell::Bash => BASH_SCRIPT,
+            Shell::Zsh => ZSH_SCRIPT,
+            Shell::Fish => FISH_SCRIPT,
+        }
+    }
+
+    /// Write the integration files under `dir` and return how to load them
+    /// without touching the user's own rc files
+    pub fn install(&self, dir: &Path) -> Result<Injection> {
+        let dir = dir.join(self.name());
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        match self {
+            Shell::Bash => {
+                // --rcfile replaces ~/.bashrc, so source it first
+                let rcfile = dir.join("voidcli.bash");
+                let contents = format!("[ -f ~/.bashrc ] && . ~/.bashrc\n\n{}", BASH_SCRIPT);
+                write_script(&rcfile, &contents)?;
+
+                Ok(Injection {
+                    args: vec!["--rcfile".to_string(), path_string(&rcfile)],
+                    env: Vec::new(),
+                })
+            }
+            Shell::Zsh => {
+                // zsh reads its startup files from ZDOTDIR, which points here
+                // until our .zshrc; each file loads the user's own from their
+                // ZDOTDIR, which their .zshenv may also have moved
+                let zshenv = format!(
+                    "VOIDCLI_ZDOTDIR=\"$ZDOTDIR\"\n{}{}{}{}",
+                    ZSH_USER_ZDOTDIR,
+                    zsh_source_user(".zshenv"),
+                    concat!(
+                        "if [[ ! -o interactive ]]; then\n",
+                        "    unset VOIDCLI_USER_ZDOTDIR VOIDCLI_ZDOTDIR\n",
+                        "    return\n",
+                        "fi\n",
+                    ),
+                    ZSH_OUR_ZDOTDIR
+                );
+                let zprofile = format!(
+                    "{}{}{}",
+                    ZSH_USER_ZDOTDIR,
+                    zsh_source_user(".zprofile"),
+                    ZSH_OUR_ZDOTDIR
+                );
+                // From here on zsh reads the user's files itself (.zlogin)
+                let zshrc = format!(
+                    "{}unset VOIDCLI_USER_ZDOTDIR VOIDCLI_ZDOTDIR\n{}\n{}",
+                    ZSH_USER_ZDOTDIR,
+                    zsh_source_user(".zshrc"),
+                    ZSH_SCRIPT
+                );
+                write_script(&dir.join(".zshenv"), &zshenv)?;
+                write_script(&dir.join(".zprofile"), &zprofile)?;
+                write_script(&dir.join(".zshrc"), &zshrc)?;
+
+                let mut env = vec![("ZDOTDIR".to_string(), path_string(&dir))];
+                if let Ok(user_zdotdir) = std::env::var("ZDOTDIR") {
+                    env.push(("VOIDCLI_USER_ZDOTDIR".to_string(), user_zdotdir));
+                }
+
+                Ok(Injection {
+                    args: Vec::new(),
+                    env,
+                })
+            }
+            Shell::Fish => {
+                let script = dir.join("voidcli.fish");
+                write_script(&script, FISH_SCRIPT)?;
+
+                Ok(Injection {
+                    args: vec![
+                        "--init-command".to_string(),
+                        format!("source '{}'", path_string(&script)),
+                    ],
+                    env: Vec::new(),
+                })
+            }
+        }
+    }
+}
+
+/// Default directory for the generated integration files, private to the
+/// user: under `$XDG_RUNTIME_DIR`, else their cache directory
+pub fn default_install_dir() -> Option<PathBuf> {
+    let var = |name| std::env::var_os(name).map(PathBuf::from);
+    let dir = var("XDG_RUNTIME_DIR")
+        .or_else(|| var("XDG_CACHE_HOME"))
+        .or_else(|| var("HOME").map(|home| home.join(".cache")))
+        .filter(|dir| dir.is_absolute())?;
+    Some(dir.join("voidcli").join("shell-integration"))
+}
+
+/// Source one of the user's zsh startup files, at the top level so that
+/// their `typeset`s stay global
+fn zsh_source_user(name: &str) -> String {
+    format!(
+        "[[ -f \"${{ZDOTDIR:-$HOME}}/{0}\" ]] && source \"${{ZDOTDIR:-$HOME}}/{0}\"\n",
+        name
+    )
+}
+
+fn write_script(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_detect_shell() {
+        assert_eq!(Shell::detect("/usr/bin/zsh"), Some(Shell::Zsh));
+        assert_eq!(Shell::detect("-bash"), Some(Shell::Bash));
+        assert_eq!(Shell::detect("/opt/homebrew/bin/fish"), Some(Shell::Fish));
+        assert_eq!(Shell::detect("/bin/tcsh"), None);
+    }
+
+    #[test]
+    fn test_install_zsh() {
+        let dir = std::env::temp_dir().join(format!("voidcli-zsh-test-{}", std::process::id()));
+        let injection = Shell::Zsh.install(&dir).unwrap();
+        let zdotdir = dir.join("zsh");
+        assert_eq!(injection.env[0], ("ZDOTDIR".to_string(), path_string(&zdotdir)));
+
+        // ZDOTDIR stays ours after .zshenv so that zsh goes on to our .zshrc
+        let zshenv = fs::read_to_string(zdotdir.join(".zshenv")).unwrap();
+        assert!(zshenv.ends_with(ZSH_OUR_ZDOTDIR));
+        let zshrc = fs::read_to_string(zdotdir.join(".zshrc")).unwrap();
+        assert!(zshrc.starts_with(ZSH_USER_ZDOTDIR) && zshrc.ends_with(ZSH_SCRIPT));
+        let mode = fs::metadata(&zdotdir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+// Sixel graphics decoder
+// Turns the data of a sixel DCS string (`DCS P1 ; P2 ; P3 q ... ST`) into an
+// RGBA image, a byte at a time as the parser receives it
+
+/// Largest width or height in pixels; drawing beyond it is dropped
+const MAX_SIXEL_DIMENSION: usize = 2048;
+
+/// Number of color registers
+const PALETTE_SIZE: usize = 256;
+
+/// The VT340 default color registers, as RGB percentages
+const DEFAULT_PALETTE: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [20, 20, 80],
+    [80, 13, 13],
+    [20, 80, 20],
+    [80, 20, 80],
+    [20, 80, 80],
+    [80, 80, 20],
+    [53, 53, 53],
+    [26, 26, 26],
+    [33, 33, 60],
+    [60, 26, 26],
+    [33, 60, 33],
+    [60, 33, 60],
+    [33, 60, 60],
+    [60, 60, 33],
+    [80, 80, 80],
+];
+
+/// A decoded sixel image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SixelImage {
+    pub width: usize,
+    pub height: usize,
+    /// RGBA pixels, row by row; unpainted pixels of a transparent image
+    /// have zero alpha
+    pub pixels: Vec<u8>,
+}
+
+/// Control being read from the data stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Control {
+    None,
+    /// `! Pn` repeat introducer
+    Repeat,
+    /// `# Pc ; Pu ; Px ; Py ; Pz` color introducer
+    Color,
+    /// `" Pan ; Pad ; Ph ; Pv` raster attributes
+    Raster,
+}
+
+/// Incremental sixel decoder
+pub struct SixelDecoder {
+    palette: Vec<[u8; 4]>,
+    color: usize,
+    /// Unpainted pixels stay transparent (P2 = 1)
+    transparent: bool,
+    /// Painted rows, each as wide as drawn so far
+    rows: Vec<Vec<[u8; 4]>>,
+    /// Size announced by the raster attributes
+    raster: (usize, usize),
+    x: usize,
+    /// Top row of the current six-pixel band
+    y: usize,
+    control: Control,
+    params: Vec<usize>,
+}
+
+impl SixelDecoder {
+    /// Start decoding with the DCS parameters before `q`
+    pub fn new(params: &[u32]) -> Self {
+        let mut palette = vec![[0, 0, 0, 255]; PALETTE_SIZE];
+        for (register, rgb) in palette.iter_mut().zip(DEFAULT_PALETTE) {
+            *register = [percent(rgb[0]), percent(rgb[1]), percent(rgb[2]), 255];
+        }
+        Self {
+            palette,
+            color: 0,
+            transparent: params.get(1) == Some(&1),
+            rows: Vec::new(),
+            raster: (0, 0),
+            x: 0,
+            y: 0,
+            control: Control::None,
+            params: Vec::new(),
+        }
+    }
+
+    pub fn feed(&mut self, byte: u8) {
+        match byte {
+            b'0'..=b'9' if self.control != Control::None => {
+                let param = self.params.last_mut().expect("a control has a parameter");
+                *param = param.saturating_mul(10).saturating_add((byte - b'0') as usize);
+                return;
+            }
+            b';' if self.control != Control::None => {
+                self.params.push(0);
+                return;
+            }
+            _ => {}
+        }
+
+        let repeat = match self.control {
+            Control::Repeat => self.params[0].max(1),
+            _ => 1,
+        };
+        self.finish_control();
+        match byte {
+            b'?'..=b'~' => self.draw(byte - b'?', repeat),
+            b'!' => self.start_control(Control::Repeat),
+            b'#' => self.start_control(Control::Color),
+            b'"' => self.start_control(Control::Raster),
+            // Graphics carriage return
+            b'$' => self.x = 0,
+            // Graphics new line
+            b'-' => {
+                self.x = 0;
+                self.y = self.y.saturating_add(6);
+            }
+            _ => {}
+        }
+    }
+
+    /// The image drawn so far, `None` if nothing was drawn or sized
+    pub fn finish(mut self) -> Option<SixelImage> {
+        self.finish_control();
+        let drawn_width = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        let width = drawn_width.max(self.raster.0).min(MAX_SIXEL_DIMENSION);
+        let height = self.rows.len().max(self.raster.1).min(MAX_SIXEL_DIMENSION);
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let background = if self.transparent { [0; 4] } else { self.palette[0] };
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for y in 0..height {
+            let row = self.rows.get(y).map(Vec::as_slice).unwrap_or_default();
+            for x in 0..width {
+                pixels.extend_from_slice(row.get(x).unwrap_or(&background));
+            }
+        }
+        Some(SixelImage {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    fn start_control(&mut self, control: Control) {
+        self.control = control;
+        self.params.clear();
+        self.params.push(0);
+    }
+
+    fn finish_control(&mut self) {
+        match self.control {
+            Control::Color => match self.params[..] {
+                [register] => self.color = register % PALETTE_SIZE,
+                [register, space, a, b, c, ..] => {
+                    self.color = register % PALETTE_SIZE;
+                    let rgb = match space {
+                        1 => hls_to_rgb(a, b, c),
+                        _ => [a, b, c].map(|value| percent(value.min(100) as u8)),
+                    };
+                    self.palette[self.color] = [rgb[0], rgb[1], rgb[2], 255];
+                }
+                _ => {}
+            },
+            Control::Raster => {
+                if let [_, _, width, height, ..] = self.params[..] {
+                    self.raster = (width, height);
+                }
+            }
+            Control::Repeat | Control::None => {}
+        }
+        self.control = Control::None;
+    }
+
+    /// Paint the pixels set in `bits`, top to bottom, `repeat` times
+    fn draw(&mut self, bits: u8, repeat: usize) {
+        let x_end = self.x.saturating_add(repeat).min(MAX_SIXEL_DIMENSION);
+        if bits != 0 && self.x < x_end {
+            let color = self.palette[self.color];
+            for bit in 0..6 {
+                let y = self.y.saturating_add(bit);
+                if bits & (1 << bit) == 0 || y >= MAX_SIXEL_DIMENSION {
+                    continue;
+                }
+                if self.rows.len() <= y {
+                    self.rows.resize(y + 1, Vec::new());
+                }
+                let row = &mut self.rows[y];
+                if row.len() < x_end {
+                    let background = if self.transparent { [0; 4] } else { self.palette[0] };
+                    row.resize(x_end, background);
+                }
+                row[self.x..x_end].fill(color);
+            }
+        }
+        self.x = self.x.saturating_add(repeat);
+    }
+}
+
+fn percent(value: u8) -> u8 {
+    ((value as u32 * 255 + 50) / 100) as u8
+}
+
+/// Sixel HLS: hue in degrees with blue at 0, lightness and saturation in
+/// percent
+fn hls_to_rgb(hue: usize, lightness: usize, saturation: usize) -> [u8; 3] {
+    let l = lightness.min(100) as f64 / 100.0;
+    let s = saturation.min(100) as f64 / 100.0;
+    // Rotate so red is at 0 as in the usual HSL model
+    let h = ((hue % 360 + 240) % 360) as f64 / 360.0;
+    if s == 0.0 {
+        let v = (l * 255.0).round() as u8;
+        return [v, v, v];
+    }
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    let channel = |t: f64| {
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 {
+            p + (q - p) * 6.0 * t
+        } else if t < 0.5 {
+            q
+        } else if t < 2.0 / 3.0 {
+            p + (q - p) * (2.0 / 3.0 - t) * 6.0
+        } else {
+            p
+        };
+        (v * 255.0).round() as u8
+    };
+    [channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(params: &[u32], data: &[u8]) -> Option<SixelImage> {
+        let mut decoder = SixelDecoder::new(params);
+        for &byte in data {
+            decoder.feed(byte);
+        }
+        decoder.finish()
+    }
+
+    #[test]
+    fn test_decode() {
+        // A red 3x6 block, then one green pixel in the next band
+        let image = decode(&[0, 1], b"#1;2;100;0;0#1!3~-#2;2;0;100;0@").unwrap();
+        assert_eq!((image.width, image.height), (3, 7));
+        assert_eq!(&image.pixels[..4], &[255, 0, 0, 255]);
+        let last_row = &image.pixels[6 * 3 * 4..];
+        assert_eq!(&last_row[..4], &[0, 255, 0, 255]);
+        // Transparent where nothing was painted
+        assert_eq!(&last_row[4..8], &[0, 0, 0, 0]);
+
+        let sized = decode(&[], b"\"1;1;8;12").unwrap();
+        assert_eq!((sized.width, sized.height), (8, 12));
+        assert!(decode(&[], b"$-").is_none());
+    }
+}
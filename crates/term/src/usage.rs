@@ -0,0 +1,181 @@
+// CPU time and memory of the commands run in a pane
+//
+// The shell reaps its jobs itself, so their rusage never reaches us through
+// wait4. Instead the foreground process group is sampled from `/proc` while
+// a command runs: each process's CPU time as last seen, and its peak RSS.
+// Processes living shorter than the sampling interval are only counted once
+// the group leader reaps them, so the figures are a lower bound.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How often to call `UsageSampler::sample` while a command runs
+pub const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Resources a command used, for its block's metadata
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommandUsage {
+    pub user_ms: u64,
+    pub system_ms: u64,
+    /// Peak resident set size of the largest process, in KiB
+    pub max_rss_kb: u64,
+}
+
+/// Fields of `/proc/<pid>/stat` used here; times are in clock ticks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcStat {
+    pub pgrp: i32,
+    /// Foreground process group of the process's terminal
+    pub tpgid: i32,
+    pub utime: u64,
+    pub stime: u64,
+    /// Times of reaped children
+    pub cutime: u64,
+    pub cstime: u64,
+}
+
+impl ProcStat {
+    pub fn parse(stat: &str) -> Option<Self> {
+        // The name is parenthesized and may contain spaces; fields after it
+        // start with the state, so pgrp is the third
+        let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+        let number = |index: usize| fields.get(index)?.parse::<i64>().ok();
+        Some(Self {
+            pgrp: number(2)? as i32,
+            tpgid: number(5)? as i32,
+            utime: number(11)? as u64,
+            stime: number(12)? as u64,
+            cutime: number(13)? as u64,
+            cstime: number(14)? as u64,
+        })
+    }
+
+    pub fn read(pid: u32) -> Option<Self> {
+        Self::parse(&std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+    }
+}
+
+/// Peak RSS (`VmHWM`) from `/proc/<pid>/status`, in KiB
+fn peak_rss_kb(pid: u32) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    line.split_whitespace().nth(1)?.parse().ok()
+}
+
+fn clock_ticks_per_second() -> u64 {
+    match nix::unistd::sysconf(nix::unistd::SysconfVar::CLK_TCK) {
+        Ok(Some(ticks)) if ticks > 0 => ticks as u64,
+        _ => 100,
+    }
+}
+
+/// Samples the foreground job of one shell from the start of a command
+/// (OSC 133 C) to its end (OSC 133 D)
+pub struct UsageSampler {
+    shell_pid: u32,
+    /// Latest user and system ticks per process of the job
+    ticks: HashMap<u32, (u64, u64)>,
+    /// User and system ticks of the job leader including reaped children
+    leader_ticks: (u64, u64),
+    max_rss_kb: u64,
+}
+
+impl UsageSampler {
+    pub fn new(shell_pid: u32) -> Self {
+        Self {
+            shell_pid,
+            ticks: HashMap::new(),
+            leader_ticks: (0, 0),
+            max_rss_kb: 0,
+        }
+    }
+
+    /// Record the current foreground job's processes
+    pub fn sample(&mut self) {
+        let Some(shell) = ProcStat::read(self.shell_pid) else {
+            return;
+        };
+        // The shell itself is in the foreground between commands
+        if shell.tpgid <= 0 || shell.tpgid == shell.pgrp {
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir("/proc") else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse().ok()) else {
+                continue;
+            };
+            match ProcStat::read(pid) {
+                Some(stat) if stat.pgrp == shell.tpgid => self.record(pid, stat),
+                _ => {}
+            }
+        }
+    }
+
+    fn record(&mut self, pid: u32, stat: ProcStat) {
+        let seen = self.ticks.entry(pid).or_default();
+        *seen = (seen.0.max(stat.utime), seen.1.max(stat.stime));
+        if pid as i32 == stat.pgrp {
+            self.leader_ticks = (
+                self.leader_ticks.0.max(stat.utime + stat.cutime),
+                self.leader_ticks.1.max(stat.stime + stat.cstime),
+            );
+        }
+        if let Some(rss) = peak_rss_kb(pid) {
+            self.max_rss_kb = self.max_rss_kb.max(rss);
+        }
+    }
+
+    /// Usage of the command, once it has finished; the sampler then starts
+    /// over for the next command
+    pub fn finish(&mut self) -> CommandUsage {
+        let summed = self
+            .ticks
+            .values()
+            .fold((0, 0), |(user, system), (u, s)| (user + u, system + s));
+        // Either estimate can miss processes; the larger one missed fewer
+        let user = summed.0.max(self.leader_ticks.0);
+        let system = summed.1.max(self.leader_ticks.1);
+        let to_ms = |ticks: u64| ticks * 1000 / clock_ticks_per_second();
+        let usage = CommandUsage {
+            user_ms: to_ms(user),
+            system_ms: to_ms(system),
+            max_rss_kb: self.max_rss_kb,
+        };
+        *self = Self::new(self.shell_pid);
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proc_stat() {
+        let stat = "4242 (cargo build) R 4200 4242 4200 34816 4242 4194304 900 0 0 0 \
+                    150 30 7 2 20 0 1 0 100 1000 200";
+        let parsed = ProcStat::parse(stat).unwrap();
+        assert_eq!(
+            parsed,
+            ProcStat {
+                pgrp: 4242,
+                tpgid: 4242,
+                utime: 150,
+                stime: 30,
+                cutime: 7,
+                cstime: 2,
+            }
+        );
+
+        let mut sampler = UsageSampler::new(4200);
+        sampler.record(4242, parsed);
+        sampler.record(4243, ProcStat { pgrp: 4242, utime: 50, ..ProcStat::default() });
+        let usage = sampler.finish();
+        let tick_ms = 1000 / clock_ticks_per_second();
+        assert_eq!(usage.user_ms, 200 * tick_ms);
+        assert_eq!(usage.system_ms, 32 * tick_ms);
+        assert_eq!(sampler.finish(), CommandUsage::default());
+    }
+}
@@ -1,7 +1,24 @@
-use std::{cell::Cell as StdCell, char, collections::HashMap, fmt::format, usize};
+use std::{cell::Cell as StdCell, char, collections::{HashMap, VecDeque}, fmt::format, usize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use anyhow::Result;
+use tracing::debug;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
 
+use crate::grid::Grid;
+use crate::hooks::ExtensionSequence;
+use crate::keyboard::KeyboardModes;
+use crate::mouse::{MouseEncoding, MouseEvent, MouseModes, MouseTracking};
 use crate::parser::TerminalAction;
+use crate::prompts::PromptMarks;
+use crate::sixel::SixelImage;
+
+/// Maximum depth of the window title stack, as in xterm
+const MAX_TITLE_STACK: usize = 10;
+
+/// Maximum depth of the kitty keyboard flag stack
+const MAX_KEYBOARD_FLAGS_STACK: usize = 16;
 
 /// Default terminal colors (ANSI 16-color palette)
 const DEFAULT_COLORS: [&str; 16] = [
@@ -23,6 +40,25 @@ const DEFAULT_COLORS: [&str; 16] = [
     "#EEEEEC", // Bright White
 ];
 
+/// Longest OSC 8 target kept, as in other terminals
+const MAX_HYPERLINK_LEN: usize = 2048;
+/// Distinct link targets per session
+const MAX_HYPERLINKS: usize = 4096;
+/// Clipboard writes held until the UI takes them
+const MAX_CLIPBOARD_REQUESTS: usize = 16;
+/// Hook sequences held until the UI takes them
+const MAX_EXTENSION_SEQUENCES: usize = 64;
+/// Longest a synchronized update may hold back drawing
+const SYNCHRONIZED_OUTPUT_TIMEOUT: Duration = Duration::from_millis(150);
+/// Query responses held until they are written to the program
+const MAX_RESPONSES: usize = 64;
+/// Images kept on the screens and in the scrollback; the oldest go first
+const MAX_IMAGES: usize = 64;
+/// Decoded RGBA bytes those images may hold between them, 16 of the largest
+const MAX_IMAGE_BYTES: usize = 256 * 1024 * 1024;
+/// Cell size in pixels assumed for images until the UI sets it
+const DEFAULT_CELL_PIXEL_SIZE: (usize, usize) = (10, 20);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CellAttributes {
     /// Foreground color (ANSI color index or RGB)
@@ -37,6 +73,10 @@ pub struct CellAttributes {
     pub reverse: bool,
     pub hidden: bool,
     pub strikethrough: bool,
+    /// Protected from selective erase (DECSCA)
+    pub protected: bool,
+    /// Index of the cell's OSC 8 link in `VirtualTerminal::hyperlink`
+    pub hyperlink: Option<u32>,
 }
 
 impl Default for CellAttributes {
@@ -51,60 +91,288 @@ impl Default for CellAttributes {
             reverse: false,
             hidden: false,
             strikethrough: false,
+            protected: false,
+            hyperlink: None,
         }
     }
 }
 
+impl CellAttributes {
+    /// SGR parameters that recreate this rendition from scratch, for
+    /// DECRQSS and for repainting a screen
+    pub fn sgr(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        let flags = [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.blink, "5"),
+            (self.reverse, "7"),
+            (self.hidden, "8"),
+            (self.strikethrough, "9"),
+        ];
+        params.extend(flags.iter().filter(|(set, _)| *set).map(|(_, p)| p.to_string()));
+
+        let defaults = CellAttributes::default();
+        let colors = [
+            (self.fg_color, defaults.fg_color, 30),
+            (self.bg_color, defaults.bg_color, 40),
+        ];
+        for (color, default, base) in colors {
+            match color {
+                Some(color) if Some(color) == default => {}
+                Some(color) if color & 0x1000000 != 0 => params.push(format!(
+                    "{};2;{};{};{}",
+                    base + 8,
+                    (color >> 16) & 0xFF,
+                    (color >> 8) & 0xFF,
+                    color & 0xFF
+                )),
+                Some(color) if color < 8 => params.push((base + color).to_string()),
+                Some(color) => params.push(format!("{};5;{}", base + 8, color)),
+                None => {}
+            }
+        }
+        params.join(";")
+    }
+}
+
+/// How a cell takes part in a double-width character
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellWidth {
+    #[default]
+    Single,
+    /// Left half of a wide character, which also covers the next cell
+    Wide,
+    /// Right half of the wide character in the cell to the left
+    Spacer,
+}
+
 /// Represents a cell in the terminal grid
 #[derive(Debug, Clone)]
 pub struct TerminalCell {
-    /// Character to display
+    /// Character to display; the first one of `grapheme` when set
     pub character: char,
+    /// The whole grapheme cluster when it is more than `character`, e.g.
+    /// a letter with combining accents, a ZWJ emoji sequence or a flag
+    pub grapheme: Option<Box<str>>,
     /// Cell attributes
     pub attributes: CellAttributes,
+    pub width: CellWidth,
 }
 
 impl Default for TerminalCell {
     fn default() -> Self {
         Self {
             character: ' ',
+            grapheme: None,
             attributes: CellAttributes::default(),
+            width: CellWidth::Single,
+        }
+    }
+}
+
+impl TerminalCell {
+    /// Append the cell's text, the whole grapheme cluster
+    pub fn push_text(&self, text: &mut String) {
+        match &self.grapheme {
+            Some(grapheme) => text.push_str(grapheme),
+            None => text.push(self.character),
         }
     }
 }
 
+/// Size of a line, set with DECDHL/DECSWL/DECDWL
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineSize {
+    #[default]
+    Single,
+    DoubleWidth,
+    DoubleHeightTop,
+    DoubleHeightBottom,
+}
+
+/// Character set designated into G0 or G1 (SCS, e.g. `ESC ( 0`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    /// DEC Special Graphics: line drawing in place of lowercase letters
+    DecSpecialGraphics,
+    /// United Kingdom: `#` is a pound sign
+    Uk,
+}
+
+impl Charset {
+    /// The character `c` prints as in this set
+    pub fn map(self, c: char) -> char {
+        match (self, c) {
+            (Charset::DecSpecialGraphics, '_'..='~') => {
+                DEC_SPECIAL_GRAPHICS[c as usize - '_' as usize]
+            }
+            (Charset::Uk, '#') => '£',
+            _ => c,
+        }
+    }
+}
+
+/// DEC Special Graphics for `_` through `~`
+const DEC_SPECIAL_GRAPHICS: [char; 32] = [
+    ' ', '◆', '▒', '␉', '␌', '␍', '␊', '°', '±', '␤', '␋', '┘', '┐', '┌', '└', '┼',
+    '⎺', '⎻', '─', '⎼', '⎽', '├', '┤', '┴', '┬', '│', '≤', '≥', 'π', '≠', '£', '·',
+];
+
+/// Per-row attributes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineAttributes {
+    /// The line was soft-wrapped and continues on the next row
+    pub wrapped: bool,
+    pub size: LineSize,
+}
+
+/// An image placed on the grid, e.g. a sixel
+#[derive(Debug, Clone)]
+pub struct ImagePlacement {
+    pub id: u32,
+    pub image: Arc<SixelImage>,
+    /// Absolute line of the top-left cell, `lines_scrolled()` + screen row
+    pub line: usize,
+    pub col: usize,
+    /// Cells covered at the cell size when the image was placed
+    pub rows: usize,
+    pub cols: usize,
+    /// Placed on the alternate screen
+    pub alternate: bool,
+}
+
+/// A row scrolled off the top of the main screen
+#[derive(Debug, Clone)]
+pub struct ScrollbackLine {
+    /// As wide as the screen was when the row scrolled off
+    pub cells: Vec<TerminalCell>,
+}
+
 /// Represent the terminal grid/buffer
 pub struct VirtualTerminal {
-    /// The grid of cells
-    grid: Vec<Vec<TerminalCell>>,
+    /// The cells and line attributes of the active screen
+    grid: Grid,
     /// Terminal dimensions
     pub cols: usize,
     pub rows: usize,
     // Cursor position
     cursor_row: usize,
     cursor_col: usize,
+    /// Cursor is on the last column and the next printable char wraps first
+    wrap_pending: bool,
     /// curernt attributes for new cells
     current_attributes: CellAttributes,
-    /// saved cursor position
-    saved_cursor_row: usize,
-    saved_cursor_col: usize,
-    // saved attributes
-    saved_attributes: CellAttributes,
+    /// Cursor saved by DECSC on the main and the alternate screen
+    saved_cursor: [SavedCursor; 2],
+    /// Character sets designated into G0 and G1
+    charsets: [Charset; 2],
+    /// G0 or G1, whichever SI/SO last invoked
+    active_charset: usize,
     // color palette
     color_palette: Vec<String>,
     // Terminal title
     pub title: String,
+    /// Titles saved with XTWINOPS 22
+    title_stack: Vec<String>,
+    /// The title changed and the change has not been taken yet
+    title_changed: bool,
     // Scroll region (top, botto)
     scroll_region: (usize, usize),
     // Alternate screen buffer flag
     alt_buffer_active: bool,
     // main screen buffer (when alt is active)
-    main_grid: Option<Vec<Vec<TerminalCell>>>,
+    main_grid: Option<Grid>,
+    /// A bell was received and not yet taken
+    bell_rung: bool,
+    /// Ignore BEL for this session
+    bell_suppressed: bool,
+    /// Keyboard modes (DECCKM, DECKPAM, modifyOtherKeys)
+    keyboard_modes: KeyboardModes,
+    /// Kitty keyboard protocol flag stack
+    keyboard_flags_stack: Vec<u32>,
+    /// Cursor shown (DECTCEM, mode 25)
+    cursor_visible: bool,
+    /// Cursor blinking requested by the program (mode 12), if it asked
+    cursor_blink: Option<bool>,
+    /// Pastes are wrapped in `CSI 200 ~` / `CSI 201 ~` (mode 2004)
+    bracketed_paste: bool,
+    /// Printing past the last column wraps to the next line (DECAWM, mode 7)
+    autowrap: bool,
+    /// Cursor addressing is relative to the scroll region (DECOM, mode 6)
+    origin_mode: bool,
+    /// Mouse events requested by the program (modes 9, 1000-1006, 1015)
+    mouse_modes: MouseModes,
+    /// Focus changes are reported as `CSI I` / `CSI O` (mode 1004)
+    focus_events: bool,
+    /// When the program started a synchronized update (mode 2026)
+    synchronized_since: Option<Instant>,
+    /// A synchronized update ended and has not been presented yet
+    frame_ready: bool,
+    /// Bidi reordering for this session; unset follows `terminal.bidi`
+    bidi: Option<bool>,
+    /// Lines scrolled off the top of the main screen, making row numbers
+    /// absolute for prompt marks
+    lines_scrolled: usize,
+    /// Rows scrolled off the top of the main screen, oldest first
+    scrollback: VecDeque<ScrollbackLine>,
+    /// Most rows kept in `scrollback` (`terminal.scrollback_lines`)
+    scrollback_limit: usize,
+    /// How many rows the view is scrolled back into the scrollback
+    display_offset: usize,
+    /// Shell integration marks (OSC 133) on the main screen
+    prompt_marks: PromptMarks,
+    /// Host and directory last reported by the shell (OSC 7)
+    working_directory: Option<(String, String)>,
+    /// Targets of OSC 8 links, indexed by `CellAttributes::hyperlink`
+    hyperlinks: Vec<String>,
+    /// OSC 52 clipboard writes not yet taken; the policy decides on them
+    clipboard_requests: Vec<ClipboardRequest>,
+    /// Sequences claimed by extension hooks, not yet dispatched
+    extension_sequences: Vec<ExtensionSequence>,
+    /// Cell printed last, which the next character may extend into a
+    /// longer grapheme cluster; cleared by anything but printing
+    last_printed: Option<(usize, usize)>,
+    /// Last graphic character printed, repeated by REP
+    last_char: Option<char>,
+    /// Columns with a tab stop, every 8 until changed by HTS and TBC
+    tab_stops: Vec<bool>,
+    /// Answers to queries, not yet written back to the program
+    responses: Vec<Vec<u8>>,
+    /// Images on both screens and in the scrollback, oldest first
+    images: Vec<ImagePlacement>,
+    next_image_id: u32,
+    /// Size of a cell in pixels, for the cells an image covers
+    cell_pixel_size: (usize, usize),
+}
+
+/// Cursor state saved by DECSC (`ESC 7`, `CSI s`) and restored by DECRC
+#[derive(Debug, Clone, Default)]
+struct SavedCursor {
+    row: usize,
+    col: usize,
+    attributes: CellAttributes,
+    wrap_pending: bool,
+    origin_mode: bool,
+    charsets: [Charset; 2],
+    active_charset: usize,
+}
+
+/// A program's request to set the clipboard (OSC 52)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipboardRequest {
+    pub selection: String,
+    /// Base64 encoded text
+    pub data: String,
 }
 
 impl VirtualTerminal {
-    /// Create a new virtual terminal with specified dimensions
+    /// Create a new virtual terminal with specified dimensions, at least 1x1
     pub fn new(cols: usize, rows: usize) -> Self {
+        let (cols, rows) = (cols.max(1), rows.max(1));
         let mut color_palette = Vec::with_capacity(256);
 
         // Add the default 16 colors
@@ -132,59 +400,86 @@ impl VirtualTerminal {
             color_palette.push(hex);
         }
 
-        // Create the grid with default cells
-        let mut grid = Vec::with_capacity(rows);
-        for _ in 0..rows {
-            let mut row = Vec::with_capacity(cols);
-            for _ in 0..cols {
-                row.push(TerminalCell::default());
-            }
-            grid.push(row);
-        }
-
         Self {
-            grid,
+            grid: Grid::new(cols, rows),
             cols,
             rows,
             cursor_row: 0,
             cursor_col: 0,
+            wrap_pending: false,
             current_attributes: CellAttributes::default(),
-            saved_cursor_row: 0,
-            saved_cursor_col: 0,
-            saved_attributes: CellAttributes::default(),
+            saved_cursor: Default::default(),
+            charsets: [Charset::Ascii; 2],
+            active_charset: 0,
             color_palette,
             title: String::from("Terminal"),
+            title_stack: Vec::new(),
+            title_changed: false,
             scroll_region: (0, rows - 1),
             alt_buffer_active: false,
             main_grid: None,
+            bell_rung: false,
+            bell_suppressed: false,
+            keyboard_modes: KeyboardModes::default(),
+            keyboard_flags_stack: Vec::new(),
+            cursor_visible: true,
+            cursor_blink: None,
+            bracketed_paste: false,
+            autowrap: true,
+            origin_mode: false,
+            mouse_modes: MouseModes::default(),
+            focus_events: false,
+            synchronized_since: None,
+            frame_ready: false,
+            bidi: None,
+            lines_scrolled: 0,
+            scrollback: VecDeque::new(),
+            scrollback_limit: 0,
+            display_offset: 0,
+            prompt_marks: PromptMarks::new(),
+            working_directory: None,
+            hyperlinks: Vec::new(),
+            clipboard_requests: Vec::new(),
+            extension_sequences: Vec::new(),
+            last_printed: None,
+            last_char: None,
+            tab_stops: default_tab_stops(cols),
+            responses: Vec::new(),
+            images: Vec::new(),
+            next_image_id: 0,
+            cell_pixel_size: DEFAULT_CELL_PIXEL_SIZE,
         }
     }
 
-    /// Resize the terminal
+    /// Change how many rows of scrollback are kept, dropping the oldest
+    pub fn set_scrollback_limit(&mut self, lines: usize) {
+        self.scrollback_limit = lines;
+        let excess = self.scrollback.len().saturating_sub(lines);
+        self.scrollback.drain(..excess);
+        self.display_offset = self.display_offset.min(self.scrollback.len());
+    }
+
+    /// Resize the terminal, to at least 1x1
     pub fn resize(&mut self, cols: usize, rows: usize) {
-        // Create a new grid with new dimensions
-        let mut new_grid = Vec::with_capacity(rows);
-        for i in 0..rows {
-            let mut row = Vec::with_capacity(cols);
-            for j in 0..cols {
-                if i < self.rows && j < self.cols {
-                    // Copy existing cells
-                    row.push(self.grid[i][j].clone());
-                } else {
-                    // Fill with default cells
-                    row.push(TerminalCell::default());
-                }
-            }
-            new_grid.push(row);
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        self.grid.resize(cols, rows);
+        // The main screen saved behind the alternate one must match too
+        if let Some(main_grid) = &mut self.main_grid {
+            main_grid.resize(cols, rows);
         }
-
-        self.grid = new_grid;
         self.cols = cols;
         self.rows = rows;
+        // New columns get the default stops, existing ones keep theirs
+        let defaults = default_tab_stops(cols);
+        let kept = self.tab_stops.len().min(cols);
+        self.tab_stops.truncate(kept);
+        self.tab_stops.extend_from_slice(&defaults[kept..]);
 
         // Adjust cursor if it's ouside the new dimensions
         self.cursor_row = self.cursor_row.min(rows - 1);
         self.cursor_col = self.cursor_col.min(cols - 1);
+        self.wrap_pending = false;
+        self.last_printed = None;
 
         // Adjust scroll region
         self.scroll_region = (0, rows - 1);
@@ -192,13 +487,55 @@ impl VirtualTerminal {
 
     /// Process a terminal action
     pub fn process_action(&mut self, action: &TerminalAction) -> Result<()> {
+        // Anything that moves the cursor cancels a pending wrap
+        let keeps_pending_wrap = matches!(
+            action,
+            TerminalAction::Print(_)
+                | TerminalAction::Bell
+                | TerminalAction::SetGraphicsRendition(_)
+                | TerminalAction::SetWindowTitle(_)
+                | TerminalAction::PushTitle
+                | TerminalAction::PopTitle
+                | TerminalAction::ApplicationKeypad(_)
+                | TerminalAction::SetModifyOtherKeys(_)
+                | TerminalAction::PushKeyboardFlags(_)
+                | TerminalAction::PopKeyboardFlags(_)
+                | TerminalAction::SetKeyboardFlags(..)
+                | TerminalAction::SaveCursor
+                | TerminalAction::DesignateCharset(..)
+                | TerminalAction::ShiftOut
+                | TerminalAction::ShiftIn
+                | TerminalAction::Response(_)
+                | TerminalAction::RequestPrivateMode(_)
+                | TerminalAction::RequestSetting(_)
+                | TerminalAction::ReportCursorPosition { .. }
+                | TerminalAction::SetColorPalette(..)
+                | TerminalAction::SetProtection(_)
+                | TerminalAction::SetImplicitBidi(_)
+                | TerminalAction::SemanticPrompt(_)
+                | TerminalAction::SetHyperlink(_)
+                | TerminalAction::ClipboardWrite { .. }
+                | TerminalAction::SetWorkingDirectory { .. }
+                | TerminalAction::Extension(_)
+                | TerminalAction::SetTabStop
+                | TerminalAction::ClearTabStop(_)
+                | TerminalAction::RepeatChar(_)
+        );
+        if !keeps_pending_wrap {
+            self.wrap_pending = false;
+        }
+        if !matches!(action, TerminalAction::Print(_) | TerminalAction::RepeatChar(_)) {
+            self.last_printed = None;
+        }
+
         match action {
-            TerminalAction::Print(byte) => {
-                let c = *byte as char;
-                self.put_char(c);
+            TerminalAction::Print(c) => {
+                self.put_char(*c)?;
             }
             TerminalAction::Bell => {
-
+                if !self.bell_suppressed {
+                    self.bell_rung = true;
+                }
             }
             TerminalAction::Backspace => {
                 if self.cursor_col > 0 {
@@ -206,43 +543,72 @@ impl VirtualTerminal {
                 }
             }
 
-            TerminalAction::Tab => {
-                // Move to next tab stop (usually 8 spaces)
-                self.cursor_col = (self.cursor_col + 8) / 8 * 8;
-                if self.cursor_col >= self.cols {
-                    self.cursor_col = self.cols - 1;
+            TerminalAction::Tab => self.tab_forward(1),
+            TerminalAction::ForwardTab(n) => self.tab_forward(*n as usize),
+            TerminalAction::BackwardTab(n) => {
+                for _ in 0..(*n as usize).min(self.cols) {
+                    match (0..self.cursor_col).rev().find(|&col| self.tab_stops[col]) {
+                        Some(col) => self.cursor_col = col,
+                        None => {
+                            self.cursor_col = 0;
+                            break;
+                        }
+                    }
                 }
             }
-
-            TerminalAction::LineFeed => {
-                self.cursor_row += 1;
-                if self.cursor_row > self.scroll_region.1 {
-                    self.scroll_up(1);
-                    self.cursor_row = self.scroll_region.1;
+            TerminalAction::SetTabStop => {
+                if let Some(stop) = self.tab_stops.get_mut(self.cursor_col) {
+                    *stop = true;
+                }
+            }
+            TerminalAction::ClearTabStop(mode) => match mode {
+                0 => {
+                    if let Some(stop) = self.tab_stops.get_mut(self.cursor_col) {
+                        *stop = false;
+                    }
+                }
+                3 => self.tab_stops.fill(false),
+                _ => {}
+            },
+            TerminalAction::RepeatChar(n) => {
+                // Capped at a screenful, which is all that can be seen
+                if let Some(c) = self.last_char {
+                    for _ in 0..(*n as usize).min(self.cols * self.rows) {
+                        self.put_char(c)?;
+                    }
                 }
             }
 
+            TerminalAction::LineFeed | TerminalAction::Index => {
+                self.index();
+            }
+
+            TerminalAction::NextLine => {
+                self.index();
+                self.cursor_col = 0;
+            }
+
             TerminalAction::CarriageReturn => {
                 self.cursor_col = 0;
             }
 
             TerminalAction::CursorUp(n) => {
-                let n = *n as usize;
-                if self.cursor_row >= n {
-                    self.cursor_row -= n;
-                } else {
-                    self.cursor_row = 0;
-                }
+                // Stops at the top margin when starting inside the region
+                let top = self.scroll_region.0;
+                let limit = if self.cursor_row >= top { top } else { 0 };
+                self.cursor_row = self.cursor_row.saturating_sub(*n as usize).max(limit);
             }
 
             TerminalAction::CursorDown(n) => {
-                let n = *n as usize;
-                self.cursor_row = (self.cursor_row + n).min(self.rows - 1);
+                let bottom = self.scroll_region.1;
+                let limit = if self.cursor_row <= bottom { bottom } else { self.rows - 1 };
+                self.cursor_row = self.cursor_row.saturating_add(*n as usize).min(limit);
             }
 
             TerminalAction::CursorForward(n) => {
                 let n = *n as usize;
-                self.cursor_col = (self.cursor_col + n).min(self.cols - 1);
+                let last = self.line_cols(self.cursor_row) - 1;
+                self.cursor_col = self.cursor_col.saturating_add(n).min(last);
             }
 
             TerminalAction::CursorBackward(n) => {
@@ -255,75 +621,62 @@ impl VirtualTerminal {
             }
 
             TerminalAction::CursorNextLine(n) => {
-                let n = *n as usize;
-                self.cursor_row = (self.cursor_row + n).min(self.rows - 1);
+                self.process_action(&TerminalAction::CursorDown(*n))?;
                 self.cursor_col = 0;
             }
 
             TerminalAction::CursorPreviousLine(n) => {
-                let n = *n as usize;
-                if self.cursor_row >= n {
-                    self.cursor_row -= n;
-                } else {
-                    self.cursor_row = 0;
-                }
-
+                self.process_action(&TerminalAction::CursorUp(*n))?;
                 self.cursor_col = 0;
             }
 
+            TerminalAction::CursorColumn(col) => {
+                let last = self.line_cols(self.cursor_row) - 1;
+                self.cursor_col = (*col as usize).saturating_sub(1).min(last);
+            }
+
+            TerminalAction::CursorRow(row) => {
+                // Counts from the top margin in origin mode, like CUP
+                let (top, bottom) = self.origin_rows();
+                self.cursor_row = top.saturating_add((*row as usize).saturating_sub(1)).min(bottom);
+                self.cursor_col = self.cursor_col.min(self.line_cols(self.cursor_row) - 1);
+            }
+
             TerminalAction::CursorPosition(row, col) => {
                 let row = *row as usize;
                 let col = *col as usize;
-                // Terminal coordinates are 1-based, convert to 0-based
-                self.cursor_row = row.saturating_sub(1).min(self.rows - 1);
-                self.cursor_col = col.saturating_sub(1).min(self.cols - 1);
+                // Terminal coordinates are 1-based, convert to 0-based; in
+                // origin mode rows count from the top margin and stay inside
+                // the region
+                let (top, bottom) = self.origin_rows();
+                self.cursor_row = top.saturating_add(row.saturating_sub(1)).min(bottom);
+                self.cursor_col = col.saturating_sub(1).min(self.line_cols(self.cursor_row) - 1);
             }
 
             TerminalAction::EraseInDisplay(n) => {
-                match n {
-                    0 => {
-                        // Erase from cursor to end of screen
-                        self.erase_region(
-                            self.cursor_row,
-                            self.cursor_col,
-                            self.rows - 1,
-                            self.cols - 1,
-                        );
-                    },
-                    1 => {
-                        // Erase from start of screen to cursor 
-                        self.erase_region(0, 0, self.cursor_row, self.cursor_col);
-                    },
-
-                    2 | 3 => {
-                        // Erase entire screen to cursor
-                        self.erase_region(0, 0, self.rows - 1, self.cols - 1);
-                    },
-                    _ => {}
-                }
+                self.erase_in_display(*n, false);
             }
 
             TerminalAction::EraseInLine(n) => {
-                match n {
-                    0 => {
-                        // Erase cursor to end of line
-                        self.erase_region(
-                            self.cursor_row,
-                            self.cursor_col,
-                            self.cursor_row,
-                            self.cols - 1,
-                        );
-                    }
+                self.erase_in_line(*n, false);
+            }
 
-                    1 => {
-                        self.erase_region(self.cursor_row, 0, self.cursor_row, self.cursor_col);
-                    }
+            TerminalAction::SelectiveEraseInDisplay(n) => {
+                self.erase_in_display(*n, true);
+            }
 
-                    2 => {
-                        self.erase_region(self.cursor_row, 0, self.cursor_row, self.cols - 1);
-                    }
+            TerminalAction::SelectiveEraseInLine(n) => {
+                self.erase_in_line(*n, true);
+            }
 
-                    _ => {}
+            TerminalAction::SetProtection(protected) => {
+                self.current_attributes.protected = *protected;
+            }
+
+            TerminalAction::SetLineSize(size) => {
+                self.grid.attrs_mut(self.cursor_row).size = *size;
+                if *size != LineSize::Single {
+                    self.cursor_col = self.cursor_col.min(self.line_cols(self.cursor_row) - 1);
                 }
             }
 
@@ -334,20 +687,174 @@ impl VirtualTerminal {
             TerminalAction::Reset => {
                 // Reset terminal state
                 self.current_attributes = CellAttributes::default();
+                self.cursor_visible = true;
+                self.cursor_blink = None;
+                self.bracketed_paste = false;
+                self.autowrap = true;
+                self.origin_mode = false;
+                self.mouse_modes = MouseModes::default();
+                self.focus_events = false;
+                self.saved_cursor = Default::default();
+                self.charsets = [Charset::Ascii; 2];
+                self.active_charset = 0;
+                self.set_private_mode(2026, false);
+                self.keyboard_modes.application_cursor = false;
+                self.use_alternate_buffer(false);
                 self.cursor_row = 0;
                 self.cursor_col = 0;
                 self.scroll_region = (0, self.rows - 1);
 
                 //Clear screen
-                self.erase_region(0, 0, self.rows - 1, self.cols -1);
+                self.grid.fill(&TerminalCell::default());
+                self.clear_scrollback();
+                self.images.clear();
+                self.tab_stops = default_tab_stops(self.cols);
+                self.last_char = None;
             }
 
             TerminalAction::ScrollUp(n) => {
                 let n = *n as usize;
                 self.scroll_up(n);
             }
+            TerminalAction::ScrollDown(n) => {
+                let n = *n as usize;
+                self.scroll_down(n);
+            }
+            TerminalAction::ReverseIndex => {
+                if self.cursor_row == self.scroll_region.0 {
+                    self.scroll_down(1);
+                } else {
+                    self.cursor_row = self.cursor_row.saturating_sub(1);
+                }
+            }
+            TerminalAction::DesignateCharset(slot, charset) => {
+                if let Some(designated) = self.charsets.get_mut(*slot as usize) {
+                    *designated = *charset;
+                }
+            }
+            TerminalAction::ShiftOut => self.active_charset = 1,
+            TerminalAction::ShiftIn => self.active_charset = 0,
+            TerminalAction::SaveCursor => self.save_cursor(),
+            TerminalAction::RestoreCursor => self.restore_cursor(),
+            TerminalAction::SetScrollRegion(top, bottom) => {
+                let top = (*top as usize).max(1) - 1;
+                let bottom = match *bottom as usize {
+                    0 => self.rows,
+                    bottom => bottom.min(self.rows),
+                } - 1;
+                // A region needs at least two rows; invalid ones are ignored
+                if top < bottom {
+                    self.scroll_region = (top, bottom);
+                    self.home_cursor();
+                }
+            }
+            TerminalAction::InsertLines(n) => {
+                self.insert_lines(*n as usize);
+            }
+            TerminalAction::DeleteLines(n) => {
+                self.delete_lines(*n as usize);
+            }
+            TerminalAction::InsertChars(n) => {
+                self.insert_chars(*n as usize);
+            }
+            TerminalAction::DeleteChars(n) => {
+                self.delete_chars(*n as usize);
+            }
+            TerminalAction::EraseChars(n) => {
+                let (row, col) = (self.cursor_row, self.cursor_col);
+                let end = col.saturating_add((*n as usize).max(1) - 1);
+                self.erase_region(row, col, row, end.min(self.line_cols(row) - 1), false);
+            }
+            TerminalAction::ScreenAlignmentTest => {
+                // Fill the screen with 'E' and reset margins and cursor
+                self.grid.fill(&TerminalCell {
+                    character: 'E',
+                    ..TerminalCell::default()
+                });
+                self.scroll_region = (0, self.rows - 1);
+                self.cursor_row = 0;
+                self.cursor_col = 0;
+            }
             TerminalAction::SetWindowTitle(title) => {
-                self.title = title.clone();
+                self.set_title(title.clone());
+            }
+            TerminalAction::PushTitle => {
+                if self.title_stack.len() == MAX_TITLE_STACK {
+                    self.title_stack.remove(0);
+                }
+                self.title_stack.push(self.title.clone());
+            }
+            TerminalAction::PopTitle => {
+                if let Some(title) = self.title_stack.pop() {
+                    self.set_title(title);
+                }
+            }
+            TerminalAction::DecPrivateModeSet(modes) => {
+                for &mode in modes {
+                    self.set_private_mode(mode, true);
+                }
+            }
+            TerminalAction::DecPrivateModeReset(modes) => {
+                for &mode in modes {
+                    self.set_private_mode(mode, false);
+                }
+            }
+            TerminalAction::RequestSetting(setting) => {
+                let value = match setting.as_str() {
+                    "m" => Some(format!("{}m", self.current_attributes.sgr())),
+                    "r" => {
+                        let (top, bottom) = self.scroll_region;
+                        Some(format!("{};{}r", top + 1, bottom + 1))
+                    }
+                    "\"q" => Some(format!("{}\"q", self.current_attributes.protected as u8)),
+                    _ => None,
+                };
+                let response = match value {
+                    Some(value) => format!("\x1bP1$r{}\x1b\\", value),
+                    None => "\x1bP0$r\x1b\\".to_string(),
+                };
+                self.respond(response.into_bytes());
+            }
+            TerminalAction::RequestPrivateMode(mode) => {
+                // 1: set, 2: reset, 0: not recognized
+                let state = match self.private_mode(*mode) {
+                    Some(true) => 1,
+                    Some(false) => 2,
+                    None => 0,
+                };
+                self.respond(format!("\x1b[?{};{}$y", mode, state).into_bytes());
+            }
+            TerminalAction::ApplicationKeypad(enable) => {
+                self.keyboard_modes.application_keypad = *enable;
+            }
+            TerminalAction::SetModifyOtherKeys(level) => {
+                self.keyboard_modes.modify_other_keys = *level;
+            }
+            TerminalAction::PushKeyboardFlags(flags) => {
+                if self.keyboard_flags_stack.len() == MAX_KEYBOARD_FLAGS_STACK {
+                    self.keyboard_flags_stack.remove(0);
+                }
+                self.keyboard_flags_stack.push(self.keyboard_modes.kitty_flags);
+                self.keyboard_modes.kitty_flags = *flags;
+            }
+            TerminalAction::PopKeyboardFlags(n) => {
+                for _ in 0..*n {
+                    match self.keyboard_flags_stack.pop() {
+                        Some(flags) => self.keyboard_modes.kitty_flags = flags,
+                        None => {
+                            self.keyboard_modes.kitty_flags = 0;
+                            break;
+                        }
+                    }
+                }
+            }
+            TerminalAction::SetKeyboardFlags(flags, mode) => {
+                let current = self.keyboard_modes.kitty_flags;
+                self.keyboard_modes.kitty_flags = match mode {
+                    2 => current | flags,
+                    3 => current & !flags,
+                    _ => *flags,
+                };
             }
             TerminalAction::SetColorPalette(index, color) => {
                 let index = *index as usize;
@@ -355,6 +862,56 @@ impl VirtualTerminal {
                     self.color_palette[index] = color.clone();
                 }
             }
+            TerminalAction::SetImplicitBidi(enable) => {
+                self.bidi = Some(*enable);
+            }
+            TerminalAction::SetHyperlink(uri) => {
+                let id = uri.as_deref().and_then(|uri| self.link_id(uri));
+                self.current_attributes.hyperlink = id;
+            }
+            TerminalAction::ClipboardWrite { selection, data } => {
+                if self.clipboard_requests.len() < MAX_CLIPBOARD_REQUESTS {
+                    self.clipboard_requests.push(ClipboardRequest {
+                        selection: selection.clone(),
+                        data: data.clone(),
+                    });
+                }
+            }
+            TerminalAction::Extension(sequence) => {
+                if self.extension_sequences.len() < MAX_EXTENSION_SEQUENCES {
+                    self.extension_sequences.push(sequence.clone());
+                }
+            }
+            TerminalAction::SixelImage(image) => {
+                self.place_image(image.clone());
+            }
+            TerminalAction::Response(response) => {
+                self.respond(response.clone());
+            }
+            TerminalAction::ReportCursorPosition { extended } => {
+                let row = self.cursor_row.saturating_sub(self.origin_rows().0) + 1;
+                let col = self.cursor_col + 1;
+                let response = if *extended {
+                    format!("\x1b[?{};{};1R", row, col)
+                } else {
+                    format!("\x1b[{};{}R", row, col)
+                };
+                self.respond(response.into_bytes());
+            }
+            TerminalAction::SetWorkingDirectory { host, path } => {
+                self.working_directory = Some((host.clone(), path.clone()));
+            }
+            TerminalAction::SemanticPrompt(mark) => {
+                // Full-screen programs draw their own prompts
+                if !self.alt_buffer_active {
+                    self.prompt_marks
+                        .record(self.lines_scrolled + self.cursor_row, *mark);
+                }
+            }
+        }
+        if !keeps_pending_wrap {
+            // Moving up or down, or scrolling, can land on a double-width line
+            self.cursor_col = self.cursor_col.min(self.line_cols(self.cursor_row) - 1);
         }
 
         Ok(())
@@ -362,9 +919,17 @@ impl VirtualTerminal {
 
     /// Process SGR(Select Graphic Rendition) parameters
     fn process_sgr(&mut self, params: &[u32]) {
+        // DECSCA protection and links are not renditions and survive SGR 0
+        let protected = self.current_attributes.protected;
+        let hyperlink = self.current_attributes.hyperlink;
+
         if params.is_empty() {
             // SGR 0 (reset/normal) is implied when no parameters are given
-            self.current_attributes = CellAttributes::default();
+            self.current_attributes = CellAttributes {
+                protected,
+                hyperlink,
+                ..CellAttributes::default()
+            };
             return;
         }
 
@@ -373,7 +938,11 @@ impl VirtualTerminal {
             match params[i] {
                 0 => {
                     // Reset all attributes
-                    self.current_attributes = CellAttributes::default();
+                    self.current_attributes = CellAttributes {
+                        protected,
+                        hyperlink,
+                        ..CellAttributes::default()
+                    };
                 }
                 1 => {
                     // Bold
@@ -455,7 +1024,7 @@ impl VirtualTerminal {
                                     let r = params[i + 2];
                                     let g = params[i + 3];
                                     let b = params[i + 4];
-                                    let rgb = (r << 16) | (g << 8) | b;
+                                    let rgb = ((r & 0xFF) << 16) | ((g & 0xFF) << 8) | (b & 0xFF);
                                     self.current_attributes.fg_color = Some(rgb | 0x1000000);
                                     i += 4;
                                 }
@@ -490,8 +1059,8 @@ impl VirtualTerminal {
                                     let r = params[i + 2];
                                     let g = params[i + 3];
                                     let b = params[i + 4];
-                                    let rgb = (r << 16) | (g << 8) | b;
-                                    self.current_attributes.fg_color = Some(rgb | 0x1000000);
+                                    let rgb = ((r & 0xFF) << 16) | ((g & 0xFF) << 8) | (b & 0xFF);
+                                    self.current_attributes.bg_color = Some(rgb | 0x1000000);
                                     i += 4;
                                 }
                             }
@@ -518,52 +1087,219 @@ impl VirtualTerminal {
     }
 
     /// Put a character at the current cursor position and advance cursor
-    fn put_char(&mut self, c: char) {
-        if c == '\n' {
-            // Line feed
-            self.process_action(&TerminalAction::LineFeed).unwrap();
-            return;
-        } else if c == '\r' {
-            self.process_action(&TerminalAction::CarriageReturn).unwrap();
-            return;
-        } else if c == '\t' {
-            // Tab
-            self.process_action(&TerminalAction::Tab).unwrap();
-            return;
-        } else if c == '\x08' {
-            // Backspace
-            self.process_action(&TerminalAction::Backspace).unwrap();
-            return;
-        } else if c == '\x07' {
-            self.process_action(&TerminalAction::Bell).unwrap();
-            return;
+    fn put_char(&mut self, c: char) -> Result<()> {
+        let control = match c {
+            '\n' => Some(TerminalAction::LineFeed),
+            '\r' => Some(TerminalAction::CarriageReturn),
+            '\t' => Some(TerminalAction::Tab),
+            '\x08' => Some(TerminalAction::Backspace),
+            '\x07' => Some(TerminalAction::Bell),
+            _ => None,
+        };
+        if let Some(action) = control {
+            return self.process_action(&action);
         }
 
-        // Put character at current position
-        if self.cursor_row < self.rows && self.cursor_col < self.cols {
-            self.grid[self.cursor_row][self.cursor_col] = TerminalCell {
-                character: c,
-                attributes: self.current_attributes.clone(),
-            };
+        let c = self.charsets[self.active_charset].map(c);
+        // ASCII never continues a cluster, so skips the segmentation
+        if !c.is_ascii() && self.extend_grapheme(c) {
+            return Ok(());
+        }
+        let width = match c.width() {
+            Some(width @ 1..=2) => width,
+            // Zero-width characters with nothing to attach to
+            _ => return Ok(()),
+        };
+        // A wide character never fits on a one-column line
+        if width > self.line_cols(self.cursor_row) {
+            return Ok(());
         }
 
-        // Advance cursor
-        self.cursor_col += 1;
-        if self.cursor_col >= self.cols {
+        // Wrap deferred from the previous character on the last column, or
+        // early for a wide character that would not fit
+        let line_cols = self.line_cols(self.cursor_row);
+        if !self.autowrap {
+            // Without autowrap the last column is overwritten
+            self.wrap_pending = false;
+            self.cursor_col = self.cursor_col.min(line_cols - width);
+        } else if self.wrap_pending || self.cursor_col + width > line_cols {
+            self.wrap_pending = false;
+            self.grid.attrs_mut(self.cursor_row).wrapped = true;
             self.cursor_col = 0;
-            self.cursor_row += 1;
-            if self.cursor_row > self.scroll_region.1 {
-                self.scroll_up(1);
-                self.cursor_row = self.scroll_region.1;
+            self.index();
+        }
+
+        // Put character at current position
+        let (row, col) = (self.cursor_row, self.cursor_col);
+        self.split_wide(row, col);
+        self.grid[row][col] = TerminalCell {
+            character: c,
+            grapheme: None,
+            attributes: self.current_attributes.clone(),
+            width: CellWidth::Single,
+        };
+        if width == 2 {
+            self.widen(row, col);
+        }
+        self.advance_past(row, col, width);
+        self.last_printed = Some((row, col));
+        self.last_char = Some(c);
+        Ok(())
+    }
+
+    /// Move the cursor to the `n`th next tab stop, or the last column
+    fn tab_forward(&mut self, n: usize) {
+        let last = self.line_cols(self.cursor_row) - 1;
+        for _ in 0..n.min(self.cols) {
+            let next = (self.cursor_col + 1..=last).find(|&col| self.tab_stops[col]);
+            self.cursor_col = next.unwrap_or(last);
+        }
+    }
+
+    /// Move the cursor after a character of `width` cells at `col`, staying
+    /// on the last column until the next char
+    fn advance_past(&mut self, row: usize, col: usize, width: usize) {
+        if col + width >= self.line_cols(row) {
+            self.cursor_col = col + width - 1;
+            self.wrap_pending = true;
+        } else {
+            self.cursor_col = col + width;
+        }
+    }
+
+    /// Make the character at `col` double-width, taking the next cell
+    fn widen(&mut self, row: usize, col: usize) {
+        self.split_wide(row, col + 1);
+        self.grid[row][col].width = CellWidth::Wide;
+        self.grid[row][col + 1] = TerminalCell {
+            character: ' ',
+            grapheme: None,
+            attributes: self.current_attributes.clone(),
+            width: CellWidth::Spacer,
+        };
+    }
+
+    /// Add `c` to the cluster in the cell printed last when it continues
+    /// it (combining marks, ZWJ sequences, variation selectors, the second
+    /// half of a flag)
+    fn extend_grapheme(&mut self, c: char) -> bool {
+        let Some((row, col)) = self.last_printed else {
+            return false;
+        };
+        let cell = &self.grid[row][col];
+        let mut cluster = String::new();
+        cell.push_text(&mut cluster);
+        cluster.push(c);
+        if cluster.graphemes(true).nth(1).is_some() {
+            return false;
+        }
+
+        // Emoji presentation and flags turn a narrow character wide
+        let wide = c == '\u{FE0F}' || is_regional_indicator(c);
+        let grow = wide
+            && cell.width == CellWidth::Single
+            && col + 2 <= self.line_cols(row)
+            && !self.wrap_pending;
+        self.grid[row][col].grapheme = Some(cluster.into_boxed_str());
+        if grow {
+            self.widen(row, col);
+            self.advance_past(row, col, 2);
+        }
+        true
+    }
+
+    /// Blank the other half of a wide character before overwriting one half
+    fn split_wide(&mut self, row: usize, col: usize) {
+        let other = match self.grid[row][col].width {
+            CellWidth::Single => return,
+            CellWidth::Wide => col + 1,
+            CellWidth::Spacer => col.wrapping_sub(1),
+        };
+        let blank = self.blank_cell();
+        if let Some(cell) = self.grid[row].get_mut(other) {
+            *cell = blank;
+        }
+    }
+
+    /// Columns usable on a row; double-size lines hold half as many characters
+    fn line_cols(&self, row: usize) -> usize {
+        match self.grid.attrs(row).size {
+            LineSize::Single => self.cols,
+            _ => (self.cols / 2).max(1),
+        }
+    }
+
+    /// A blank cell carrying the current colors but never protection or a link
+    fn blank_cell(&self) -> TerminalCell {
+        TerminalCell {
+            character: ' ',
+            grapheme: None,
+            attributes: CellAttributes {
+                protected: false,
+                hyperlink: None,
+                ..self.current_attributes.clone()
+            },
+            width: CellWidth::Single,
+        }
+    }
+
+    /// ED and DECSED (0=below, 1=above, 2=all, 3=saved lines)
+    fn erase_in_display(&mut self, n: u32, selective: bool) {
+        match n {
+            0 => {
+                // Erase from cursor to end of screen
+                self.erase_region(
+                    self.cursor_row,
+                    self.cursor_col,
+                    self.rows - 1,
+                    self.cols - 1,
+                    selective,
+                );
             }
+            1 => {
+                // Erase from start of screen to cursor
+                self.erase_region(0, 0, self.cursor_row, self.cursor_col, selective);
+            }
+            2 => {
+                // Erase entire screen
+                self.erase_region(0, 0, self.rows - 1, self.cols - 1, selective);
+                if !selective {
+                    let (top, alternate) = (self.lines_scrolled, self.alt_buffer_active);
+                    self.images.retain(|image| {
+                        image.alternate != alternate || image.line + image.rows <= top
+                    });
+                }
+            }
+            3 => self.clear_scrollback(),
+            _ => {}
         }
     }
 
-    fn erase_region(&mut self, start_row: usize, start_col: usize, end_row: usize, end_col: usize) {
+    /// EL and DECSEL (0=to right, 1=to left, 2=all)
+    fn erase_in_line(&mut self, n: u32, selective: bool) {
+        let row = self.cursor_row;
+        match n {
+            0 => self.erase_region(row, self.cursor_col, row, self.cols - 1, selective),
+            1 => self.erase_region(row, 0, row, self.cursor_col, selective),
+            2 => self.erase_region(row, 0, row, self.cols - 1, selective),
+            _ => {}
+        }
+    }
+
+    /// Blank a region; a selective erase leaves protected cells alone
+    fn erase_region(
+        &mut self,
+        start_row: usize,
+        start_col: usize,
+        end_row: usize,
+        end_col: usize,
+        selective: bool,
+    ) {
         let start_row = start_row.min(self.rows - 1);
         let start_col = start_col.min(self.cols - 1);
         let end_row = end_row.min(self.rows - 1);
         let end_col = end_col.min(self.cols - 1);
+        let blank = self.blank_cell();
 
         for row in start_row..= end_row {
             let col_start = if row == start_row { start_col } else { 0 };
@@ -573,12 +1309,32 @@ impl VirtualTerminal {
                 self.cols - 1
             };
 
+            // Wide characters cut by the region lose their other half too
+            for col in [col_start, col_end] {
+                if !(selective && self.grid[row][col].attributes.protected) {
+                    self.split_wide(row, col);
+                }
+            }
             for col in col_start..= col_end {
-                self.grid[row][col] = TerminalCell {
-                    character: ' ',
-                    attributes: self.current_attributes.clone(),
-                };
+                if selective && self.grid[row][col].attributes.protected {
+                    continue;
+                }
+                self.grid[row][col] = blank.clone();
             }
+
+            // Nothing is left to continue onto the next row
+            if col_end == self.cols - 1 && !selective {
+                self.grid.attrs_mut(row).wrapped = false;
+            }
+        }
+    }
+
+    /// Move the cursor down one line, scrolling if it is on the bottom margin
+    fn index(&mut self) {
+        if self.cursor_row == self.scroll_region.1 {
+            self.scroll_up(1);
+        } else if self.cursor_row < self.rows - 1 {
+            self.cursor_row += 1;
         }
     }
 
@@ -590,44 +1346,279 @@ impl VirtualTerminal {
         if n == 0 {
             return;
         }
-
-        // Move all lines up
-        for row in top..(bottom + 1 - n)  {
-            for col in 0..self.cols {
-                self.grid[row][col] = self.grid[row + n][col].clone();
+        self.last_printed = None;
+        if top == 0 && !self.alt_buffer_active {
+            self.lines_scrolled += n;
+            for row in 0..n {
+                self.push_scrollback(row);
             }
         }
 
-        // Clear the bottom n lines
-        for row in (bottom + 1 - n)..= bottom {
-            for col in 0..self.cols {
-                self.grid[row][col] = TerminalCell {
-                    character: ' ',
-                    attributes: self.current_attributes.clone(),
-                };
-            }
+        // Move all lines up, the top n lines coming round to be cleared
+        self.grid.rotate_up(top, bottom, n);
+        self.blank_rows(bottom + 1 - n, bottom + 1);
+    }
+
+    /// Save a screen row that is about to scroll off the top
+    fn push_scrollback(&mut self, row: usize) {
+        if self.scrollback_limit == 0 {
+            return;
+        }
+        // Reuse the allocation of the line dropped to make room
+        let mut cells = if self.scrollback.len() == self.scrollback_limit {
+            self.scrollback.pop_front().map(|line| line.cells).unwrap_or_default()
+        } else {
+            Vec::with_capacity(self.cols)
+        };
+        cells.clear();
+        cells.extend_from_slice(&self.grid[row]);
+        self.scrollback.push_back(ScrollbackLine { cells });
+        if self.display_offset > 0 {
+            // Keep the rows being looked at in view
+            self.display_offset = (self.display_offset + 1).min(self.scrollback.len());
+        }
+    }
+
+    /// Drop the saved lines (ED 3)
+    fn clear_scrollback(&mut self) {
+        self.scrollback.clear();
+        self.display_offset = 0;
+        let top = self.lines_scrolled;
+        self.images.retain(|image| image.alternate || image.line + image.rows > top);
+    }
+
+    /// Put an image at the cursor, scrolling until its last row is on the
+    /// screen; the cursor ends on that row
+    fn place_image(&mut self, image: Arc<SixelImage>) {
+        let (cell_width, cell_height) = self.cell_pixel_size;
+        let cols = image.width.div_ceil(cell_width);
+        let rows = image.height.div_ceil(cell_height);
+        let col = self.cursor_col;
+        for _ in 1..rows {
+            self.index();
         }
+        let line = (self.lines_scrolled + self.cursor_row + 1).saturating_sub(rows);
+
+        // Forget images scrolled out of the scrollback
+        let first_line = self.lines_scrolled - self.scrollback.len();
+        self.images.retain(|image| image.alternate || image.line + image.rows > first_line);
+        let size = |image: &SixelImage| image.width * image.height * 4;
+        let mut bytes: usize = self.images.iter().map(|placed| size(&placed.image)).sum();
+        while !self.images.is_empty()
+            && (self.images.len() == MAX_IMAGES || bytes + size(&image) > MAX_IMAGE_BYTES)
+        {
+            bytes -= size(&self.images.remove(0).image);
+        }
+        self.images.push(ImagePlacement {
+            id: self.next_image_id,
+            image,
+            line,
+            col,
+            rows,
+            cols,
+            alternate: self.alt_buffer_active,
+        });
+        self.next_image_id = self.next_image_id.wrapping_add(1);
+    }
+
+    /// Images on the active screen and, for the main screen, its scrollback
+    pub fn images(&self) -> impl Iterator<Item = &ImagePlacement> {
+        let alternate = self.alt_buffer_active;
+        self.images.iter().filter(move |image| image.alternate == alternate)
+    }
+
+    /// Set the size of a cell in pixels, for images placed from now on
+    pub fn set_cell_pixel_size(&mut self, width: usize, height: usize) {
+        self.cell_pixel_size = (width.max(1), height.max(1));
+    }
+
+    /// Rows held in the scrollback
+    pub fn scrollback_len(&self) -> usize {
+        self.scrollback.len()
+    }
+
+    /// A row of the scrollback; 0 is the oldest
+    pub fn scrollback_line(&self, index: usize) -> Option<&ScrollbackLine> {
+        self.scrollback.get(index)
+    }
+
+    /// Rows the view is scrolled back; 0 shows the live screen
+    pub fn display_offset(&self) -> usize {
+        self.display_offset
     }
 
-    /// Switch to alternate screen buffer
+    /// Scroll the view back into the scrollback (positive) or towards the
+    /// live screen (negative)
+    pub fn scroll_display(&mut self, lines: isize) {
+        if self.alt_buffer_active {
+            return;
+        }
+        self.display_offset = self
+            .display_offset
+            .saturating_add_signed(lines)
+            .min(self.scrollback.len());
+    }
+
+    pub fn scroll_display_to_bottom(&mut self) {
+        self.display_offset = 0;
+    }
+
+    /// The cell shown at a position of the view, from the scrollback while
+    /// scrolled back; rows saved at a narrower width have no cell there
+    pub fn display_cell(&self, row: usize, col: usize) -> Option<&TerminalCell> {
+        if row >= self.rows || col >= self.cols {
+            return None;
+        }
+        match row.checked_sub(self.display_offset) {
+            Some(screen_row) => self.get_cell(screen_row, col),
+            None => self
+                .scrollback
+                .get(self.scrollback.len() - self.display_offset + row)?
+                .cells
+                .get(col),
+        }
+    }
+
+    /// Scroll the screen down by n lines, inserting blank lines at the top
+    fn scroll_down(&mut self, n: usize) {
+        let (top, bottom) = self.scroll_region;
+        let n = n.min(bottom - top + 1);
+
+        if n == 0 {
+            return;
+        }
+        self.last_printed = None;
+
+        // Move all lines down, the bottom n lines coming round to be cleared
+        self.grid.rotate_down(top, bottom, n);
+        self.blank_rows(top, top + n);
+    }
+
+    /// IL: push the rows from the cursor to the bottom margin down, blank
+    /// rows taking their place; nothing happens outside the scroll region
+    fn insert_lines(&mut self, n: usize) {
+        let (top, bottom) = self.scroll_region;
+        let row = self.cursor_row;
+        if row < top || row > bottom {
+            return;
+        }
+        let n = n.min(bottom - row + 1);
+        self.grid.rotate_down(row, bottom, n);
+        self.blank_rows(row, row + n);
+        self.cursor_col = 0;
+    }
+
+    /// DL: remove rows at the cursor, pulling the rows below up to the
+    /// bottom margin and blanking those left at the bottom
+    fn delete_lines(&mut self, n: usize) {
+        let (top, bottom) = self.scroll_region;
+        let row = self.cursor_row;
+        if row < top || row > bottom {
+            return;
+        }
+        let n = n.min(bottom - row + 1);
+        self.grid.rotate_up(row, bottom, n);
+        self.blank_rows(bottom + 1 - n, bottom + 1);
+        self.cursor_col = 0;
+    }
+
+    /// ICH: shift the cursor and the cells after it right by `n`, dropping
+    /// those pushed off the line and blanking the gap
+    fn insert_chars(&mut self, n: usize) {
+        let row = self.cursor_row;
+        let cols = self.line_cols(row);
+        // The cursor may be past the end of a double-width line
+        let col = self.cursor_col.min(cols - 1);
+        let n = n.min(cols - col);
+        self.blank_spacer(row, col);
+        self.grid[row][col..cols].rotate_right(n);
+        let blank = self.blank_cell();
+        self.grid[row][col..col + n].fill(blank.clone());
+        // A wide character whose right half was pushed off
+        if self.grid[row][cols - 1].width == CellWidth::Wide {
+            self.grid[row][cols - 1] = blank;
+        }
+    }
+
+    /// DCH: remove `n` cells at the cursor, shifting the rest of the line
+    /// left and blanking the cells freed at its end
+    fn delete_chars(&mut self, n: usize) {
+        let row = self.cursor_row;
+        let cols = self.line_cols(row);
+        // The cursor may be past the end of a double-width line
+        let col = self.cursor_col.min(cols - 1);
+        let n = n.min(cols - col);
+        self.blank_spacer(row, col);
+        self.grid[row][col..cols].rotate_left(n);
+        let blank = self.blank_cell();
+        self.grid[row][cols - n..cols].fill(blank);
+        // The right half of a wide character whose left half was deleted
+        self.blank_spacer(row, col);
+    }
+
+    /// Blank a spacer at `col` along with the wide character it belongs to
+    fn blank_spacer(&mut self, row: usize, col: usize) {
+        if self.grid[row][col].width == CellWidth::Spacer {
+            self.split_wide(row, col);
+            self.grid[row][col] = self.blank_cell();
+        }
+    }
+
+    /// Blank the rows `start..end` with the current background
+    fn blank_rows(&mut self, start: usize, end: usize) {
+        let blank = self.blank_cell();
+        for row in start..end {
+            self.grid[row].fill(blank.clone());
+            *self.grid.attrs_mut(row) = LineAttributes::default();
+        }
+    }
+
+    /// DECSC: remember the cursor, its attributes and charsets
+    fn save_cursor(&mut self) {
+        self.saved_cursor[self.alt_buffer_active as usize] = SavedCursor {
+            row: self.cursor_row,
+            col: self.cursor_col,
+            attributes: self.current_attributes.clone(),
+            wrap_pending: self.wrap_pending,
+            origin_mode: self.origin_mode,
+            charsets: self.charsets,
+            active_charset: self.active_charset,
+        };
+    }
+
+    /// DECRC: go back to the saved cursor, clamped to the screen
+    fn restore_cursor(&mut self) {
+        // Without a save this homes the cursor with default attributes
+        let saved = self.saved_cursor[self.alt_buffer_active as usize].clone();
+        self.cursor_row = saved.row.min(self.rows - 1);
+        self.cursor_col = saved.col.min(self.line_cols(self.cursor_row) - 1);
+        self.current_attributes = saved.attributes;
+        // A wrap was pending on the last column, unless clamping moved it
+        self.wrap_pending = saved.wrap_pending && self.cursor_col == saved.col;
+        self.origin_mode = saved.origin_mode;
+        self.charsets = saved.charsets;
+        self.active_charset = saved.active_charset;
+    }
+
+    /// Switch to or from the alternate screen buffer
+    ///
+    /// The alternate screen starts out blank, has no scrollback and is
+    /// discarded on leaving it.
     pub fn use_alternate_buffer(&mut self, enable: bool) {
         if enable != self.alt_buffer_active {
+            // Neither screen shows the other's scrollback
+            self.display_offset = 0;
+            self.last_printed = None;
             if enable {
                 // Switch to alternate buffer
-                let mut alt_grid = Vec::with_capacity(self.rows);
-                for _ in 0..self.rows {
-                    let mut row = Vec::with_capacity(self.cols);
-                    for _ in 0..self.cols {
-                        row.push(TerminalCell::default());
-                    }
-                    alt_grid.push(row);
-                }
+                let alt_grid = Grid::new(self.cols, self.rows);
                 self.main_grid = Some(std::mem::replace(&mut self.grid, alt_grid));
             } else {
-                // Switch back to main buffer
+                // Switch back to main buffer, resized along with the alternate one
                 if let Some(main_grid) = self.main_grid.take() {
                     self.grid = main_grid;
                 }
+                self.images.retain(|image| !image.alternate);
             }
             self.alt_buffer_active = enable;
         }
@@ -642,6 +1633,275 @@ impl VirtualTerminal {
         }
     }
 
+    /// Get the attributes of a row
+    pub fn line_attributes(&self, row: usize) -> Option<&LineAttributes> {
+        (row < self.rows).then(|| self.grid.attrs(row))
+    }
+
+    /// Apply a single DECSET/DECRST mode
+    fn set_private_mode(&mut self, mode: u32, enable: bool) {
+        match mode {
+            1 => self.keyboard_modes.application_cursor = enable,
+            6 => {
+                self.origin_mode = enable;
+                self.home_cursor();
+            }
+            7 => {
+                self.autowrap = enable;
+                self.wrap_pending = false;
+            }
+            12 => self.cursor_blink = Some(enable),
+            25 => self.cursor_visible = enable,
+            47 | 1047 => self.use_alternate_buffer(enable),
+            // Like 1047, with DECSC before entering and DECRC after leaving
+            1049 => {
+                if enable && !self.alt_buffer_active {
+                    self.save_cursor();
+                    self.use_alternate_buffer(true);
+                } else if !enable && self.alt_buffer_active {
+                    self.use_alternate_buffer(false);
+                    self.restore_cursor();
+                }
+            }
+            1004 => self.focus_events = enable,
+            2004 => self.bracketed_paste = enable,
+            2026 => {
+                if !enable && self.synchronized_since.take().is_some() {
+                    self.frame_ready = true;
+                }
+                if enable && self.synchronized_since.is_none() {
+                    self.synchronized_since = Some(Instant::now());
+                }
+            }
+            _ if self.mouse_modes.set(mode, enable) => {}
+            _ => debug!("Ignoring unsupported DEC private mode {}", mode),
+        }
+    }
+
+    /// Current state of a DEC private mode, `None` for unknown modes
+    fn private_mode(&self, mode: u32) -> Option<bool> {
+        let mouse = self.mouse_modes;
+        match mode {
+            1 => Some(self.keyboard_modes.application_cursor),
+            6 => Some(self.origin_mode),
+            7 => Some(self.autowrap),
+            12 => Some(self.cursor_blink.unwrap_or(false)),
+            25 => Some(self.cursor_visible),
+            47 | 1047 | 1049 => Some(self.alt_buffer_active),
+            9 => Some(mouse.tracking == MouseTracking::X10),
+            1000 => Some(mouse.tracking == MouseTracking::Normal),
+            1002 => Some(mouse.tracking == MouseTracking::ButtonEvent),
+            1003 => Some(mouse.tracking == MouseTracking::AnyEvent),
+            1004 => Some(self.focus_events),
+            1005 => Some(mouse.encoding == MouseEncoding::Utf8),
+            1006 => Some(mouse.encoding == MouseEncoding::Sgr),
+            1015 => Some(mouse.encoding == MouseEncoding::Urxvt),
+            2004 => Some(self.bracketed_paste),
+            2026 => Some(self.synchronized_since.is_some()),
+            _ => None,
+        }
+    }
+
+    /// Whether the program is in the middle of a synchronized update
+    /// (mode 2026), so the screen should not be drawn yet; an update that
+    /// never ends stops holding drawing back after a short timeout
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized_since
+            .is_some_and(|since| since.elapsed() < SYNCHRONIZED_OUTPUT_TIMEOUT)
+    }
+
+    /// Whether a synchronized update ended since the last call: a complete
+    /// frame to present now
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::replace(&mut self.frame_ready, false)
+    }
+
+    /// Whether cursor addressing is relative to the scroll region (DECOM)
+    pub fn origin_mode(&self) -> bool {
+        self.origin_mode
+    }
+
+    /// First and last row of the scroll region (DECSTBM)
+    pub fn scroll_region(&self) -> (usize, usize) {
+        self.scroll_region
+    }
+
+    /// Rows the cursor can be addressed on: the scroll region in origin
+    /// mode, otherwise the whole screen
+    fn origin_rows(&self) -> (usize, usize) {
+        if self.origin_mode {
+            self.scroll_region
+        } else {
+            (0, self.rows - 1)
+        }
+    }
+
+    /// Move the cursor to the home position, the top margin in origin mode
+    fn home_cursor(&mut self) {
+        self.cursor_row = self.origin_rows().0;
+        self.cursor_col = 0;
+    }
+
+    /// Whether printing at the last column wraps (DECAWM)
+    pub fn autowrap(&self) -> bool {
+        self.autowrap
+    }
+
+    /// Mouse reporting requested by the program
+    pub fn mouse_modes(&self) -> MouseModes {
+        self.mouse_modes
+    }
+
+    /// Whether the program wants focus in/out reports (mode 1004)
+    pub fn focus_events(&self) -> bool {
+        self.focus_events
+    }
+
+    /// Bytes to write to the PTY for a mouse event from the UI, if the
+    /// program asked for it
+    pub fn encode_mouse(&self, event: &MouseEvent) -> Option<Vec<u8>> {
+        let event = MouseEvent {
+            row: event.row.min(self.rows - 1),
+            col: event.col.min(self.cols - 1),
+            ..*event
+        };
+        self.mouse_modes.encode(&event)
+    }
+
+    /// Bytes to write to the PTY when the window gains or loses focus
+    pub fn encode_focus(&self, focused: bool) -> Option<&'static [u8]> {
+        match (self.focus_events, focused) {
+            (false, _) => None,
+            (true, true) => Some(b"\x1b[I"),
+            (true, false) => Some(b"\x1b[O"),
+        }
+    }
+
+    pub fn is_alternate_screen(&self) -> bool {
+        self.alt_buffer_active
+    }
+
+    /// Whether the program wants the cursor shown (DECTCEM)
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Cursor blinking as requested by the program, if it set mode 12
+    pub fn cursor_blink(&self) -> Option<bool> {
+        self.cursor_blink
+    }
+
+    /// Whether the program asked for bracketed paste (mode 2004)
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    /// Current keyboard modes, for encoding key presses
+    pub fn keyboard_modes(&self) -> KeyboardModes {
+        self.keyboard_modes
+    }
+
+    fn set_title(&mut self, title: String) {
+        if title != self.title {
+            self.title = title;
+            self.title_changed = true;
+        }
+    }
+
+    /// Returns the new title if it changed since the last call
+    pub fn take_title_change(&mut self) -> Option<String> {
+        if std::mem::replace(&mut self.title_changed, false) {
+            Some(self.title.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Index of `uri` in the link table, adding it if new; overlong URIs
+    /// and links beyond the table's limit are dropped
+    fn link_id(&mut self, uri: &str) -> Option<u32> {
+        if uri.len() > MAX_HYPERLINK_LEN {
+            return None;
+        }
+        let index = match self.hyperlinks.iter().position(|link| link == uri) {
+            Some(index) => index,
+            None if self.hyperlinks.len() < MAX_HYPERLINKS => {
+                self.hyperlinks.push(uri.to_string());
+                self.hyperlinks.len() - 1
+            }
+            None => return None,
+        };
+        Some(index as u32)
+    }
+
+    /// Target of the OSC 8 link on a cell, if any
+    pub fn hyperlink_at(&self, row: usize, col: usize) -> Option<&str> {
+        let id = self.get_cell(row, col)?.attributes.hyperlink?;
+        self.hyperlinks.get(id as usize).map(String::as_str)
+    }
+
+    fn respond(&mut self, response: Vec<u8>) {
+        if self.responses.len() < MAX_RESPONSES {
+            self.responses.push(response);
+        }
+    }
+
+    /// Query responses to write to the program, since the last call
+    pub fn take_responses(&mut self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut self.responses)
+    }
+
+    /// OSC 52 clipboard writes received since the last call
+    pub fn take_clipboard_requests(&mut self) -> Vec<ClipboardRequest> {
+        std::mem::take(&mut self.clipboard_requests)
+    }
+
+    /// Sequences for extension hooks received since the last call
+    pub fn take_extension_sequences(&mut self) -> Vec<ExtensionSequence> {
+        std::mem::take(&mut self.extension_sequences)
+    }
+
+    /// Host and directory last reported by the shell via OSC 7
+    pub fn working_directory(&self) -> Option<(&str, &str)> {
+        self.working_directory
+            .as_ref()
+            .map(|(host, path)| (host.as_str(), path.as_str()))
+    }
+
+    /// Shell integration marks received on the main screen
+    pub fn prompt_marks(&self) -> &PromptMarks {
+        &self.prompt_marks
+    }
+
+    /// Lines scrolled off the top of the main screen so far; the absolute
+    /// line of screen row `r` is `lines_scrolled() + r`
+    pub fn lines_scrolled(&self) -> usize {
+        self.lines_scrolled
+    }
+
+    /// Returns whether a bell rang since the last call, clearing it
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::replace(&mut self.bell_rung, false)
+    }
+
+    /// Bidi reordering chosen for this session, by the user or the program
+    pub fn bidi(&self) -> Option<bool> {
+        self.bidi
+    }
+
+    /// Override `terminal.bidi` for this session; `None` follows the config
+    pub fn set_bidi(&mut self, bidi: Option<bool>) {
+        self.bidi = bidi;
+    }
+
+    /// Suppress or re-enable the bell for this session
+    pub fn set_bell_suppressed(&mut self, suppressed: bool) {
+        self.bell_suppressed = suppressed;
+        if suppressed {
+            self.bell_rung = false;
+        }
+    }
+
     /// Get cursor position
     pub fn get_cursor_position(&self) -> (usize, usize) {
         (self.cursor_row, self.cursor_col)
@@ -666,4 +1926,474 @@ impl VirtualTerminal {
     }
 }
 
+/// Tab stops every 8 columns, as on power-up
+fn default_tab_stops(cols: usize) -> Vec<bool> {
+    (0..cols).map(|col| col > 0 && col.is_multiple_of(8)).collect()
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::TerminalParser;
+
+    fn feed(vt: &mut VirtualTerminal, data: &[u8]) {
+        let mut parser = TerminalParser::new();
+        for action in parser.parse(data).unwrap() {
+            vt.process_action(&action).unwrap();
+        }
+    }
+
+    fn row_text(vt: &VirtualTerminal, row: usize) -> String {
+        (0..vt.cols)
+            .map(|col| vt.get_cell(row, col).unwrap())
+            .filter(|cell| cell.width != CellWidth::Spacer)
+            .fold(String::new(), |mut text, cell| {
+                cell.push_text(&mut text);
+                text
+            })
+    }
+
+    #[test]
+    fn test_index_and_scroll() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        feed(&mut vt, b"a\r\nb\r\nc\x1bD");
+        assert_eq!(row_text(&vt, 0), "b   ");
+        assert_eq!(vt.get_cursor_position(), (2, 1));
+
+        feed(&mut vt, b"\x1b[2T");
+        assert_eq!(row_text(&vt, 0), "    ");
+        assert_eq!(row_text(&vt, 2), "b   ");
+
+        feed(&mut vt, b"\x1b#8");
+        assert_eq!(row_text(&vt, 1), "EEEE");
+        assert_eq!(vt.get_cursor_position(), (0, 0));
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let mut vt = VirtualTerminal::new(3, 5);
+        vt.set_scrollback_limit(10);
+        feed(&mut vt, b"a\r\nb\r\nc\r\nd\r\ne\x1b[2;4r");
+        assert_eq!(vt.get_cursor_position(), (0, 0));
+
+        // Scrolling at the bottom margin stays inside the region and keeps
+        // the rows outside it, without feeding the scrollback
+        feed(&mut vt, b"\x1b[4H\nx");
+        let rows: Vec<_> = (0..5).map(|row| row_text(&vt, row)).collect();
+        assert_eq!(rows, ["a  ", "c  ", "d  ", "x  ", "e  "]);
+        assert_eq!(vt.scrollback_len(), 0);
+
+        // RI at the top margin scrolls the region down
+        feed(&mut vt, b"\x1b[2H\x1bM");
+        let rows: Vec<_> = (0..5).map(|row| row_text(&vt, row)).collect();
+        assert_eq!(rows, ["a  ", "   ", "c  ", "d  ", "e  "]);
+
+        // Cursor movement stops at the margins from inside the region
+        feed(&mut vt, b"\x1b[3H\x1b[9A");
+        assert_eq!(vt.get_cursor_position(), (1, 0));
+        feed(&mut vt, b"\x1b[9B");
+        assert_eq!(vt.get_cursor_position(), (3, 0));
+
+        // Invalid regions are ignored
+        feed(&mut vt, b"\x1b[4;4r");
+        assert_eq!(vt.scroll_region, (1, 3));
+        feed(&mut vt, b"\x1b[r");
+        assert_eq!(vt.scroll_region, (0, 4));
+    }
+
+    #[test]
+    fn test_origin_mode() {
+        let mut vt = VirtualTerminal::new(4, 6);
+        feed(&mut vt, b"\x1b[2;4r\x1b[?6h");
+        assert!(vt.origin_mode());
+        assert_eq!(vt.get_cursor_position(), (1, 0));
+
+        feed(&mut vt, b"\x1b[2;3H");
+        assert_eq!(vt.get_cursor_position(), (2, 2));
+        feed(&mut vt, b"\x1b[9;1H\x1b[6n");
+        assert_eq!(vt.get_cursor_position(), (3, 0));
+        assert_eq!(vt.take_responses(), vec![b"\x1b[3;1R".to_vec()]);
+
+        feed(&mut vt, b"\x1b[?6l\x1b[9;1H");
+        assert_eq!(vt.get_cursor_position(), (5, 0));
+    }
+
+    #[test]
+    fn test_save_and_restore_cursor() {
+        let mut vt = VirtualTerminal::new(4, 6);
+        feed(&mut vt, b"\x1b[2;4r\x1b[?6h\x1b[2;3H\x1b[1;31m\x1b7");
+        feed(&mut vt, b"\x1b[?6l\x1b[0m\x1b[6;1H\x1b8x");
+        assert!(vt.origin_mode());
+        assert_eq!(vt.get_cursor_position(), (2, 3));
+        let cell = vt.get_cell(2, 2).unwrap();
+        assert_eq!(cell.character, 'x');
+        assert!(cell.attributes.bold);
+
+        // SCOSC/SCORC keep a wrap pending on the last column
+        feed(&mut vt, b"\x1b[?6l\x1b[0mabcd\x1b[s\x1b[5;1H\x1b[uy");
+        assert_eq!(row_text(&vt, 0), "abcd");
+        assert_eq!(row_text(&vt, 1), "y   ");
+    }
+
+    #[test]
+    fn test_alternate_screen() {
+        let mut vt = VirtualTerminal::new(4, 2);
+        vt.set_scrollback_limit(10);
+        feed(&mut vt, b"ab\r\ncd\x1b[1;31m\x1b[?1049h");
+        assert_eq!(vt.get_cursor_position(), (1, 2));
+        assert_eq!(row_text(&vt, 0), "    ");
+
+        // Scrolling on the alternate screen leaves the scrollback alone
+        feed(&mut vt, b"\x1b[0mxy\r\n\r\n\r\n");
+        assert_eq!(vt.scrollback_len(), 0);
+        vt.scroll_display(1);
+        assert_eq!(vt.display_offset(), 0);
+
+        // Leaving restores the main screen, cursor and attributes, even
+        // after DECSC on the alternate screen
+        feed(&mut vt, b"\x1b[2;1H\x1b7\x1b[?1049lz");
+        assert_eq!(row_text(&vt, 0), "ab  ");
+        assert_eq!(row_text(&vt, 1), "cdz ");
+        assert!(vt.get_cell(1, 2).unwrap().attributes.bold);
+
+        // 1047 switches without touching the cursor, and starts blank again
+        feed(&mut vt, b"\x1b[?1047hq\x1b[?1047l\x1b[?1047h");
+        assert_eq!(row_text(&vt, 1), "    ");
+        assert_eq!(vt.get_cursor_position(), (1, 3));
+    }
+
+    #[test]
+    fn test_charsets() {
+        let mut vt = VirtualTerminal::new(6, 2);
+        // Box drawing through G0, then through G1 with SO and back with SI
+        feed(&mut vt, b"\x1b(0lqk\x1b(Bx\x1b)0\x0ex\x0fx");
+        assert_eq!(row_text(&vt, 0), "┌─┐x│x");
+
+        // The saved cursor keeps the charsets
+        feed(&mut vt, b"\x1b(0\x1b7\x1b(B\x1b8\r\nq");
+        assert_eq!(row_text(&vt, 1), "─     ");
+    }
+
+    #[test]
+    fn test_insert_and_delete_lines() {
+        let mut vt = VirtualTerminal::new(3, 5);
+        feed(&mut vt, b"a\r\nb\r\nc\r\nd\r\ne\x1b[2;4r");
+
+        // IL at row 2 pushes c and d down; d falls off the bottom margin
+        feed(&mut vt, b"\x1b[3;2H\x1b[L");
+        let rows: Vec<_> = (0..5).map(|row| row_text(&vt, row)).collect();
+        assert_eq!(rows, ["a  ", "b  ", "   ", "c  ", "e  "]);
+        assert_eq!(vt.get_cursor_position(), (2, 0));
+
+        // DL at row 1 pulls the region up and blanks its bottom
+        feed(&mut vt, b"\x1b[2H\x1b[2M");
+        let rows: Vec<_> = (0..5).map(|row| row_text(&vt, row)).collect();
+        assert_eq!(rows, ["a  ", "c  ", "   ", "   ", "e  "]);
+
+        // Outside the region both are ignored
+        feed(&mut vt, b"\x1b[5H\x1b[9L\x1b[1H\x1b[M");
+        assert_eq!(row_text(&vt, 0), "a  ");
+        assert_eq!(row_text(&vt, 4), "e  ");
+    }
+
+    #[test]
+    fn test_insert_delete_and_erase_chars() {
+        let mut vt = VirtualTerminal::new(6, 1);
+        feed(&mut vt, b"abcdef\x1b[1;3H\x1b[2@");
+        assert_eq!(row_text(&vt, 0), "ab  cd");
+        assert_eq!(vt.get_cursor_position(), (0, 2));
+
+        feed(&mut vt, b"\x1b[3P");
+        assert_eq!(row_text(&vt, 0), "abd   ");
+
+        feed(&mut vt, b"\x1b[1;2H\x1b[X");
+        assert_eq!(row_text(&vt, 0), "a d   ");
+        feed(&mut vt, b"\x1b[9X");
+        assert_eq!(row_text(&vt, 0), "a     ");
 
+        // Deleting half of a wide character blanks the other half
+        let mut vt = VirtualTerminal::new(4, 1);
+        vt.put_char('\u{4E2D}').unwrap();
+        vt.put_char('x').unwrap();
+        feed(&mut vt, b"\x1b[1;2H\x1b[P");
+        assert_eq!(row_text(&vt, 0), " x  ");
+
+        // Past the end of a double-width line the last character is used
+        let mut vt = VirtualTerminal::new(8, 1);
+        feed(&mut vt, b"\x1b#6abcd\x1b[1;7H\x1b[@\x1b[P\x1b[60C\t\x1b[P");
+        assert_eq!(row_text(&vt, 0), "abc     ");
+    }
+
+    #[test]
+    fn test_scrollback() {
+        let mut vt = VirtualTerminal::new(4, 2);
+        vt.set_scrollback_limit(2);
+        feed(&mut vt, b"1\r\n2\r\n3\r\n4\r\n5");
+        assert_eq!(vt.scrollback_len(), 2);
+        assert_eq!(vt.scrollback_line(0).unwrap().cells[0].character, '2');
+
+        vt.scroll_display(5);
+        assert_eq!(vt.display_offset(), 2);
+        assert_eq!(vt.display_cell(0, 0).unwrap().character, '2');
+        assert_eq!(vt.display_cell(1, 0).unwrap().character, '3');
+        vt.scroll_display(-1);
+        assert_eq!(vt.display_cell(1, 0).unwrap().character, '4');
+
+        // ED 3 drops the saved lines but leaves the screen alone
+        feed(&mut vt, b"\x1b[3J");
+        assert_eq!(vt.scrollback_len(), 0);
+        assert_eq!(vt.display_offset(), 0);
+        assert_eq!(row_text(&vt, 1), "5   ");
+    }
+
+    #[test]
+    fn test_title_stack() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        feed(&mut vt, b"\x1b]2;shell\x07");
+        assert_eq!(vt.take_title_change().as_deref(), Some("shell"));
+        assert_eq!(vt.take_title_change(), None);
+
+        feed(&mut vt, b"\x1b[22;0t\x1b]0;vim\x07");
+        assert_eq!(vt.title, "vim");
+
+        feed(&mut vt, b"\x1b[23;0t");
+        assert_eq!(vt.take_title_change().as_deref(), Some("shell"));
+    }
+
+    #[test]
+    fn test_cursor_visibility() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        feed(&mut vt, b"\x1b[?25l");
+        assert!(!vt.cursor_visible());
+
+        feed(&mut vt, b"\x1b[?12;25h");
+        assert!(vt.cursor_visible());
+        assert_eq!(vt.cursor_blink(), Some(true));
+    }
+
+    #[test]
+    fn test_private_modes() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        feed(&mut vt, b"\x1b[?7labcdef");
+        assert!(!vt.autowrap());
+        assert_eq!(row_text(&vt, 0), "abcf");
+        assert_eq!(vt.get_cursor_position(), (0, 3));
+
+        feed(&mut vt, b"\x1b[?1049;1000;1006;1004h");
+        assert!(vt.is_alternate_screen());
+        assert_eq!(row_text(&vt, 0), "    ");
+        assert_eq!(vt.mouse_modes().tracking, MouseTracking::Normal);
+        assert_eq!(vt.mouse_modes().encoding, MouseEncoding::Sgr);
+        assert!(vt.focus_events());
+
+        feed(&mut vt, b"\x1b[?1049l\x1bc");
+        assert!(!vt.is_alternate_screen());
+        assert!(vt.autowrap());
+        assert_eq!(vt.mouse_modes(), MouseModes::default());
+    }
+
+    #[test]
+    fn test_sixel_placement() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        vt.set_scrollback_limit(10);
+        // A 20x45 pixel image covers 2x3 cells of 10x20 pixels
+        feed(&mut vt, b"\r\n \x1bP0;1q\"1;1;20;45\x1b\\");
+        let images: Vec<_> = vt.images().collect();
+        assert_eq!(images.len(), 1);
+        assert_eq!((images[0].line, images[0].col), (1, 1));
+        assert_eq!((images[0].rows, images[0].cols), (3, 2));
+        assert_eq!(vt.lines_scrolled(), 1);
+        assert_eq!(vt.get_cursor_position(), (2, 1));
+
+        feed(&mut vt, b"\x1b[?1049h");
+        assert_eq!(vt.images().count(), 0);
+        feed(&mut vt, b"\x1b[?1049l\x1b[2J");
+        assert_eq!(vt.images().count(), 0);
+
+        // Large images are dropped well before the count limit
+        let mut vt = VirtualTerminal::new(4, 3);
+        vt.set_scrollback_limit(5000);
+        for _ in 0..20 {
+            vt.place_image(Arc::new(SixelImage {
+                width: 2048,
+                height: 2048,
+                pixels: Vec::new(),
+            }));
+        }
+        assert_eq!(vt.images().count(), 16);
+    }
+
+    #[test]
+    fn test_query_responses() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        feed(&mut vt, b"\x1b[2;3H\x1b[6n\x1b[c");
+        assert_eq!(
+            vt.take_responses(),
+            vec![b"\x1b[2;3R".to_vec(), b"\x1b[?62;4;22c".to_vec()]
+        );
+        assert!(vt.take_responses().is_empty());
+    }
+
+    #[test]
+    fn test_request_setting() {
+        let mut vt = VirtualTerminal::new(4, 5);
+        feed(&mut vt, b"\x1b[1;31;48;5;200m\x1bP$qm\x1b\\\x1b[2;4r\x1bP$qr\x1b\\\x1bP$qx\x1b\\");
+        assert_eq!(
+            vt.take_responses(),
+            vec![
+                b"\x1bP1$r0;1;31;48;5;200m\x1b\\".to_vec(),
+                b"\x1bP1$r2;4r\x1b\\".to_vec(),
+                b"\x1bP0$r\x1b\\".to_vec(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_synchronized_output() {
+        let mut vt = VirtualTerminal::new(4, 2);
+        feed(&mut vt, b"\x1b[?2026$p\x1b[?2026h");
+        assert!(vt.is_synchronized());
+        assert!(!vt.take_frame_ready());
+
+        feed(&mut vt, b"frame\x1b[?2026$p\x1b[?2026l");
+        assert!(!vt.is_synchronized());
+        assert!(vt.take_frame_ready());
+        assert!(!vt.take_frame_ready());
+        assert_eq!(
+            vt.take_responses(),
+            vec![b"\x1b[?2026;2$y".to_vec(), b"\x1b[?2026;1$y".to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_pending_wrap() {
+        let mut vt = VirtualTerminal::new(4, 3);
+        feed(&mut vt, b"abcd");
+        assert_eq!(vt.get_cursor_position(), (0, 3));
+
+        // CR on the last column must not produce an extra blank line
+        feed(&mut vt, b"\r\nxy");
+        assert_eq!(row_text(&vt, 1), "xy  ");
+
+        feed(&mut vt, b"zwv");
+        assert_eq!(row_text(&vt, 1), "xyzw");
+        assert_eq!(row_text(&vt, 2), "v   ");
+        assert!(vt.line_attributes(1).unwrap().wrapped);
+        assert!(!vt.line_attributes(0).unwrap().wrapped);
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        let mut vt = VirtualTerminal::new(4, 2);
+        for c in "a中文".chars() {
+            vt.put_char(c).unwrap();
+        }
+        // The second one doesn't fit in the last column, so it wraps early
+        assert_eq!(row_text(&vt, 0), "a中 ");
+        assert_eq!(row_text(&vt, 1), "文  ");
+        assert_eq!(vt.get_cell(0, 2).unwrap().width, CellWidth::Spacer);
+        assert!(vt.line_attributes(0).unwrap().wrapped);
+        assert_eq!(vt.get_cursor_position(), (1, 2));
+
+        // Overwriting or erasing either half blanks the whole character
+        feed(&mut vt, b"\x1b[1;3Hx\x1b[2;2Hy");
+        assert_eq!(row_text(&vt, 0), "a x ");
+        assert_eq!(row_text(&vt, 1), " y  ");
+        vt.put_char('字').unwrap();
+        feed(&mut vt, b"\x1b[2;4H\x1b[K");
+        assert_eq!(row_text(&vt, 1), " y  ");
+    }
+
+    #[test]
+    fn test_grapheme_clusters() {
+        let mut vt = VirtualTerminal::new(8, 2);
+        // e + combining acute, a family emoji, the flag of Japan
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let text = format!("e\u{301}{family}\u{1F1EF}\u{1F1F5}!");
+        for c in text.chars() {
+            vt.put_char(c).unwrap();
+        }
+        assert_eq!(vt.get_cell(0, 0).unwrap().grapheme.as_deref(), Some("e\u{301}"));
+        assert_eq!(vt.get_cell(0, 1).unwrap().width, CellWidth::Wide);
+        assert_eq!(vt.get_cell(0, 3).unwrap().width, CellWidth::Wide);
+        assert_eq!(row_text(&vt, 0), format!("{text}  "));
+        assert_eq!(vt.get_cursor_position(), (0, 6));
+
+        // Moving the cursor ends the cluster
+        feed(&mut vt, b"\x1b[1;7H");
+        vt.put_char('\u{301}').unwrap();
+        assert_eq!(vt.get_cell(0, 5).unwrap().grapheme, None);
+
+        // So does resizing, which may drop the cell printed last
+        feed(&mut vt, b"\x1b[2;1Habcdef");
+        vt.resize(3, 2);
+        vt.put_char('\u{301}').unwrap();
+        assert_eq!(row_text(&vt, 1), "abc");
+    }
+
+    #[test]
+    fn test_selective_erase() {
+        let mut vt = VirtualTerminal::new(6, 2);
+        // Protected status line, then ordinary text
+        feed(&mut vt, b"\x1b[1\"qstat\x1b[0m\x1b[0\"qus\r\nbody");
+
+        feed(&mut vt, b"\x1b[?2J");
+        assert_eq!(row_text(&vt, 0), "stat  ");
+        assert_eq!(row_text(&vt, 1), "      ");
+
+        // A regular erase ignores protection
+        feed(&mut vt, b"\x1b[H\x1b[2K");
+        assert_eq!(row_text(&vt, 0), "      ");
+    }
+
+    #[test]
+    fn test_double_width_line() {
+        let mut vt = VirtualTerminal::new(6, 2);
+        feed(&mut vt, b"\x1b#6abcd");
+        assert_eq!(vt.line_attributes(0).unwrap().size, LineSize::DoubleWidth);
+        assert_eq!(row_text(&vt, 0), "abc   ");
+        assert_eq!(row_text(&vt, 1), "d     ");
+
+        // Cursor moves stop at the end of the line
+        for moves in [&b"\x1b[1;5H"[..], b"\x1b[9C", b"\t", b"\x1b[2;5H\x1b[A"] {
+            feed(&mut vt, b"\x1b[1;1H");
+            feed(&mut vt, moves);
+            assert_eq!(vt.get_cursor_position(), (0, 2));
+        }
+    }
+
+    #[test]
+    fn test_degenerate_sizes_and_hostile_input() {
+        let mut vt = VirtualTerminal::new(0, 0);
+        feed(&mut vt, b"\tab\x1b[99999;99999H\x1b[4294967295B\x1b[1;2;3m");
+        assert_eq!(vt.get_cursor_position(), (0, 0));
+
+        // Resizing behind the alternate screen keeps the main screen usable
+        let mut vt = VirtualTerminal::new(4, 4);
+        vt.use_alternate_buffer(true);
+        vt.resize(8, 6);
+        vt.use_alternate_buffer(false);
+        feed(&mut vt, b"\x1b[6;8Hx\x1b[38;2;999;1;2;48;2;1;2;3m\x1b[2J");
+        assert_eq!(vt.get_cursor_position(), (5, 7));
+
+        // Arbitrary bytes biased towards escape sequences
+        let alphabet = b"\x1b[];?0123456789mHJKABCDhlSTrt\x07\\P_\n\r\t\x08\x9b\x9d#";
+        let mut seed: u32 = 1;
+        let mut data = Vec::new();
+        for _ in 0..20_000 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            data.push(alphabet[(seed >> 16) as usize % alphabet.len()]);
+        }
+        let mut parser = TerminalParser::new();
+        parser.set_utf8_only(false);
+        for chunk in data.chunks(7) {
+            for action in parser.parse(chunk).unwrap() {
+                vt.process_action(&action).unwrap();
+            }
+        }
+    }
+}
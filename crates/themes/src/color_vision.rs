@@ -0,0 +1,185 @@
+// Palette transforms for color vision deficiencies
+//
+// Simulation projects colors onto what a dichromat sees (Viénot, Brettel &
+// Mollon, in LMS space). Correction daltonizes: the difference lost by the
+// simulation is shifted into channels the viewer can still tell apart, so
+// theme colors like error red and success green stay distinguishable.
+
+use config::{ColorVisionConfig, ColorVisionDeficiency, ColorVisionMode};
+
+use crate::{parse_hex_color, Theme};
+
+type Matrix = [[f32; 3]; 3];
+
+const RGB_TO_LMS: Matrix = [
+    [17.8824, 43.5161, 4.11935],
+    [3.45565, 27.1554, 3.86714],
+    [0.0299566, 0.184309, 1.46709],
+];
+
+const LMS_TO_RGB: Matrix = [
+    [0.080_944_45, -0.130_504_41, 0.116_721_07],
+    [-0.010_248_534, 0.054_019_33, -0.113_614_71],
+    [-0.000_365_296_94, -0.004_121_614_7, 0.693_511_4],
+];
+
+/// Moves the error of the simulation into green and blue
+const ERROR_SHIFT: Matrix = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+
+fn lms_projection(deficiency: ColorVisionDeficiency) -> Matrix {
+    match deficiency {
+        ColorVisionDeficiency::Protanopia => {
+            [[0.0, 2.02344, -2.52581], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]
+        }
+        ColorVisionDeficiency::Deuteranopia => {
+            [[1.0, 0.0, 0.0], [0.494207, 0.0, 1.24827], [0.0, 0.0, 1.0]]
+        }
+        ColorVisionDeficiency::Tritanopia => {
+            [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-0.395913, 0.801109, 0.0]]
+        }
+    }
+}
+
+fn apply(matrix: &Matrix, v: [f32; 3]) -> [f32; 3] {
+    let row = |r: [f32; 3]| r[0] * v[0] + r[1] * v[1] + r[2] * v[2];
+    [row(matrix[0]), row(matrix[1]), row(matrix[2])]
+}
+
+fn to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn simulate_linear(rgb: [f32; 3], deficiency: ColorVisionDeficiency) -> [f32; 3] {
+    let lms = apply(&RGB_TO_LMS, rgb);
+    apply(&LMS_TO_RGB, apply(&lms_projection(deficiency), lms))
+}
+
+/// How a color looks with the given deficiency
+pub fn simulate(color: [f32; 4], deficiency: ColorVisionDeficiency) -> [f32; 4] {
+    let linear = [to_linear(color[0]), to_linear(color[1]), to_linear(color[2])];
+    let seen = simulate_linear(linear, deficiency);
+    [to_srgb(seen[0]), to_srgb(seen[1]), to_srgb(seen[2]), color[3]]
+}
+
+/// Daltonized color, blended with the original by `strength` (0.0–1.0)
+pub fn correct(color: [f32; 4], deficiency: ColorVisionDeficiency, strength: f32) -> [f32; 4] {
+    let linear = [to_linear(color[0]), to_linear(color[1]), to_linear(color[2])];
+    let seen = simulate_linear(linear, deficiency);
+    let error = [linear[0] - seen[0], linear[1] - seen[1], linear[2] - seen[2]];
+    let shift = apply(&ERROR_SHIFT, error);
+    let strength = strength.clamp(0.0, 1.0);
+    let channel = |i: usize| to_srgb(linear[i] + shift[i] * strength);
+    [channel(0), channel(1), channel(2), color[3]]
+}
+
+/// Apply the configured transform to a color
+pub fn adjust(color: [f32; 4], config: &ColorVisionConfig) -> [f32; 4] {
+    match config.mode {
+        ColorVisionMode::Simulate => simulate(color, config.deficiency),
+        ColorVisionMode::Correct => correct(color, config.deficiency, config.strength),
+    }
+}
+
+/// `#rrggbb[aa]` after `adjust`; invalid colors are left as they are
+fn adjust_hex(hex: &mut String, config: &ColorVisionConfig) {
+    let Some(color) = parse_hex_color(hex) else {
+        return;
+    };
+    let [r, g, b, a] = adjust(color, config).map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    *hex = if hex.len() == 9 {
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    } else {
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    };
+}
+
+impl Theme {
+    /// The theme with every color passed through the color vision transform
+    pub fn adjusted_for(&self, config: &ColorVisionConfig) -> Theme {
+        let mut theme = self.clone();
+        let colors = &mut theme.colors;
+        let ui = &mut theme.ui;
+        let hexes = [
+            &mut colors.background,
+            &mut colors.foreground,
+            &mut colors.accent,
+            &mut colors.error,
+            &mut colors.success,
+            &mut colors.warning,
+            &mut colors.cursor,
+            &mut colors.cursor_text,
+            &mut colors.selection_background,
+            &mut colors.search_match,
+            &mut colors.search_current_match,
+            &mut ui.tab_active_background,
+            &mut ui.tab_active_foreground,
+            &mut ui.tab_inactive_background,
+            &mut ui.tab_inactive_foreground,
+            &mut ui.tab_bar_background,
+            &mut ui.pane_border,
+            &mut ui.pane_border_active,
+            &mut ui.block_header_background,
+            &mut ui.block_header_foreground,
+            &mut ui.popup_background,
+            &mut ui.popup_foreground,
+            &mut ui.popup_selected_background,
+            &mut ui.scrollbar_thumb,
+            &mut ui.scrollbar_track,
+        ];
+        for hex in hexes.into_iter().chain(colors.selection_foreground.as_mut()) {
+            adjust_hex(hex, config);
+        }
+        theme
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ThemeManager;
+
+    fn distance(a: [f32; 4], b: [f32; 4]) -> f32 {
+        (0..3).map(|i| (a[i] - b[i]).powi(2)).sum::<f32>().sqrt()
+    }
+
+    #[test]
+    fn test_correction_keeps_status_colors_apart() {
+        let theme = ThemeManager::new().get_current_theme();
+        let config = ColorVisionConfig {
+            deficiency: ColorVisionDeficiency::Deuteranopia,
+            mode: ColorVisionMode::Correct,
+            strength: 1.0,
+        };
+        let seen = |theme: &Theme, hex: fn(&Theme) -> &str| {
+            simulate(parse_hex_color(hex(theme)).unwrap(), config.deficiency)
+        };
+
+        let corrected = theme.adjusted_for(&config);
+        let before = distance(
+            seen(&theme, |t| &t.colors.error),
+            seen(&theme, |t| &t.colors.success),
+        );
+        let after = distance(
+            seen(&corrected, |t| &t.colors.error),
+            seen(&corrected, |t| &t.colors.success),
+        );
+        assert!(after > before, "{} <= {}", after, before);
+
+        // Grays are seen the same and left alone
+        let gray = parse_hex_color("#808080").unwrap();
+        assert!(distance(correct(gray, config.deficiency, 1.0), gray) < 0.02);
+    }
+}
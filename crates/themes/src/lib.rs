@@ -3,15 +3,22 @@
 // This module provides functionality for managing terminal color schemes and styling
 
 use anyhow::Result;
+use config::ColorVisionConfig;
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub mod color_vision;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Theme {
     pub name: String,
     pub colors: ThemeColors,
     pub styles: ThemeStyles,
+    /// Application chrome; themes without it get the default dark chrome
+    #[serde(default)]
+    pub ui: ThemeUi,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +29,171 @@ pub struct ThemeColors {
     pub error: String,
     pub success: String,
     pub warning: String,
+    /// Cursor block color
+    #[serde(default = "default_cursor")]
+    pub cursor: String,
+    /// Text drawn under a block cursor
+    #[serde(default = "default_cursor_text")]
+    pub cursor_text: String,
+    #[serde(default = "default_selection_background")]
+    pub selection_background: String,
+    /// Selected text color; `None` keeps the text's own color
+    #[serde(default)]
+    pub selection_foreground: Option<String>,
+    /// Background of search matches
+    #[serde(default = "default_search_match")]
+    pub search_match: String,
+    /// Background of the focused search match
+    #[serde(default = "default_search_current_match")]
+    pub search_current_match: String,
+}
+
+fn default_cursor() -> String {
+    "#c0c0c0".to_string()
+}
+
+fn default_cursor_text() -> String {
+    "#000000".to_string()
+}
+
+fn default_selection_background() -> String {
+    "#264f78".to_string()
+}
+
+fn default_search_match() -> String {
+    "#613214".to_string()
+}
+
+fn default_search_current_match() -> String {
+    "#ff9632".to_string()
+}
+
+/// Parse `#rrggbb` or `#rrggbbaa` into RGBA components in `0.0..=1.0`
+pub fn parse_hex_color(hex: &str) -> Option<[f32; 4]> {
+    let hex = hex.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .ok()
+            .map(|v| v as f32 / 255.0)
+    };
+    let alpha = if hex.len() == 8 { channel(6)? } else { 1.0 };
+    Some([channel(0)?, channel(2)?, channel(4)?, alpha])
+}
+
+/// WCAG relative luminance of an RGB color
+pub fn relative_luminance(color: [f32; 4]) -> f32 {
+    let linear = |c: f32| {
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.0722 * linear(color[2])
+}
+
+/// WCAG contrast ratio between two colors, from 1.0 to 21.0
+pub fn contrast_ratio(a: [f32; 4], b: [f32; 4]) -> f32 {
+    let (la, lb) = (relative_luminance(a), relative_luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+/// Move `foreground` towards white or black until it reaches `minimum`
+/// contrast against `background`, changing it as little as possible
+pub fn ensure_contrast(foreground: [f32; 4], background: [f32; 4], minimum: f32) -> [f32; 4] {
+    if contrast_ratio(foreground, background) >= minimum {
+        return foreground;
+    }
+
+    let mix = |target: f32, t: f32| {
+        let channel = |c: f32| c + (target - c) * t;
+        [
+            channel(foreground[0]),
+            channel(foreground[1]),
+            channel(foreground[2]),
+            foreground[3],
+        ]
+    };
+
+    // Smallest mix factor towards `target` that reaches the minimum
+    let search = |target: f32| {
+        if contrast_ratio(mix(target, 1.0), background) < minimum {
+            return None;
+        }
+        let (mut low, mut high) = (0.0f32, 1.0f32);
+        for _ in 0..16 {
+            let mid = (low + high) / 2.0;
+            if contrast_ratio(mix(target, mid), background) >= minimum {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Some(high)
+    };
+
+    match (search(1.0), search(0.0)) {
+        (Some(light), Some(dark)) if dark < light => mix(0.0, dark),
+        (Some(light), _) => mix(1.0, light),
+        (None, Some(dark)) => mix(0.0, dark),
+        (None, None) => {
+            // Unreachable ratio: use whichever extreme gets closest
+            let (white, black) = (mix(1.0, 1.0), mix(0.0, 1.0));
+            if contrast_ratio(white, background) >= contrast_ratio(black, background) {
+                white
+            } else {
+                black
+            }
+        }
+    }
+}
+
+/// Colors of the application chrome around the terminal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeUi {
+    pub tab_active_background: String,
+    pub tab_active_foreground: String,
+    pub tab_inactive_background: String,
+    pub tab_inactive_foreground: String,
+    pub tab_bar_background: String,
+    /// Split borders between panes
+    pub pane_border: String,
+    /// Border of the focused pane
+    pub pane_border_active: String,
+    pub block_header_background: String,
+    pub block_header_foreground: String,
+    /// Suggestion and completion popup
+    pub popup_background: String,
+    pub popup_foreground: String,
+    pub popup_selected_background: String,
+    pub scrollbar_thumb: String,
+    pub scrollbar_track: String,
+}
+
+impl Default for ThemeUi {
+    fn default() -> Self {
+        Self {
+            tab_active_background: "#1a1a1a".to_string(),
+            tab_active_foreground: "#ffffff".to_string(),
+            tab_inactive_background: "#2a2a2a".to_string(),
+            tab_inactive_foreground: "#8a8a8a".to_string(),
+            tab_bar_background: "#141414".to_string(),
+            pane_border: "#333333".to_string(),
+            pane_border_active: "#007acc".to_string(),
+            block_header_background: "#232323".to_string(),
+            block_header_foreground: "#bbbbbb".to_string(),
+            popup_background: "#252526".to_string(),
+            popup_foreground: "#d4d4d4".to_string(),
+            popup_selected_background: "#04395e".to_string(),
+            scrollbar_thumb: "#4f4f4f".to_string(),
+            scrollbar_track: "#1a1a1a".to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +219,12 @@ lazy_static! {
                 error: "#ff5555".to_string(),
                 success: "#50fa7b".to_string(),
                 warning: "#ffb86c".to_string(),
+                cursor: "#f8f8f2".to_string(),
+                cursor_text: "#1a1a1a".to_string(),
+                selection_background: "#44475a".to_string(),
+                selection_foreground: None,
+                search_match: "#6272a4".to_string(),
+                search_current_match: "#ffb86c".to_string(),
             },
             styles: ThemeStyles {
                 font_family: "monospace".to_string(),
@@ -55,6 +233,7 @@ lazy_static! {
                 padding: 8,
                 border_radius: 4,
             },
+            ui: ThemeUi::default(),
         });
 
         // Default light theme
@@ -67,6 +246,12 @@ lazy_static! {
                 error: "#ff0000".to_string(),
                 success: "#00ff00".to_string(),
                 warning: "#ffa500".to_string(),
+                cursor: "#000000".to_string(),
+                cursor_text: "#ffffff".to_string(),
+                selection_background: "#add6ff".to_string(),
+                selection_foreground: None,
+                search_match: "#ffe58f".to_string(),
+                search_current_match: "#ffa500".to_string(),
             },
             styles: ThemeStyles {
                 font_family: "monospace".to_string(),
@@ -75,6 +260,22 @@ lazy_static! {
                 padding: 8,
                 border_radius: 4,
             },
+            ui: ThemeUi {
+                tab_active_background: "#ffffff".to_string(),
+                tab_active_foreground: "#000000".to_string(),
+                tab_inactive_background: "#ececec".to_string(),
+                tab_inactive_foreground: "#6e6e6e".to_string(),
+                tab_bar_background: "#f3f3f3".to_string(),
+                pane_border: "#d4d4d4".to_string(),
+                pane_border_active: "#007acc".to_string(),
+                block_header_background: "#f5f5f5".to_string(),
+                block_header_foreground: "#444444".to_string(),
+                popup_background: "#f3f3f3".to_string(),
+                popup_foreground: "#1e1e1e".to_string(),
+                popup_selected_background: "#cce8ff".to_string(),
+                scrollbar_thumb: "#c1c1c1".to_string(),
+                scrollbar_track: "#ffffff".to_string(),
+            },
         });
 
         themes
@@ -83,17 +284,58 @@ lazy_static! {
 
 pub struct ThemeManager {
     current_theme: Theme,
+    /// Themes registered at runtime or loaded from the user themes directory
+    user_themes: HashMap<String, Theme>,
+    themes_dir: Option<PathBuf>,
+    /// Transform applied to the theme handed out by `get_current_theme`
+    color_vision: Option<ColorVisionConfig>,
+}
+
+/// Default directory for user themes, `~/.config/voidcli/themes`
+pub fn user_themes_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("voidcli").join("themes"))
 }
 
 impl ThemeManager {
     pub fn new() -> Self {
         Self {
             current_theme: THEMES.get("dark").unwrap().clone(),
+            user_themes: HashMap::new(),
+            themes_dir: user_themes_dir(),
+            color_vision: None,
         }
     }
 
+    /// Adjust the current theme for a color vision deficiency, as set by
+    /// `color_vision` in the config
+    pub fn with_color_vision(mut self, color_vision: Option<ColorVisionConfig>) -> Self {
+        self.color_vision = color_vision;
+        self
+    }
+
+    /// Use `dir` instead of the default user themes directory
+    pub fn with_themes_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.themes_dir = Some(dir.into());
+        self
+    }
+
     pub fn get_theme(&self, name: &str) -> Option<Theme> {
-        THEMES.get(name).cloned()
+        self.user_themes
+            .get(name)
+            .or_else(|| THEMES.get(name))
+            .cloned()
+    }
+
+    /// Names of all built-in and user themes, sorted
+    pub fn theme_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = THEMES
+            .keys()
+            .chain(self.user_themes.keys())
+            .cloned()
+            .collect();
+        names.sort();
+        names.dedup();
+        names
     }
 
     pub fn load_theme_from_file(&mut self, path: &str) -> Result<()> {
@@ -103,13 +345,106 @@ impl ThemeManager {
         Ok(())
     }
 
+    /// Register every `*.yaml` theme in the user themes directory
+    pub fn load_user_themes(&mut self) -> Result<usize> {
+        let Some(dir) = self.themes_dir.clone() else {
+            return Ok(0);
+        };
+        if !dir.is_dir() {
+            return Ok(0);
+        }
+
+        let mut loaded = 0;
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                continue;
+            }
+            let contents = std::fs::read_to_string(&path)?;
+            let theme: Theme = serde_yaml::from_str(&contents)
+                .map_err(|e| anyhow::anyhow!("Invalid theme {}: {}", path.display(), e))?;
+            self.register_theme(theme);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    /// Make a theme available by name for this session
+    ///
+    /// A user theme with the name of a built-in one shadows it.
+    pub fn register_theme(&mut self, theme: Theme) {
+        if self.current_theme.name == theme.name {
+            self.current_theme = theme.clone();
+        }
+        self.user_themes.insert(theme.name.clone(), theme);
+    }
+
+    /// Write the current theme to the user themes directory as `name`
+    pub fn save_current_as(&mut self, name: &str) -> Result<PathBuf> {
+        let path = self.theme_path(name)?;
+        let mut theme = self.current_theme.clone();
+        theme.name = name.to_string();
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, serde_yaml::to_string(&theme)?)?;
+
+        self.current_theme = theme.clone();
+        self.register_theme(theme);
+        Ok(path)
+    }
+
+    /// Remove a user theme and its file; built-in themes cannot be deleted
+    pub fn delete_theme(&mut self, name: &str) -> Result<()> {
+        let path = self.theme_path(name)?;
+        let registered = self.user_themes.remove(name).is_some();
+        let on_disk = path.is_file();
+
+        if !registered && !on_disk {
+            return if THEMES.contains_key(name) {
+                Err(anyhow::anyhow!("Cannot delete built-in theme: {}", name))
+            } else {
+                Err(anyhow::anyhow!("Theme not found: {}", name))
+            };
+        }
+        if on_disk {
+            std::fs::remove_file(&path)?;
+        }
+
+        if self.current_theme.name == name {
+            self.current_theme = THEMES
+                .get(name)
+                .or_else(|| THEMES.get("dark"))
+                .unwrap()
+                .clone();
+        }
+        Ok(())
+    }
+
+    fn theme_path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(anyhow::anyhow!("Invalid theme name: {}", name));
+        }
+        let dir = self
+            .themes_dir
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("No user themes directory"))?;
+        Ok(Path::new(dir).join(format!("{}.yaml", name)))
+    }
+
+    /// The current theme, with the color vision transform applied; saved
+    /// themes keep their original colors
     pub fn get_current_theme(&self) -> Theme {
-        self.current_theme.clone()
+        match &self.color_vision {
+            Some(color_vision) => self.current_theme.adjusted_for(color_vision),
+            None => self.current_theme.clone(),
+        }
     }
 
     pub fn set_theme(&mut self, name: &str) -> Result<()> {
-        if let Some(theme) = THEMES.get(name) {
-            self.current_theme = theme.clone();
+        if let Some(theme) = self.get_theme(name) {
+            self.current_theme = theme;
             Ok(())
         } else {
             Err(anyhow::anyhow!("Theme not found: {}", name))
@@ -119,8 +454,54 @@ impl ThemeManager {
 
 impl Default for ThemeManager {
     fn default() -> Self {
-        Self {
-            current_theme: THEMES.get("dark").unwrap().clone(),
-        }
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#ff0000"), Some([1.0, 0.0, 0.0, 1.0]));
+        assert_eq!(parse_hex_color("#00000000"), Some([0.0, 0.0, 0.0, 0.0]));
+        assert_eq!(parse_hex_color("ff0000"), None);
+        assert_eq!(parse_hex_color("#ff00"), None);
+    }
+
+    #[test]
+    fn test_ensure_contrast() {
+        let black = [0.0, 0.0, 0.0, 1.0];
+        let white = [1.0, 1.0, 1.0, 1.0];
+        assert!((contrast_ratio(black, white) - 21.0).abs() < 0.01);
+
+        let dark_blue = parse_hex_color("#00008b").unwrap();
+        assert!(contrast_ratio(dark_blue, black) < 3.0);
+        let nudged = ensure_contrast(dark_blue, black, 4.5);
+        assert!(contrast_ratio(nudged, black) >= 4.5);
+        assert!(nudged[2] > nudged[0]);
+
+        assert_eq!(ensure_contrast(white, black, 4.5), white);
+    }
+
+    #[test]
+    fn test_save_and_delete_theme() {
+        let dir = std::env::temp_dir().join(format!("voidcli-themes-{}", std::process::id()));
+        let mut manager = ThemeManager::new().with_themes_dir(&dir);
+
+        let path = manager.save_current_as("midnight").unwrap();
+        assert!(path.is_file());
+        assert_eq!(manager.get_current_theme().name, "midnight");
+        assert!(manager.theme_names().contains(&"midnight".to_string()));
+
+        manager.delete_theme("midnight").unwrap();
+        assert!(!path.exists());
+        assert!(manager.get_theme("midnight").is_none());
+        assert_eq!(manager.get_current_theme().name, "dark");
+
+        assert!(manager.delete_theme("dark").is_err());
+        assert!(manager.save_current_as("../escape").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
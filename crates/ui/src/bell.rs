@@ -0,0 +1,90 @@
+use std::time::{Duration, Instant};
+
+use config::{BellConfig, Cue};
+use winit::window::{UserAttentionType, Window};
+
+use crate::renderer::Renderer;
+
+/// Tracks the border flash and plays the cues picked by `core::feedback`
+pub struct BellState {
+    config: BellConfig,
+    flash_started: Option<Instant>,
+}
+
+impl BellState {
+    pub fn new(config: &BellConfig) -> Self {
+        Self {
+            config: config.clone(),
+            flash_started: None,
+        }
+    }
+
+    /// React to a bell from the focused or a background session with the
+    /// bell's cue
+    pub fn ring(&mut self, window: &Window, cue: Option<&Cue>) {
+        if let Some(cue) = cue {
+            self.play(cue);
+        }
+
+        if self.config.urgency_hint && !window.has_focus() {
+            window.request_user_attention(Some(UserAttentionType::Informational));
+        }
+    }
+
+    /// Flash the border and/or play the sound of a cue
+    pub fn play(&mut self, cue: &Cue) {
+        if cue.flash {
+            self.flash_started = Some(Instant::now());
+        }
+        if let Some(sound) = &cue.sound {
+            play_sound(sound);
+        }
+    }
+
+    /// Strength of the border flash in `0.0..=1.0`, fading out over the flash duration
+    pub fn flash_intensity(&self) -> f32 {
+        let started = match self.flash_started {
+            Some(started) => started,
+            None => return 0.0,
+        };
+
+        let duration = Duration::from_millis(self.config.flash_duration_ms);
+        let elapsed = started.elapsed();
+        if duration.is_zero() || elapsed >= duration {
+            return 0.0;
+        }
+
+        1.0 - elapsed.as_secs_f32() / duration.as_secs_f32()
+    }
+
+    /// Whether frames still need to be drawn for the flash animation
+    pub fn is_animating(&self) -> bool {
+        self.flash_intensity() > 0.0
+    }
+
+    /// Hand the current flash to the renderer; call before each frame
+    pub fn draw(&self, renderer: &mut Renderer) {
+        renderer.set_flash(self.flash_intensity());
+    }
+}
+
+/// Play the platform alert sound (`system`) or a sound file without
+/// blocking the UI thread
+fn play_sound(sound: &str) {
+    #[cfg(target_os = "macos")]
+    let command = match sound {
+        "system" => ("afplay", vec!["/System/Library/Sounds/Ping.aiff"]),
+        file => ("afplay", vec![file]),
+    };
+    #[cfg(not(target_os = "macos"))]
+    let command = match sound {
+        "system" => ("canberra-gtk-play", vec!["-i", "bell"]),
+        file => ("canberra-gtk-play", vec!["-f", file]),
+    };
+
+    let _ = std::process::Command::new(command.0)
+        .args(command.1)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+}
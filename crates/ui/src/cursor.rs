@@ -0,0 +1,75 @@
+use std::time::{Duration, Instant};
+
+use config::TerminalConfig;
+
+/// Default time the cursor spends in each blink phase
+const DEFAULT_BLINK_INTERVAL: Duration = Duration::from_millis(530);
+
+/// Cursor blink state machine
+///
+/// The cursor is solid while the user is typing and resumes blinking once
+/// input has been idle for a full interval.
+pub struct CursorBlink {
+    enabled: bool,
+    interval: Duration,
+    /// Start of the current blink cycle; the cursor is visible first
+    epoch: Instant,
+}
+
+impl CursorBlink {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            interval: DEFAULT_BLINK_INTERVAL,
+            epoch: Instant::now(),
+        }
+    }
+
+    /// Blink according to `terminal.cursor_blink`
+    pub fn from_config(config: &TerminalConfig) -> Self {
+        Self::new(config.cursor_blink)
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Enable or disable blinking, e.g. when a program sets mode 12
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Restart the cycle so the cursor stays solid while typing
+    pub fn on_input(&mut self, now: Instant) {
+        self.epoch = now;
+    }
+
+    /// Blink phase: whether the cursor is drawn at `now`
+    pub fn is_visible(&self, now: Instant) -> bool {
+        if !self.enabled || self.interval.is_zero() {
+            return true;
+        }
+
+        let elapsed = now.saturating_duration_since(self.epoch);
+        // Hold the cursor solid for the first full interval after input
+        if elapsed < self.interval * 2 {
+            return true;
+        }
+
+        let phase = elapsed.as_millis() / self.interval.as_millis();
+        phase.is_multiple_of(2)
+    }
+
+    /// When the blink phase next changes, for scheduling a redraw
+    pub fn next_transition(&self, now: Instant) -> Option<Instant> {
+        if !self.enabled || self.interval.is_zero() {
+            return None;
+        }
+
+        let elapsed = now.saturating_duration_since(self.epoch);
+        let interval = self.interval.as_millis();
+        let next_phase = (elapsed.as_millis() / interval + 1).max(2);
+        Some(self.epoch + Duration::from_millis((next_phase * interval) as u64))
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+
+use term::metrics::{self, MetricsSnapshot};
+
+use crate::renderer::OverlaySpan;
+
+/// How often the displayed rates are recomputed
+const REFRESH: Duration = Duration::from_millis(500);
+
+/// Debug overlay with frame, throughput and queue metrics, toggled with the
+/// `toggle-debug-hud` binding
+pub struct DebugHud {
+    visible: bool,
+    previous: MetricsSnapshot,
+    previous_at: Instant,
+    lines: Vec<String>,
+}
+
+impl DebugHud {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            previous: metrics::global().snapshot(),
+            previous_at: Instant::now(),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.previous = metrics::global().snapshot();
+        self.previous_at = Instant::now();
+        self.lines.clear();
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Overlay spans for the top-right corner of a grid `cols` wide;
+    /// empty while hidden
+    pub fn overlays(&mut self, cols: usize) -> Vec<OverlaySpan> {
+        if !self.visible {
+            return Vec::new();
+        }
+        let elapsed = self.previous_at.elapsed();
+        if self.lines.is_empty() || elapsed >= REFRESH {
+            let current = metrics::global().snapshot();
+            self.lines = hud_lines(&self.previous, &current, elapsed);
+            self.previous = current;
+            self.previous_at = Instant::now();
+        }
+
+        self.lines
+            .iter()
+            .enumerate()
+            .map(|(row, line)| OverlaySpan {
+                row,
+                col: cols.saturating_sub(line.chars().count()),
+                text: line.clone(),
+                dimmed: false,
+            })
+            .collect()
+    }
+}
+
+impl Default for DebugHud {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn megabytes(bytes: f64) -> f64 {
+    bytes / (1024.0 * 1024.0)
+}
+
+fn millis(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!("{:.1}ms", duration.as_secs_f64() * 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+/// Text of the overlay from two snapshots `elapsed` apart
+fn hud_lines(
+    previous: &MetricsSnapshot,
+    current: &MetricsSnapshot,
+    elapsed: Duration,
+) -> Vec<String> {
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    let frames = current.frames.saturating_sub(previous.frames);
+    let window = MetricsSnapshot {
+        frames,
+        frame_time_total_us: current
+            .frame_time_total_us
+            .saturating_sub(previous.frame_time_total_us),
+        frame_buckets: std::array::from_fn(|i| {
+            current.frame_buckets[i].saturating_sub(previous.frame_buckets[i])
+        }),
+        ..*current
+    };
+    let pty_rate = current.bytes_parsed.saturating_sub(previous.bytes_parsed) as f64 / seconds;
+
+    vec![
+        format!(
+            " {:.0} fps  frame {} p95 {} ",
+            frames as f64 / seconds,
+            millis(window.mean_frame_time()),
+            millis(window.frame_time_quantile(0.95)),
+        ),
+        format!(
+            " dropped {}  damage {} rects ",
+            current.dropped_frames.saturating_sub(previous.dropped_frames),
+            current.damage_rects
+        ),
+        format!(
+            " pty {:.2} MB/s  {:.0} actions/s ",
+            megabytes(pty_rate),
+            current.actions_per_sec(previous, elapsed)
+        ),
+        format!(
+            " memory {:.1}/{:.1} MB  queue {} ",
+            megabytes(current.memory_used as f64),
+            megabytes(current.memory_budget as f64),
+            current.event_queue_depth
+        ),
+    ]
+}
@@ -0,0 +1,96 @@
+use term::keyboard::{Key, Modifiers};
+use term::mouse::MouseButton;
+use winit::{
+    event::{KeyEvent, MouseButton as WinitMouseButton, MouseScrollDelta},
+    keyboard::{Key as WinitKey, KeyLocation, ModifiersState, NamedKey},
+};
+
+/// Convert a winit key event into a key for `term::keyboard::KeyEncoder`
+pub fn key_from_event(event: &KeyEvent) -> Option<Key> {
+    let numpad = event.location == KeyLocation::Numpad;
+
+    match &event.logical_key {
+        WinitKey::Named(named) => match named {
+            NamedKey::Enter if numpad => Some(Key::Keypad('\r')),
+            NamedKey::Enter => Some(Key::Enter),
+            NamedKey::Tab => Some(Key::Tab),
+            NamedKey::Backspace => Some(Key::Backspace),
+            NamedKey::Escape => Some(Key::Escape),
+            NamedKey::Space => Some(Key::Char(' ')),
+            NamedKey::ArrowUp => Some(Key::Up),
+            NamedKey::ArrowDown => Some(Key::Down),
+            NamedKey::ArrowLeft => Some(Key::Left),
+            NamedKey::ArrowRight => Some(Key::Right),
+            NamedKey::Home => Some(Key::Home),
+            NamedKey::End => Some(Key::End),
+            NamedKey::PageUp => Some(Key::PageUp),
+            NamedKey::PageDown => Some(Key::PageDown),
+            NamedKey::Insert => Some(Key::Insert),
+            NamedKey::Delete => Some(Key::Delete),
+            NamedKey::F1 => Some(Key::F(1)),
+            NamedKey::F2 => Some(Key::F(2)),
+            NamedKey::F3 => Some(Key::F(3)),
+            NamedKey::F4 => Some(Key::F(4)),
+            NamedKey::F5 => Some(Key::F(5)),
+            NamedKey::F6 => Some(Key::F(6)),
+            NamedKey::F7 => Some(Key::F(7)),
+            NamedKey::F8 => Some(Key::F(8)),
+            NamedKey::F9 => Some(Key::F(9)),
+            NamedKey::F10 => Some(Key::F(10)),
+            NamedKey::F11 => Some(Key::F(11)),
+            NamedKey::F12 => Some(Key::F(12)),
+            _ => None,
+        },
+        WinitKey::Character(text) => {
+            let mut chars = text.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() {
+                return None;
+            }
+            if numpad {
+                Some(Key::Keypad(c))
+            } else {
+                Some(Key::Char(c))
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn modifiers_from_state(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        shift: state.shift_key(),
+        alt: state.alt_key(),
+        ctrl: state.control_key(),
+        super_key: state.super_key(),
+    }
+}
+
+/// Convert a winit button for `term::mouse::MouseModes::encode`
+pub fn mouse_button_from_winit(button: WinitMouseButton) -> Option<MouseButton> {
+    match button {
+        WinitMouseButton::Left => Some(MouseButton::Left),
+        WinitMouseButton::Middle => Some(MouseButton::Middle),
+        WinitMouseButton::Right => Some(MouseButton::Right),
+        _ => None,
+    }
+}
+
+/// The wheel "button" a scroll reports, `None` when it did not move
+pub fn wheel_button(delta: MouseScrollDelta) -> Option<MouseButton> {
+    let (x, y) = match delta {
+        MouseScrollDelta::LineDelta(x, y) => (x as f64, y as f64),
+        MouseScrollDelta::PixelDelta(position) => (position.x, position.y),
+    };
+    if y > 0.0 {
+        Some(MouseButton::WheelUp)
+    } else if y < 0.0 {
+        Some(MouseButton::WheelDown)
+    } else if x < 0.0 {
+        Some(MouseButton::WheelLeft)
+    } else if x > 0.0 {
+        Some(MouseButton::WheelRight)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,94 @@
+use term::inspector::Inspector;
+
+use crate::renderer::OverlaySpan;
+
+/// Ignored sequences listed under the stream
+const MAX_UNHANDLED: usize = 5;
+
+/// Side panel over the right half of a pane showing the decoded stream,
+/// toggled with the `toggle-inspector` binding
+#[derive(Default)]
+pub struct InspectorPanel {
+    visible: bool,
+}
+
+impl InspectorPanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Overlay spans for a pane `cols` by `rows`: raw reads with their
+    /// actions, newest at the bottom, then the most frequent ignored
+    /// sequences; empty while hidden
+    pub fn overlays(&self, inspector: &Inspector, cols: usize, rows: usize) -> Vec<OverlaySpan> {
+        if !self.visible || rows == 0 {
+            return Vec::new();
+        }
+        let width = (cols / 2).max(1);
+        let left = cols - width;
+
+        let header = match (inspector.is_paused(), inspector.next_action()) {
+            (true, Some(next)) => {
+                format!("inspector: paused, {} held, next {}", inspector.held(), next)
+            }
+            (true, None) => "inspector: paused".to_string(),
+            (false, _) => "inspector: live".to_string(),
+        };
+        let unhandled = inspector.unhandled_counts();
+        let mut footer: Vec<(String, bool)> = Vec::new();
+        if !unhandled.is_empty() {
+            footer.push(("ignored:".to_string(), false));
+            footer.extend(
+                unhandled
+                    .iter()
+                    .take(MAX_UNHANDLED)
+                    .map(|(sequence, count)| (format!("{:>5} {}", count, sequence), true)),
+            );
+        }
+
+        // Newest reads that fit between the header and the footer
+        let space = rows.saturating_sub(1 + footer.len());
+        let mut stream: Vec<(String, bool)> = Vec::new();
+        for entry in inspector.entries().rev() {
+            let mut lines = vec![(format!("< {}", entry.escaped_bytes()), false)];
+            lines.extend(entry.actions.iter().map(|action| (format!("  {}", action), true)));
+            lines.extend(
+                entry
+                    .unhandled
+                    .iter()
+                    .map(|sequence| (format!("  ? {}", sequence), true)),
+            );
+            if stream.len() + lines.len() > space {
+                // Show the start of a newest read too long for the panel
+                if stream.is_empty() {
+                    lines.truncate(space);
+                    stream = lines;
+                }
+                break;
+            }
+            lines.extend(stream);
+            stream = lines;
+        }
+
+        std::iter::once((header, false))
+            .chain(stream)
+            .chain(footer)
+            .take(rows)
+            .enumerate()
+            .map(|(row, (text, dimmed))| OverlaySpan {
+                row,
+                col: left,
+                text: format!("{:<width$.width$}", text, width = width),
+                dimmed,
+            })
+            .collect()
+    }
+}
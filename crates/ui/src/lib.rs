@@ -2,5 +2,13 @@
 // This crate will handle all the rendering and UI logic
 
 // Re-export the renderer module
+pub mod bell;
+pub mod cursor;
+pub mod hud;
+pub mod inspector;
+pub mod input;
+pub mod overlay;
+pub mod paste;
 pub mod renderer;
+pub mod title;
 
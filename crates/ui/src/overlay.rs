@@ -0,0 +1,357 @@
+use std::path::{Path, PathBuf};
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use tracing::warn;
+
+use crate::renderer::{CellMetrics, OverlaySpan};
+
+/// Coverage kept for dimmed spans such as ghost text
+const DIMMED_ALPHA: f32 = 0.5;
+
+/// Directories searched for `font.name`, in order
+const FONT_DIRS: &[&str] = &[
+    "~/.local/share/fonts",
+    "~/.fonts",
+    "~/Library/Fonts",
+    "/usr/local/share/fonts",
+    "/usr/share/fonts",
+    "/Library/Fonts",
+    "/System/Library/Fonts",
+];
+
+const SHADER: &str = r#"
+@group(0) @binding(0) var overlay: texture_2d<f32>;
+@group(0) @binding(1) var overlay_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// One triangle covering the whole surface
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(overlay, overlay_sampler, in.uv);
+}
+"#;
+
+/// What the overlay texture is rasterized from
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct OverlayScene {
+    pub spans: Vec<OverlaySpan>,
+    pub cell: CellMetrics,
+    pub padding: f32,
+    /// Surface size in physical pixels
+    pub size: (u32, u32),
+    /// Text color as sRGB bytes
+    pub color: [u8; 3],
+}
+
+/// Draws overlay spans (ghost text, HUD, inspector) over the grid
+///
+/// Spans are rasterized on the CPU into a texture the size of the surface,
+/// again only when they, the cell size or the surface change, and blended
+/// over the frame.
+pub struct OverlayLayer {
+    font: Option<FontVec>,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
+    target: Option<(wgpu::Texture, wgpu::BindGroup)>,
+    drawn: Option<OverlayScene>,
+}
+
+impl OverlayLayer {
+    /// Set up drawing onto a surface of `surface_format`, with the font
+    /// named by `font.name`
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, font: &str) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Overlay Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Overlay Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Overlay Sampler"),
+            ..Default::default()
+        });
+
+        // Colors are written as sRGB; let the GPU decode them when the
+        // surface encodes again on write
+        let format = if surface_format.is_srgb() {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+
+        Self {
+            font: load_font(font),
+            pipeline,
+            bind_group_layout,
+            sampler,
+            format,
+            target: None,
+            drawn: None,
+        }
+    }
+
+    /// Draw the scene's spans over `view`, which was already cleared for
+    /// this frame
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        scene: OverlayScene,
+    ) {
+        let size = scene.size;
+        let font = match &self.font {
+            Some(font) if !scene.spans.is_empty() && size.0 > 0 && size.1 > 0 => font,
+            _ => return,
+        };
+
+        if self.drawn.as_ref() != Some(&scene) {
+            let resized = self.drawn.as_ref().map(|drawn| drawn.size) != Some(size);
+            if resized || self.target.is_none() {
+                self.target = Some(self.create_target(device, size));
+            }
+            if let Some((texture, _)) = &self.target {
+                let pixels = rasterize(font, &scene);
+                queue.write_texture(
+                    texture.as_image_copy(),
+                    &pixels,
+                    wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(size.0 * 4),
+                        rows_per_image: Some(size.1),
+                    },
+                    wgpu::Extent3d {
+                        width: size.0,
+                        height: size.1,
+                        depth_or_array_layers: 1,
+                    },
+                );
+            }
+            self.drawn = Some(scene);
+        }
+
+        let Some((_, bind_group)) = &self.target else {
+            return;
+        };
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn create_target(
+        &self,
+        device: &wgpu::Device,
+        (width, height): (u32, u32),
+    ) -> (wgpu::Texture, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Overlay Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Overlay Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        (texture, bind_group)
+    }
+}
+
+/// RGBA pixels of the spans, one character per cell, transparent elsewhere
+fn rasterize(font: &FontVec, scene: &OverlayScene) -> Vec<u8> {
+    let (width, height) = scene.size;
+    let (cell, padding) = (scene.cell, scene.padding);
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    let scaled = font.as_scaled(PxScale::from(cell.font_px));
+    // Center the line's ascent and descent in the cell
+    let baseline = (cell.height as f32 + scaled.ascent() + scaled.descent()) / 2.0;
+
+    for span in &scene.spans {
+        let alpha = if span.dimmed { DIMMED_ALPHA } else { 1.0 };
+        let top = padding + (span.row * cell.height as usize) as f32;
+        for (offset, c) in span.text.chars().enumerate() {
+            let left = padding + ((span.col + offset) * cell.width as usize) as f32;
+            let mut glyph = scaled.scaled_glyph(c);
+            glyph.position = point(left, top + baseline);
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, coverage| {
+                let x = bounds.min.x as i64 + x as i64;
+                let y = bounds.min.y as i64 + y as i64;
+                if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                    return;
+                }
+                let index = (y as usize * width as usize + x as usize) * 4;
+                let value = (coverage * alpha * 255.0).round() as u8;
+                pixels[index..index + 3].copy_from_slice(&scene.color);
+                pixels[index + 3] = pixels[index + 3].max(value);
+            });
+        }
+    }
+    pixels
+}
+
+/// Load `name` as a font file path, or find a file whose name starts with
+/// it (ignoring case, spaces and dashes), preferring the regular style
+fn load_font(name: &str) -> Option<FontVec> {
+    let path = if Path::new(name).is_file() {
+        Some(PathBuf::from(name))
+    } else {
+        find_font(name)
+    };
+    let font = path.and_then(|path| {
+        let data = std::fs::read(&path).ok()?;
+        FontVec::try_from_vec(data).ok()
+    });
+    if font.is_none() {
+        warn!("Font {} not found; overlays will not be drawn", name);
+    }
+    font
+}
+
+fn find_font(name: &str) -> Option<PathBuf> {
+    let wanted = normalize(name);
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut found = Vec::new();
+    for dir in FONT_DIRS {
+        let dir = match (dir.strip_prefix("~/"), &home) {
+            (Some(rest), Some(home)) => home.join(rest),
+            (Some(_), None) => continue,
+            (None, _) => PathBuf::from(dir),
+        };
+        collect_fonts(&dir, &wanted, 4, &mut found);
+    }
+
+    // "JetBrainsMono-Regular" over "JetBrainsMono-BoldItalic"
+    found.into_iter().min_by_key(|path| {
+        let stem = path.file_stem().map(|stem| normalize(&stem.to_string_lossy()));
+        let style = stem.as_deref().map_or("", |stem| &stem[wanted.len().min(stem.len())..]);
+        (!matches!(style, "" | "regular"), style.len())
+    })
+}
+
+fn collect_fonts(dir: &Path, wanted: &str, depth: usize, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth > 0 {
+                collect_fonts(&path, wanted, depth - 1, found);
+            }
+            continue;
+        }
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let stem = path.file_stem().map(|stem| normalize(&stem.to_string_lossy()));
+        if matches!(extension.to_ascii_lowercase().as_str(), "ttf" | "otf")
+            && stem.is_some_and(|stem| stem.starts_with(wanted))
+        {
+            found.push(path);
+        }
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
@@ -0,0 +1,134 @@
+use term::keyboard::Key;
+use term::paste::{reveal, strip_hidden, PasteReport};
+
+use crate::renderer::OverlaySpan;
+
+/// What the preview wants done after a key press
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewAction {
+    /// Send this text, encoded with `term::paste::encode`
+    Paste(String),
+    Cancel,
+    Redraw,
+    Ignored,
+}
+
+/// Overlay holding back a risky paste until confirmed, with quick fixes and
+/// a plain editor; the caller plays `FeedbackEvent::PasteBlocked` when it
+/// opens
+pub struct PastePreview {
+    text: String,
+    report: PasteReport,
+    editing: bool,
+}
+
+impl PastePreview {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            report: PasteReport::analyze(text),
+            editing: false,
+        }
+    }
+
+    pub fn report(&self) -> &PasteReport {
+        &self.report
+    }
+
+    fn set_text(&mut self, text: String) -> PreviewAction {
+        self.report = PasteReport::analyze(&text);
+        self.text = text;
+        PreviewAction::Redraw
+    }
+
+    pub fn handle_key(&mut self, key: Key) -> PreviewAction {
+        if self.editing {
+            return self.edit(key);
+        }
+        match key {
+            Key::Enter => PreviewAction::Paste(self.text.clone()),
+            Key::Escape | Key::Char('q') => PreviewAction::Cancel,
+            Key::Char('e') => {
+                self.editing = true;
+                PreviewAction::Redraw
+            }
+            // Trim the trailing newline so the command waits for Enter
+            Key::Char('t') => {
+                let text = self.text.trim_end_matches(['\r', '\n']).to_string();
+                self.set_text(text)
+            }
+            Key::Char('s') => {
+                let text = strip_hidden(&self.text);
+                self.set_text(text)
+            }
+            Key::Char('j') => {
+                let text = self
+                    .text
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                self.set_text(text)
+            }
+            _ => PreviewAction::Ignored,
+        }
+    }
+
+    /// Editing appends at the end of the text; Escape returns to the preview
+    fn edit(&mut self, key: Key) -> PreviewAction {
+        let mut text = self.text.clone();
+        match key {
+            Key::Escape => {
+                self.editing = false;
+                return PreviewAction::Redraw;
+            }
+            Key::Char(c) => text.push(c),
+            Key::Tab => text.push('\t'),
+            Key::Enter => text.push('\n'),
+            Key::Backspace => {
+                text.pop();
+            }
+            _ => return PreviewAction::Ignored,
+        }
+        self.set_text(text)
+    }
+
+    /// Box over the middle of a pane `cols` by `rows`
+    pub fn overlays(&self, cols: usize, rows: usize) -> Vec<OverlaySpan> {
+        if rows < 3 || cols == 0 {
+            return Vec::new();
+        }
+        let width = (cols * 3 / 4).max(cols.min(20));
+        let left = (cols - width) / 2;
+        let header = format!("Paste {}?", self.report.summary());
+        let footer = if self.editing {
+            "editing: type to append, backspace deletes, esc done"
+        } else {
+            "enter paste  esc cancel  e edit  t trim newline  s strip hidden  j join"
+        };
+
+        let body: Vec<String> = reveal(&self.text).lines().map(str::to_string).collect();
+        let space = rows.saturating_sub(2).min(body.len().max(1));
+        let mut lines: Vec<(String, bool)> = vec![(header, false)];
+        lines.extend(body.iter().take(space).map(|line| (format!("  {}", line), false)));
+        if body.len() > space {
+            let more = body.len() - space + 1;
+            lines.pop();
+            lines.push((format!("  … {} more lines", more), true));
+        }
+        lines.push((footer.to_string(), true));
+
+        let top = rows.saturating_sub(lines.len()) / 2;
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(row, (text, dimmed))| OverlaySpan {
+                row: top + row,
+                col: left,
+                text: format!("{:<width$.width$}", text, width = width),
+                dimmed,
+            })
+            .collect()
+    }
+}
@@ -1,9 +1,117 @@
+use std::time::Instant;
+
 use anyhow::{Context, Result};
-use config::Config;
-use themes::Theme;
+use config::font::FontFeature;
+use config::{Config, FontConfig};
+use term::{bidi, metrics};
+use themes::{ensure_contrast, parse_hex_color, Theme, ThemeUi};
+use tracing::{trace_span, warn};
 use wgpu::{Adapter, Device, Queue, Surface};
 use winit::window::Window;
 
+use crate::overlay::{OverlayLayer, OverlayScene};
+
+/// Text drawn on top of the terminal grid without being part of it,
+/// such as autosuggestion ghost text
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlaySpan {
+    pub row: usize,
+    pub col: usize,
+    pub text: String,
+    /// Draw with reduced intensity (ghost text)
+    pub dimmed: bool,
+}
+
+/// A run of cells on one row, end exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRange {
+    pub row: usize,
+    pub start_col: usize,
+    pub end_col: usize,
+}
+
+/// A search match to highlight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchHighlight {
+    pub range: CellRange,
+    /// The match the search is focused on
+    pub current: bool,
+}
+
+/// Sizes of the interface around the grid at UI scale 1, in pixels
+const PADDING: f32 = 8.0;
+const BLOCK_HEADER_HEIGHT: f32 = 24.0;
+const TAB_BAR_HEIGHT: f32 = 32.0;
+
+/// Sizes of the interface around the grid after UI and display scaling, in
+/// physical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UiMetrics {
+    pub padding: f32,
+    pub block_header_height: f32,
+    pub tab_bar_height: f32,
+    /// Text size of overlays (HUD, inspector, menus)
+    pub overlay_font_size: f32,
+}
+
+/// Horizontal advance of a monospace cell relative to the font size, used
+/// until the shaper reports the font's own
+const DEFAULT_ADVANCE: f32 = 0.6;
+
+/// Size of one grid cell in physical pixels
+///
+/// Glyphs are rasterized at `font_px` for the window's scale factor instead
+/// of being drawn at 1x and stretched, so text stays sharp at fractional
+/// scales like 1.25 (Wayland `wp_fractional_scale_v1`, delivered by winit as
+/// `ScaleFactorChanged`). Cells are whole pixels so glyphs land on the pixel
+/// grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellMetrics {
+    pub width: u32,
+    pub height: u32,
+    /// Font size to rasterize at
+    pub font_px: f32,
+}
+
+impl CellMetrics {
+    /// Metrics for `font` at `scale_factor`; `advance` is the cell width
+    /// relative to the font size
+    pub fn new(font: &FontConfig, advance: f32, scale_factor: f64) -> Self {
+        let font_px = font.size * scale_factor as f32;
+        Self {
+            width: (font_px * advance).round().max(1.0) as u32,
+            height: (font_px * font.line_height).round().max(1.0) as u32,
+            font_px,
+        }
+    }
+
+    /// Columns and rows fitting in `width` x `height` physical pixels after
+    /// `padding` on every side
+    pub fn grid_size(&self, width: u32, height: u32, padding: f32) -> (u16, u16) {
+        let inner = |size: u32| (size as f32 - 2.0 * padding).max(0.0) as u32;
+        let cols = (inner(width) / self.width).clamp(1, u16::MAX as u32);
+        let rows = (inner(height) / self.height).clamp(1, u16::MAX as u32);
+        (cols as u16, rows as u16)
+    }
+}
+
+/// A theme color as sRGB bytes, black for invalid values
+fn srgb_bytes(hex: &str) -> [u8; 3] {
+    let [r, g, b, _] = parse_hex_color(hex).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    [r, g, b].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Convert a theme color, falling back to opaque black for invalid values
+fn theme_color(hex: &str) -> wgpu::Color {
+    let [r, g, b, a] = parse_hex_color(hex).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    wgpu::Color {
+        r: r as f64,
+        g: g as f64,
+        b: b as f64,
+        a: a as f64,
+    }
+}
+
 pub struct Renderer<'a> {
     config: Config,
     device: Option<Device>,
@@ -11,18 +119,191 @@ pub struct Renderer<'a> {
     surface: Option<Surface<'a>>,
     adapter: Option<Adapter>,
     theme: Theme,
+    overlays: Vec<OverlaySpan>,
+    overlay_layer: Option<OverlayLayer>,
+    /// Surface size in physical pixels
+    size: (u32, u32),
+    /// Visual bell flash strength in `0.0..=1.0`
+    flash: f32,
+    /// UI zoom, applied to everything but the terminal text
+    ui_scale: f32,
+    /// Display scale of the monitor the window is on, possibly fractional
+    scale_factor: f64,
+    /// Cell width relative to the font size
+    advance: f32,
+    /// Draw the cursor this frame (DECTCEM and blink phase combined)
+    cursor_visible: bool,
+    selection: Vec<CellRange>,
+    search_matches: Vec<SearchHighlight>,
 }
 
 impl<'a> Renderer<'a> {
     pub fn new(config: Config, theme: Theme) -> Self {
         Self {
+            ui_scale: config.ui.scale,
+            scale_factor: 1.0,
+            advance: DEFAULT_ADVANCE,
             config,
             device: None,
             queue: None,
             surface: None,
             adapter: None,
             theme,
+            overlays: Vec::new(),
+            overlay_layer: None,
+            size: (0, 0),
+            flash: 0.0,
+            cursor_visible: true,
+            selection: Vec::new(),
+            search_matches: Vec::new(),
+        }
+    }
+
+    /// Display order of a grid row as (logical column, character) per visual
+    /// column; `session` is the session's bidi override, if any
+    pub fn visual_row(&self, row: &[char], session: Option<bool>) -> Vec<(usize, char)> {
+        if session.unwrap_or(self.config.terminal.bidi) {
+            bidi::visual_order(row)
+        } else {
+            row.iter().copied().enumerate().collect()
+        }
+    }
+
+    /// OpenType features to hand the shaper for runs in `family`
+    pub fn font_features(&self, family: &str) -> Vec<FontFeature> {
+        self.config.font.features_for(family).unwrap_or_else(|err| {
+            warn!("Ignoring font features for {}: {}", family, err);
+            Vec::new()
+        })
+    }
+
+    /// Change the UI zoom at runtime, e.g. after `ui-zoom-in`
+    pub fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale;
+    }
+
+    /// Paddings, headers, tab bar and overlay text at the current UI and
+    /// display scale
+    pub fn ui_metrics(&self) -> UiMetrics {
+        let scale = self.ui_scale * self.scale_factor as f32;
+        UiMetrics {
+            padding: PADDING * scale,
+            block_header_height: BLOCK_HEADER_HEIGHT * scale,
+            tab_bar_height: TAB_BAR_HEIGHT * scale,
+            overlay_font_size: self.config.font.size * scale,
+        }
+    }
+
+    /// Follow the window to a monitor with another scale factor, or a
+    /// compositor's new fractional scale; returns whether the cell size
+    /// changed, in which case glyphs must be rasterized again and the grid
+    /// and PTY resized with `grid_size` once the new surface size arrives
+    pub fn set_scale_factor(&mut self, scale_factor: f64) -> bool {
+        if !scale_factor.is_finite() || scale_factor <= 0.0 {
+            warn!("Ignoring invalid scale factor {}", scale_factor);
+            return false;
         }
+        let before = self.cell_metrics();
+        self.scale_factor = scale_factor;
+        self.cell_metrics() != before
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Use the font's real cell advance once the shaper has loaded it
+    pub fn set_advance(&mut self, advance: f32) {
+        self.advance = advance;
+    }
+
+    /// Grid cell size at the current scale factor
+    pub fn cell_metrics(&self) -> CellMetrics {
+        CellMetrics::new(&self.config.font, self.advance, self.scale_factor)
+    }
+
+    /// Columns and rows for a surface of `width` x `height` physical pixels,
+    /// to resize the grid and the PTY with
+    pub fn grid_size(&self, width: u32, height: u32) -> (u16, u16) {
+        self.cell_metrics().grid_size(width, height, self.ui_metrics().padding)
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Resolve a chrome color from the theme's `ui` section
+    pub fn ui_color(&self, pick: impl Fn(&ThemeUi) -> &str) -> wgpu::Color {
+        theme_color(pick(&self.theme.ui))
+    }
+
+    /// Cells to draw with the selection colors
+    pub fn set_selection(&mut self, selection: Vec<CellRange>) {
+        self.selection = selection;
+    }
+
+    /// Search matches to highlight on the next frame
+    pub fn set_search_matches(&mut self, matches: Vec<SearchHighlight>) {
+        self.search_matches = matches;
+    }
+
+    /// Cursor color and the color of the text under it
+    pub fn cursor_colors(&self) -> (wgpu::Color, wgpu::Color) {
+        let colors = &self.theme.colors;
+        (theme_color(&colors.cursor), theme_color(&colors.cursor_text))
+    }
+
+    /// Selection background, and foreground if the theme overrides it
+    pub fn selection_colors(&self) -> (wgpu::Color, Option<wgpu::Color>) {
+        let colors = &self.theme.colors;
+        (
+            theme_color(&colors.selection_background),
+            colors.selection_foreground.as_deref().map(theme_color),
+        )
+    }
+
+    /// Background of a search match
+    pub fn search_match_color(&self, current: bool) -> wgpu::Color {
+        let colors = &self.theme.colors;
+        if current {
+            theme_color(&colors.search_current_match)
+        } else {
+            theme_color(&colors.search_match)
+        }
+    }
+
+    /// Foreground for a cell, adjusted to `minimum_contrast` when configured
+    pub fn cell_foreground(&self, foreground: wgpu::Color, background: wgpu::Color) -> wgpu::Color {
+        let Some(minimum) = self.config.minimum_contrast else {
+            return foreground;
+        };
+        let rgba = |c: wgpu::Color| [c.r as f32, c.g as f32, c.b as f32, c.a as f32];
+        let [r, g, b, a] = ensure_contrast(rgba(foreground), rgba(background), minimum.clamp(1.0, 21.0));
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: a as f64,
+        }
+    }
+
+    /// Replace the overlay spans drawn over the grid on the next frame
+    pub fn set_overlays(&mut self, overlays: Vec<OverlaySpan>) {
+        self.overlays = overlays;
+    }
+
+    pub fn clear_overlays(&mut self) {
+        self.overlays.clear();
+    }
+
+    /// Show or hide the cursor for the next frame
+    pub fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor_visible = visible;
+    }
+
+    /// Set the visual bell flash strength for the next frame
+    pub fn set_flash(&mut self, intensity: f32) {
+        self.flash = intensity.clamp(0.0, 1.0);
     }
 
     pub async fn initialize(&mut self, window: &'a Window) -> Result<()> {
@@ -64,6 +345,8 @@ impl<'a> Renderer<'a> {
     }
 
     pub fn render(&mut self) -> Result<()> {
+        let _span = trace_span!("render").entered();
+        let started = Instant::now();
         if let (Some(device), Some(queue), Some(surface)) = (
             &self.device,
             &self.queue,
@@ -78,6 +361,18 @@ impl<'a> Renderer<'a> {
                 label: Some("Render Encoder"),
             });
 
+            // Theme background, washed towards white by the visual bell
+            let background = theme_color(&self.theme.colors.background);
+            let flash = self.flash as f64;
+
+            let scene = OverlayScene {
+                spans: self.overlays.clone(),
+                cell: self.cell_metrics(),
+                padding: self.ui_metrics().padding,
+                size: self.size,
+                color: srgb_bytes(&self.theme.colors.foreground),
+            };
+
             {
                 let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                     label: Some("Render Pass"),
@@ -86,9 +381,9 @@ impl<'a> Renderer<'a> {
                         resolve_target: None,
                         ops: wgpu::Operations {
                             load: wgpu::LoadOp::Clear(wgpu::Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
+                                r: background.r + (1.0 - background.r) * flash,
+                                g: background.g + (1.0 - background.g) * flash,
+                                b: background.b + (1.0 - background.b) * flash,
                                 a: 1.0,
                             }),
                             store: wgpu::StoreOp::Store,
@@ -100,8 +395,13 @@ impl<'a> Renderer<'a> {
                 });
             }
 
+            if let Some(layer) = &mut self.overlay_layer {
+                layer.draw(device, queue, &mut encoder, &view, scene);
+            }
+
             queue.submit(std::iter::once(encoder.finish()));
             frame.present();
+            metrics::global().record_frame(started.elapsed());
         }
 
         Ok(())
@@ -128,6 +428,11 @@ impl<'a> Renderer<'a> {
                     desired_maximum_frame_latency: 2,
                 },
             );
+            self.size = (width, height);
+            if self.overlay_layer.is_none() {
+                let layer = OverlayLayer::new(device, format, &self.config.font.name);
+                self.overlay_layer = Some(layer);
+            }
         }
     }
 }
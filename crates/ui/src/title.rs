@@ -0,0 +1,31 @@
+/// Values available to the title format template
+#[derive(Debug, Clone, Default)]
+pub struct TitleContext<'a> {
+    /// Title set by the program via OSC 0/2
+    pub title: &'a str,
+    /// Current working directory of the session
+    pub cwd: &'a str,
+    /// Shell running in the session
+    pub shell: &'a str,
+}
+
+/// Expand a title template such as `{title} — {cwd}` for the tab bar and OS window
+pub fn format_title(template: &str, context: &TitleContext) -> String {
+    let cwd = shorten_home(context.cwd);
+    let shell = context.shell.rsplit('/').next().unwrap_or(context.shell);
+
+    template
+        .replace("{title}", context.title)
+        .replace("{cwd}", &cwd)
+        .replace("{shell}", shell)
+}
+
+/// Replace the home directory prefix with `~`
+fn shorten_home(path: &str) -> String {
+    match std::env::var("HOME") {
+        Ok(home) if !home.is_empty() && path.starts_with(&home) => {
+            format!("~{}", &path[home.len()..])
+        }
+        _ => path.to_string(),
+    }
+}
@@ -1,30 +1,854 @@
-use clap::{Parser, command};
-use log::info;
-use anyhow::Result;
-use config::Config;
+use std::collections::HashMap;
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use clap::{Parser, Subcommand, command};
+use tracing::{info, warn};
+use anyhow::{anyhow, Context, Result};
+use blocks::{Block, BlockReport, DiagnosticParser, Stream};
+use config::keymap::{BindingSource, Keymap, Mode};
+use config::{Config, LoggingConfig, UpdateChannel};
 use core::app::VoidCLI;
+use core::crash;
+use core::layout::{LayoutBuilder, PaneId, SessionLayout, WindowLayout};
+use core::logging::{self, RotatingFile};
+use core::preferences::Preferences;
+use core::protocol::{self, Message, ScreenDiff, WireCell};
+use core::session::{self, DaemonConnection, Request, Response, SessionInfo, SessionRegistry};
+use core::update;
+use term::headless::HeadlessTerminal;
+use term::shell_integration::Shell;
+use term::{CellAttributes, HostedPane, TermEvent};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
-#[command()]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
     config: Option<String>,
+
+    /// Start the shell as a login shell
+    #[arg(short, long)]
+    login: bool,
+
+    /// Command typed into the shell once it has started
+    #[arg(long, value_name = "COMMAND")]
+    startup_command: Option<String>,
+
+    /// Keep the window open after the `-e` command exits
+    #[arg(long)]
+    hold: bool,
+
+    /// Run a program instead of the shell; all following arguments are passed to it
+    #[arg(
+        short = 'e',
+        long = "command",
+        value_name = "COMMAND",
+        num_args = 1..,
+        allow_hyphen_values = true
+    )]
+    exec: Vec<String>,
+
+    /// Run without a window or GPU; prints the final screen to stdout and
+    /// exits with the program's exit code
+    #[arg(long, conflicts_with_all = ["restore_layout", "layout"])]
+    headless: bool,
+
+    /// Restore the windows, tabs and panes saved when VoidCLI last exited
+    #[arg(long)]
+    restore_layout: bool,
+
+    /// Open a layout preset from the `layouts` config section
+    #[arg(long, value_name = "NAME", conflicts_with = "restore_layout")]
+    layout: Option<String>,
+
+    /// Log level or filter directives (`debug`, `info,term::parser=trace`);
+    /// overrides `logging.level`, `logging.modules` and `RUST_LOG`
+    #[arg(long, value_name = "FILTER")]
+    log_level: Option<String>,
+
+    /// Write logs to this file instead of stderr; overrides `logging.file`
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the shell integration snippet for manual installation
+    ShellInit {
+        /// Shell to print the snippet for (bash, zsh or fish)
+        shell: String,
+    },
+    /// Inspect key bindings
+    Keys {
+        #[command(subcommand)]
+        command: KeysCommand,
+    },
+    /// Run the session daemon, keeping named sessions across clients
+    Daemon,
+    /// List the sessions of the running daemon
+    Ls,
+    /// Create a daemon session; the daemon names it when no name is given
+    New {
+        name: Option<String>,
+    },
+    /// Attach this terminal to a daemon session's shell by name; Ctrl-]
+    /// detaches and leaves the shell running
+    Attach {
+        name: String,
+    },
+    /// Rename a daemon session
+    Rename {
+        from: String,
+        to: String,
+    },
+    /// Host panes for remote clients; listens on localhost only, so reach
+    /// it from another machine through an SSH tunnel (`ssh -L`)
+    Host {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:7420")]
+        listen: String,
+    },
+    /// Measure parser, VT and frame preparation speed on synthetic output
+    Bench {
+        /// Megabytes of output per workload
+        #[arg(long, default_value_t = 32)]
+        size: usize,
+        #[arg(long, default_value_t = 200)]
+        cols: usize,
+        #[arg(long, default_value_t = 50)]
+        rows: usize,
+    },
+    /// Run a command without a window and print its block: the output,
+    /// exit code, duration and diagnostics; exits with the command's code
+    Run {
+        /// Print the block as JSON
+        #[arg(long)]
+        json: bool,
+        #[arg(long, default_value_t = 120)]
+        cols: u16,
+        #[arg(long, default_value_t = 40)]
+        rows: u16,
+        /// Program and its arguments
+        #[arg(
+            required = true,
+            num_args = 1..,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+    /// Bundle crash reports into one file to attach to a bug report
+    Report {
+        /// File to write the bundle to
+        #[arg(long, default_value = "voidcli-crash-reports.txt")]
+        output: String,
+    },
+    /// Download, verify and install the newest release
+    SelfUpdate {
+        /// Release channel (stable or nightly); defaults to `updates.channel`
+        #[arg(long)]
+        channel: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// List the effective key bindings and report conflicts
+    List {
+        /// Only list bindings of this mode
+        #[arg(long)]
+        mode: Option<String>,
+        /// Configuration file to read bindings from
+        #[arg(long)]
+        config: Option<String>,
+    },
+}
+
+fn load_config(path: Option<&str>) -> Result<Config> {
+    match path {
+        Some(path) => Config::from_file(path),
+        None => Ok(Config::default()),
+    }
+}
+
+fn list_keys(mode: Option<&str>, config: Option<&str>) -> Result<()> {
+    let mode = mode
+        .map(|name| Mode::from_name(name).ok_or_else(|| anyhow!("Unknown mode: {}", name)))
+        .transpose()?;
+    let config = load_config(config)?;
+    let keymap = Keymap::from_config(&config.keybindings)?;
+
+    for binding in keymap.list(mode) {
+        let source = match binding.source {
+            BindingSource::Default => "",
+            BindingSource::User => " (user)",
+        };
+        println!(
+            "{:<18} {:<28} {}{}",
+            binding.mode.name(),
+            binding.sequence.to_string(),
+            binding.action,
+            source
+        );
+    }
+
+    for conflict in keymap.conflicts() {
+        eprintln!("warning: {}", conflict);
+    }
+    Ok(())
+}
+
+/// Command line launch flags override the `terminal` config section
+fn apply_launch_args(cli: &Cli, config: &mut Config) {
+    let terminal = &mut config.terminal;
+    terminal.login_shell |= cli.login;
+    terminal.hold |= cli.hold;
+    if let Some(command) = &cli.startup_command {
+        terminal.startup_command = Some(command.clone());
+    }
+    if !cli.exec.is_empty() {
+        terminal.command = Some(cli.exec.clone());
+    }
+}
+
+/// Set up logging from the `logging` config section, the command line and
+/// `RUST_LOG`
+fn init_logging(cli: &Cli, config: &LoggingConfig) -> Result<()> {
+    let directives = match (&cli.log_level, std::env::var("RUST_LOG")) {
+        (Some(filter), _) => filter.clone(),
+        (None, Ok(filter)) => filter,
+        (None, Err(_)) => logging::directives(config),
+    };
+    let filter = EnvFilter::try_new(&directives)
+        .with_context(|| format!("Invalid log filter: {}", directives))?;
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match cli.log_file.as_ref().or(config.file.as_ref()) {
+        Some(path) => {
+            let file = RotatingFile::open(
+                std::path::Path::new(path),
+                config.max_size_mb.saturating_mul(1024 * 1024),
+                config.max_files,
+            )?;
+            subscriber
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(file))
+                .init();
+        }
+        None => subscriber.with_writer(std::io::stderr).init(),
+    }
+    Ok(())
+}
+
+/// Send a request to the daemon, turning error responses into errors
+fn daemon_request(request: Request) -> Result<Response> {
+    match session::send_request(&session::socket_path(), &request)? {
+        Response::Error { message } => Err(anyhow!("{}", message)),
+        response => Ok(response),
+    }
+}
+
+/// Byte that detaches an attached terminal: Ctrl-]
+const DETACH_KEY: u8 = 0x1d;
+
+/// Attach this terminal to a daemon session's shell until Ctrl-] or the
+/// shell exits
+async fn attach_session(name: &str) -> Result<()> {
+    let mut connection = DaemonConnection::connect(&session::socket_path())?;
+    let attach = Request::Attach {
+        name: name.to_string(),
+    };
+    if let Response::Error { message } = connection.request(&attach)? {
+        return Err(anyhow!("{}", message));
+    }
+    let input = Arc::new(Mutex::new(connection.try_clone()?));
+    // Terminals that do not know their size report zero
+    let send_size = |input: &Mutex<DaemonConnection>| match crossterm::terminal::size() {
+        Ok((cols, rows)) if cols > 0 && rows > 0 => {
+            input.lock().unwrap().send(&Request::Resize { cols, rows })
+        }
+        _ => Ok(()),
+    };
+    send_size(&input)?;
+
+    let mut resized = signal(SignalKind::window_change())?;
+    let resize_input = Arc::clone(&input);
+    tokio::spawn(async move {
+        while resized.recv().await.is_some() {
+            if send_size(&resize_input).is_err() {
+                break;
+            }
+        }
+    });
+
+    crossterm::terminal::enable_raw_mode()?;
+    let detached = Arc::new(AtomicBool::new(false));
+    let detach = Arc::clone(&detached);
+    let detach_name = name.to_string();
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let n = match std::io::stdin().read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => n,
+            };
+            let data = &buffer[..n];
+            let request = if data.contains(&DETACH_KEY) {
+                detach.store(true, Ordering::SeqCst);
+                Request::Detach {
+                    name: detach_name.clone(),
+                }
+            } else {
+                Request::Input {
+                    data: data.to_vec(),
+                }
+            };
+            if input.lock().unwrap().send(&request).is_err() || detach.load(Ordering::SeqCst) {
+                break;
+            }
+        }
+    });
+
+    // Stdout only flushes on newlines by itself, and prompts have none
+    let copied = tokio::task::spawn_blocking(move || -> Result<()> {
+        let mut buffer = [0u8; 4096];
+        let mut stdout = std::io::stdout().lock();
+        loop {
+            let n = connection.read(&mut buffer)?;
+            if n == 0 {
+                return Ok(());
+            }
+            stdout.write_all(&buffer[..n])?;
+            stdout.flush()?;
+        }
+    })
+    .await?;
+    crossterm::terminal::disable_raw_mode()?;
+    copied?;
+    // The session may have hidden the cursor
+    print!("\x1b[?25h");
+    if detached.load(Ordering::SeqCst) {
+        println!("\r\n[detached from session {}]", name);
+    } else {
+        println!("\r\n[session {} ended]", name);
+    }
+    Ok(())
+}
+
+fn print_session(session: &SessionInfo) {
+    let age = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_secs().saturating_sub(session.created_at))
+        .unwrap_or(0);
+    println!(
+        "{}: {} windows (created {}m ago){}",
+        session.name,
+        session.windows,
+        age / 60,
+        if session.attached_clients > 0 {
+            format!(" ({} attached)", session.attached_clients)
+        } else {
+            String::new()
+        }
+    );
+}
+
+/// Point at reports left by a crash since the last launch
+fn announce_crash_reports() {
+    let Some(dir) = crash::crash_dir() else {
+        return;
+    };
+    match crash::take_new_reports(&dir) {
+        Ok(reports) => {
+            if let Some(latest) = reports.last() {
+                eprintln!(
+                    "VoidCLI crashed last time; the report is at {}.\n\
+                     Run `voidcli report` and attach the bundle to a bug report.",
+                    latest.display()
+                );
+            }
+        }
+        Err(e) => warn!("Failed to check for crash reports: {:#}", e),
+    }
+}
+
+/// `voidcli run`: the command goes through the PTY and parser like in a
+/// pane, then its block is analyzed as the UI would
+async fn run_command(
+    config: &mut Config,
+    command: &[String],
+    cols: u16,
+    rows: u16,
+    json: bool,
+) -> Result<i32> {
+    config.terminal.command = Some(command.to_vec());
+    config.terminal.startup_command = None;
+    let run = term::run_headless(config, cols, rows).await?;
+
+    let mut block = Block::new(0, blocks::Command::new(&command.join(" ")));
+    block.append_output(Stream::Stdout, &run.output);
+    block.finish(run.exit_code, run.duration.as_millis() as u64);
+    if config.diagnostics.enabled {
+        block.analyze(&DiagnosticParser::new(&config.diagnostics));
+    }
+
+    let report = BlockReport::new(&block);
+    if json {
+        println!("{}", report.to_json()?);
+    } else {
+        print!("{}", report.to_text());
+    }
+    Ok(run.exit_code)
+}
+
+/// How often hosted panes are checked for output to send
+const HOST_FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// The screen of a hosted pane as sent over the wire
+fn wire_rows(screen: &HeadlessTerminal) -> Vec<Vec<WireCell>> {
+    let (_, rows) = screen.size();
+    (0..rows)
+        .map(|row| {
+            screen
+                .row_cells(row)
+                .into_iter()
+                .map(|(text, attributes)| wire_cell(text, &attributes))
+                .collect()
+        })
+        .collect()
+}
+
+fn wire_cell(text: String, attributes: &CellAttributes) -> WireCell {
+    let flags = [
+        (attributes.bold, WireCell::BOLD),
+        (attributes.italic, WireCell::ITALIC),
+        (attributes.underline, WireCell::UNDERLINE),
+        (attributes.blink, WireCell::BLINK),
+        (attributes.reverse, WireCell::REVERSE),
+        (attributes.hidden, WireCell::HIDDEN),
+        (attributes.strikethrough, WireCell::STRIKETHROUGH),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .fold(0, |flags, (_, flag)| flags | flag);
+    WireCell {
+        text,
+        fg: attributes.fg_color,
+        bg: attributes.bg_color,
+        flags,
+    }
+}
+
+/// A pane hosted for a remote client, with what the client was last sent
+struct RemotePane {
+    pane: HostedPane,
+    sent: Vec<Vec<WireCell>>,
+    cursor: (u16, u16),
+    seq: u64,
+    acked: u64,
+}
+
+/// Serve panes to remote clients on `listen`, which must be a loopback
+/// address: the protocol has no authentication or encryption of its own
+async fn run_host(listen: &str, config: &Config) -> Result<()> {
+    let addresses: Vec<SocketAddr> = listen
+        .to_socket_addrs()
+        .with_context(|| format!("Invalid listen address {}", listen))?
+        .collect();
+    if addresses.is_empty() || addresses.iter().any(|address| !address.ip().is_loopback()) {
+        return Err(anyhow!(
+            "Refusing to listen on {}: the host only accepts loopback addresses, \
+             reach it from other machines through an SSH tunnel",
+            listen
+        ));
+    }
+    let listener = tokio::net::TcpListener::bind(&addresses[..]).await?;
+    info!("Hosting on {} (protocol v{})", listen, protocol::PROTOCOL_VERSION);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let stream = stream.into_std()?;
+        stream.set_nonblocking(false)?;
+        let config = config.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_remote_client(stream, &config).await {
+                warn!("Remote client failed: {:#}", e);
+            }
+        });
+    }
+}
+
+/// Host one client's panes until it disconnects
+async fn serve_remote_client(mut stream: TcpStream, config: &Config) -> Result<()> {
+    let mut reader = stream.try_clone()?;
+    let (mut reader, client) = tokio::task::spawn_blocking(move || {
+        let client = protocol::handshake(&mut reader, "voidcli-host");
+        (reader, client)
+    })
+    .await?;
+    let client = client?;
+    info!("Client {} connected", client);
+
+    // Blocking reads and writes happen on their own threads, so a slow
+    // client never stalls the runtime
+    let (incoming_tx, mut incoming) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(message) = protocol::read_message(&mut reader) {
+            if incoming_tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    let (outgoing, outgoing_rx) = mpsc::channel::<Message>();
+    std::thread::spawn(move || {
+        for message in outgoing_rx {
+            if let Err(e) = protocol::write_message(&mut stream, &message) {
+                warn!("Writing to client failed: {:#}", e);
+                break;
+            }
+        }
+        // Also ends the reader thread
+        let _ = stream.shutdown(Shutdown::Both);
+    });
+
+    let mut panes: HashMap<PaneId, RemotePane> = HashMap::new();
+    let mut ticker = tokio::time::interval(HOST_FRAME_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            message = incoming.recv() => {
+                let Some(message) = message else {
+                    break Ok(());
+                };
+                if let Some(reply) = handle_remote_message(message, &mut panes, config).await {
+                    if let Err(e) = send_to_client(&outgoing, reply) {
+                        break Err(e);
+                    }
+                }
+            }
+            _ = ticker.tick() => {
+                if let Err(e) = send_pane_updates(&mut panes, &outgoing).await {
+                    break Err(e);
+                }
+            }
+        }
+    };
+
+    for remote in panes.values_mut() {
+        let _ = remote.pane.kill().await;
+    }
+    // The writer flushes what is queued, then closes the connection
+    drop(outgoing);
+    info!("Client {} disconnected", client);
+    result
+}
+
+/// Queue a message for the client's writer thread
+fn send_to_client(outgoing: &mpsc::Sender<Message>, message: Message) -> Result<()> {
+    outgoing
+        .send(message)
+        .map_err(|_| anyhow!("Connection to client closed"))
+}
+
+/// Apply a client message; the reply to send back, if any
+async fn handle_remote_message(
+    message: Message,
+    panes: &mut HashMap<PaneId, RemotePane>,
+    config: &Config,
+) -> Option<Message> {
+    let result = match message {
+        Message::Open { pane, cols, rows } => HostedPane::spawn(config, cols, rows)
+            .await
+            .map(|hosted| {
+                let remote = RemotePane {
+                    pane: hosted,
+                    sent: Vec::new(),
+                    cursor: (0, 0),
+                    seq: 0,
+                    acked: 0,
+                };
+                panes.insert(pane, remote);
+            }),
+        Message::Input { pane, data } => match panes.get_mut(&pane) {
+            Some(remote) => remote.pane.write(&data).await,
+            None => Err(anyhow!("No pane {}", pane)),
+        },
+        Message::Resize {
+            pane,
+            cols,
+            rows,
+            cell_size,
+        } => match panes.get_mut(&pane) {
+            Some(remote) => {
+                if let Some((width, height)) = cell_size {
+                    remote.pane.set_cell_pixel_size(width, height);
+                }
+                remote.pane.resize(cols, rows).await
+            }
+            None => Err(anyhow!("No pane {}", pane)),
+        },
+        Message::Focus { pane, focused } => match panes.get_mut(&pane) {
+            Some(remote) => remote.pane.set_focused(focused).await,
+            None => Err(anyhow!("No pane {}", pane)),
+        },
+        Message::SuppressBell { pane, suppressed } => match panes.get_mut(&pane) {
+            Some(remote) => {
+                remote.pane.set_bell_suppressed(suppressed);
+                Ok(())
+            }
+            None => Err(anyhow!("No pane {}", pane)),
+        },
+        Message::Ack { pane, seq } => {
+            if let Some(remote) = panes.get_mut(&pane) {
+                remote.acked = remote.acked.max(seq);
+            }
+            Ok(())
+        }
+        Message::MetricsRequest => {
+            return Some(Message::Metrics {
+                counters: term::metrics::global().snapshot().to_map(),
+            })
+        }
+        _ => Ok(()),
+    };
+    result.err().map(|e| Message::Error {
+        message: format!("{:#}", e),
+    })
+}
+
+/// Send each pane's changes once the client has acknowledged the previous
+/// diff, and report bells and panes whose program exited
+async fn send_pane_updates(
+    panes: &mut HashMap<PaneId, RemotePane>,
+    outgoing: &mpsc::Sender<Message>,
+) -> Result<()> {
+    let mut exited = Vec::new();
+    for (id, remote) in panes.iter_mut() {
+        let events = match remote.pane.poll().await {
+            Ok(events) => events,
+            Err(e) => {
+                warn!("Pane {} failed: {:#}", id, e);
+                vec![TermEvent::ProcessExit(-1)]
+            }
+        };
+        let mut exit_code = None;
+        for event in events {
+            match event {
+                TermEvent::Bell => {
+                    send_to_client(outgoing, Message::Bell { pane: *id })?;
+                }
+                TermEvent::TitleChanged(title) => {
+                    send_to_client(outgoing, Message::Title { pane: *id, title })?;
+                }
+                TermEvent::ContextChanged(context) => {
+                    let message = Message::Context {
+                        pane: *id,
+                        name: context.name().to_string(),
+                        badge: context.badge(),
+                    };
+                    send_to_client(outgoing, message)?;
+                }
+                TermEvent::CommandUsage(usage) => {
+                    let message = Message::CommandUsage {
+                        pane: *id,
+                        user_ms: usage.user_ms,
+                        system_ms: usage.system_ms,
+                        max_rss_kb: usage.max_rss_kb,
+                    };
+                    send_to_client(outgoing, message)?;
+                }
+                TermEvent::ProcessExit(code) => exit_code = Some(code),
+                _ => {}
+            }
+        }
+        if let Some(code) = exit_code {
+            exited.push((*id, code));
+        } else if remote.acked < remote.seq {
+            continue;
+        }
+
+        let screen = remote.pane.screen();
+        let rows = wire_rows(screen);
+        let (row, col) = screen.cursor();
+        let cursor = (row as u16, col as u16);
+        if rows != remote.sent || cursor != remote.cursor {
+            remote.seq += 1;
+            let diff = ScreenDiff::between(*id, remote.seq, &remote.sent, &rows, cursor);
+            send_to_client(outgoing, Message::Screen(diff))?;
+            remote.sent = rows;
+            remote.cursor = cursor;
+        }
+    }
+    for (pane, code) in exited {
+        panes.remove(&pane);
+        send_to_client(outgoing, Message::PaneExited { pane, code })?;
+    }
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
     let cli = Cli::parse();
+    let mut config = load_config(cli.config.as_deref())?;
+    init_logging(&cli, &config.logging)?;
+
+    match &cli.command {
+        Some(Commands::ShellInit { shell }) => {
+            let shell = Shell::from_name(shell).ok_or_else(|| {
+                anyhow!("Unsupported shell: {} (expected bash, zsh or fish)", shell)
+            })?;
+            print!("{}", shell.script());
+            return Ok(());
+        }
+        Some(Commands::Keys {
+            command: KeysCommand::List { mode, config },
+        }) => {
+            return list_keys(mode.as_deref(), config.as_deref());
+        }
+        Some(Commands::Daemon) => {
+            let registry = SessionRegistry::new().with_shells(&config);
+            return session::serve(&session::socket_path(), registry);
+        }
+        Some(Commands::Ls) => {
+            if let Response::Sessions { sessions } = daemon_request(Request::List)? {
+                sessions.iter().for_each(print_session);
+            }
+            return Ok(());
+        }
+        Some(Commands::New { name }) => {
+            if let Response::Session { session } =
+                daemon_request(Request::New { name: name.clone() })?
+            {
+                print_session(&session);
+            }
+            return Ok(());
+        }
+        Some(Commands::Rename { from, to }) => {
+            daemon_request(Request::Rename {
+                from: from.clone(),
+                to: to.clone(),
+            })?;
+            return Ok(());
+        }
+        Some(Commands::Host { listen }) => return run_host(listen, &config).await,
+        Some(Commands::Bench { size, cols, rows }) => {
+            print!("{}", term::bench::run_all(*cols, *rows, size * 1024 * 1024)?);
+            return Ok(());
+        }
+        Some(Commands::Run {
+            json,
+            cols,
+            rows,
+            command,
+        }) => {
+            let code = run_command(&mut config, command, *cols, *rows, *json).await?;
+            std::process::exit(code);
+        }
+        Some(Commands::Report { output }) => {
+            let dir = crash::crash_dir().ok_or_else(|| anyhow!("No data directory"))?;
+            let count = crash::bundle_reports(&dir, std::path::Path::new(output))?;
+            println!("Bundled {} crash report(s) into {}", count, output);
+            return Ok(());
+        }
+        Some(Commands::SelfUpdate { channel }) => {
+            let channel = match channel {
+                Some(name) => UpdateChannel::from_name(name).ok_or_else(|| {
+                    anyhow!("Unknown channel: {} (expected stable or nightly)", name)
+                })?,
+                None => config.updates.channel,
+            };
+            match update::self_update(&config.updates, channel)? {
+                Some(version) => println!("Updated VoidCLI to {}", version),
+                None => println!("VoidCLI {} is up to date", update::CURRENT_VERSION),
+            }
+            return Ok(());
+        }
+        Some(Commands::Attach { name }) => return attach_session(name).await,
+        None => {}
+    }
 
     info!("Starting VoidCLI Terminal");
 
-    let config = match cli.config {
-        Some(ref path) => Config::from_file(path)?,
-        None => Config::default(),
+    apply_launch_args(&cli, &mut config);
+    announce_crash_reports();
+    crash::install(&config);
+    update::check_in_background(&config.updates);
+
+    if cli.headless {
+        let run = term::run_headless(&config, 80, 24).await?;
+        print!("{}", run.screen.text());
+        std::process::exit(run.exit_code);
+    }
+
+    if config.terminal.shell_integration {
+        match Shell::detect(&config.terminal.shell) {
+            Some(shell) => info!("Shell integration enabled for {}", shell.name()),
+            None => info!(
+                "No shell integration for {}; see `voidcli shell-init`",
+                config.terminal.shell
+            ),
+        }
+    }
+
+    let preset = match &cli.layout {
+        Some(name) => {
+            let preset = config
+                .layouts
+                .get(name)
+                .ok_or_else(|| anyhow!("No layout preset named '{}'", name))?;
+            let cwd = std::env::current_dir()?.to_string_lossy().into_owned();
+            let tab = LayoutBuilder::from_preset(preset)?.build(&mut 0, &cwd);
+            Some(SessionLayout {
+                windows: vec![WindowLayout {
+                    tabs: vec![tab],
+                    active_tab: 0,
+                }],
+                ..SessionLayout::default()
+            })
+        }
+        None => None,
     };
 
-    let app = VoidCLI::new(config);
+    let layout_path = SessionLayout::default_path();
+    let preferences_path = Preferences::default_path();
+    let preferences = match preferences_path.as_deref().map(Preferences::load) {
+        Some(Ok(preferences)) => preferences,
+        Some(Err(e)) => {
+            warn!("Ignoring saved preferences: {:#}", e);
+            Preferences::default()
+        }
+        None => Preferences::default(),
+    };
+    let mut app = VoidCLI::new(config).with_preferences(preferences);
+    if let Some(layout) = preset {
+        app = app.with_layout(layout);
+    }
+    if cli.restore_layout {
+        match layout_path.as_deref().map(SessionLayout::load) {
+            Some(Ok(layout)) => app = app.with_layout(layout),
+            Some(Err(e)) => warn!("Not restoring layout: {:#}", e),
+            None => warn!("Not restoring layout: no data directory"),
+        }
+    }
     app.run().await?;
 
+    if let Some(path) = &layout_path {
+        if let Err(e) = app.layout().save(path) {
+            warn!("Failed to save layout: {:#}", e);
+        }
+    }
+    if let Some(path) = &preferences_path {
+        if let Err(e) = app.preferences().save(path) {
+            warn!("Failed to save preferences: {:#}", e);
+        }
+    }
+
     info!("Shutting down");
     Ok(())
 }